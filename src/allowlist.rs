@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    attrs::{host_matches, is_executable_script},
+    raw_text::text_context,
+    BaseUrl, CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// What a [`ScriptAllowlist`] does with the scripts it blocks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockedScripts {
+    /// Leave the scripts and their content out
+    #[default]
+    Drop,
+    /// Keep the scripts with `type="text/blocked"`, so they don't run but a consent manager can restore them.
+    /// Any original `type` is kept in `data-original-type`
+    Neutralize,
+}
+
+/// A script blocked by a [`ScriptAllowlist`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockedScript {
+    /// The resolved `src`, or `None` for an inline script
+    pub src: Option<Url>,
+    /// The css path of the script, as described by [`CssPathTracker`]
+    pub path: String,
+}
+
+/// A filter which blocks the `<script>`s of each document which aren't from an allowed host, and outputs them along with the inner sink's output.
+///
+/// External scripts are allowed when they are on the document's origin or a host matching one of the [`ScriptAllowlist::allowed_hosts`],
+/// inline scripts only when [`ScriptAllowlist::allow_inline`] is set. Scripts holding data, such as `application/ld+json`, are left alone.
+/// Blocked scripts are dropped or neutralized, as set with [`ScriptAllowlist::blocked`].
+pub struct ScriptAllowlist<Handle, S> {
+    inner: S,
+    document_url: Url,
+    base: BaseUrl,
+    allowed_hosts: Vec<String>,
+    allow_inline: bool,
+    blocked: BlockedScripts,
+    paths: CssPathTracker,
+    skip_handle: Option<Handle>,
+    report: Vec<BlockedScript>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> ScriptAllowlist<Handle, S> {
+    pub fn wrap(inner: S, document_url: Url) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            allowed_hosts: vec![],
+            allow_inline: false,
+            blocked: BlockedScripts::Drop,
+            paths: CssPathTracker::new(),
+            skip_handle: None,
+            report: vec![],
+        }
+    }
+
+    /// Sets the hosts scripts may be loaded from besides the document's origin, such as `cdn.example.com` or `*.example.com`
+    pub fn allowed_hosts(self, allowed_hosts: &[&str]) -> Self {
+        Self {
+            allowed_hosts: allowed_hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Sets whether inline scripts are allowed
+    pub fn allow_inline(self, allow_inline: bool) -> Self {
+        Self {
+            allow_inline,
+            ..self
+        }
+    }
+
+    pub fn blocked(self, blocked: BlockedScripts) -> Self {
+        Self { blocked, ..self }
+    }
+
+    /// Sets the url of the next document
+    pub fn document_url(&mut self, document_url: Url) {
+        self.base.reset(document_url.clone());
+        self.document_url = document_url;
+    }
+
+    /// Whether the script is blocked, with its resolved `src`
+    fn check(&self, script: &HtmlPathElement<'_, Handle>) -> Option<Option<Url>> {
+        if !is_executable_script(script.attr_local("type").map(|t| &**t)) {
+            return None;
+        }
+        if !script.has_attr("src") {
+            return (!self.allow_inline).then_some(None);
+        }
+        let src = script.attr_url("src", self.base.url());
+        let allowed = src.as_ref().is_some_and(|src| {
+            src.origin() == self.document_url.origin()
+                || src.host_str().is_some_and(|host| {
+                    self.allowed_hosts
+                        .iter()
+                        .any(|pattern| host_matches(pattern, host))
+                })
+        });
+        (!allowed).then_some(src)
+    }
+
+    /// Whether the event in the context is inside a dropped script
+    fn skip(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        match self.skip_handle {
+            Some(handle) if context.iter().any(|element| element.handle == handle) => true,
+            _ => {
+                self.skip_handle = None;
+                false
+            }
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for ScriptAllowlist<Handle, S> {
+    type Output = (S::Output, Vec<BlockedScript>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.base.observe(element);
+        if self.skip(context) {
+            return;
+        }
+        let blocked = if element.name.ns == ns!(html) && element.tag_is(local_name!("script")) {
+            self.check(element)
+        } else {
+            None
+        };
+        let Some(src) = blocked else {
+            return self.inner.append_element(context, element);
+        };
+        self.report.push(BlockedScript {
+            src,
+            path: self.paths.element_path(context, element),
+        });
+        match self.blocked {
+            BlockedScripts::Drop => self.skip_handle = Some(element.handle),
+            BlockedScripts::Neutralize => {
+                let mut script = Cow::Borrowed(element);
+                if let Some(original) = element.attr_local("type") {
+                    script.to_mut().set_attr("data-original-type", original);
+                }
+                script.to_mut().set_attr("type", "text/blocked");
+                self.inner.append_element(context, &script)
+            }
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.skip(context) {
+            self.inner.append_text(context, text)
+        }
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        if !self.skip(context) {
+            self.inner
+                .append_text_with_boundary(context, text, boundary)
+        }
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if !self.skip(&text_context(context, element)) {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.skip(context) {
+            self.inner.append_comment(context, text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if !self.skip(context) {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.base.reset(self.document_url.clone());
+        self.skip_handle = None;
+        (self.inner.reset(), std::mem::take(&mut self.report))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head><script src="/app.js"></script><script src="https://cdn.example.net/lib.js"></script><script src="https://tracker.example.org/t.js"></script><script>track()</script><script type="application/ld+json">{}</script></head><body></body></html>"#;
+
+    fn allowlist<S: HtmlSink<u32>>(inner: S) -> ScriptAllowlist<u32, S> {
+        ScriptAllowlist::wrap(inner, "https://example.com/".parse().unwrap())
+            .allowed_hosts(&["*.example.net"])
+    }
+
+    #[test]
+    fn drop_scripts() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), blocked) = parse_document(allowlist(&mut serializer), Default::default())
+            .one(DOC)
+            .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><script src="/app.js"></script><script src="https://cdn.example.net/lib.js"></script><script type="application/ld+json">{}</script></head><body></body></html>"#
+        );
+        assert_eq!(
+            blocked,
+            [
+                BlockedScript {
+                    src: Some("https://tracker.example.org/t.js".parse().unwrap()),
+                    path: "html > head > script:nth-child(3)".to_owned()
+                },
+                BlockedScript {
+                    src: None,
+                    path: "html > head > script:nth-child(4)".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn neutralize_scripts() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), blocked) = parse_document(
+            allowlist(&mut serializer)
+                .allow_inline(true)
+                .blocked(BlockedScripts::Neutralize),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        drop(serializer);
+        assert!(String::from_utf8(buf).unwrap().contains(
+            r#"<script src="https://tracker.example.org/t.js" type="text/blocked"></script><script>track()</script>"#
+        ));
+        assert_eq!(blocked.len(), 1);
+    }
+}
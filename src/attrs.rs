@@ -0,0 +1,275 @@
+use std::convert::TryFrom;
+
+use html5ever::{namespace_url, ns, Attribute, QualName};
+
+use crate::HtmlPathElement;
+
+/// Parses an integer by the html rules, leading whitespace is skipped and anything after the digits is ignored, so `" 12px"` is 12
+pub fn parse_html_integer(value: &str) -> Option<i64> {
+    let value = value.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let magnitude = digits[..end].parse::<i64>().ok()?;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Parses a non-negative integer by the html rules, see [`parse_html_integer`]
+pub fn parse_html_non_negative_integer(value: &str) -> Option<u64> {
+    parse_html_integer(value).and_then(|value| u64::try_from(value).ok())
+}
+
+/// Whether a host matches a pattern, which is a host or `*.` followed by a domain to match its subdomains
+#[cfg(feature = "url")]
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Whether a script's `type` makes it run, rather than hold data such as json
+pub(crate) fn is_executable_script(script_type: Option<&str>) -> bool {
+    let Some(script_type) = script_type.map(str::trim) else {
+        return true;
+    };
+    script_type.is_empty()
+        || [
+            "module",
+            "text/javascript",
+            "application/javascript",
+            "text/ecmascript",
+        ]
+        .iter()
+        .any(|t| script_type.eq_ignore_ascii_case(t))
+}
+
+/// Whether a script `type` is a legacy way of saying javascript, which is the default
+pub(crate) fn is_legacy_type(value: &str) -> bool {
+    let value = value.trim();
+    value.eq_ignore_ascii_case("text/javascript")
+        || value.eq_ignore_ascii_case("application/javascript")
+}
+
+/// Parses a `srcset` attribute into its candidates, each a url and its descriptor such as `2x` or `640w`, which is empty when there is none.
+/// Candidates are split at commas, so urls containing commas, such as some `data:` urls, aren't supported
+pub fn parse_srcset(value: &str) -> Vec<(&str, &str)> {
+    value
+        .split(',')
+        .map(|candidate| candidate.trim_matches(|c: char| c.is_ascii_whitespace()))
+        .filter(|candidate| !candidate.is_empty())
+        .map(
+            |candidate| match candidate.find(|c: char| c.is_ascii_whitespace()) {
+                Some(end) => (
+                    &candidate[..end],
+                    candidate[end..].trim_start_matches(|c: char| c.is_ascii_whitespace()),
+                ),
+                None => (candidate, ""),
+            },
+        )
+        .collect()
+}
+
+impl<'a, Handle> HtmlPathElement<'a, Handle> {
+    /// Whether a boolean attribute such as `disabled` is set, by html rules it is set when present whatever its value
+    pub fn has_attr(&self, local: &str) -> bool {
+        self.attr_local(local).is_some()
+    }
+
+    /// The attribute parsed as an integer, such as `tabindex`
+    pub fn attr_integer(&self, local: &str) -> Option<i64> {
+        parse_html_integer(self.attr_local(local)?)
+    }
+
+    /// The attribute parsed as a non-negative integer, such as `width` or `colspan`
+    pub fn attr_non_negative_integer(&self, local: &str) -> Option<u64> {
+        parse_html_non_negative_integer(self.attr_local(local)?)
+    }
+
+    /// The tokens of a space-separated attribute such as `rel` or `sandbox`, empty when the attribute is absent
+    pub fn attr_tokens(&self, local: &str) -> impl Iterator<Item = &str> {
+        self.attr_local(local)
+            .map(|value| value.split_ascii_whitespace())
+            .into_iter()
+            .flatten()
+    }
+
+    /// Whether a space-separated attribute contains the token, compared ascii case-insensitively as for `rel`
+    pub fn has_token(&self, local: &str, token: &str) -> bool {
+        self.attr_tokens(local)
+            .any(|t| t.eq_ignore_ascii_case(token))
+    }
+
+    /// Sets an attribute without a namespace, adding it if it is absent and copying the attributes if they are borrowed
+    pub fn set_attr(&mut self, local: &str, value: &str) {
+        let attrs = self.attrs.to_mut();
+        match attrs
+            .iter_mut()
+            .find(|attr| attr.name.ns.is_empty() && &*attr.name.local == local)
+        {
+            Some(attr) => attr.value = value.into(),
+            None => attrs.push(Attribute {
+                name: QualName::new(None, ns!(), local.into()),
+                value: value.into(),
+            }),
+        }
+    }
+
+    /// Removes an attribute without a namespace, returning whether it was present. The attributes are only copied if it is
+    pub fn remove_attr(&mut self, local: &str) -> bool {
+        if !self.has_attr(local) {
+            return false;
+        }
+        self.attrs
+            .to_mut()
+            .retain(|attr| !(attr.name.ns.is_empty() && &*attr.name.local == local));
+        true
+    }
+
+    /// The attribute parsed as a url and resolved against `base`, see [`BaseUrl`] to track the document's base
+    #[cfg(feature = "url")]
+    pub fn attr_url(&self, local: &str, base: &url::Url) -> Option<url::Url> {
+        base.join(self.attr_local(local)?.trim()).ok()
+    }
+}
+
+/// Tracks a document's base url, which is its own url until the first `<base href>` element
+#[cfg(feature = "url")]
+#[derive(Clone, Debug)]
+pub struct BaseUrl {
+    document: url::Url,
+    base: Option<url::Url>,
+}
+
+#[cfg(feature = "url")]
+impl BaseUrl {
+    pub fn new(document: url::Url) -> Self {
+        Self {
+            document,
+            base: None,
+        }
+    }
+
+    /// Updates the base if the element is the document's first `<base>` with an `href`, call this for each appended element
+    pub fn observe<Handle>(&mut self, element: &HtmlPathElement<'_, Handle>) {
+        use html5ever::{local_name, namespace_url, ns};
+        if self.base.is_none()
+            && element.name.ns == ns!(html)
+            && element.tag_is(local_name!("base"))
+        {
+            self.base = element.attr_url("href", &self.document);
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        self.base.as_ref().unwrap_or(&self.document)
+    }
+
+    /// Starts again for another document
+    pub fn reset(&mut self, document: url::Url) {
+        *self = Self::new(document);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use html5ever::local_name;
+
+    use super::*;
+
+    fn element(attrs: &[(&str, &str)]) -> HtmlPathElement<'static, u32> {
+        HtmlPathElement::new(
+            1,
+            QualName::new(None, ns!(html), local_name!("a")),
+            attrs
+                .iter()
+                .map(|(name, value)| Attribute {
+                    name: QualName::new(None, ns!(), (*name).into()),
+                    value: (*value).into(),
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn typed_attributes() {
+        assert_eq!(parse_html_integer("  -12px"), Some(-12));
+        assert_eq!(parse_html_integer("+3"), Some(3));
+        assert_eq!(parse_html_integer("px"), None);
+        assert_eq!(parse_html_non_negative_integer("-1"), None);
+        assert_eq!(
+            parse_srcset(" a.png,b.png 2x ,\n c.png  640w,"),
+            [("a.png", ""), ("b.png", "2x"), ("c.png", "640w")]
+        );
+
+        let a = element(&[
+            ("width", " 640 "),
+            ("tabindex", "-1"),
+            ("hidden", ""),
+            ("rel", "NoOpener  noreferrer"),
+            ("href", " ../page?q=1 "),
+        ]);
+        assert_eq!(a.attr_non_negative_integer("width"), Some(640));
+        assert_eq!(a.attr_non_negative_integer("tabindex"), None);
+        assert_eq!(a.attr_integer("tabindex"), Some(-1));
+        assert!(a.has_attr("hidden"));
+        assert!(!a.has_attr("disabled"));
+        assert_eq!(
+            a.attr_tokens("rel").collect::<Vec<_>>(),
+            ["NoOpener", "noreferrer"]
+        );
+        assert!(a.has_token("rel", "noopener"));
+        assert!(!a.has_token("rel", "nofollow"));
+        assert_eq!(a.attr_tokens("sandbox").count(), 0);
+    }
+
+    #[test]
+    fn edit_attributes() {
+        let attrs = [Attribute {
+            name: QualName::new(None, ns!(), local_name!("type")),
+            value: "text/javascript".into(),
+        }];
+        let mut script = HtmlPathElement::new(
+            1,
+            QualName::new(None, ns!(html), local_name!("script")),
+            &attrs[..],
+        );
+        assert!(!script.remove_attr("defer"));
+        assert!(matches!(script.attrs, Cow::Borrowed(_)));
+        script.set_attr("type", "module");
+        script.set_attr("src", "/app.js");
+        assert_eq!(script.attr_local("type").unwrap(), &"module".into());
+        assert!(script.remove_attr("type"));
+        assert_eq!(script.attrs.len(), 1);
+        assert_eq!(attrs[0].value, "text/javascript".into());
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn resolve_urls() {
+        let mut base = BaseUrl::new("https://example.com/a/b/doc.html".parse().unwrap());
+        let a = element(&[("href", " ../page?q=1 ")]);
+        assert_eq!(
+            a.attr_url("href", base.url()).unwrap().as_str(),
+            "https://example.com/a/page?q=1"
+        );
+
+        let mut base_element = element(&[("href", "https://cdn.example.com/x/")]);
+        base_element.name.local = local_name!("base");
+        base.observe(&base_element);
+        base.observe(&element(&[("href", "/ignored/")]));
+        assert_eq!(
+            a.attr_url("href", base.url()).unwrap().as_str(),
+            "https://cdn.example.com/page?q=1"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    selector::ContextualSelector, CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink,
+    HtmlStreamError, TextBoundary,
+};
+
+/// A sensitive form field which didn't have `autocomplete="off"`, as reported by an [`AutofillPolicy`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutofillViolation {
+    /// The css path of the field, as described by [`crate::CssPathTracker`]
+    pub path: String,
+    /// The field's `name`
+    pub name: Option<String>,
+    /// The field's `autocomplete` before the policy was applied
+    pub autocomplete: Option<String>,
+}
+
+/// Whether an element is a form control which takes typed or chosen input
+fn is_field<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    if element.name.ns != ns!(html) {
+        return false;
+    }
+    match element.name.local {
+        local_name!("textarea") | local_name!("select") => true,
+        local_name!("input") => !element.attr_local("type").is_some_and(|t| {
+            [
+                "hidden", "submit", "reset", "button", "image", "checkbox", "radio", "file",
+            ]
+            .iter()
+            .any(|skipped| t.trim().eq_ignore_ascii_case(skipped))
+        }),
+        _ => false,
+    }
+}
+
+/// The `inputmode` and `autocapitalize` defaults for an input's type
+fn input_defaults(input_type: &str) -> (Option<&'static str>, Option<&'static str>) {
+    match &*input_type.trim().to_ascii_lowercase() {
+        "email" => (Some("email"), Some("off")),
+        "url" => (Some("url"), Some("off")),
+        "tel" => (Some("tel"), None),
+        "search" => (Some("search"), None),
+        "number" => (Some("decimal"), None),
+        "password" => (None, Some("off")),
+        _ => (None, None),
+    }
+}
+
+/// A filter enforcing a policy on the fields of forms, and outputting the sensitive fields which broke it along with the inner sink's output.
+///
+/// Fields matching the sensitive selector, such as card numbers or one-time codes, are given `autocomplete="off"`, and each of them
+/// which didn't have it is reported. Inputs are also given the `inputmode` and `autocapitalize` suited to their type where they don't set them,
+/// such as `inputmode="email"` and `autocapitalize="off"` for `type="email"`, unless [`AutofillPolicy::input_defaults`] is turned off.
+pub struct AutofillPolicy<S, M> {
+    inner: S,
+    sensitive: M,
+    input_defaults: bool,
+    paths: CssPathTracker,
+    violations: Vec<AutofillViolation>,
+}
+
+impl<S, M: ContextualSelector> AutofillPolicy<S, M> {
+    pub fn wrap(inner: S, sensitive: M) -> Self {
+        Self {
+            inner,
+            sensitive,
+            input_defaults: true,
+            paths: CssPathTracker::new(),
+            violations: vec![],
+        }
+    }
+
+    /// Sets whether to add `inputmode` and `autocapitalize` defaults to inputs by their type
+    pub fn input_defaults(self, input_defaults: bool) -> Self {
+        Self {
+            input_defaults,
+            ..self
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: ContextualSelector> HtmlSink<Handle>
+    for AutofillPolicy<S, M>
+{
+    type Output = (S::Output, Vec<AutofillViolation>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        if !is_field(element) {
+            return self.inner.append_element(context, element);
+        }
+        let mut field = Cow::Borrowed(element);
+        if self.sensitive.context_match(context, element) {
+            let autocomplete = element.attr_local("autocomplete");
+            if !autocomplete.is_some_and(|value| value.trim().eq_ignore_ascii_case("off")) {
+                self.violations.push(AutofillViolation {
+                    path: self.paths.element_path(context, element),
+                    name: element.attr_local("name").map(|name| name.to_string()),
+                    autocomplete: autocomplete.map(|value| value.to_string()),
+                });
+                field.to_mut().set_attr("autocomplete", "off");
+            }
+        }
+        if self.input_defaults && element.tag_is(local_name!("input")) {
+            let (inputmode, autocapitalize) =
+                input_defaults(element.attr_local("type").map_or("text", |t| &**t));
+            for (attribute, value) in [("inputmode", inputmode), ("autocapitalize", autocapitalize)]
+            {
+                if let (Some(value), false) = (value, element.has_attr(attribute)) {
+                    field.to_mut().set_attr(attribute, value);
+                }
+            }
+        }
+        self.inner.append_element(context, &field)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.violations))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer};
+
+    #[test]
+    fn enforce_autofill_policy() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), violations) = parse_document(
+            AutofillPolicy::wrap(&mut serializer, css_select!(."sensitive")),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><form><input type="email" name="email"><input type="tel" inputmode="numeric"><input class="sensitive" name="card" autocomplete="cc-number"><input class="sensitive" name="otp" autocomplete="off"><input type="hidden" class="sensitive"></form></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body><form>"#,
+                r#"<input type="email" name="email" inputmode="email" autocapitalize="off"><input type="tel" inputmode="numeric">"#,
+                r#"<input class="sensitive" name="card" autocomplete="off"><input class="sensitive" name="otp" autocomplete="off">"#,
+                r#"<input type="hidden" class="sensitive"></form></body></html>"#
+            )
+        );
+        assert_eq!(
+            violations,
+            [AutofillViolation {
+                path: "html > body > form:nth-child(1) > input.sensitive:nth-child(3)".to_owned(),
+                name: Some("card".to_owned()),
+                autocomplete: Some("cc-number".to_owned()),
+            }]
+        );
+    }
+}
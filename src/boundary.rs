@@ -0,0 +1,235 @@
+use html5ever::tendril::StrTendril;
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError};
+
+/// Where a text event falls, as given to [`HtmlSink::append_text_with_boundary`] by a [`TextBoundaries`] wrapper
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextBoundary {
+    /// No more of this text node follows, the next event isn't text in the same element
+    pub last_in_text_node: bool,
+    /// Nothing more follows in the text's element, it is closed by the next event
+    pub last_in_element: bool,
+}
+
+/// Appends text to the sink with its boundary, if a wrapper gave it one, for filters which treat both kinds of text alike
+pub(crate) fn append_text_to<Handle: Eq + Copy, S: HtmlSink<Handle>>(
+    sink: &mut S,
+    context: HtmlContext<Handle>,
+    text: &str,
+    boundary: Option<TextBoundary>,
+) {
+    match boundary {
+        Some(boundary) => sink.append_text_with_boundary(context, text, boundary),
+        None => sink.append_text(context, text),
+    }
+}
+
+/// A wrapper which flags each text event with whether it ends its text node and its element, by calling `append_text_with_boundary` on the inner sink.
+///
+/// There are no end events, so each text event is held back until the next event shows where it falls, which lets rewriters
+/// act at the end of an element's text, such as appending a citation, without buffering the text themselves.
+pub struct TextBoundaries<Handle, S> {
+    inner: S,
+    context: Vec<HtmlPathElement<'static, Handle>>,
+    text: String,
+    pending: bool,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> TextBoundaries<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            context: vec![],
+            text: String::new(),
+            pending: false,
+        }
+    }
+
+    /// Forwards the pending text now that the next event is known, `next` is its context and whether it is text, or `None` at the end of the document
+    fn flush(&mut self, next: Option<(HtmlContext<'_, Handle>, bool)>) {
+        if !std::mem::take(&mut self.pending) {
+            return;
+        }
+        let context = &self.context;
+        let inside = |next_context: HtmlContext<'_, Handle>| {
+            next_context.len() >= context.len()
+                && context
+                    .iter()
+                    .zip(next_context)
+                    .all(|(a, b)| a.handle == b.handle)
+        };
+        let boundary = match next {
+            Some((next_context, is_text)) if inside(next_context) => TextBoundary {
+                last_in_text_node: !(is_text && next_context.len() == context.len()),
+                last_in_element: false,
+            },
+            _ => TextBoundary {
+                last_in_text_node: true,
+                last_in_element: true,
+            },
+        };
+        self.inner
+            .append_text_with_boundary(&self.context, &self.text, boundary);
+        self.context.clear();
+        self.text.clear();
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for TextBoundaries<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.flush(None);
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.flush(Some((context, false)));
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.flush(Some((context, true)));
+        self.context
+            .extend(context.iter().cloned().map(HtmlPathElement::into_owned));
+        self.text.push_str(text);
+        self.pending = true;
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.flush(Some((context, false)));
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.flush(Some((context, false)));
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.flush(Some((context, false)));
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.flush(None);
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, test::stream_doc, ElementRemover};
+
+    #[derive(Default)]
+    struct Boundaries(Vec<(String, TextBoundary)>);
+
+    impl HtmlSink<u32> for &mut Boundaries {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            _element: &HtmlPathElement<'_, u32>,
+        ) {
+        }
+
+        fn append_text(&mut self, _context: HtmlContext<u32>, _text: &str) {
+            unreachable!("text is given with its boundary")
+        }
+
+        fn append_text_with_boundary(
+            &mut self,
+            _context: HtmlContext<u32>,
+            text: &str,
+            boundary: TextBoundary,
+        ) {
+            self.0.push((text.to_owned(), boundary));
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn flag_text_boundaries() {
+        let mut boundaries = Boundaries::default();
+        stream_doc(
+            "<!DOCTYPE html><html><head></head><body><p>Tom &amp; Jerry<!-- c -->, <b>cat</b> and mouse</p><p>end</p></body></html>",
+            TextBoundaries::wrap(&mut boundaries),
+        );
+        let at = |last_in_text_node, last_in_element| TextBoundary {
+            last_in_text_node,
+            last_in_element,
+        };
+        assert_eq!(
+            boundaries.0,
+            [
+                ("Tom ".to_owned(), at(false, false)),
+                ("&".to_owned(), at(false, false)),
+                (" ".to_owned(), at(false, false)),
+                ("Jerry".to_owned(), at(true, false)),
+                (", ".to_owned(), at(true, false)),
+                ("cat".to_owned(), at(true, true)),
+                (" and mouse".to_owned(), at(true, true)),
+                ("end".to_owned(), at(true, true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn boundaries_pass_through_filters() {
+        let mut boundaries = Boundaries::default();
+        stream_doc(
+            "<!DOCTYPE html><html><head></head><body><p>Tom <b>cat</b> and mouse</p></body></html>",
+            TextBoundaries::wrap(ElementRemover::wrap(&mut boundaries, css_select!("b"))),
+        );
+        let at = |last_in_text_node, last_in_element| TextBoundary {
+            last_in_text_node,
+            last_in_element,
+        };
+        assert_eq!(
+            boundaries.0,
+            [
+                ("Tom ".to_owned(), at(true, false)),
+                (" and mouse".to_owned(), at(true, true)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, LocalName, QualName};
+
+use crate::{
+    boundary::append_text_to, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    TextBoundary, TextCoalescer,
+};
+
+/// Whether whitespace is significant in the element's text
+fn preserves_whitespace(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && matches!(
+            name.local,
+            local_name!("pre")
+                | local_name!("textarea")
+                | local_name!("listing")
+                | local_name!("plaintext")
+                | local_name!("script")
+                | local_name!("style")
+        )
+}
+
+fn lowercase(name: &QualName) -> QualName {
+    if name.ns == ns!(html) || name.ns == ns!() {
+        QualName::new(
+            name.prefix.clone(),
+            name.ns.clone(),
+            LocalName::from(name.local.to_ascii_lowercase()),
+        )
+    } else {
+        name.clone()
+    }
+}
+
+fn canonical_element<Handle: Copy>(
+    element: &HtmlPathElement<'_, Handle>,
+) -> HtmlPathElement<'static, Handle> {
+    let mut attrs = element
+        .attrs
+        .iter()
+        .map(|attr| html5ever::Attribute {
+            name: if element.name.ns == ns!(html) {
+                lowercase(&attr.name)
+            } else {
+                attr.name.clone()
+            },
+            value: attr.value.clone(),
+        })
+        .collect::<Vec<_>>();
+    attrs.sort_by(|a, b| (&*a.name.ns, &*a.name.local).cmp(&(&*b.name.ns, &*b.name.local)));
+    HtmlPathElement {
+        handle: element.handle,
+        name: lowercase(&element.name),
+        attrs: Cow::Owned(attrs),
+        inherited: element.inherited.clone(),
+        position: element.position,
+        previous_sibling: element.previous_sibling.clone(),
+        content: element.content.clone(),
+    }
+}
+
+/// Collapses runs of whitespace into a single space
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Puts each (already coalesced) event in canonical form
+struct Normalizer<S> {
+    inner: S,
+}
+
+impl<S> Normalizer<S> {
+    fn context<Handle: Copy>(
+        context: HtmlContext<'_, Handle>,
+    ) -> Vec<HtmlPathElement<'static, Handle>> {
+        context.iter().map(canonical_element).collect()
+    }
+
+    /// Collapses the whitespace of text outside the elements which preserve it, keeping its boundary if it has one
+    fn text<Handle: Eq + Copy>(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) where
+        S: HtmlSink<Handle>,
+    {
+        let context = Self::context(context);
+        let text = if context
+            .iter()
+            .any(|element| preserves_whitespace(&element.name))
+        {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(collapse_whitespace(text))
+        };
+        append_text_to(&mut self.inner, &context, &text, boundary)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for Normalizer<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        self.inner.append_doctype_to_document(
+            &StrTendril::from_slice(&name.to_ascii_lowercase()),
+            &StrTendril::new(),
+            &StrTendril::new(),
+        )
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner
+            .append_element(&Self::context(context), &canonical_element(element))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner
+            .append_raw_text(&Self::context(context), &canonical_element(element), text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(&Self::context(context), text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(&Self::context(context), target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// A wrapper which puts the stream in a canonical form, so that semantically equal documents produce the same events and serialize identically.
+///
+/// Attributes are sorted by namespace and name, html element and attribute names are lowercased, doctype ids are dropped,
+/// and text is merged into one event per node with runs of whitespace collapsed to a single space, except inside `pre`, `textarea`,
+/// `script` and similar elements. Entities and attribute quoting are normalized by parsing and serialization themselves.
+pub struct Canonicalizer<Handle, S> {
+    inner: TextCoalescer<Handle, Normalizer<S>>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> Canonicalizer<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner: TextCoalescer::wrap(Normalizer { inner }),
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for Canonicalizer<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::serialize::SerializeOpts;
+
+    use super::*;
+    use crate::{test::stream_doc, HtmlSerializer};
+
+    fn canonical(doc: &str) -> String {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(doc, Canonicalizer::wrap(&mut serializer));
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn canonical_equivalence() {
+        let a = canonical(
+            "<!DOCTYPE html><html><head></head><body><P ID=x CLASS='a'>Tom &amp;\n  Jerry</P><pre>  keep\n  this</pre></body></html>",
+        );
+        let b = canonical(
+            "<!doctype HTML><html><head></head><body><p class=\"a\" id=\"x\">Tom &#38; Jerry</p><pre>  keep\n  this</pre></body></html>",
+        );
+        assert_eq!(a, b);
+        assert_eq!(
+            a,
+            "<!DOCTYPE html><html><head></head><body><p class=\"a\" id=\"x\">Tom &amp; Jerry</p><pre>  keep\n  this</pre></body></html>"
+        );
+    }
+}
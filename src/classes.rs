@@ -0,0 +1,201 @@
+use std::{iter::FusedIterator, str::SplitWhitespace};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+/// An iterator over the classes of an element, see [`crate::HtmlPathElement::classes`]
+#[derive(Clone, Debug)]
+pub struct Classes<'a> {
+    split: SplitWhitespace<'a>,
+    remaining: usize,
+}
+
+impl<'a> Classes<'a> {
+    pub(crate) fn new(value: Option<&'a str>) -> Self {
+        let value = value.unwrap_or("");
+        Self {
+            split: value.split_whitespace(),
+            remaining: value.split_whitespace().count(),
+        }
+    }
+
+    /// Whether `class` is one of the remaining classes
+    pub fn contains(&self, class: &str) -> bool {
+        self.clone().any(|c| c == class)
+    }
+}
+
+impl<'a> Iterator for Classes<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let class = self.split.next()?;
+        self.remaining -= 1;
+        Some(class)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Classes<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let class = self.split.next_back()?;
+        self.remaining -= 1;
+        Some(class)
+    }
+}
+
+impl ExactSizeIterator for Classes<'_> {}
+
+impl FusedIterator for Classes<'_> {}
+
+/// Edits the class attribute in a list of attributes, for rewriters, see [`crate::HtmlPathElement::class_list_mut`].
+///
+/// The classes are written back separated by single spaces and the attribute is removed when the last class is.
+pub struct ClassListMut<'a> {
+    attrs: &'a mut Vec<Attribute>,
+}
+
+impl<'a> ClassListMut<'a> {
+    pub fn new(attrs: &'a mut Vec<Attribute>) -> Self {
+        Self { attrs }
+    }
+
+    fn position(&self) -> Option<usize> {
+        self.attrs
+            .iter()
+            .position(|attr| attr.name.ns == ns!() && attr.name.local == local_name!("class"))
+    }
+
+    pub fn classes(&self) -> Classes<'_> {
+        Classes::new(self.position().map(|i| &*self.attrs[i].value))
+    }
+
+    pub fn contains(&self, class: &str) -> bool {
+        self.classes().contains(class)
+    }
+
+    /// Adds the class if it isn't already present, returning whether it was added
+    pub fn add(&mut self, class: &str) -> bool {
+        if self.contains(class) {
+            return false;
+        }
+        match self.position() {
+            Some(i) => {
+                let value = self.classes().chain(Some(class)).collect::<Vec<_>>();
+                self.attrs[i].value = StrTendril::from(value.join(" "));
+            }
+            None => self.attrs.push(Attribute {
+                name: QualName::new(None, ns!(), local_name!("class")),
+                value: class.into(),
+            }),
+        }
+        true
+    }
+
+    /// Removes the class, returning whether it was present
+    pub fn remove(&mut self, class: &str) -> bool {
+        self.retain(|c| c != class)
+    }
+
+    /// Keeps only the classes for which `keep` returns true, returning whether any were removed
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> bool {
+        let Some(i) = self.position() else {
+            return false;
+        };
+        let before = self.classes().len();
+        let kept = self.classes().filter(|c| keep(c)).collect::<Vec<_>>();
+        if kept.len() == before {
+            return false;
+        }
+        if kept.is_empty() {
+            self.attrs.remove(i);
+        } else {
+            self.attrs[i].value = StrTendril::from(kept.join(" "));
+        }
+        true
+    }
+
+    /// Adds the class if it is absent or removes it if present, returning whether it is now present
+    pub fn toggle(&mut self, class: &str) -> bool {
+        if self.remove(class) {
+            false
+        } else {
+            self.add(class)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{
+        event::{HtmlEvent, Recorder},
+        parse_fragment, HtmlPathElement, HtmlSerializer, HtmlSink,
+    };
+
+    /// Serializes the element, then parses the html back for its classes
+    fn round_trip(element: &HtmlPathElement<'_, u32>) -> (String, Vec<String>) {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        (&mut serializer).append_element(&[], element);
+        (&mut serializer).finish().unwrap();
+        drop(serializer);
+        let html = String::from_utf8(buf).unwrap();
+        let events = parse_fragment(Recorder::new(), Default::default())
+            .one(html.as_str())
+            .unwrap();
+        let classes = events
+            .iter()
+            .find_map(|event| match event {
+                HtmlEvent::Element { element, .. } if &*element.name.local == "p" => Some(
+                    element
+                        .to_path_element()
+                        .classes()
+                        .map(str::to_owned)
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .unwrap();
+        (html, classes)
+    }
+
+    #[test]
+    fn edit_through_serializer() {
+        let mut element = HtmlPathElement::new(
+            1,
+            QualName::new(None, ns!(html), local_name!("p")),
+            vec![Attribute {
+                name: QualName::new(None, ns!(), local_name!("class")),
+                value: " a\tb  ".into(),
+            }],
+        );
+        let mut list = element.class_list_mut();
+        assert!(list.add("c"));
+        assert!(!list.add("a"));
+        assert!(list.remove("b"));
+        assert!(!list.remove("b"));
+        assert!(list.toggle("d"));
+        assert!(!list.toggle("a"));
+        assert_eq!(
+            round_trip(&element),
+            (
+                r#"<p class="c d"></p>"#.to_owned(),
+                vec!["c".to_owned(), "d".to_owned()]
+            )
+        );
+
+        assert!(element.class_list_mut().retain(|_| false));
+        assert_eq!(round_trip(&element), ("<p></p>".to_owned(), vec![]));
+
+        assert!(element.class_list_mut().toggle("e"));
+        assert_eq!(
+            round_trip(&element),
+            (r#"<p class="e"></p>"#.to_owned(), vec!["e".to_owned()])
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    boundary::append_text_to, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    MemoryMetrics, TextBoundary,
+};
+
+/// Merges consecutive `append_text` calls made within the same context into a single call on the inner sink.
+///
+/// html5ever splits text at entity and chunk boundaries, so a single text node can arrive as many small events.
+/// The pending text is forwarded as soon as any other event arrives, the context changes, or the sink is reset.
+/// Empty text events are dropped. Merged text keeps the boundary of its last chunk, if a [`crate::TextBoundaries`] gave it one.
+pub struct TextCoalescer<Handle, S> {
+    inner: S,
+    context: Vec<HtmlPathElement<'static, Handle>>,
+    text: String,
+    boundary: Option<TextBoundary>,
+    metrics: Option<MemoryMetrics>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> TextCoalescer<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            context: vec![],
+            text: String::new(),
+            boundary: None,
+            metrics: None,
+        }
+    }
+
+    /// Records the most text held back at once into `metrics`
+    pub fn metrics(self, metrics: MemoryMetrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            append_text_to(
+                &mut self.inner,
+                &self.context,
+                &self.text,
+                self.boundary.take(),
+            );
+            self.text.clear();
+        }
+        self.context.clear();
+    }
+
+    fn push_text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+        if !self.text.is_empty() && !self.is_pending_context(context) {
+            self.flush_text();
+        }
+        if self.text.is_empty() {
+            self.context
+                .extend(context.iter().cloned().map(HtmlPathElement::into_owned));
+        }
+        self.text.push_str(text);
+        self.boundary = boundary;
+        if let Some(metrics) = &self.metrics {
+            metrics.buffered_text_bytes(self.text.len());
+        }
+    }
+
+    fn is_pending_context(&self, context: HtmlContext<'_, Handle>) -> bool {
+        self.context.len() == context.len()
+            && self
+                .context
+                .iter()
+                .zip(context)
+                .all(|(a, b)| a.handle == b.handle)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for TextCoalescer<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &html5ever::tendril::StrTendril,
+        public_id: &html5ever::tendril::StrTendril,
+        system_id: &html5ever::tendril::StrTendril,
+    ) {
+        self.flush_text();
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.flush_text();
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.push_text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.push_text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.flush_text();
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.flush_text();
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.flush_text();
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.flush_text();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::stream_doc;
+
+    #[derive(Default)]
+    struct TextCollector(Vec<String>);
+
+    impl HtmlSink<u32> for &mut TextCollector {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &html5ever::tendril::StrTendril,
+            _public_id: &html5ever::tendril::StrTendril,
+            _system_id: &html5ever::tendril::StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            _element: &HtmlPathElement<'_, u32>,
+        ) {
+        }
+
+        fn append_text(&mut self, _context: HtmlContext<u32>, text: &str) {
+            self.0.push(text.to_owned());
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn coalesce_entity_split_text() {
+        let test = "<!DOCTYPE html><html><head></head><body><p>fish &amp; chips</p><p>peas</p></body></html>";
+        let mut uncoalesced = TextCollector::default();
+        stream_doc(test, &mut uncoalesced);
+        assert!(uncoalesced.0.len() > 2);
+
+        let mut coalesced = TextCollector::default();
+        stream_doc(test, TextCoalescer::wrap(&mut coalesced));
+        assert_eq!(coalesced.0, vec!["fish & chips", "peas"]);
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+};
+
+use html5ever::{driver::Parser, ParseOpts};
+
+use crate::{
+    selector::NameSelector,
+    traverser::{parse_fragment_with, ParseTraverser},
+    tree::{stream_tree_with, StreamTree},
+    ElementUnwrapper, HtmlSink, HtmlStreamError, SiblingChain, SiblingTracking, SourceLines,
+};
+
+/// A handle collecting the parse errors which a lenient parse recovered from, see [`ParseConfig::lenient`].
+///
+/// Clones share the same errors, so one can be kept to read them while another is moved into the parse.
+#[derive(Clone, Debug, Default)]
+pub struct ParseErrors {
+    errors: Arc<Mutex<Vec<Cow<'static, str>>>>,
+}
+
+impl ParseErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the errors collected since creation or the last take, to get them per parse
+    pub fn take(&self) -> Vec<Cow<'static, str>> {
+        std::mem::take(&mut self.errors.lock().unwrap())
+    }
+
+    pub(crate) fn push(&self, error: Cow<'static, str>) {
+        self.errors.lock().unwrap().push(error)
+    }
+}
+
+/// What a parse does with the errors in its input
+#[derive(Clone, Debug, Default)]
+pub enum ErrorPolicy {
+    /// Stop forwarding events at the first error, and return it as a [`crate::HtmlStreamError::Parse`], this is the default
+    #[default]
+    FailFast,
+    /// Recover as browsers do, collecting the errors
+    ///
+    /// Content which browsers would move has already been streamed: fostered content streams inside its table and a
+    /// repeated `<html>` or `<body>` tag's attributes are dropped, while misnested formatting elements which the
+    /// adoption agency would restructure, such as `<b><p></b>`, still fail with
+    /// [`crate::HtmlStreamError::UnsupportedTreeOperation`]
+    Collect(ParseErrors),
+    /// Recover as [`ErrorPolicy::Collect`] does, without the errors
+    Ignore,
+}
+
+/// The options of a parse, with presets for the html5ever options which matter for streaming.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{ParseConfig, ParseErrors, StatsCollector};
+///
+/// let errors = ParseErrors::new();
+/// ParseConfig::lenient(errors.clone())
+///     .parse_document(StatsCollector::new())
+///     .one("<p>No doctype</p>")
+///     .unwrap();
+/// assert!(!errors.take().is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct ParseConfig {
+    opts: ParseOpts,
+    errors: ErrorPolicy,
+    max_depth: Option<usize>,
+    lines: Option<SourceLines>,
+    siblings: SiblingTracking,
+}
+
+impl ParseConfig {
+    /// Fails at the first error, with an exact description of it
+    pub fn strict() -> Self {
+        Self::default().exact_errors(true)
+    }
+
+    /// Recovers from errors as [`ErrorPolicy::Collect`] describes, collecting exact descriptions of them
+    pub fn lenient(errors: ParseErrors) -> Self {
+        Self::default()
+            .exact_errors(true)
+            .errors(ErrorPolicy::Collect(errors))
+    }
+
+    /// Recovers from errors as [`ErrorPolicy::Collect`] describes, without describing them, so the tokenizer doesn't
+    /// spend time formatting messages nobody reads
+    pub fn fast() -> Self {
+        Self::default().errors(ErrorPolicy::Ignore)
+    }
+
+    /// Sets what the parse does with errors
+    pub fn errors(self, errors: ErrorPolicy) -> Self {
+        Self { errors, ..self }
+    }
+
+    /// Fails the parse with [`crate::HtmlStreamError::LimitExceeded`] when more than `max_depth` elements are open at once, to bound
+    /// the context each event is given for untrusted input, which browsers would nest without limit
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Self {
+            max_depth: Some(max_depth),
+            ..self
+        }
+    }
+
+    /// Records the source lines of the parse, for a [`crate::ProvenanceTracker`]
+    pub fn source_lines(self, lines: SourceLines) -> Self {
+        Self {
+            lines: Some(lines),
+            ..self
+        }
+    }
+
+    /// Sets how much of the siblings before each element are kept, for selectors with `+` and `~`, none by default
+    pub fn sibling_tracking(self, siblings: SiblingTracking) -> Self {
+        Self { siblings, ..self }
+    }
+
+    /// Sets whether errors are described exactly, which costs time formatting them
+    pub fn exact_errors(mut self, exact_errors: bool) -> Self {
+        self.opts.tokenizer.exact_errors = exact_errors;
+        self.opts.tree_builder.exact_errors = exact_errors;
+        self
+    }
+
+    /// Sets whether `<noscript>` content is parsed as raw text, as in a browser with scripting, rather than as markup
+    pub fn scripting_enabled(mut self, scripting_enabled: bool) -> Self {
+        self.opts.tree_builder.scripting_enabled = scripting_enabled;
+        self
+    }
+
+    /// Sets whether the document is an iframe's `srcdoc`, which doesn't need a doctype
+    pub fn iframe_srcdoc(mut self, iframe_srcdoc: bool) -> Self {
+        self.opts.tree_builder.iframe_srcdoc = iframe_srcdoc;
+        self
+    }
+
+    /// Sets whether a leading byte order mark is discarded
+    pub fn discard_bom(mut self, discard_bom: bool) -> Self {
+        self.opts.tokenizer.discard_bom = discard_bom;
+        self
+    }
+
+    /// The html5ever options
+    pub fn parse_opts(&self) -> ParseOpts {
+        self.opts.clone()
+    }
+
+    /// Parses a document like [`crate::parse_document`], with these options
+    pub fn parse_document<Sink>(self, sink: Sink) -> Parser<ParseTraverser<Sink>>
+    where
+        Sink: HtmlSink<u32>,
+    {
+        let mut sink = ParseTraverser::new_document(sink);
+        sink.errors = self.errors;
+        sink.max_depth = self.max_depth;
+        sink.lines = self.lines;
+        sink.chain = SiblingChain::new(self.siblings);
+        html5ever::parse_document(sink, self.opts)
+    }
+
+    /// Parses a fragment like [`crate::parse_fragment`], with these options
+    pub fn parse_fragment<Sink>(
+        self,
+        sink: Sink,
+    ) -> Parser<ParseTraverser<ElementUnwrapper<u32, Sink, NameSelector>>>
+    where
+        Sink: HtmlSink<u32>,
+    {
+        parse_fragment_with(
+            sink,
+            self.opts,
+            self.errors,
+            self.max_depth,
+            self.lines,
+            self.siblings,
+        )
+    }
+
+    /// Streams a tree like [`crate::stream_tree`], keeping the siblings these options track, the parse options don't apply
+    pub fn stream_tree<T, S>(self, node: &T, sink: S) -> Result<S::Output, HtmlStreamError>
+    where
+        T: StreamTree + ?Sized,
+        S: HtmlSink<u32>,
+    {
+        stream_tree_with(node, sink, self.siblings)
+    }
+
+    /// Streams xml like [`crate::stream_quick_xml`], keeping the siblings these options track, the parse options don't apply
+    #[cfg(feature = "quick-xml")]
+    pub fn stream_quick_xml<S: HtmlSink<u32>>(
+        self,
+        xml: &str,
+        sink: S,
+    ) -> Result<S::Output, HtmlStreamError> {
+        crate::xml_events::stream_quick_xml_with(xml, sink, self.siblings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{HtmlSerializer, HtmlStreamError, StatsCollector};
+
+    const DOC: &str =
+        "<html><head></head><body><p>No doctype, stray end tag</p></span></body></html>";
+
+    #[test]
+    fn presets() {
+        assert!(matches!(
+            ParseConfig::strict()
+                .parse_document(StatsCollector::new())
+                .one(DOC),
+            Err(HtmlStreamError::Parse(_))
+        ));
+
+        let errors = ParseErrors::new();
+        ParseConfig::lenient(errors.clone())
+            .parse_document(StatsCollector::new())
+            .one(DOC)
+            .unwrap();
+        assert_eq!(errors.take().len(), 2);
+
+        ParseConfig::fast()
+            .parse_document(StatsCollector::new())
+            .one(DOC)
+            .unwrap();
+    }
+
+    fn serialize(config: ParseConfig, html: &str) -> Result<String, HtmlStreamError> {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        config.parse_document(&mut serializer).one(html)?;
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn recover() {
+        const FOSTERED: &str =
+            "<!DOCTYPE html><table>stray<tr><td>x</td></tr><div>y</div></table><p>after</p>";
+        const REPEATED_HTML: &str = r#"<!DOCTYPE html><html><body><html lang="en"><p>x</p>"#;
+
+        for html in [FOSTERED, REPEATED_HTML] {
+            assert!(matches!(
+                serialize(ParseConfig::strict(), html),
+                Err(HtmlStreamError::Parse(_))
+            ));
+        }
+
+        let errors = ParseErrors::new();
+        assert_eq!(
+            serialize(ParseConfig::lenient(errors.clone()), FOSTERED).unwrap(),
+            "<!DOCTYPE html><html><head></head><body><table>stray<tbody><tr><td>x</td><div>y</div></tr></tbody></table><p>after</p></body></html>"
+        );
+        assert!(!errors.take().is_empty());
+        assert_eq!(
+            serialize(ParseConfig::fast(), REPEATED_HTML).unwrap(),
+            "<!DOCTYPE html><html><head></head><body><p>x</p></body></html>"
+        );
+        serialize(ParseConfig::lenient(errors.clone()), REPEATED_HTML).unwrap();
+        assert!(errors
+            .take()
+            .iter()
+            .any(|error| error.contains("already been streamed")));
+
+        assert!(matches!(
+            serialize(ParseConfig::fast(), "<b><p>x</b>y</p>"),
+            Err(HtmlStreamError::UnsupportedTreeOperation(_))
+        ));
+    }
+
+    #[test]
+    fn limit_depth() {
+        let nested = format!("<!DOCTYPE html>{}", "<div>".repeat(6));
+        let stats = ParseConfig::fast()
+            .max_depth(8)
+            .parse_document(StatsCollector::new())
+            .one(nested.as_str())
+            .unwrap();
+        assert_eq!(stats.max_depth, 8);
+        assert!(matches!(
+            ParseConfig::fast()
+                .max_depth(7)
+                .parse_document(StatsCollector::new())
+                .one(nested.as_str()),
+            Err(HtmlStreamError::LimitExceeded {
+                limit: "depth",
+                max: 7
+            })
+        ));
+
+        let fragment = "<div><p>a</p></div>";
+        ParseConfig::fast()
+            .max_depth(2)
+            .parse_fragment(StatsCollector::new())
+            .one(fragment)
+            .unwrap();
+        assert!(ParseConfig::fast()
+            .max_depth(1)
+            .parse_fragment(StatsCollector::new())
+            .one(fragment)
+            .is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, LocalName};
+
+use crate::{
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, Severity,
+    TextBoundary, Violation, ViolationKind,
+};
+
+fn is_html<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    element.name.ns == ns!(html)
+}
+
+/// Interactive content, which mustn't be nested in links and buttons
+fn is_interactive<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    is_html(element)
+        && match element.name.local {
+            local_name!("a")
+            | local_name!("button")
+            | local_name!("details")
+            | local_name!("embed")
+            | local_name!("iframe")
+            | local_name!("label")
+            | local_name!("select")
+            | local_name!("textarea") => true,
+            local_name!("input") => !element
+                .attr_local("type")
+                .is_some_and(|t| t.eq_ignore_ascii_case("hidden")),
+            _ => false,
+        }
+}
+
+/// Elements which only take phrasing content, so can't contain blocks
+fn is_phrasing_only<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    is_html(element)
+        && matches!(
+            element.name.local,
+            local_name!("abbr")
+                | local_name!("b")
+                | local_name!("bdi")
+                | local_name!("bdo")
+                | local_name!("cite")
+                | local_name!("code")
+                | local_name!("dfn")
+                | local_name!("em")
+                | local_name!("h1")
+                | local_name!("h2")
+                | local_name!("h3")
+                | local_name!("h4")
+                | local_name!("h5")
+                | local_name!("h6")
+                | local_name!("i")
+                | local_name!("kbd")
+                | local_name!("label")
+                | local_name!("mark")
+                | local_name!("pre")
+                | local_name!("q")
+                | local_name!("s")
+                | local_name!("samp")
+                | local_name!("small")
+                | local_name!("span")
+                | local_name!("strong")
+                | local_name!("sub")
+                | local_name!("sup")
+                | local_name!("time")
+                | local_name!("u")
+                | local_name!("var")
+        )
+}
+
+/// Block level elements, flow content which isn't phrasing content
+fn is_block<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    is_html(element)
+        && matches!(
+            element.name.local,
+            local_name!("address")
+                | local_name!("article")
+                | local_name!("aside")
+                | local_name!("blockquote")
+                | local_name!("div")
+                | local_name!("dl")
+                | local_name!("figure")
+                | local_name!("footer")
+                | local_name!("form")
+                | local_name!("h1")
+                | local_name!("h2")
+                | local_name!("h3")
+                | local_name!("h4")
+                | local_name!("h5")
+                | local_name!("h6")
+                | local_name!("header")
+                | local_name!("hr")
+                | local_name!("nav")
+                | local_name!("ol")
+                | local_name!("p")
+                | local_name!("pre")
+                | local_name!("section")
+                | local_name!("table")
+                | local_name!("ul")
+        )
+}
+
+fn is_named<Handle>(element: Option<&HtmlPathElement<'_, Handle>>, names: &[LocalName]) -> bool {
+    element.is_some_and(|element| is_html(element) && names.contains(&element.name.local))
+}
+
+/// A wrapper which reports common nesting mistakes against html's content model, along with the inner sink's output.
+///
+/// The reported nestings are those which the parser keeps: interactive elements inside `<a>` or `<button>`, blocks inside elements
+/// which only take phrasing content such as `<span>` or `<h1>`, and list items outside their lists.
+/// Mistakes which the parser corrects without a parse error, such as a `<div>` inside a `<p>` which closes the `<p>`,
+/// have already been fixed by the time they reach a sink so aren't seen.
+pub struct ContentModelChecker<S> {
+    inner: S,
+    severity: Severity,
+    paths: CssPathTracker,
+    violations: Vec<Violation>,
+}
+
+impl ContentModelChecker<()> {
+    /// A checker which doesn't forward the content anywhere
+    pub fn new() -> Self {
+        Self::wrap(())
+    }
+}
+
+impl Default for ContentModelChecker<()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> ContentModelChecker<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            severity: Severity::Error,
+            paths: CssPathTracker::new(),
+            violations: vec![],
+        }
+    }
+
+    /// Sets the severity of the violations, they are errors by default
+    pub fn severity(self, severity: Severity) -> Self {
+        Self { severity, ..self }
+    }
+
+    fn check<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<String> {
+        let name = &element.name.local;
+        let parent = context.last();
+        if is_interactive(element) {
+            let container = context.iter().rev().find(|ancestor| {
+                is_named(Some(*ancestor), &[local_name!("a"), local_name!("button")])
+            });
+            if let Some(container) = container {
+                return Some(format!(
+                    "interactive <{}> is inside <{}>",
+                    name, container.name.local
+                ));
+            }
+        }
+        if let Some(parent) = parent.filter(|parent| is_block(element) && is_phrasing_only(parent))
+        {
+            return Some(format!(
+                "block <{}> is inside <{}>, which only takes phrasing content",
+                name, parent.name.local
+            ));
+        }
+        if !is_html(element) {
+            return None;
+        }
+        let outside = match element.name.local {
+            local_name!("li")
+                if !is_named(
+                    parent,
+                    &[local_name!("ul"), local_name!("ol"), local_name!("menu")],
+                ) =>
+            {
+                "a list"
+            }
+            local_name!("dt") | local_name!("dd") => {
+                let grandparent = context.len().checked_sub(2).map(|i| &context[i]);
+                if is_named(parent, &[local_name!("dl")])
+                    || is_named(parent, &[local_name!("div")])
+                        && is_named(grandparent, &[local_name!("dl")])
+                {
+                    return None;
+                }
+                "a description list"
+            }
+            local_name!("figcaption") if !is_named(parent, &[local_name!("figure")]) => "a figure",
+            _ => return None,
+        };
+        Some(match parent {
+            Some(parent) => format!(
+                "<{}> is outside {}, in <{}>",
+                name, outside, parent.name.local
+            ),
+            None => format!("<{}> is outside {}", name, outside),
+        })
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for ContentModelChecker<S> {
+    type Output = (S::Output, Vec<Violation>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        if !context
+            .iter()
+            .any(|ancestor| is_html(ancestor) && ancestor.name.local == local_name!("template"))
+        {
+            if let Some(message) = self.check(context, element) {
+                self.violations.push(Violation {
+                    kind: ViolationKind::ContentModel,
+                    severity: self.severity,
+                    path: self.paths.element_path(context, element),
+                    message,
+                });
+            }
+        }
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.violations))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, test::stream_docs};
+
+    #[test]
+    fn content_model_violations() {
+        let ((), violations) = parse_document(ContentModelChecker::new(), Default::default())
+            .one(r#"<!DOCTYPE html><html><head></head><body><a href="/"><button>go</button><input type="hidden"></a><span><div>block</div></span><p>a<div>b</div><li>loose</li><dl><div><dt>t</dt><dd>d</dd></div></dl><figcaption>c</figcaption></body></html>"#)
+            .unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.path.as_str(), v.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "html > body > a:nth-child(1) > button:nth-child(1)",
+                    "interactive <button> is inside <a>"
+                ),
+                (
+                    "html > body > span:nth-child(2) > div:nth-child(1)",
+                    "block <div> is inside <span>, which only takes phrasing content"
+                ),
+                (
+                    "html > body > li:nth-child(5)",
+                    "<li> is outside a list, in <body>"
+                ),
+                (
+                    "html > body > figcaption:nth-child(7)",
+                    "<figcaption> is outside a figure, in <body>"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_each_document() {
+        let mut checker = ContentModelChecker::new();
+        let found = stream_docs(
+            &[
+                "<!DOCTYPE html>",
+                r#"<!DOCTYPE html><html><head></head><body><a href="/"><span><em><button>go</button></em></span></a><ul><li><span><div>x</div></span></li></ul></body></html>"#,
+                "<!DOCTYPE html><html><head></head><body><ul><li><div>fine</div></li></ul></body></html>",
+            ],
+            &mut checker,
+        )
+        .into_iter()
+        .map(|((), violations)| {
+            violations
+                .into_iter()
+                .map(|v| (v.path, v.message))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                vec![],
+                vec![
+                    (
+                        "html > body > a:nth-child(1) > span:nth-child(1) > em:nth-child(1) > button:nth-child(1)".to_owned(),
+                        "interactive <button> is inside <a>".to_owned()
+                    ),
+                    (
+                        "html > body > ul:nth-child(2) > li:nth-child(1) > span:nth-child(1) > div:nth-child(1)".to_owned(),
+                        "block <div> is inside <span>, which only takes phrasing content".to_owned()
+                    ),
+                ],
+                vec![],
+            ]
+        );
+    }
+}
@@ -0,0 +1,518 @@
+use std::{cell::OnceCell, collections::HashMap, fmt, ops::Deref, sync::Arc};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+use crate::{
+    event::{OwnedPathElement, OwnedSibling},
+    selector::Selector,
+    HtmlPathElement,
+};
+
+/// The text direction set by a `dir` attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+    Auto,
+}
+
+/// The state an element inherits from its ancestors, carried on each [`HtmlPathElement`] so it can be queried without walking the context.
+///
+/// Producers compute it incrementally as each element is opened, with [`InheritedState::for_element`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InheritedState {
+    /// The language from the nearest `lang` or `xml:lang` attribute
+    pub lang: Option<StrTendril>,
+    /// The direction from the nearest valid `dir` attribute
+    pub dir: Option<Direction>,
+    /// Inside a `pre` or `listing` element, where whitespace is preserved
+    pub in_pre: bool,
+    /// Inside a `template` element, so not rendered
+    pub in_template: bool,
+    /// Editable because of the nearest `contenteditable` attribute
+    pub editable: bool,
+}
+
+impl InheritedState {
+    /// The state of an element with the given name and attributes whose parent has the `parent` state, or which is a root
+    pub fn for_element(parent: Option<&Self>, name: &QualName, attrs: &[Attribute]) -> Self {
+        let mut state = parent.cloned().unwrap_or_default();
+        let attr = |ns, local| {
+            attrs
+                .iter()
+                .find(|attr| attr.name.ns == ns && attr.name.local == local)
+                .map(|attr| &attr.value)
+        };
+        if let Some(lang) = attr(ns!(xml), local_name!("lang")).or(attr(ns!(), local_name!("lang")))
+        {
+            state.lang = Some(lang.clone());
+        }
+        match attr(ns!(), local_name!("dir")).map(|dir| dir.to_ascii_lowercase()) {
+            Some(dir) if dir == "ltr" => state.dir = Some(Direction::Ltr),
+            Some(dir) if dir == "rtl" => state.dir = Some(Direction::Rtl),
+            Some(dir) if dir == "auto" => state.dir = Some(Direction::Auto),
+            _ => {}
+        }
+        match attr(ns!(), local_name!("contenteditable")).map(|value| value.to_ascii_lowercase()) {
+            Some(value) if value.is_empty() || value == "true" || value == "plaintext-only" => {
+                state.editable = true
+            }
+            Some(value) if value == "false" => state.editable = false,
+            _ => {}
+        }
+        if name.ns == ns!(html) {
+            match name.local {
+                local_name!("pre") | local_name!("listing") => state.in_pre = true,
+                local_name!("template") => state.in_template = true,
+                _ => {}
+            }
+        }
+        state
+    }
+}
+
+/// What an element contains, which a [`crate::ContentBuffer`] records for css's `:empty` and [`crate::selector::TextContainsSelector`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementContent {
+    /// Whether it has no element or text children, comments aside, as in `:empty`
+    pub empty: bool,
+    /// The text of all its descendants, as in the DOM's `textContent`
+    pub text: StrTendril,
+}
+
+/// The position of an element among the element children of its parent, or of the document, counting from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SiblingPosition {
+    /// The position among all the element siblings, as in `:nth-child`
+    pub index: usize,
+    /// The position among the siblings with the same name, as in `:nth-of-type`
+    pub index_of_type: usize,
+    /// The number of element siblings, including the element, only known once the parent has closed, so only set by a
+    /// [`crate::LookaheadBuffer`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub count: Option<usize>,
+    /// The number of element siblings with the same name, including the element, set along with the count
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub count_of_type: Option<usize>,
+}
+
+/// Counts the element children of the document and of each open element, so producers can give each element its [`SiblingPosition`]
+#[derive(Debug, Default)]
+pub struct SiblingCounter {
+    /// The number of children of each open element, in total and by name
+    open: Vec<(usize, HashMap<QualName, usize>)>,
+}
+
+impl SiblingCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The position of an element with the name appended at the depth, the length of its context
+    pub fn element(&mut self, depth: usize, name: &QualName) -> SiblingPosition {
+        // the children of the previous sibling are no longer open
+        self.open.truncate(depth + 1);
+        self.open.resize_with(depth + 1, Default::default);
+        let (children, by_name) = &mut self.open[depth];
+        *children += 1;
+        let of_type = by_name.entry(name.clone()).or_default();
+        *of_type += 1;
+        SiblingPosition {
+            index: *children,
+            index_of_type: *of_type,
+            count: None,
+            count_of_type: None,
+        }
+    }
+
+    /// Starts again for another document
+    pub fn reset(&mut self) {
+        self.open.clear();
+    }
+}
+
+/// The previous element sibling of an element, a shared copy which links on to the siblings before it
+#[derive(Clone)]
+pub struct PreviousSibling<Handle>(Arc<Sibling<Handle>>);
+
+struct Sibling<Handle> {
+    element: HtmlPathElement<'static, Handle>,
+    /// The owned copy of the sibling, made once for all the owned copies of the elements after it
+    owned: OnceCell<OwnedSibling<Handle>>,
+}
+
+impl<Handle> PreviousSibling<Handle> {
+    pub(crate) fn new(
+        element: HtmlPathElement<'static, Handle>,
+        owned: Option<OwnedSibling<Handle>>,
+    ) -> Self {
+        Self(Arc::new(Sibling {
+            element,
+            owned: owned.map(OnceCell::from).unwrap_or_default(),
+        }))
+    }
+}
+
+impl<Handle: Copy> PreviousSibling<Handle> {
+    /// The owned copy of the sibling, linking on to the copies of the siblings before it
+    pub(crate) fn to_owned_sibling(&self) -> OwnedSibling<Handle> {
+        // walk back to the nearest sibling which has been copied, and copy those after it from the first, as copying a long chain
+        // recursively would overflow the stack
+        let mut uncopied = vec![];
+        let mut copied = None;
+        let mut sibling = Some(self);
+        while let Some(next) = sibling {
+            if let Some(owned) = next.0.owned.get() {
+                copied = Some(owned.clone());
+                break;
+            }
+            uncopied.push(next);
+            sibling = next.previous_sibling.as_ref();
+        }
+        for sibling in uncopied.into_iter().rev() {
+            let owned = OwnedSibling::new(OwnedPathElement {
+                previous_sibling: copied.take(),
+                ..OwnedPathElement::without_siblings(sibling)
+            });
+            copied = Some(sibling.0.owned.get_or_init(|| owned).clone());
+        }
+        copied.expect("the sibling has been copied")
+    }
+}
+
+impl<Handle> Deref for PreviousSibling<Handle> {
+    type Target = HtmlPathElement<'static, Handle>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.element
+    }
+}
+
+impl<Handle: fmt::Debug> fmt::Debug for PreviousSibling<Handle> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PreviousSibling")
+            .field(&self.0.element.handle)
+            .finish()
+    }
+}
+
+impl<Handle> Drop for PreviousSibling<Handle> {
+    fn drop(&mut self) {
+        // unlink the chain iteratively, dropping a long one recursively would overflow the stack
+        let mut previous =
+            Arc::get_mut(&mut self.0).and_then(|sibling| sibling.element.previous_sibling.take());
+        while let Some(mut sibling) = previous {
+            previous = Arc::get_mut(&mut sibling.0)
+                .and_then(|sibling| sibling.element.previous_sibling.take());
+        }
+    }
+}
+
+/// How much of the siblings before each element a producer keeps, for the sibling combinators `+` and `~`, see
+/// [`crate::ParseConfig::sibling_tracking`]
+///
+/// The siblings are those of the source, as the positions are, so an element which a filter such as an [`crate::ElementRemover`]
+/// drops is still the sibling of those after it, for selectors after the filter as for those before it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SiblingTracking {
+    /// No siblings are kept, so `+` and `~` select nothing. This is the default, as it costs nothing
+    #[default]
+    Off,
+    /// Only the previous sibling is kept, for `+`, which holds a copy of one element for each open element
+    Adjacent,
+    /// All the previous siblings are kept, for `~`, which holds a copy of each element child of an open element until it closes
+    All,
+}
+
+/// Keeps the last element child of the document and of each open element, so producers can link each element to its
+/// [`PreviousSibling`]
+#[derive(Debug)]
+pub struct SiblingChain<Handle> {
+    tracking: SiblingTracking,
+    last: Vec<Option<PreviousSibling<Handle>>>,
+}
+
+impl<Handle> Default for SiblingChain<Handle> {
+    fn default() -> Self {
+        Self {
+            tracking: SiblingTracking::Off,
+            last: vec![],
+        }
+    }
+}
+
+impl<Handle: Clone> SiblingChain<Handle> {
+    pub fn new(tracking: SiblingTracking) -> Self {
+        Self {
+            tracking,
+            last: vec![],
+        }
+    }
+
+    /// The previous sibling of an element appended at the depth, the length of its context, recording a copy of the element as the
+    /// previous sibling of the next, unless tracking is off
+    pub fn element(
+        &mut self,
+        depth: usize,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<PreviousSibling<Handle>> {
+        if self.tracking == SiblingTracking::Off {
+            return None;
+        }
+        // the children of the previous sibling are no longer open
+        self.last.truncate(depth + 1);
+        self.last.resize_with(depth + 1, Default::default);
+        let previous = self.last[depth].take();
+        let chained = match self.tracking {
+            SiblingTracking::All => previous.clone(),
+            _ => None,
+        };
+        self.last[depth] = Some(PreviousSibling::new(
+            HtmlPathElement {
+                previous_sibling: chained,
+                content: None,
+                ..element.clone().into_owned()
+            },
+            None,
+        ));
+        previous
+    }
+
+    /// Starts again for another document
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
+
+/// Queries on the open elements of an [`crate::HtmlContext`], walking the ancestors from the innermost outwards
+pub trait ContextExt<'a, Handle> {
+    /// The innermost open element matching the selector
+    fn closest<S: Selector>(&self, selector: &S) -> Option<&HtmlPathElement<'a, Handle>>;
+
+    /// Whether any open element matches the selector
+    fn is_inside<S: Selector>(&self, selector: &S) -> bool {
+        self.closest(selector).is_some()
+    }
+
+    /// The number of open elements, an element appended in this context has this depth plus one
+    fn depth(&self) -> usize;
+
+    /// The value of the attribute on the innermost open element which has it, such as the inherited `lang`
+    fn nearest_attr(&self, local: &str) -> Option<&StrTendril>;
+
+    /// The state inherited from the innermost open element, `None` at the document level
+    fn inherited(&self) -> Option<&InheritedState>;
+
+    fn lang(&self) -> Option<&str> {
+        self.inherited()?.lang.as_deref()
+    }
+
+    fn dir(&self) -> Option<Direction> {
+        self.inherited()?.dir
+    }
+
+    fn in_pre(&self) -> bool {
+        self.inherited().is_some_and(|state| state.in_pre)
+    }
+
+    fn in_template(&self) -> bool {
+        self.inherited().is_some_and(|state| state.in_template)
+    }
+
+    fn is_editable(&self) -> bool {
+        self.inherited().is_some_and(|state| state.editable)
+    }
+}
+
+impl<'a, Handle> ContextExt<'a, Handle> for [HtmlPathElement<'a, Handle>] {
+    fn closest<S: Selector>(&self, selector: &S) -> Option<&HtmlPathElement<'a, Handle>> {
+        self.iter().rev().find(|element| selector.is_match(element))
+    }
+
+    fn depth(&self) -> usize {
+        self.len()
+    }
+
+    fn nearest_attr(&self, local: &str) -> Option<&StrTendril> {
+        self.iter()
+            .rev()
+            .find_map(|element| element.attr_local(local))
+    }
+
+    fn inherited(&self) -> Option<&InheritedState> {
+        self.last().map(|element| &element.inherited)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, test::stream_doc, HtmlContext, HtmlSink};
+
+    /// The text, its closest box, whether it's emphasised, its depth and its language
+    type TextContext = (String, Option<u32>, bool, usize, Option<String>);
+
+    #[derive(Default)]
+    struct TextContexts(Vec<TextContext>);
+
+    impl HtmlSink<u32> for &mut TextContexts {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            _element: &HtmlPathElement<'_, u32>,
+        ) {
+        }
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            self.0.push((
+                text.to_owned(),
+                context
+                    .closest(&css_select!(."box"))
+                    .map(|element| element.handle),
+                context.is_inside(&css_select!("em")),
+                context.depth(),
+                context.nearest_attr("lang").map(|lang| lang.to_string()),
+            ));
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    /// The text, its language and direction, whether it's preformatted and whether it's editable
+    type TextState = (String, Option<String>, Option<Direction>, bool, bool);
+
+    #[derive(Default)]
+    struct TextStates(Vec<TextState>);
+
+    impl HtmlSink<u32> for &mut TextStates {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            _element: &HtmlPathElement<'_, u32>,
+        ) {
+        }
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            self.0.push((
+                text.to_owned(),
+                context.lang().map(str::to_owned),
+                context.dir(),
+                context.in_pre(),
+                context.is_editable(),
+            ));
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn inherited_state() {
+        let mut texts = TextStates::default();
+        stream_doc(
+            r#"<!DOCTYPE html><html lang="en" dir="ltr"><head></head><body><div dir="RTL" contenteditable><section lang="ar" dir="bogus">a<pre contenteditable="false">b</pre></section></div><p>c</p></body></html>"#,
+            &mut texts,
+        );
+        assert_eq!(
+            texts.0,
+            [
+                (
+                    "a".to_owned(),
+                    Some("ar".to_owned()),
+                    Some(Direction::Rtl),
+                    false,
+                    true
+                ),
+                (
+                    "b".to_owned(),
+                    Some("ar".to_owned()),
+                    Some(Direction::Rtl),
+                    true,
+                    false
+                ),
+                (
+                    "c".to_owned(),
+                    Some("en".to_owned()),
+                    Some(Direction::Ltr),
+                    false,
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn context_queries() {
+        let mut texts = TextContexts::default();
+        stream_doc(
+            r#"<!DOCTYPE html><html lang="en"><head></head><body><div class="box"><p class="box" lang="fr">a<em>b</em></p></div><p>c</p></body></html>"#,
+            &mut texts,
+        );
+        assert_eq!(
+            texts.0,
+            [
+                ("a".to_owned(), Some(5), false, 4, Some("fr".to_owned())),
+                ("b".to_owned(), Some(5), true, 5, Some("fr".to_owned())),
+                ("c".to_owned(), None, false, 3, Some("en".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn sibling_chain() {
+        let element = |handle| {
+            HtmlPathElement::new(
+                handle,
+                QualName::new(None, ns!(html), local_name!("li")),
+                vec![],
+            )
+        };
+        let mut chain = SiblingChain::new(SiblingTracking::All);
+        let mut last = None;
+        for handle in 0..200_000 {
+            last = Some(element(handle));
+            let previous = chain.element(1, last.as_ref().unwrap());
+            last.as_mut().unwrap().previous_sibling = previous;
+        }
+        let last = last.unwrap();
+        assert_eq!(
+            last.previous_siblings()
+                .take(3)
+                .map(|sibling| sibling.handle)
+                .collect::<Vec<_>>(),
+            [199_998, 199_997, 199_996]
+        );
+        // a child of the last doesn't follow it
+        assert!(chain.element(2, &element(200_000)).is_none());
+        // and the list is no longer open once the next element is appended at its parent's depth
+        assert!(chain.element(0, &element(200_001)).is_none());
+        assert!(chain.element(1, &element(200_002)).is_none());
+        // dropping the chain unlinks it without recursing
+        drop(chain);
+        drop(last);
+    }
+}
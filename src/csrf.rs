@@ -0,0 +1,172 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+use crate::{
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, SyntheticHandle, SyntheticHandles,
+    TextBoundary,
+};
+
+/// A filter which adds a hidden csrf token field as the first child of each `<form method="post">`.
+///
+/// The callback is called with the form's `action`, empty when it has none, and gives the token, so tokens can be bound to
+/// the endpoint they are for, or `None` to leave the form alone. Forms which submit with `GET` or close a `<dialog>` don't get a token.
+pub struct CsrfInjector<S, F> {
+    inner: S,
+    token: F,
+    field_name: String,
+    handles: SyntheticHandles,
+}
+
+impl<S, F: FnMut(&str) -> Option<String>> CsrfInjector<S, F> {
+    pub fn wrap(inner: S, token: F) -> Self {
+        Self {
+            inner,
+            token,
+            field_name: "csrf_token".to_owned(),
+            handles: SyntheticHandles::new(),
+        }
+    }
+
+    /// Sets the name of the field, `csrf_token` by default
+    pub fn field_name(self, field_name: &str) -> Self {
+        Self {
+            field_name: field_name.to_owned(),
+            ..self
+        }
+    }
+}
+
+impl<Handle, S, F> HtmlSink<Handle> for CsrfInjector<S, F>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    F: FnMut(&str) -> Option<String>,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element);
+        let posts = element.name.ns == ns!(html)
+            && element.tag_is(local_name!("form"))
+            && element
+                .attr_local("method")
+                .is_some_and(|method| method.trim().eq_ignore_ascii_case("post"));
+        if !posts {
+            return;
+        }
+        let action = element
+            .attr_local("action")
+            .map_or("", |action| action.trim());
+        let Some(token) = (self.token)(action) else {
+            return;
+        };
+        let attribute = |local: &str, value: &str| Attribute {
+            name: QualName::new(None, ns!(), local.into()),
+            value: value.into(),
+        };
+        let mut form_context = context.to_vec();
+        form_context.push(element.clone());
+        let input = HtmlPathElement::new_child(
+            self.handles.allocate(),
+            form_context.last(),
+            QualName::new(None, ns!(html), local_name!("input")),
+            vec![
+                attribute("type", "hidden"),
+                attribute("name", &self.field_name),
+                attribute("value", &token),
+            ],
+        );
+        self.inner.append_element(&form_context, &input)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.handles.reset();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn inject_tokens() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            CsrfInjector::wrap(&mut serializer, |action| {
+                (action != "https://other.example/").then(|| format!("token-for:{}", action))
+            }),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><form method="POST" action="/login"><input name="user"></form><form action="/search"><input name="q"></form><form method="post" action="https://other.example/"></form><form method="post"><!-- empty --></form></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body>"#,
+                r#"<form method="POST" action="/login"><input type="hidden" name="csrf_token" value="token-for:/login"><input name="user"></form>"#,
+                r#"<form action="/search"><input name="q"></form><form method="post" action="https://other.example/"></form>"#,
+                r#"<form method="post"><input type="hidden" name="csrf_token" value="token-for:"><!-- empty --></form></body></html>"#
+            )
+        );
+    }
+}
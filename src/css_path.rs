@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use html5ever::{local_name, namespace_url, ns};
+
+use crate::{HtmlContext, HtmlPathElement};
+
+/// Writes one step of a path, with the element's id or classes and its position among its siblings when it needs one to be located
+fn write_step<Handle>(
+    path: &mut String,
+    element: &HtmlPathElement<'_, Handle>,
+    position: Option<usize>,
+) {
+    if !path.is_empty() {
+        path.push_str(" > ");
+    }
+    path.push_str(&element.name.local);
+    if let Some(id) = element.id() {
+        write!(path, "#{}", id).unwrap();
+        return;
+    }
+    for class in element.classes() {
+        write!(path, ".{}", class).unwrap();
+    }
+    let unique = element.name.ns == ns!(html)
+        && matches!(
+            element.name.local,
+            local_name!("html") | local_name!("head") | local_name!("body")
+        );
+    if let (Some(position), false) = (position, unique) {
+        write!(path, ":nth-child({})", position).unwrap();
+    }
+}
+
+/// Describes where an element is as a css-like path, such as `html > body > div#main > p.lead`, for error messages and reports.
+///
+/// Sibling positions aren't known from the context alone, use a [`CssPathTracker`] to include them.
+pub fn css_path<Handle>(
+    context: HtmlContext<'_, Handle>,
+    element: &HtmlPathElement<'_, Handle>,
+) -> String {
+    let mut path = String::new();
+    for ancestor in context.iter().chain(Some(element)) {
+        write_step(&mut path, ancestor, None);
+    }
+    path
+}
+
+/// Tracks the positions of elements among their siblings, so that paths can locate them with `:nth-child`, as in `html > body > div#main > p.lead:nth-child(3)`.
+///
+/// Every element of the stream must be passed to [`CssPathTracker::element`], positions count the elements which reached the sink.
+#[derive(Default)]
+pub struct CssPathTracker {
+    /// The number of child elements seen of the document and each open element
+    children: Vec<usize>,
+    /// The position of each open element
+    positions: Vec<usize>,
+}
+
+impl CssPathTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an element appended in the context
+    pub fn element<Handle>(&mut self, context: HtmlContext<'_, Handle>) {
+        let depth = context.len();
+        self.children.resize(depth + 1, 0);
+        self.children[depth] += 1;
+        self.positions.resize(depth, 0);
+        self.positions.push(self.children[depth]);
+        self.children.push(0);
+    }
+
+    /// The path to the element most recently passed to [`CssPathTracker::element`]
+    pub fn element_path<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> String {
+        let mut path = self.context_path(context);
+        write_step(
+            &mut path,
+            element,
+            self.positions.get(context.len()).copied(),
+        );
+        path
+    }
+
+    /// The path to the innermost element of the context, where a text or comment event is
+    pub fn context_path<Handle>(&self, context: HtmlContext<'_, Handle>) -> String {
+        let mut path = String::new();
+        for (depth, ancestor) in context.iter().enumerate() {
+            write_step(&mut path, ancestor, self.positions.get(depth).copied());
+        }
+        path
+    }
+
+    /// Starts again for another document
+    pub fn reset(&mut self) {
+        self.children.clear();
+        self.positions.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::StrTendril;
+
+    use super::*;
+    use crate::{test::stream_doc, HtmlSink};
+
+    #[derive(Default)]
+    struct Paths {
+        tracker: CssPathTracker,
+        paths: Vec<String>,
+    }
+
+    impl HtmlSink<u32> for &mut Paths {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            context: HtmlContext<'_, u32>,
+            element: &HtmlPathElement<'_, u32>,
+        ) {
+            self.tracker.element(context);
+            if element.has_class("lead") {
+                self.paths.push(self.tracker.element_path(context, element));
+                self.paths.push(css_path(context, element));
+            }
+        }
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            if text == "here" {
+                self.paths.push(self.tracker.context_path(context));
+            }
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {
+            self.tracker.reset()
+        }
+    }
+
+    #[test]
+    fn element_paths() {
+        let mut paths = Paths::default();
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><div id="main"><h1>Title</h1><ul><li>a</li></ul><p class="lead">Lead <b>here</b></p></div></body></html>"#,
+            &mut paths,
+        );
+        assert_eq!(
+            paths.paths,
+            [
+                "html > body > div#main > p.lead:nth-child(3)",
+                "html > body > div#main > p.lead",
+                "html > body > div#main > p.lead:nth-child(3) > b:nth-child(1)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use html5ever::{namespace_url, ns, tendril::StrTendril, Attribute};
+
+use crate::{
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// Attributes which refer to elements by a list of ids
+const ID_REFERENCES: &[&str] = &[
+    "for",
+    "form",
+    "list",
+    "headers",
+    "aria-activedescendant",
+    "aria-controls",
+    "aria-describedby",
+    "aria-details",
+    "aria-errormessage",
+    "aria-flowto",
+    "aria-labelledby",
+    "aria-owns",
+];
+
+/// The prefix of the ids given to renamed duplicates, which the document's own ids are assumed not to use
+const RENAMED_PREFIX: &str = "dup-";
+
+/// An element whose id was already used earlier in the document, as reported by [`DuplicateIds`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateId {
+    pub id: String,
+    /// The css path of the duplicate, as described by [`CssPathTracker`]
+    pub path: String,
+    /// The id the duplicate was given, if renaming
+    pub renamed_to: Option<String>,
+}
+
+/// A wrapper which tracks the `id`s seen in each document and outputs the duplicates along with the inner sink's output.
+///
+/// With [`DuplicateIds::rename_duplicates`], later occurrences are given new ids by prefixing `dup-2-`, `dup-3-` and so on, and same-document
+/// references which follow a renamed element, fragment links and attributes like `for` and `aria-labelledby`, are rewritten to refer to it.
+/// References before the duplicate keep referring to the earlier element, which is where a browser would resolve them to. The `dup-`
+/// prefix is reserved for the new ids, so they can't clash with the ids which come later in the document, as long as it doesn't use it.
+pub struct DuplicateIds<S> {
+    inner: S,
+    rename: bool,
+    paths: CssPathTracker,
+    /// The number of times each id of the document has been seen, not counting the new ids
+    seen: HashMap<String, usize>,
+    /// The id each renamed id currently refers to
+    renamed: HashMap<String, String>,
+    duplicates: Vec<DuplicateId>,
+}
+
+impl<S> DuplicateIds<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            rename: false,
+            paths: CssPathTracker::new(),
+            seen: HashMap::new(),
+            renamed: HashMap::new(),
+            duplicates: vec![],
+        }
+    }
+
+    /// Gives duplicates new ids with the reserved prefix and rewrites the references which follow them
+    pub fn rename_duplicates(self, rename: bool) -> Self {
+        Self { rename, ..self }
+    }
+
+    /// Rewrites a reference attribute's ids to the renamed ids, if any are renamed
+    fn rewrite_reference(&self, attr: &Attribute) -> Option<StrTendril> {
+        if attr.name.ns != ns!() || self.renamed.is_empty() {
+            return None;
+        }
+        let value = &*attr.value;
+        if &*attr.name.local == "href" {
+            let fragment = value.strip_prefix('#')?;
+            let renamed = self.renamed.get(fragment)?;
+            return Some(StrTendril::from(format!("#{}", renamed)));
+        }
+        if !ID_REFERENCES.contains(&&*attr.name.local) {
+            return None;
+        }
+        let ids = value.split_ascii_whitespace().collect::<Vec<_>>();
+        if !ids.iter().any(|id| self.renamed.contains_key(*id)) {
+            return None;
+        }
+        let ids = ids
+            .iter()
+            .map(|id| self.renamed.get(*id).map_or(*id, |renamed| renamed))
+            .collect::<Vec<_>>();
+        Some(StrTendril::from(ids.join(" ")))
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for DuplicateIds<S> {
+    type Output = (S::Output, Vec<DuplicateId>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        let mut attrs = Cow::Borrowed(&*element.attrs);
+        for (i, attr) in element.attrs.iter().enumerate() {
+            if let Some(value) = self.rewrite_reference(attr) {
+                attrs.to_mut()[i].value = value;
+            }
+        }
+        if let Some(id) = element.id() {
+            let count = self.seen.entry(id.to_owned()).or_default();
+            *count += 1;
+            if *count > 1 {
+                let renamed_to = self
+                    .rename
+                    .then(|| format!("{}{}-{}", RENAMED_PREFIX, count, id));
+                self.duplicates.push(DuplicateId {
+                    id: id.to_owned(),
+                    path: self.paths.element_path(context, element),
+                    renamed_to: renamed_to.clone(),
+                });
+                if let Some(renamed_to) = renamed_to {
+                    let attr = attrs
+                        .to_mut()
+                        .iter_mut()
+                        .find(|attr| attr.name.ns == ns!() && &*attr.name.local == "id")
+                        .unwrap();
+                    attr.value = StrTendril::from(renamed_to.as_str());
+                    self.renamed.insert(id.to_owned(), renamed_to);
+                }
+            }
+        }
+        match attrs {
+            Cow::Borrowed(_) => self.inner.append_element(context, element),
+            Cow::Owned(attrs) => self.inner.append_element(
+                context,
+                &HtmlPathElement {
+                    handle: element.handle,
+                    name: element.name.clone(),
+                    attrs: Cow::Owned(attrs),
+                    inherited: element.inherited.clone(),
+                    position: element.position,
+                    previous_sibling: element.previous_sibling.clone(),
+                    content: element.content.clone(),
+                },
+            ),
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.seen.clear();
+        self.renamed.clear();
+        (self.inner.reset(), std::mem::take(&mut self.duplicates))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn rename_duplicate_ids() {
+        let doc = r##"<!DOCTYPE html><html><head></head><body><input id="q"><label for="q">1</label><a href="#q">1</a><input id="q"><label for="q">2</label><a href="#q">2</a><p id="q-2" aria-labelledby="x q"></p></body></html>"##;
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), duplicates) = parse_document(
+            DuplicateIds::wrap(&mut serializer).rename_duplicates(true),
+            Default::default(),
+        )
+        .one(doc)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r##"<!DOCTYPE html><html><head></head><body><input id="q"><label for="q">1</label><a href="#q">1</a><input id="dup-2-q"><label for="dup-2-q">2</label><a href="#dup-2-q">2</a><p id="q-2" aria-labelledby="x dup-2-q"></p></body></html>"##
+        );
+        assert_eq!(
+            duplicates,
+            [DuplicateId {
+                id: "q".to_owned(),
+                path: "html > body > input#q".to_owned(),
+                renamed_to: Some("dup-2-q".to_owned()),
+            },]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    attrs::is_legacy_type, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// The attributes of an html element which have their default values, or are empty where that means nothing
+fn redundant_attrs<Handle>(element: &HtmlPathElement<'_, Handle>) -> Vec<&'static str> {
+    if element.name.ns != ns!(html) {
+        return vec![];
+    }
+    let is = |local: &str, default: &str| {
+        element
+            .attr_local(local)
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case(default))
+    };
+    let mut redundant = vec![];
+    let default_type = match element.name.local {
+        local_name!("script") => element
+            .attr_local("type")
+            .is_some_and(|t| is_legacy_type(t)),
+        local_name!("style") | local_name!("link") => is("type", "text/css"),
+        local_name!("input") => is("type", "text"),
+        _ => false,
+    };
+    if default_type {
+        redundant.push("type");
+    }
+    if element.tag_is(local_name!("form")) && is("method", "get") {
+        redundant.push("method");
+    }
+    for local in ["class", "style"] {
+        if element
+            .attr_local(local)
+            .is_some_and(|value| value.trim().is_empty())
+        {
+            redundant.push(local);
+        }
+    }
+    if element.attr_local("id").is_some_and(|id| id.is_empty()) {
+        redundant.push("id");
+    }
+    redundant
+}
+
+/// A filter which removes attributes which have their default values, such as `type="text/javascript"` on scripts, `type="text/css"`
+/// on styles and stylesheet links, `method="get"` on forms and `type="text"` on inputs, and empty `class`, `style` and `id` attributes.
+///
+/// Only html elements are affected, the document renders and behaves the same without them.
+pub struct DefaultAttributeRemover<S> {
+    inner: S,
+}
+
+impl<S> DefaultAttributeRemover<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for DefaultAttributeRemover<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let redundant = redundant_attrs(element);
+        if redundant.is_empty() {
+            return self.inner.append_element(context, element);
+        }
+        let mut stripped = Cow::Borrowed(element);
+        for local in redundant {
+            stripped.to_mut().remove_attr(local);
+        }
+        self.inner.append_element(context, &stripped)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn remove_default_attributes() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            DefaultAttributeRemover::wrap(&mut serializer),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><script type="text/javascript" src="/a.js"></script><script type="module" src="/m.js"></script><link rel="stylesheet" type="text/css" href="/a.css"><style type="text/css"></style></head><body class=" " id=""><form method="GET"><input type="text" style="" name="q"><input type="email"></form><svg><rect class=""></rect></svg></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head><script src="/a.js"></script><script type="module" src="/m.js"></script><link rel="stylesheet" href="/a.css"><style></style></head>"#,
+                r#"<body><form><input name="q"><input type="email"></form><svg><rect class=""></rect></svg></body></html>"#
+            )
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    attrs::is_executable_script,
+    boundary::append_text_to,
+    event::{owned_context, HtmlEvent},
+    raw_text::text_context,
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, RelocationBuffer,
+    TextBoundary,
+};
+
+/// What a [`ScriptDeferrer`] did with a script in the `<head>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScriptDecision {
+    /// `defer` was added to the blocking script
+    Deferred,
+    /// The blocking script was moved to the end of the body
+    Moved,
+    /// The script was left as it was, as it doesn't block the parser, being a module, `async`, `defer` or not javascript,
+    /// or is inline, which `defer` has no effect on
+    Unchanged,
+}
+
+/// The decision a [`ScriptDeferrer`] made for a script in the `<head>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScriptPlacement {
+    /// The css path of the script where it was found, as described by [`crate::CssPathTracker`]
+    pub path: String,
+    /// The script's `src`, `None` for an inline script
+    pub src: Option<String>,
+    pub decision: ScriptDecision,
+}
+
+/// Whether a script blocks the parser until it has loaded and run
+fn is_blocking<Handle>(script: &HtmlPathElement<'_, Handle>) -> bool {
+    let script_type = script.attr_local("type").map(|t| &**t);
+    is_executable_script(script_type)
+        && !script_type.is_some_and(|t| t.trim().eq_ignore_ascii_case("module"))
+        && !script.has_attr("async")
+        && !script.has_attr("defer")
+}
+
+/// A filter which stops the scripts in the `<head>` of each document blocking the parser, and outputs what it did with each of them
+/// along with the inner sink's output.
+///
+/// By default external blocking scripts get `defer`, leaving inline scripts in place. With [`ScriptDeferrer::move_to_body_end`],
+/// all blocking scripts, external and inline, are instead moved to the end of the `<body>` in their order, through a [`RelocationBuffer`].
+/// Module, `async` and `defer` scripts are left alone.
+pub struct ScriptDeferrer<Handle, S> {
+    inner: S,
+    move_to_body_end: bool,
+    relocation: RelocationBuffer<Handle>,
+    /// The script being moved, with its depth
+    moving: Option<(Handle, usize)>,
+    paths: CssPathTracker,
+    placements: Vec<ScriptPlacement>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> ScriptDeferrer<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            move_to_body_end: false,
+            relocation: RelocationBuffer::new(),
+            moving: None,
+            paths: CssPathTracker::new(),
+            placements: vec![],
+        }
+    }
+
+    /// Sets whether to move blocking scripts to the end of the body rather than adding `defer`
+    pub fn move_to_body_end(self, move_to_body_end: bool) -> Self {
+        Self {
+            move_to_body_end,
+            ..self
+        }
+    }
+
+    /// The depth of the moving script, if the event in the context is in it
+    fn moving_depth(&mut self, context: HtmlContext<'_, Handle>) -> Option<usize> {
+        match self.moving {
+            Some((handle, depth))
+                if context
+                    .get(depth)
+                    .is_some_and(|element| element.handle == handle) =>
+            {
+                Some(depth)
+            }
+            _ => {
+                self.moving = None;
+                None
+            }
+        }
+    }
+
+    /// Moves or passes on text, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        self.relocation.observe(context, None, &mut self.inner);
+        match self.moving_depth(context) {
+            Some(depth) => self
+                .relocation
+                .push(depth, HtmlEvent::text(context, text, boundary)),
+            None => append_text_to(&mut self.inner, context, text, boundary),
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for ScriptDeferrer<Handle, S> {
+    type Output = (S::Output, Vec<ScriptPlacement>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.relocation
+            .observe(context, Some(element), &mut self.inner);
+        if let Some(depth) = self.moving_depth(context) {
+            return self.relocation.push(
+                depth,
+                HtmlEvent::Element {
+                    context: owned_context(context),
+                    element: element.into(),
+                },
+            );
+        }
+        let in_head = context.last().is_some_and(|parent| {
+            parent.name.ns == ns!(html) && parent.tag_is(local_name!("head"))
+        });
+        if !(in_head && element.name.ns == ns!(html) && element.tag_is(local_name!("script"))) {
+            return self.inner.append_element(context, element);
+        }
+        let src = element.attr_local("src").map(|src| src.to_string());
+        let decision = match (is_blocking(element), self.move_to_body_end, &src) {
+            (false, _, _) | (true, false, None) => ScriptDecision::Unchanged,
+            (true, true, _) => ScriptDecision::Moved,
+            (true, false, Some(_)) => ScriptDecision::Deferred,
+        };
+        self.placements.push(ScriptPlacement {
+            path: self.paths.element_path(context, element),
+            src,
+            decision,
+        });
+        match decision {
+            ScriptDecision::Unchanged => self.inner.append_element(context, element),
+            ScriptDecision::Deferred => {
+                let mut script = Cow::Borrowed(element);
+                script.to_mut().set_attr("defer", "");
+                self.inner.append_element(context, &script)
+            }
+            ScriptDecision::Moved => {
+                self.moving = Some((element.handle, context.len()));
+                self.relocation.push(
+                    context.len(),
+                    HtmlEvent::Element {
+                        context: owned_context(context),
+                        element: element.into(),
+                    },
+                )
+            }
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.relocation.observe(context, None, &mut self.inner);
+        match self.moving_depth(&text_context(context, element)) {
+            Some(depth) => self
+                .relocation
+                .push(depth, HtmlEvent::raw_text(context, element, text)),
+            None => self.inner.append_raw_text(context, element, text),
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.relocation.observe(context, None, &mut self.inner);
+        match self.moving_depth(context) {
+            Some(depth) => self.relocation.push(
+                depth,
+                HtmlEvent::Comment {
+                    context: owned_context(context),
+                    text: text.to_owned(),
+                },
+            ),
+            None => self.inner.append_comment(context, text),
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.relocation.observe(context, None, &mut self.inner);
+        match self.moving_depth(context) {
+            Some(depth) => self.relocation.push(
+                depth,
+                HtmlEvent::ProcessingInstruction {
+                    context: owned_context(context),
+                    target: target.to_owned(),
+                    data: data.to_owned(),
+                },
+            ),
+            None => self
+                .inner
+                .append_processing_instruction(context, target, data),
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.relocation.flush(&mut self.inner);
+        self.relocation.reset();
+        self.moving = None;
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.placements))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head><script src="/a.js"></script><script>init()</script><script type="module" src="/m.js"></script><script async src="/b.js"></script></head><body><p>Text</p></body></html>"#;
+
+    #[test]
+    fn defer_scripts() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), placements) =
+            parse_document(ScriptDeferrer::wrap(&mut serializer), Default::default())
+                .one(DOC)
+                .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><script src="/a.js" defer=""></script><script>init()</script><script type="module" src="/m.js"></script><script async="" src="/b.js"></script></head><body><p>Text</p></body></html>"#
+        );
+        assert_eq!(
+            placements
+                .iter()
+                .map(|placement| placement.decision)
+                .collect::<Vec<_>>(),
+            [
+                ScriptDecision::Deferred,
+                ScriptDecision::Unchanged,
+                ScriptDecision::Unchanged,
+                ScriptDecision::Unchanged
+            ]
+        );
+    }
+
+    #[test]
+    fn move_scripts() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), placements) = parse_document(
+            ScriptDeferrer::wrap(&mut serializer).move_to_body_end(true),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head><script type="module" src="/m.js"></script><script async="" src="/b.js"></script></head>"#,
+                r#"<body><p>Text</p><script src="/a.js"></script><script>init()</script></body></html>"#
+            )
+        );
+        assert_eq!(
+            placements[1],
+            ScriptPlacement {
+                path: "html > head > script:nth-child(2)".to_owned(),
+                src: None,
+                decision: ScriptDecision::Moved,
+            }
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use html5ever::{
+    local_name, namespace_url, ns,
+    tendril::{StrTendril, TendrilSink},
+    QualName,
+};
+
+use crate::{
+    event::{replay, HtmlEvent, OwnedAttribute, OwnedPathElement, Recorder},
+    expect::describe_element,
+    parse_document,
+    patch::attribute_name,
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+};
+
+/// A structural difference between a document and its reference, as found by [`StructuralDiff`].
+///
+/// Paths are child indices from the document root, doctypes aren't counted. Removed nodes have their path in the reference,
+/// the others have their path in the new document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "change", rename_all = "snake_case")
+)]
+pub enum Difference {
+    /// A node, with its subtree, which isn't in the reference
+    Added { path: Vec<usize>, node: String },
+    /// A node, with its subtree, of the reference which isn't in the document
+    Removed { path: Vec<usize>, node: String },
+    AttributeChanged {
+        path: Vec<usize>,
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// The text of a text or comment node changed
+    TextChanged {
+        path: Vec<usize>,
+        old: String,
+        new: String,
+    },
+}
+
+#[derive(Debug)]
+enum Kind {
+    Element {
+        name: QualName,
+        attrs: Vec<OwnedAttribute>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+impl Kind {
+    fn id(attrs: &[OwnedAttribute]) -> Option<&str> {
+        attrs
+            .iter()
+            .find(|attr| attr.name.ns == ns!() && attr.name.local == local_name!("id"))
+            .map(|attr| &*attr.value)
+    }
+
+    /// Nodes which match are compared, rather than reported as removed and added
+    fn matches(&self, other: &Kind) -> bool {
+        match (self, other) {
+            (
+                Kind::Element { name, attrs },
+                Kind::Element {
+                    name: other_name,
+                    attrs: other_attrs,
+                },
+            ) => name == other_name && Kind::id(attrs) == Kind::id(other_attrs),
+            (Kind::Text(_), Kind::Text(_)) | (Kind::Comment(_), Kind::Comment(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Kind::Element { name, attrs } => describe_element(&HtmlPathElement::new(
+                (),
+                name.clone(),
+                attrs
+                    .iter()
+                    .map(|attr| html5ever::Attribute {
+                        name: attr.name.clone(),
+                        value: StrTendril::from_slice(&attr.value),
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            Kind::Text(text) => format!("{:?}", text),
+            Kind::Comment(text) => format!("<!--{}-->", text),
+        }
+    }
+}
+
+/// Numbers the nodes of a document with their child index paths as they arrive
+#[derive(Default)]
+struct PathCounter(Vec<usize>);
+
+impl PathCounter {
+    fn next(&mut self, depth: usize) -> Vec<usize> {
+        self.0.truncate(depth + 1);
+        if self.0.len() == depth + 1 {
+            self.0[depth] += 1;
+        } else {
+            self.0.push(0);
+        }
+        self.0.clone()
+    }
+}
+
+/// A node of the reference document, in document order
+#[derive(Debug)]
+struct Entry {
+    depth: usize,
+    path: Vec<usize>,
+    kind: Kind,
+    /// The index of the entry after this node's subtree
+    end: usize,
+}
+
+/// Flattens the reference document into entries, merging split text
+#[derive(Default)]
+struct ReferenceBuilder {
+    nodes: Vec<(usize, Kind)>,
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for ReferenceBuilder {
+    type Output = Vec<Entry>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.nodes.push((
+            context.len(),
+            Kind::Element {
+                name: element.name.clone(),
+                attrs: OwnedPathElement::without_siblings(element).attrs,
+            },
+        ))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if let Some((depth, Kind::Text(last))) = self.nodes.last_mut() {
+            if *depth == context.len() {
+                last.push_str(text);
+                return;
+            }
+        }
+        self.nodes
+            .push((context.len(), Kind::Text(text.to_owned())))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.nodes
+            .push((context.len(), Kind::Comment(text.to_owned())))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        let nodes = std::mem::take(&mut self.nodes);
+        let mut ends = vec![nodes.len(); nodes.len()];
+        let mut open: Vec<usize> = vec![];
+        for (i, (depth, _)) in nodes.iter().enumerate() {
+            while let Some(&last) = open.last() {
+                if nodes[last].0 < *depth {
+                    break;
+                }
+                ends[last] = i;
+                open.pop();
+            }
+            open.push(i);
+        }
+        let mut paths = PathCounter::default();
+        nodes
+            .into_iter()
+            .zip(ends)
+            .map(|((depth, kind), end)| Entry {
+                depth,
+                path: paths.next(depth),
+                kind,
+                end,
+            })
+            .collect()
+    }
+}
+
+/// Compares each document with a reference document as it streams, outputting the [`Difference`]s without building a DOM of either.
+///
+/// The reference is kept as a flat list of nodes. Each node of the document is matched to the next node of the reference with the same
+/// kind, element name and id among the siblings at its position, the siblings skipped over are removed and a node without a match is added.
+/// Doctypes and processing instructions aren't compared.
+pub struct StructuralDiff<Handle> {
+    reference: Vec<Entry>,
+    position: usize,
+    paths: PathCounter,
+    skip_handle: Option<Handle>,
+    text: Option<(usize, String)>,
+    differences: Vec<Difference>,
+}
+
+impl<Handle: Eq + Copy> StructuralDiff<Handle> {
+    pub fn new<R: Eq + Copy>(reference: &[HtmlEvent<R>]) -> Self {
+        Self {
+            reference: replay(reference, ReferenceBuilder::default())
+                .expect("building the reference never fails"),
+            position: 0,
+            paths: PathCounter::default(),
+            skip_handle: None,
+            text: None,
+            differences: vec![],
+        }
+    }
+
+    /// Reports the reference nodes from the current position which are deeper than `depth`, or are siblings before `until`
+    fn remove_reference(&mut self, depth: usize, until: Option<usize>) {
+        while let Some(entry) = self.reference.get(self.position) {
+            if entry.depth > depth || until.is_some_and(|until| self.position < until) {
+                self.differences.push(Difference::Removed {
+                    path: entry.path.clone(),
+                    node: entry.kind.describe(),
+                });
+                self.position = entry.end;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Matches a node of the document to the reference, returning whether it was matched
+    fn compare(&mut self, depth: usize, kind: Kind) -> bool {
+        self.remove_reference(depth, None);
+        let path = self.paths.next(depth);
+        let mut sibling = self.position;
+        let matched = loop {
+            match self.reference.get(sibling) {
+                Some(entry) if entry.depth == depth => {
+                    if entry.kind.matches(&kind) {
+                        break Some(sibling);
+                    }
+                    sibling = entry.end;
+                }
+                _ => break None,
+            }
+        };
+        let Some(matched) = matched else {
+            self.differences.push(Difference::Added {
+                path,
+                node: kind.describe(),
+            });
+            return false;
+        };
+        self.remove_reference(depth, Some(matched));
+        let entry = &self.reference[matched];
+        match (&entry.kind, kind) {
+            (Kind::Element { attrs: old, .. }, Kind::Element { attrs: new, .. }) => {
+                for old_attr in old {
+                    let new_value = new.iter().find(|attr| attr.name == old_attr.name);
+                    if new_value.map(|attr| &attr.value) != Some(&old_attr.value) {
+                        self.differences.push(Difference::AttributeChanged {
+                            path: path.clone(),
+                            name: attribute_name(&old_attr.name),
+                            old: Some(old_attr.value.clone()),
+                            new: new_value.map(|attr| attr.value.clone()),
+                        });
+                    }
+                }
+                for new_attr in new {
+                    if !old.iter().any(|attr| attr.name == new_attr.name) {
+                        self.differences.push(Difference::AttributeChanged {
+                            path: path.clone(),
+                            name: attribute_name(&new_attr.name),
+                            old: None,
+                            new: Some(new_attr.value),
+                        });
+                    }
+                }
+            }
+            (Kind::Text(old), Kind::Text(new)) | (Kind::Comment(old), Kind::Comment(new)) => {
+                if *old != new {
+                    self.differences.push(Difference::TextChanged {
+                        path,
+                        old: old.clone(),
+                        new,
+                    });
+                }
+            }
+            _ => unreachable!("only nodes of the same kind match"),
+        }
+        self.position = matched + 1;
+        true
+    }
+
+    fn flush_text(&mut self) {
+        if let Some((depth, text)) = self.text.take() {
+            self.compare(depth, Kind::Text(text));
+        }
+    }
+
+    /// Returns whether the event is in an added subtree
+    fn skip(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        if let Some(skip_handle) = self.skip_handle {
+            if context.iter().any(|elem| elem.handle == skip_handle) {
+                return true;
+            } else {
+                self.skip_handle = None
+            }
+        }
+        false
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for StructuralDiff<Handle> {
+    type Output = Vec<Difference>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.flush_text();
+        if self.skip(context) {
+            return;
+        }
+        let kind = Kind::Element {
+            name: element.name.clone(),
+            attrs: OwnedPathElement::without_siblings(element).attrs,
+        };
+        if !self.compare(context.len(), kind) {
+            self.skip_handle = Some(element.handle);
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.skip(context) {
+            return;
+        }
+        match &mut self.text {
+            // the parser can split a text node over many events
+            Some((depth, pending)) if *depth == context.len() => pending.push_str(text),
+            _ => {
+                self.flush_text();
+                self.text = Some((context.len(), text.to_owned()));
+            }
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.flush_text();
+        if self.skip(context) {
+            return;
+        }
+        self.compare(context.len(), Kind::Comment(text.to_owned()));
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.flush_text();
+        self.remove_reference(0, Some(self.reference.len()));
+        self.position = 0;
+        self.paths = PathCounter::default();
+        self.skip_handle = None;
+        std::mem::take(&mut self.differences)
+    }
+}
+
+/// Parses both html documents and returns the structural differences of the new one from the old one, see [`StructuralDiff`]
+pub fn diff_documents(old: &str, new: &str) -> Result<Vec<Difference>, HtmlStreamError> {
+    let reference = parse_document(Recorder::new(), Default::default()).one(old)?;
+    parse_document(StructuralDiff::new(&reference), Default::default()).one(new)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_changes() {
+        let old = r#"<!DOCTYPE html><html><head><title>Old</title></head><body><nav>menu</nav><div id="main" class="a"><p>one</p><p>two</p></div></body></html>"#;
+        let new = r#"<!DOCTYPE html><html><head><title>New</title></head><body><div id="main" class="b" lang="en"><p>one</p><ul><li>x</li></ul><p>two</p></div><!-- end --></body></html>"#;
+        assert_eq!(
+            diff_documents(old, new).unwrap(),
+            vec![
+                Difference::TextChanged {
+                    path: vec![0, 0, 0, 0],
+                    old: "Old".to_owned(),
+                    new: "New".to_owned()
+                },
+                Difference::Removed {
+                    path: vec![0, 1, 0],
+                    node: "<nav>".to_owned()
+                },
+                Difference::AttributeChanged {
+                    path: vec![0, 1, 0],
+                    name: "class".to_owned(),
+                    old: Some("a".to_owned()),
+                    new: Some("b".to_owned())
+                },
+                Difference::AttributeChanged {
+                    path: vec![0, 1, 0],
+                    name: "lang".to_owned(),
+                    old: None,
+                    new: Some("en".to_owned())
+                },
+                Difference::Added {
+                    path: vec![0, 1, 0, 1],
+                    node: "<ul>".to_owned()
+                },
+                Difference::Added {
+                    path: vec![0, 1, 1],
+                    node: "<!-- end -->".to_owned()
+                },
+            ]
+        );
+    }
+}
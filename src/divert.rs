@@ -0,0 +1,190 @@
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    boundary::append_text_to, raw_text::text_context, selector::ContextualSelector, HtmlContext,
+    HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// A filter like [`crate::ElementRemover`] which, instead of discarding the matched subtrees, routes them to a second sink, and outputs both sinks' outputs.
+///
+/// For example `<script>`s can be stripped from the output while they are collected for analysis.
+/// The diverted sink sees each subtree as a root, with the context above it left out, as with [`crate::RootFilter`].
+pub struct Diverter<Handle, S, D, M> {
+    inner: S,
+    diverted: D,
+    matcher: M,
+    /// The root of the subtree being diverted, with its depth, the length of its context
+    divert_root: Option<(Handle, usize)>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>, D: HtmlSink<Handle>, M: ContextualSelector>
+    Diverter<Handle, S, D, M>
+{
+    pub fn wrap(inner: S, diverted: D, matcher: M) -> Self {
+        Self {
+            inner,
+            diverted,
+            matcher,
+            divert_root: None,
+        }
+    }
+
+    /// The index in the context of the root of the subtree being diverted, if the event is in one
+    fn diverted_index(&mut self, context: HtmlContext<'_, Handle>) -> Option<usize> {
+        let (handle, depth) = self.divert_root?;
+        if context
+            .get(depth)
+            .is_some_and(|element| element.handle == handle)
+        {
+            Some(depth)
+        } else {
+            self.divert_root = None;
+            None
+        }
+    }
+
+    /// Passes text on to the sink for its subtree, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        match self.diverted_index(context) {
+            Some(index) => append_text_to(&mut self.diverted, &context[index..], text, boundary),
+            None => append_text_to(&mut self.inner, context, text, boundary),
+        }
+    }
+}
+
+impl<Handle, S, D, M> HtmlSink<Handle> for Diverter<Handle, S, D, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    D: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = (S::Output, D::Output);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if let Some(index) = self.diverted_index(context) {
+            self.diverted.append_element(&context[index..], element)
+        } else if self.matcher.context_match(context, element) {
+            self.divert_root = Some((element.handle, context.len()));
+            self.diverted.append_element(&[], element)
+        } else {
+            self.inner.append_element(context, element)
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        match self.diverted_index(&text_context(context, element)) {
+            Some(index) => self
+                .diverted
+                .append_raw_text(&context[index..], element, text),
+            None => self.inner.append_raw_text(context, element, text),
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        match self.diverted_index(context) {
+            Some(index) => self.diverted.append_comment(&context[index..], text),
+            None => self.inner.append_comment(context, text),
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        match self.diverted_index(context) {
+            Some(index) => {
+                self.diverted
+                    .append_processing_instruction(&context[index..], target, data)
+            }
+            None => self
+                .inner
+                .append_processing_instruction(context, target, data),
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.divert_root = None;
+        (self.inner.reset(), self.diverted.reset())
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        let inner = self.inner.take_error();
+        let diverted = self.diverted.take_error();
+        inner.or(diverted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer};
+
+    #[test]
+    fn divert_scripts() {
+        let doc = r#"<!DOCTYPE html><html><head><script>track()</script></head><body><p>hello</p><script src="a.js"></script></body></html>"#;
+        let (mut page, mut scripts) = (Vec::new(), Vec::new());
+        let mut page_serializer = HtmlSerializer::new(&mut page, SerializeOpts::default());
+        let mut script_serializer = HtmlSerializer::new(&mut scripts, SerializeOpts::default());
+        parse_document(
+            Diverter::wrap(
+                &mut page_serializer,
+                &mut script_serializer,
+                css_select!("script"),
+            ),
+            Default::default(),
+        )
+        .one(doc)
+        .unwrap();
+        drop((page_serializer, script_serializer));
+        assert_eq!(
+            String::from_utf8(page).unwrap(),
+            "<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>"
+        );
+        assert_eq!(
+            String::from_utf8(scripts).unwrap(),
+            r#"<script>track()</script><script src="a.js"></script>"#
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use html5ever::tendril::StrTendril;
+
+use crate::{logger::DEFAULT_MAX_LOGGED_TEXT, HtmlContext, HtmlPathElement, HtmlSink};
+
+enum Entry {
+    Node(String),
+    Text(String),
+}
+
+/// A sink which outputs a `tree` style rendering of each document's structure, for debugging and for snapshot tests which shouldn't depend on the exact serialization.
+///
+/// Elements are shown with their id and classes, text and comments are shown truncated and text which is only whitespace is left out.
+pub struct TreeDump {
+    entries: Vec<(usize, Entry)>,
+    max_text: usize,
+}
+
+impl TreeDump {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            max_text: DEFAULT_MAX_LOGGED_TEXT,
+        }
+    }
+
+    /// Sets the number of characters of text and comments to show
+    pub fn max_text(self, max_text: usize) -> Self {
+        Self { max_text, ..self }
+    }
+
+    fn truncate(&self, text: &str) -> String {
+        match text.char_indices().nth(self.max_text) {
+            Some((end, _)) => format!("{}…", &text[..end]),
+            None => text.to_owned(),
+        }
+    }
+
+    fn render(&self) -> String {
+        // an entry is the last of its siblings if there is no later entry at its depth before the parent ends
+        let mut is_last = vec![false; self.entries.len()];
+        let mut has_following = vec![];
+        for (i, (depth, _)) in self.entries.iter().enumerate().rev() {
+            has_following.resize(depth + 1, false);
+            is_last[i] = !has_following[*depth];
+            has_following[*depth] = true;
+        }
+
+        let mut out = String::new();
+        let mut ancestors_continue = vec![];
+        for ((depth, entry), is_last) in self.entries.iter().zip(is_last) {
+            ancestors_continue.truncate(*depth);
+            for continues in &ancestors_continue {
+                out.push_str(if *continues { "│   " } else { "    " });
+            }
+            out.push_str(if is_last { "└── " } else { "├── " });
+            match entry {
+                Entry::Node(label) => out.push_str(label),
+                Entry::Text(text) => write!(out, "{:?}", self.truncate(text.trim())).unwrap(),
+            }
+            out.push('\n');
+            ancestors_continue.push(!is_last);
+        }
+        out
+    }
+}
+
+impl Default for TreeDump {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for TreeDump {
+    type Output = String;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        self.entries
+            .push((0, Entry::Node(format!("<!DOCTYPE {}>", name))))
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let mut label = element.name.local.to_string();
+        if let Some(id) = element.id() {
+            write!(label, "#{}", id).unwrap();
+        }
+        for class in element.classes() {
+            write!(label, ".{}", class).unwrap();
+        }
+        self.entries.push((context.len(), Entry::Node(label)))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        // the parser can split a text node over many events
+        if let Some((depth, Entry::Text(last))) = self.entries.last_mut() {
+            if *depth == context.len() {
+                last.push_str(text);
+                return;
+            }
+        }
+        self.entries
+            .push((context.len(), Entry::Text(text.to_owned())))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let label = format!("<!--{}-->", self.truncate(text));
+        self.entries.push((context.len(), Entry::Node(label)))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.entries
+            .retain(|(_, entry)| !matches!(entry, Entry::Text(text) if text.trim().is_empty()));
+        let out = self.render();
+        self.entries.clear();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    #[test]
+    fn dump_tree() {
+        let doc = r#"<!DOCTYPE html>
+<html><head><title>Dump</title></head>
+<body>
+  <div id="main" class="content wide"><p>hello <b>world</b></p><!-- note --></div>
+  <p>a long paragraph which will be truncated</p>
+</body></html>"#;
+        let dump = parse_document(TreeDump::new().max_text(20), Default::default())
+            .one(doc)
+            .unwrap();
+        assert_eq!(
+            dump,
+            r#"├── <!DOCTYPE html>
+└── html
+    ├── head
+    │   └── title
+    │       └── "Dump"
+    └── body
+        ├── div#main.content.wide
+        │   ├── p
+        │   │   ├── "hello"
+        │   │   └── b
+        │   │       └── "world"
+        │   └── <!-- note -->
+        └── p
+            └── "a long paragraph whi…"
+"#
+        );
+    }
+}
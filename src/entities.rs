@@ -0,0 +1,303 @@
+use std::io::{self, Write};
+
+use html5ever::{local_name, namespace_url, ns, Attribute, QualName};
+
+/// How [`crate::HtmlSerializer`] writes the characters of text and attribute values, see [`crate::HtmlSerializer::character_references`].
+///
+/// Character references in the input are decoded by the tokenizer, so how they were written isn't known to the serializer,
+/// `&#233;` and `é` in the input are both written according to the policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CharacterReferences {
+    /// Only escape what html requires: `&`, `<` and `>` in text, `&` and `"` in attributes, and no-break spaces as `&nbsp;`.
+    /// Everything else is written as utf-8, this is the default
+    #[default]
+    Minimal,
+    /// Also write non-ascii characters as the named references of html 4, such as `&eacute;` and `&hellip;`,
+    /// and as numeric references when they have none, for legacy targets which understand those names
+    Named,
+    /// Also write non-ascii characters as numeric references, such as `&#8230;`, so the output is plain ascii
+    Numeric,
+}
+
+/// The html 4 names of the latin-1 characters from U+00A0
+const LATIN_1: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf",
+    "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3", "acute", "micro",
+    "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute",
+    "THORN", "szlig", "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc",
+    "uuml", "yacute", "thorn", "yuml",
+];
+
+/// The other html 4 names, ordered by character. `&lang;` and `&rang;` are left out as html5 gave them other characters
+const NAMED: &[(char, &str)] = &[
+    ('\u{0152}', "OElig"),
+    ('\u{0153}', "oelig"),
+    ('\u{0160}', "Scaron"),
+    ('\u{0161}', "scaron"),
+    ('\u{0178}', "Yuml"),
+    ('\u{0192}', "fnof"),
+    ('\u{02C6}', "circ"),
+    ('\u{02DC}', "tilde"),
+    ('\u{0391}', "Alpha"),
+    ('\u{0392}', "Beta"),
+    ('\u{0393}', "Gamma"),
+    ('\u{0394}', "Delta"),
+    ('\u{0395}', "Epsilon"),
+    ('\u{0396}', "Zeta"),
+    ('\u{0397}', "Eta"),
+    ('\u{0398}', "Theta"),
+    ('\u{0399}', "Iota"),
+    ('\u{039A}', "Kappa"),
+    ('\u{039B}', "Lambda"),
+    ('\u{039C}', "Mu"),
+    ('\u{039D}', "Nu"),
+    ('\u{039E}', "Xi"),
+    ('\u{039F}', "Omicron"),
+    ('\u{03A0}', "Pi"),
+    ('\u{03A1}', "Rho"),
+    ('\u{03A3}', "Sigma"),
+    ('\u{03A4}', "Tau"),
+    ('\u{03A5}', "Upsilon"),
+    ('\u{03A6}', "Phi"),
+    ('\u{03A7}', "Chi"),
+    ('\u{03A8}', "Psi"),
+    ('\u{03A9}', "Omega"),
+    ('\u{03B1}', "alpha"),
+    ('\u{03B2}', "beta"),
+    ('\u{03B3}', "gamma"),
+    ('\u{03B4}', "delta"),
+    ('\u{03B5}', "epsilon"),
+    ('\u{03B6}', "zeta"),
+    ('\u{03B7}', "eta"),
+    ('\u{03B8}', "theta"),
+    ('\u{03B9}', "iota"),
+    ('\u{03BA}', "kappa"),
+    ('\u{03BB}', "lambda"),
+    ('\u{03BC}', "mu"),
+    ('\u{03BD}', "nu"),
+    ('\u{03BE}', "xi"),
+    ('\u{03BF}', "omicron"),
+    ('\u{03C0}', "pi"),
+    ('\u{03C1}', "rho"),
+    ('\u{03C2}', "sigmaf"),
+    ('\u{03C3}', "sigma"),
+    ('\u{03C4}', "tau"),
+    ('\u{03C5}', "upsilon"),
+    ('\u{03C6}', "phi"),
+    ('\u{03C7}', "chi"),
+    ('\u{03C8}', "psi"),
+    ('\u{03C9}', "omega"),
+    ('\u{03D1}', "thetasym"),
+    ('\u{03D2}', "upsih"),
+    ('\u{03D6}', "piv"),
+    ('\u{2002}', "ensp"),
+    ('\u{2003}', "emsp"),
+    ('\u{2009}', "thinsp"),
+    ('\u{200C}', "zwnj"),
+    ('\u{200D}', "zwj"),
+    ('\u{200E}', "lrm"),
+    ('\u{200F}', "rlm"),
+    ('\u{2013}', "ndash"),
+    ('\u{2014}', "mdash"),
+    ('\u{2018}', "lsquo"),
+    ('\u{2019}', "rsquo"),
+    ('\u{201A}', "sbquo"),
+    ('\u{201C}', "ldquo"),
+    ('\u{201D}', "rdquo"),
+    ('\u{201E}', "bdquo"),
+    ('\u{2020}', "dagger"),
+    ('\u{2021}', "Dagger"),
+    ('\u{2022}', "bull"),
+    ('\u{2026}', "hellip"),
+    ('\u{2030}', "permil"),
+    ('\u{2032}', "prime"),
+    ('\u{2033}', "Prime"),
+    ('\u{2039}', "lsaquo"),
+    ('\u{203A}', "rsaquo"),
+    ('\u{203E}', "oline"),
+    ('\u{2044}', "frasl"),
+    ('\u{20AC}', "euro"),
+    ('\u{2111}', "image"),
+    ('\u{2118}', "weierp"),
+    ('\u{211C}', "real"),
+    ('\u{2122}', "trade"),
+    ('\u{2135}', "alefsym"),
+    ('\u{2190}', "larr"),
+    ('\u{2191}', "uarr"),
+    ('\u{2192}', "rarr"),
+    ('\u{2193}', "darr"),
+    ('\u{2194}', "harr"),
+    ('\u{21B5}', "crarr"),
+    ('\u{21D0}', "lArr"),
+    ('\u{21D1}', "uArr"),
+    ('\u{21D2}', "rArr"),
+    ('\u{21D3}', "dArr"),
+    ('\u{21D4}', "hArr"),
+    ('\u{2200}', "forall"),
+    ('\u{2202}', "part"),
+    ('\u{2203}', "exist"),
+    ('\u{2205}', "empty"),
+    ('\u{2207}', "nabla"),
+    ('\u{2208}', "isin"),
+    ('\u{2209}', "notin"),
+    ('\u{220B}', "ni"),
+    ('\u{220F}', "prod"),
+    ('\u{2211}', "sum"),
+    ('\u{2212}', "minus"),
+    ('\u{2217}', "lowast"),
+    ('\u{221A}', "radic"),
+    ('\u{221D}', "prop"),
+    ('\u{221E}', "infin"),
+    ('\u{2220}', "ang"),
+    ('\u{2227}', "and"),
+    ('\u{2228}', "or"),
+    ('\u{2229}', "cap"),
+    ('\u{222A}', "cup"),
+    ('\u{222B}', "int"),
+    ('\u{2234}', "there4"),
+    ('\u{223C}', "sim"),
+    ('\u{2245}', "cong"),
+    ('\u{2248}', "asymp"),
+    ('\u{2260}', "ne"),
+    ('\u{2261}', "equiv"),
+    ('\u{2264}', "le"),
+    ('\u{2265}', "ge"),
+    ('\u{2282}', "sub"),
+    ('\u{2283}', "sup"),
+    ('\u{2284}', "nsub"),
+    ('\u{2286}', "sube"),
+    ('\u{2287}', "supe"),
+    ('\u{2295}', "oplus"),
+    ('\u{2297}', "otimes"),
+    ('\u{22A5}', "perp"),
+    ('\u{22C5}', "sdot"),
+    ('\u{2308}', "lceil"),
+    ('\u{2309}', "rceil"),
+    ('\u{230A}', "lfloor"),
+    ('\u{230B}', "rfloor"),
+    ('\u{25CA}', "loz"),
+    ('\u{2660}', "spades"),
+    ('\u{2663}', "clubs"),
+    ('\u{2665}', "hearts"),
+    ('\u{2666}', "diams"),
+];
+
+/// The html 4 name of a character, without the `&` and `;`
+pub fn named_reference(c: char) -> Option<&'static str> {
+    match c {
+        '\u{00A0}'..='\u{00FF}' => Some(LATIN_1[c as usize - 0xA0]),
+        _ => NAMED
+            .binary_search_by_key(&c, |(c, _)| *c)
+            .ok()
+            .map(|i| NAMED[i].1),
+    }
+}
+
+/// Writes text or an attribute value escaped according to the policy
+pub(crate) fn write_escaped(
+    writer: &mut impl Write,
+    text: &str,
+    attr_mode: bool,
+    references: CharacterReferences,
+) -> io::Result<()> {
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        let escaped = match c {
+            '&' => Some("&amp;"),
+            '\u{00A0}' => Some("&nbsp;"),
+            '"' if attr_mode => Some("&quot;"),
+            '<' if !attr_mode => Some("&lt;"),
+            '>' if !attr_mode => Some("&gt;"),
+            _ => None,
+        };
+        if escaped.is_none() && (c.is_ascii() || references == CharacterReferences::Minimal) {
+            continue;
+        }
+        writer.write_all(&text.as_bytes()[start..i])?;
+        start = i + c.len_utf8();
+        match (escaped, references) {
+            (Some(escaped), _) => writer.write_all(escaped.as_bytes())?,
+            (None, CharacterReferences::Named) => match named_reference(c) {
+                Some(name) => write!(writer, "&{};", name)?,
+                None => write!(writer, "&#{};", c as u32)?,
+            },
+            (None, _) => write!(writer, "&#{};", c as u32)?,
+        }
+    }
+    writer.write_all(&text.as_bytes()[start..])
+}
+
+/// Whether text in the element is written unescaped, as html5ever's serializer decides
+pub(crate) fn is_raw_text(name: &QualName, scripting_enabled: bool) -> bool {
+    name.ns == ns!(html)
+        && match name.local {
+            local_name!("style")
+            | local_name!("script")
+            | local_name!("xmp")
+            | local_name!("iframe")
+            | local_name!("noembed")
+            | local_name!("noframes")
+            | local_name!("plaintext") => true,
+            local_name!("noscript") => scripting_enabled,
+            _ => false,
+        }
+}
+
+/// Writes a start tag as html5ever's serializer does, with the attribute values escaped according to the policy
+pub(crate) fn write_start_tag(
+    writer: &mut impl Write,
+    name: &QualName,
+    attrs: &[Attribute],
+    references: CharacterReferences,
+) -> io::Result<()> {
+    write!(writer, "<{}", name.local)?;
+    for attr in attrs {
+        writer.write_all(b" ")?;
+        match attr.name.ns {
+            ns!() => (),
+            ns!(xml) => writer.write_all(b"xml:")?,
+            ns!(xmlns) if attr.name.local != local_name!("xmlns") => writer.write_all(b"xmlns:")?,
+            ns!(xmlns) => (),
+            ns!(xlink) => writer.write_all(b"xlink:")?,
+            _ => writer.write_all(b"unknown_namespace:")?,
+        }
+        write!(writer, "{}=\"", attr.name.local)?;
+        write_escaped(writer, &attr.value, true, references)?;
+        writer.write_all(b"\"")?;
+    }
+    writer.write_all(b">")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn escape(text: &str, attr_mode: bool, references: CharacterReferences) -> String {
+        let mut out = vec![];
+        write_escaped(&mut out, text, attr_mode, references).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escaping() {
+        assert!(NAMED.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let text = "Café <b> & \"more\"\u{00A0}… ☃ 😀";
+        assert_eq!(
+            escape(text, false, CharacterReferences::Minimal),
+            "Café &lt;b&gt; &amp; \"more\"&nbsp;… ☃ 😀"
+        );
+        assert_eq!(
+            escape(text, false, CharacterReferences::Named),
+            "Caf&eacute; &lt;b&gt; &amp; \"more\"&nbsp;&hellip; &#9731; &#128512;"
+        );
+        assert_eq!(
+            escape(text, true, CharacterReferences::Numeric),
+            "Caf&#233; <b> &amp; &quot;more&quot;&nbsp;&#8230; &#9731; &#128512;"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::{borrow::Cow, error::Error, fmt, io};
+
+/// Errors which can occur while streaming a document through a sink pipeline
+#[derive(Debug)]
+pub enum HtmlStreamError {
+    /// A sink was given a context which doesn't agree with the events it has previously received
+    ContextMismatch(String),
+    /// The parser requested a tree operation which can't be performed on a stream
+    UnsupportedTreeOperation(&'static str),
+    /// Writing the output failed
+    Io(io::Error),
+    /// The input document is malformed
+    Parse(Cow<'static, str>),
+    /// A configured limit, such as [`crate::ParseConfig::max_depth`], was exceeded by the input
+    LimitExceeded { limit: &'static str, max: usize },
+    /// A sink checking the stream found an event which differs from what it expected
+    ExpectationFailed(String),
+    /// The parse was cancelled by its progress callback
+    Cancelled,
+}
+
+impl fmt::Display for HtmlStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HtmlStreamError::ContextMismatch(msg) => write!(f, "context mismatch: {}", msg),
+            HtmlStreamError::UnsupportedTreeOperation(op) => {
+                write!(f, "unsupported tree operation: {}", op)
+            }
+            HtmlStreamError::Io(err) => write!(f, "io error: {}", err),
+            HtmlStreamError::Parse(msg) => write!(f, "parse error: {}", msg),
+            HtmlStreamError::LimitExceeded { limit, max } => {
+                write!(f, "limit exceeded: {} (max {})", limit, max)
+            }
+            HtmlStreamError::ExpectationFailed(msg) => write!(f, "expectation failed: {}", msg),
+            HtmlStreamError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl Error for HtmlStreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HtmlStreamError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HtmlStreamError {
+    fn from(err: io::Error) -> Self {
+        HtmlStreamError::Io(err)
+    }
+}
@@ -0,0 +1,691 @@
+use std::{borrow::Cow, fmt, ops::Deref, sync::Arc};
+
+use html5ever::{tendril::StrTendril, Attribute, QualName};
+
+use crate::{
+    ElementContent, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, InheritedState,
+    PreviousSibling, SiblingPosition, TextBoundary,
+};
+
+/// An owned copy of an attribute, unlike `Attribute` it can be sent to another thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedAttribute {
+    #[cfg_attr(feature = "serde", serde(with = "qual_name"))]
+    pub name: QualName,
+    pub value: String,
+}
+
+/// An owned copy of an [`ElementContent`] which can be sent to another thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedContent {
+    pub empty: bool,
+    pub text: String,
+}
+
+/// An owned copy of a [`PreviousSibling`] which can be sent to another thread, linking on to copies of the siblings before it. The
+/// copies of the elements after a sibling share its copy.
+#[derive(Clone)]
+pub struct OwnedSibling<Handle>(Arc<OwnedPathElement<Handle>>);
+
+impl<Handle> OwnedSibling<Handle> {
+    pub(crate) fn new(element: OwnedPathElement<Handle>) -> Self {
+        Self(Arc::new(element))
+    }
+
+    fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<Handle> Deref for OwnedSibling<Handle> {
+    type Target = OwnedPathElement<Handle>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<Handle: fmt::Debug> fmt::Debug for OwnedSibling<Handle> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwnedSibling").field(&self.0.handle).finish()
+    }
+}
+
+impl<Handle: PartialEq> PartialEq for OwnedSibling<Handle> {
+    fn eq(&self, other: &Self) -> bool {
+        // compare the chains iteratively, comparing a long one recursively would overflow the stack
+        let (mut sibling, mut other) = (Some(self), Some(other));
+        while let (Some(a), Some(b)) = (sibling, other) {
+            if a.ptr_eq(b) {
+                return true;
+            }
+            if (&a.handle, &a.name, &a.attrs, a.position, &a.content)
+                != (&b.handle, &b.name, &b.attrs, b.position, &b.content)
+            {
+                return false;
+            }
+            sibling = a.previous_sibling.as_ref();
+            other = b.previous_sibling.as_ref();
+        }
+        sibling.is_none() && other.is_none()
+    }
+}
+
+impl<Handle: Eq> Eq for OwnedSibling<Handle> {}
+
+impl<Handle> Drop for OwnedSibling<Handle> {
+    fn drop(&mut self) {
+        // unlink the chain iteratively, dropping a long one recursively would overflow the stack
+        let mut previous =
+            Arc::get_mut(&mut self.0).and_then(|element| element.previous_sibling.take());
+        while let Some(mut sibling) = previous {
+            previous =
+                Arc::get_mut(&mut sibling.0).and_then(|element| element.previous_sibling.take());
+        }
+    }
+}
+
+/// An owned copy of an `HtmlPathElement` which can be sent to another thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct OwnedPathElement<Handle> {
+    pub handle: Handle,
+    #[cfg_attr(feature = "serde", serde(with = "qual_name"))]
+    pub name: QualName,
+    pub attrs: Vec<OwnedAttribute>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub position: Option<SiblingPosition>,
+    /// A copy of the previous sibling, which isn't serialized as each element would repeat all the siblings before it
+    #[cfg_attr(feature = "serde", serde(skip, default = "Option::default"))]
+    pub previous_sibling: Option<OwnedSibling<Handle>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub content: Option<OwnedContent>,
+}
+
+impl<Handle> OwnedPathElement<Handle> {
+    /// An element which isn't from the source, so it has no position, siblings or content
+    pub fn new(handle: Handle, name: QualName, attrs: Vec<OwnedAttribute>) -> Self {
+        Self {
+            handle,
+            name,
+            attrs,
+            position: None,
+            previous_sibling: None,
+            content: None,
+        }
+    }
+}
+
+impl<Handle: Copy> OwnedPathElement<Handle> {
+    /// Copies the element without its previous sibling
+    pub(crate) fn without_siblings(element: &HtmlPathElement<'_, Handle>) -> Self {
+        Self {
+            handle: element.handle,
+            name: element.name.clone(),
+            attrs: element
+                .attrs
+                .iter()
+                .map(|attr| OwnedAttribute {
+                    name: attr.name.clone(),
+                    value: attr.value.to_string(),
+                })
+                .collect(),
+            position: element.position,
+            previous_sibling: None,
+            content: element.content.as_ref().map(|content| OwnedContent {
+                empty: content.empty,
+                text: content.text.to_string(),
+            }),
+        }
+    }
+
+    /// Converts the element as if it were a root, see [`OwnedPathElement::to_child_element`] to inherit a parent's state
+    pub fn to_path_element(&self) -> HtmlPathElement<'static, Handle> {
+        self.to_child_element(None)
+    }
+
+    /// Converts the element, which is a child of `parent`
+    pub fn to_child_element(
+        &self,
+        parent: Option<&HtmlPathElement<'_, Handle>>,
+    ) -> HtmlPathElement<'static, Handle> {
+        let previous_sibling = self
+            .previous_sibling
+            .as_ref()
+            .map(|sibling| SiblingCopies::default().previous_sibling(0, sibling, parent));
+        self.with_sibling(parent, previous_sibling)
+    }
+
+    /// Converts the element, which is a child of `parent`, linking it to the converted copy of its previous sibling
+    fn with_sibling(
+        &self,
+        parent: Option<&HtmlPathElement<'_, Handle>>,
+        previous_sibling: Option<PreviousSibling<Handle>>,
+    ) -> HtmlPathElement<'static, Handle> {
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|attr| Attribute {
+                name: attr.name.clone(),
+                value: StrTendril::from_slice(&attr.value),
+            })
+            .collect::<Vec<_>>();
+        HtmlPathElement {
+            handle: self.handle,
+            name: self.name.clone(),
+            inherited: InheritedState::for_element(
+                parent.map(|parent| &parent.inherited),
+                &self.name,
+                &attrs,
+            ),
+            attrs: Cow::Owned(attrs),
+            position: self.position,
+            previous_sibling,
+            content: self.content.as_ref().map(|content| ElementContent {
+                empty: content.empty,
+                text: StrTendril::from_slice(&content.text),
+            }),
+        }
+    }
+}
+
+impl<Handle: Copy> From<&HtmlPathElement<'_, Handle>> for OwnedPathElement<Handle> {
+    fn from(element: &HtmlPathElement<'_, Handle>) -> Self {
+        Self {
+            previous_sibling: element
+                .previous_sibling
+                .as_ref()
+                .map(PreviousSibling::to_owned_sibling),
+            ..Self::without_siblings(element)
+        }
+    }
+}
+
+/// Converts the copies of previous siblings back as a stream of events is replayed, converting each sibling once for the elements
+/// after it rather than once for each of their events
+pub(crate) struct SiblingCopies<Handle> {
+    /// The last sibling converted at each depth, with its conversion
+    last: Vec<Option<(OwnedSibling<Handle>, PreviousSibling<Handle>)>>,
+}
+
+impl<Handle> Default for SiblingCopies<Handle> {
+    fn default() -> Self {
+        Self { last: vec![] }
+    }
+}
+
+impl<Handle: Copy> SiblingCopies<Handle> {
+    /// The conversion of the previous sibling of an element at the depth, the length of its context
+    fn previous_sibling(
+        &mut self,
+        depth: usize,
+        sibling: &OwnedSibling<Handle>,
+        parent: Option<&HtmlPathElement<'_, Handle>>,
+    ) -> PreviousSibling<Handle> {
+        if self.last.len() <= depth {
+            self.last.resize_with(depth + 1, Default::default);
+        }
+        let mut unconverted = vec![];
+        let mut converted = None;
+        let mut next = Some(sibling);
+        while let Some(owned) = next {
+            match &self.last[depth] {
+                Some((last, path)) if last.ptr_eq(owned) => {
+                    converted = Some(path.clone());
+                    break;
+                }
+                _ => {}
+            }
+            unconverted.push(owned);
+            next = owned.previous_sibling.as_ref();
+        }
+        // convert from the first, as converting a long chain recursively would overflow the stack
+        for owned in unconverted.into_iter().rev() {
+            converted = Some(PreviousSibling::new(
+                owned.with_sibling(parent, converted.take()),
+                Some(owned.clone()),
+            ));
+        }
+        let converted = converted.expect("the sibling has been converted");
+        self.last[depth] = Some((sibling.clone(), converted.clone()));
+        converted
+    }
+
+    /// The context, converting the copies of the previous siblings of its elements
+    pub(crate) fn path_context(
+        &mut self,
+        context: &[OwnedPathElement<Handle>],
+    ) -> Vec<HtmlPathElement<'static, Handle>> {
+        let mut path: Vec<HtmlPathElement<'static, Handle>> = Vec::with_capacity(context.len());
+        for element in context {
+            let element = self.path_element(path.len(), element, path.last());
+            path.push(element);
+        }
+        path
+    }
+
+    /// Converts an element at the depth, the length of its context
+    fn path_element(
+        &mut self,
+        depth: usize,
+        element: &OwnedPathElement<Handle>,
+        parent: Option<&HtmlPathElement<'_, Handle>>,
+    ) -> HtmlPathElement<'static, Handle> {
+        let previous_sibling = element
+            .previous_sibling
+            .as_ref()
+            .map(|sibling| self.previous_sibling(depth, sibling, parent));
+        element.with_sibling(parent, previous_sibling)
+    }
+}
+
+/// An owned copy of a single call on an `HtmlSink`, including the context it was made in
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum HtmlEvent<Handle> {
+    Doctype {
+        name: String,
+        public_id: String,
+        system_id: String,
+    },
+    Element {
+        context: Vec<OwnedPathElement<Handle>>,
+        element: OwnedPathElement<Handle>,
+    },
+    Text {
+        context: Vec<OwnedPathElement<Handle>>,
+        text: String,
+    },
+    /// Text given with where it falls by a [`crate::TextBoundaries`]
+    TextWithBoundary {
+        context: Vec<OwnedPathElement<Handle>>,
+        text: String,
+        boundary: TextBoundary,
+    },
+    /// The whole text of a `<script>` or `<style>`, given by a [`crate::RawTextCapture`] once it has closed
+    RawText {
+        context: Vec<OwnedPathElement<Handle>>,
+        element: OwnedPathElement<Handle>,
+        text: String,
+    },
+    Comment {
+        context: Vec<OwnedPathElement<Handle>>,
+        text: String,
+    },
+    ProcessingInstruction {
+        context: Vec<OwnedPathElement<Handle>>,
+        target: String,
+        data: String,
+    },
+}
+
+impl<Handle: Copy> HtmlEvent<Handle> {
+    /// A text event, with its boundary if it has one
+    pub(crate) fn text(
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) -> Self {
+        let (context, text) = (owned_context(context), text.to_owned());
+        match boundary {
+            Some(boundary) => HtmlEvent::TextWithBoundary {
+                context,
+                text,
+                boundary,
+            },
+            None => HtmlEvent::Text { context, text },
+        }
+    }
+
+    /// The whole text of a raw text element
+    pub(crate) fn raw_text(
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) -> Self {
+        HtmlEvent::RawText {
+            context: owned_context(context),
+            element: element.into(),
+            text: text.to_owned(),
+        }
+    }
+}
+
+pub(crate) fn owned_context<Handle: Copy>(
+    context: &[HtmlPathElement<'_, Handle>],
+) -> Vec<OwnedPathElement<Handle>> {
+    context.iter().map(OwnedPathElement::from).collect()
+}
+
+impl<Handle: Eq + Copy> HtmlEvent<Handle> {
+    /// Makes the call this event represents on the sink
+    pub fn replay<S: HtmlSink<Handle>>(&self, sink: &mut S) {
+        self.replay_with(sink, &mut SiblingCopies::default())
+    }
+
+    /// Makes the call this event represents on the sink, sharing the conversions of the siblings with the other events of a stream
+    pub(crate) fn replay_with<S: HtmlSink<Handle>>(
+        &self,
+        sink: &mut S,
+        siblings: &mut SiblingCopies<Handle>,
+    ) {
+        let mut path_context =
+            |context: &[OwnedPathElement<Handle>]| siblings.path_context(context);
+        match self {
+            HtmlEvent::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => sink.append_doctype_to_document(
+                &StrTendril::from_slice(name),
+                &StrTendril::from_slice(public_id),
+                &StrTendril::from_slice(system_id),
+            ),
+            HtmlEvent::Element { context, element } => {
+                let context = path_context(context);
+                let element = siblings.path_element(context.len(), element, context.last());
+                sink.append_element(&context, &element)
+            }
+            HtmlEvent::Text { context, text } => sink.append_text(&path_context(context), text),
+            HtmlEvent::TextWithBoundary {
+                context,
+                text,
+                boundary,
+            } => sink.append_text_with_boundary(&path_context(context), text, *boundary),
+            HtmlEvent::RawText {
+                context,
+                element,
+                text,
+            } => {
+                let context = path_context(context);
+                let element = siblings.path_element(context.len(), element, context.last());
+                sink.append_raw_text(&context, &element, text)
+            }
+            HtmlEvent::Comment { context, text } => {
+                sink.append_comment(&path_context(context), text)
+            }
+            HtmlEvent::ProcessingInstruction {
+                context,
+                target,
+                data,
+            } => sink.append_processing_instruction(&path_context(context), target, data),
+        }
+    }
+}
+
+/// Feeds recorded events into the sink, then finishes it
+pub fn replay<'a, Handle, S>(
+    events: impl IntoIterator<Item = &'a HtmlEvent<Handle>>,
+    mut sink: S,
+) -> Result<S::Output, HtmlStreamError>
+where
+    Handle: Eq + Copy + 'a,
+    S: HtmlSink<Handle>,
+{
+    replay_into(events, &mut sink);
+    sink.finish()
+}
+
+/// Feeds events into the sink without finishing it, converting each of the previous siblings they link to once
+pub(crate) fn replay_into<'a, Handle, S>(
+    events: impl IntoIterator<Item = &'a HtmlEvent<Handle>>,
+    sink: &mut S,
+) where
+    Handle: Eq + Copy + 'a,
+    S: HtmlSink<Handle>,
+{
+    let mut siblings = SiblingCopies::default();
+    for event in events {
+        event.replay_with(sink, &mut siblings);
+    }
+}
+
+/// A sink which records the events it is given, outputting them on each reset so they can be replayed into other sinks with [`replay`]
+pub struct Recorder<Handle> {
+    events: Vec<HtmlEvent<Handle>>,
+}
+
+impl<Handle> Recorder<Handle> {
+    pub fn new() -> Self {
+        Self { events: vec![] }
+    }
+}
+
+impl<Handle> Default for Recorder<Handle> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for Recorder<Handle> {
+    type Output = Vec<HtmlEvent<Handle>>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.events.push(HtmlEvent::Doctype {
+            name: name.to_string(),
+            public_id: public_id.to_string(),
+            system_id: system_id.to_string(),
+        })
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.events.push(HtmlEvent::Element {
+            context: owned_context(context),
+            element: OwnedPathElement::from(element),
+        })
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.events.push(HtmlEvent::text(context, text, None))
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.events
+            .push(HtmlEvent::text(context, text, Some(boundary)))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.events
+            .push(HtmlEvent::raw_text(context, element, text))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.events.push(HtmlEvent::Comment {
+            context: owned_context(context),
+            text: text.to_owned(),
+        })
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.events.push(HtmlEvent::ProcessingInstruction {
+            context: owned_context(context),
+            target: target.to_owned(),
+            data: data.to_owned(),
+        })
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// (De)serializes a `QualName` as a struct of its prefix, namespace url and local name
+#[cfg(feature = "serde")]
+mod qual_name {
+    use html5ever::{LocalName, Namespace, Prefix, QualName};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct QualNameRef<'a> {
+        prefix: Option<&'a str>,
+        ns: &'a str,
+        local: &'a str,
+    }
+
+    #[derive(Deserialize)]
+    struct OwnedQualName {
+        prefix: Option<String>,
+        ns: String,
+        local: String,
+    }
+
+    pub fn serialize<S: Serializer>(name: &QualName, serializer: S) -> Result<S::Ok, S::Error> {
+        QualNameRef {
+            prefix: name.prefix.as_deref(),
+            ns: &name.ns,
+            local: &name.local,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QualName, D::Error> {
+        let name = OwnedQualName::deserialize(deserializer)?;
+        Ok(QualName::new(
+            name.prefix.map(Prefix::from),
+            Namespace::from(name.ns),
+            LocalName::from(name.local),
+        ))
+    }
+}
+
+/// Serialized in the same form as `OwnedPathElement`
+#[cfg(feature = "serde")]
+impl<Handle: serde::Serialize + Copy> serde::Serialize for HtmlPathElement<'_, Handle> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        OwnedPathElement::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Handle: serde::Deserialize<'de> + Copy> serde::Deserialize<'de>
+    for HtmlPathElement<'static, Handle>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        OwnedPathElement::deserialize(deserializer).map(|element| element.to_path_element())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        css_select, parse_document, ContentBuffer, HtmlSerializer, ParseConfig, SelectToStrings,
+        SiblingTracking,
+    };
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    #[test]
+    fn record_and_replay() {
+        let doc = "<!DOCTYPE html><html><head></head><body><!-- comment --><p>hello <b>world</b></p></body></html>";
+        let events = parse_document(Recorder::new(), Default::default())
+            .one(doc)
+            .unwrap();
+        assert!(matches!(events[0], HtmlEvent::Doctype { .. }));
+
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        replay(&events, &mut serializer).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), doc);
+    }
+
+    #[test]
+    fn replay_siblings_and_content() {
+        let events = ParseConfig::default()
+            .sibling_tracking(SiblingTracking::All)
+            .parse_document(ContentBuffer::wrap(Recorder::new(), css_select!("div")))
+            .one("<!DOCTYPE html><html><head></head><body><div><p>a</p></div><div><p>b</p></div><div></div></body></html>")
+            .unwrap();
+        assert_eq!(
+            replay(&events, SelectToStrings::new(css_select!("div" + "div"))).unwrap(),
+            ["<div><p>b</p></div>", "<div></div>"]
+        );
+        assert_eq!(
+            replay(
+                &events,
+                SelectToStrings::new(css_select!("div" ~ "div" "p"))
+            )
+            .unwrap(),
+            ["<p>b</p>"]
+        );
+        assert_eq!(
+            replay(&events, SelectToStrings::new(css_select!("div":empty))).unwrap(),
+            ["<div></div>"]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use html5ever::{local_name, namespace_url, ns};
+
+        let mut p = OwnedPathElement::new(
+            3u32,
+            QualName::new(None, ns!(html), local_name!("p")),
+            vec![OwnedAttribute {
+                name: QualName::new(None, ns!(), local_name!("class")),
+                value: "lead".to_owned(),
+            }],
+        );
+        p.position = Some(SiblingPosition {
+            index: 2,
+            index_of_type: 1,
+            count: Some(3),
+            count_of_type: None,
+        });
+        p.content = Some(OwnedContent {
+            empty: false,
+            text: "hello".to_owned(),
+        });
+        let events = vec![
+            HtmlEvent::Doctype {
+                name: "html".to_owned(),
+                public_id: String::new(),
+                system_id: String::new(),
+            },
+            HtmlEvent::Text {
+                context: vec![p.clone()],
+                text: "hello".to_owned(),
+            },
+        ];
+        let json = serde_json::to_string(&events).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<HtmlEvent<u32>>>(&json).unwrap(),
+            events
+        );
+
+        let element: HtmlPathElement<u32> =
+            serde_json::from_str(&serde_json::to_string(&p.to_path_element()).unwrap()).unwrap();
+        assert_eq!(OwnedPathElement::from(&element), p);
+    }
+}
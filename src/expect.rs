@@ -0,0 +1,249 @@
+use std::fmt::Write;
+
+use html5ever::{
+    tendril::{StrTendril, TendrilSink},
+    Attribute,
+};
+
+use crate::{
+    event::{replay, HtmlEvent},
+    parse_document, CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+};
+
+pub(crate) fn describe_element<Handle>(element: &HtmlPathElement<'_, Handle>) -> String {
+    let mut description = format!("<{}", element.name.local);
+    for Attribute { name, value } in element.attrs.iter() {
+        write!(description, " {}={:?}", name.local, &**value).unwrap();
+    }
+    description.push('>');
+    description
+}
+
+/// Describes an event as a line listing the path to it, handles are left out so streams with different handles can be compared
+fn describe<Handle>(context: HtmlContext<'_, Handle>, node: String) -> String {
+    context
+        .iter()
+        .map(describe_element)
+        .chain(Some(node))
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+/// Collects the descriptions of the events
+#[derive(Default)]
+struct Describer(Vec<String>);
+
+impl Describer {
+    fn push(&mut self, line: String) {
+        self.0.push(line)
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for Describer {
+    type Output = Vec<String>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        self.push(format!("<!DOCTYPE {}>", name))
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.push(describe(context, describe_element(element)))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.push(describe(context, format!("{:?}", text)))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.push(describe(context, format!("<!--{}-->", text)))
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.push(describe(context, format!("<?{} {}?>", target, data)))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        std::mem::take(&mut self.0)
+    }
+}
+
+/// A sink for testing filters, which checks the events it is given against an expected sequence of events.
+///
+/// Events are compared on their element paths, names, attributes and text, handles aren't compared.
+/// The first divergence is recorded as a `HtmlStreamError::ExpectationFailed` showing the expected and found events with their full paths
+/// and the css path of the found event, so finishing a parse into this sink fails with a readable message. Text is compared per event, wrap the filter in a
+/// `TextCoalescer` when the expectation and the filter output split text differently.
+pub struct ExpectEvents {
+    expected: Vec<String>,
+    position: usize,
+    describer: Describer,
+    paths: CssPathTracker,
+    error: Option<HtmlStreamError>,
+}
+
+impl ExpectEvents {
+    pub fn new<Handle: Eq + Copy>(expected: &[HtmlEvent<Handle>]) -> Self {
+        let expected = replay(expected, Describer::default()).expect("describing never fails");
+        Self {
+            expected,
+            position: 0,
+            describer: Describer::default(),
+            paths: CssPathTracker::new(),
+            error: None,
+        }
+    }
+
+    /// Expects the events of the parsed html document
+    pub fn from_html(expected: &str) -> Result<Self, HtmlStreamError> {
+        let expected = parse_document(Describer::default(), Default::default()).one(expected)?;
+        Ok(Self {
+            expected,
+            position: 0,
+            describer: Describer::default(),
+            paths: CssPathTracker::new(),
+            error: None,
+        })
+    }
+
+    /// Compares the event just described with the next expected event, `location` is the found event's css path
+    fn check(&mut self, location: String) {
+        let Some(found) = self.describer.0.pop() else {
+            return;
+        };
+        if self.error.is_some() {
+            return;
+        }
+        let message = match self.expected.get(self.position) {
+            Some(expected) if *expected == found => None,
+            Some(expected) => Some(format!(
+                "event {} differs\n  expected: {}\n     found: {}\n        at: {}",
+                self.position, expected, found, location
+            )),
+            None => Some(format!(
+                "event {} is unexpected, found: {}",
+                self.position, found
+            )),
+        };
+        self.position += 1;
+        if let Some(message) = message {
+            self.error = Some(HtmlStreamError::ExpectationFailed(message));
+        }
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for ExpectEvents {
+    type Output = ();
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        HtmlSink::<Handle>::append_doctype_to_document(
+            &mut self.describer,
+            name,
+            public_id,
+            system_id,
+        );
+        self.check("document".to_owned())
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.describer.append_element(context, element);
+        self.paths.element(context);
+        self.check(self.paths.element_path(context, element))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.describer.append_text(context, text);
+        self.check(self.paths.context_path(context))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.describer.append_comment(context, text);
+        self.check(self.paths.context_path(context))
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.describer
+            .append_processing_instruction(context, target, data);
+        self.check(self.paths.context_path(context))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        if self.error.is_none() && self.position < self.expected.len() {
+            self.error = Some(HtmlStreamError::ExpectationFailed(format!(
+                "expected {} more events, starting with event {}: {}",
+                self.expected.len() - self.position,
+                self.position,
+                self.expected[self.position]
+            )));
+        }
+        self.position = 0;
+        self.paths.reset();
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, ElementRemover};
+
+    #[test]
+    fn expect_filtered_events() {
+        let doc = r#"<!DOCTYPE html><html><head></head><body><p class="ad">buy</p><p>hello</p></body></html>"#;
+        let expect = ExpectEvents::from_html(
+            "<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>",
+        )
+        .unwrap();
+        parse_document(
+            ElementRemover::wrap(expect, css_select!(."ad")),
+            Default::default(),
+        )
+        .one(doc)
+        .unwrap();
+
+        let expect = ExpectEvents::from_html(
+            "<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>",
+        )
+        .unwrap();
+        let err = parse_document(expect, Default::default())
+            .one(doc)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expectation failed: event 4 differs\n  \
+            expected: <html> > <body> > <p>\n     \
+            found: <html> > <body> > <p class=\"ad\">\n        \
+            at: html > body > p.ad:nth-child(1)"
+        );
+    }
+}
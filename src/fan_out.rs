@@ -0,0 +1,224 @@
+use html5ever::tendril::StrTendril;
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// Implements `HtmlSink` for a tuple of sinks, which each get every event, so a single parse can feed many analyzers at once
+macro_rules! tuple_sink {
+    ($($sink:ident $index:tt),+) => {
+        impl<Handle: Copy + Eq, $($sink: HtmlSink<Handle>),+> HtmlSink<Handle> for ($($sink,)+) {
+            type Output = ($($sink::Output,)+);
+
+            fn append_doctype_to_document(
+                &mut self,
+                name: &StrTendril,
+                public_id: &StrTendril,
+                system_id: &StrTendril,
+            ) {
+                $(self.$index.append_doctype_to_document(name, public_id, system_id);)+
+            }
+
+            fn append_element(
+                &mut self,
+                context: HtmlContext<'_, Handle>,
+                element: &HtmlPathElement<'_, Handle>,
+            ) {
+                $(self.$index.append_element(context, element);)+
+            }
+
+            fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+                $(self.$index.append_text(context, text);)+
+            }
+
+            fn append_text_with_boundary(
+                &mut self,
+                context: HtmlContext<Handle>,
+                text: &str,
+                boundary: TextBoundary,
+            ) {
+                $(self.$index.append_text_with_boundary(context, text, boundary);)+
+            }
+
+            fn append_raw_text(
+                &mut self,
+                context: HtmlContext<Handle>,
+                element: &HtmlPathElement<'_, Handle>,
+                text: &str,
+            ) {
+                $(self.$index.append_raw_text(context, element, text);)+
+            }
+
+            fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+                $(self.$index.append_comment(context, text);)+
+            }
+
+            fn append_processing_instruction(
+                &mut self,
+                context: HtmlContext<Handle>,
+                target: &str,
+                data: &str,
+            ) {
+                $(self.$index.append_processing_instruction(context, target, data);)+
+            }
+
+            fn reset(&mut self) -> Self::Output {
+                ($(self.$index.reset(),)+)
+            }
+
+            /// Takes the errors of all the sinks, returning the first
+            fn take_error(&mut self) -> Option<HtmlStreamError> {
+                let errors = [$(self.$index.take_error()),+];
+                IntoIterator::into_iter(errors).flatten().next()
+            }
+        }
+    };
+}
+
+tuple_sink!(A 0, B 1);
+tuple_sink!(A 0, B 1, C 2);
+tuple_sink!(A 0, B 1, C 2, D 3);
+tuple_sink!(A 0, B 1, C 2, D 3, E 4);
+tuple_sink!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_sink!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_sink!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// Implements `HtmlSink` for a collection of sinks of the same type, which each get every event, in order
+macro_rules! collection_sink {
+    ($(#[$meta:meta])* [$($generics:tt)*] $sinks:ty => $output:ty, |$self:ident| $reset:expr) => {
+        $(#[$meta])*
+        impl<Handle: Copy + Eq, S: HtmlSink<Handle>, $($generics)*> HtmlSink<Handle> for $sinks {
+            type Output = $output;
+
+            fn append_doctype_to_document(
+                &mut self,
+                name: &StrTendril,
+                public_id: &StrTendril,
+                system_id: &StrTendril,
+            ) {
+                for sink in self.iter_mut() {
+                    sink.append_doctype_to_document(name, public_id, system_id);
+                }
+            }
+
+            fn append_element(
+                &mut self,
+                context: HtmlContext<'_, Handle>,
+                element: &HtmlPathElement<'_, Handle>,
+            ) {
+                for sink in self.iter_mut() {
+                    sink.append_element(context, element);
+                }
+            }
+
+            fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+                for sink in self.iter_mut() {
+                    sink.append_text(context, text);
+                }
+            }
+
+            fn append_text_with_boundary(
+                &mut self,
+                context: HtmlContext<Handle>,
+                text: &str,
+                boundary: TextBoundary,
+            ) {
+                for sink in self.iter_mut() {
+                    sink.append_text_with_boundary(context, text, boundary);
+                }
+            }
+
+            fn append_raw_text(
+                &mut self,
+                context: HtmlContext<Handle>,
+                element: &HtmlPathElement<'_, Handle>,
+                text: &str,
+            ) {
+                for sink in self.iter_mut() {
+                    sink.append_raw_text(context, element, text);
+                }
+            }
+
+            fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+                for sink in self.iter_mut() {
+                    sink.append_comment(context, text);
+                }
+            }
+
+            fn append_processing_instruction(
+                &mut self,
+                context: HtmlContext<Handle>,
+                target: &str,
+                data: &str,
+            ) {
+                for sink in self.iter_mut() {
+                    sink.append_processing_instruction(context, target, data);
+                }
+            }
+
+            fn reset(&mut $self) -> Self::Output {
+                $reset
+            }
+
+            /// Takes the errors of all the sinks, returning the first
+            fn take_error(&mut self) -> Option<HtmlStreamError> {
+                self.iter_mut()
+                    .fold(None, |error, sink| error.or(sink.take_error()))
+            }
+        }
+    };
+}
+
+collection_sink!(
+    /// Each sink gets every event, and the outputs are in the order of the sinks
+    [const N: usize] [S; N] => [S::Output; N],
+    |self| self.each_mut().map(HtmlSink::reset)
+);
+
+collection_sink!(
+    /// Each sink gets every event, and the outputs are in the order of the sinks, for when the number of sinks is only known at runtime
+    [] Vec<S> => Vec<S::Output>,
+    |self| self.iter_mut().map(HtmlSink::reset).collect()
+);
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use crate::{css_select, parse_document, SelectToStrings, StatsCollector};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head></head><body><h1>Title</h1><p>a <a href="/a">link</a></p></body></html>"#;
+
+    #[test]
+    fn feed_many_sinks() {
+        let (headings, links, paragraphs) = parse_document(
+            (
+                SelectToStrings::new(css_select!("h1")),
+                SelectToStrings::new(css_select!("a")),
+                SelectToStrings::new(css_select!("p")),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(headings, ["<h1>Title</h1>"]);
+        assert_eq!(links, [r#"<a href="/a">link</a>"#]);
+        assert_eq!(paragraphs, [r#"<p>a <a href="/a">link</a></p>"#]);
+
+        let [headings, links] = parse_document(
+            [
+                SelectToStrings::new(css_select!("h1")),
+                SelectToStrings::new(css_select!("a")),
+            ],
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(headings, ["<h1>Title</h1>"]);
+        assert_eq!(links, [r#"<a href="/a">link</a>"#]);
+
+        let sinks = (0..3).map(|_| StatsCollector::new()).collect::<Vec<_>>();
+        let stats = parse_document(sinks, Default::default()).one(DOC).unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].elements, 6);
+        assert!(stats.iter().all(|other| *other == stats[0]));
+    }
+}
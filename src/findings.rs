@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+/// What a security scan found, see [`SecurityFinding`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FindingKind {
+    /// A url with a query parameter such as `next` or `redirect` holding an absolute url, which may be an open redirect
+    OpenRedirect,
+    /// A protocol-relative url, such as `//cdn.example.com/x.js`, to a host which isn't expected
+    ProtocolRelativeUrl,
+    /// A `target="_blank"` without `rel="noopener"` or `noreferrer`, which gives the opened page access to `window.opener`
+    BlankTargetWithoutNoopener,
+    /// A form which posts to another origin
+    ThirdPartyFormAction,
+    /// An event handler attribute such as `onclick`
+    InlineEventHandler,
+    /// A `javascript:` or `vbscript:` url
+    ScriptUrl,
+    /// An inline `<script>` without a `nonce`, which a strict content security policy would block
+    ScriptWithoutNonce,
+    /// An svg construct which can run script or load markup, such as `<foreignObject>` or an `<animate>` of `href`
+    DangerousSvg,
+}
+
+/// A finding of a security scan, such as a [`crate::XssAuditor`] or a `UrlScanner`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecurityFinding {
+    pub kind: FindingKind,
+    /// The css path of the element, as described by [`crate::CssPathTracker`]
+    pub path: String,
+    /// The markup found, such as the attribute and its value
+    pub snippet: String,
+}
+
+/// The findings of a security scan of a document, in document order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecurityReport {
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    /// The findings grouped by the path of their element
+    pub fn by_path(&self) -> BTreeMap<&str, Vec<&SecurityFinding>> {
+        let mut by_path = BTreeMap::<_, Vec<_>>::new();
+        for finding in &self.findings {
+            by_path
+                .entry(finding.path.as_str())
+                .or_default()
+                .push(finding);
+        }
+        by_path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
@@ -0,0 +1,441 @@
+use std::borrow::Cow;
+
+use html5ever::{namespace_url, ns, tendril::StrTendril, Attribute, LocalName, QualName};
+
+use crate::{
+    boundary::append_text_to, raw_text::text_context, selector::ContextualSelector, HtmlContext,
+    HtmlFragment, HtmlPathElement, HtmlSink, HtmlStreamError, SyntheticHandle, SyntheticHandles,
+    TextBoundary,
+};
+
+/// Registers an element handler for a [`HtmlRewriter`], in the style of lol_html
+///
+/// ```
+/// use html5streams::{css_select, element};
+///
+/// let handler = element!(css_select!("a"), |el| el.set_attribute("rel", "nofollow"));
+/// # let _: html5streams::ContentHandler<u32> = handler;
+/// ```
+#[macro_export]
+macro_rules! element {
+    ($selector:expr, $handler:expr) => {
+        $crate::ContentHandler::element($selector, $handler)
+    };
+}
+
+/// Registers a handler for the text directly inside elements matching the selector, for a [`HtmlRewriter`], in the style of lol_html
+#[macro_export]
+macro_rules! text {
+    ($selector:expr, $handler:expr) => {
+        $crate::ContentHandler::text($selector, $handler)
+    };
+}
+
+type ElementHandlerFn<'h, Handle> =
+    Box<dyn FnMut(HtmlContext<'_, Handle>, &mut ElementMut<'_, Handle>) + 'h>;
+type TextHandlerFn<'h, Handle> = Box<dyn FnMut(HtmlContext<'_, Handle>, &mut TextChunk<'_>) + 'h>;
+
+/// A selector and handler pair registered with [`element!`] or [`text!`]
+pub enum ContentHandler<'h, Handle> {
+    Element(ElementHandlerFn<'h, Handle>),
+    Text(TextHandlerFn<'h, Handle>),
+}
+
+impl<'h, Handle: 'h> ContentHandler<'h, Handle> {
+    pub fn element<M, F>(selector: M, mut handler: F) -> Self
+    where
+        M: ContextualSelector + 'h,
+        F: FnMut(&mut ElementMut<'_, Handle>) + 'h,
+    {
+        ContentHandler::Element(Box::new(move |context, element| {
+            if selector.context_match(context, &element.element) {
+                handler(element)
+            }
+        }))
+    }
+
+    pub fn text<M, F>(selector: M, mut handler: F) -> Self
+    where
+        M: ContextualSelector + 'h,
+        F: FnMut(&mut TextChunk<'_>) + 'h,
+    {
+        ContentHandler::Text(Box::new(move |context, text| {
+            if let Some((parent, context)) = context.split_last() {
+                if selector.context_match(context, parent) {
+                    handler(text)
+                }
+            }
+        }))
+    }
+}
+
+/// An element about to be appended, which a handler can modify
+pub struct ElementMut<'a, Handle> {
+    element: HtmlPathElement<'a, Handle>,
+    before: Vec<HtmlFragment>,
+    after: Vec<HtmlFragment>,
+    removed: bool,
+}
+
+impl<'a, Handle> ElementMut<'a, Handle> {
+    pub fn tag_name(&self) -> &str {
+        &self.element.name.local
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.element
+            .attrs
+            .iter()
+            .find(|attr| attr.name.ns == ns!() && &*attr.name.local == name)
+            .map(|attr| &*attr.value)
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.get_attribute(name).is_some()
+    }
+
+    /// Sets the value of an attribute, adding it if it isn't present
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        let attrs = self.element.attrs.to_mut();
+        match attrs
+            .iter_mut()
+            .find(|attr| attr.name.ns == ns!() && &*attr.name.local == name)
+        {
+            Some(attr) => attr.value = StrTendril::from_slice(value),
+            None => attrs.push(Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(name)),
+                value: StrTendril::from_slice(value),
+            }),
+        }
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) {
+        if self.has_attribute(name) {
+            self.element
+                .attrs
+                .to_mut()
+                .retain(|attr| !(attr.name.ns == ns!() && &*attr.name.local == name));
+        }
+    }
+
+    /// Inserts content before the element, after any inserted before it already
+    pub fn before(&mut self, fragment: HtmlFragment) {
+        self.before.push(fragment)
+    }
+
+    /// Inserts content right after the element, before any inserted after it already, it is emitted once the element has closed
+    pub fn after(&mut self, fragment: HtmlFragment) {
+        self.after.insert(0, fragment)
+    }
+
+    /// Removes the element and its children, content inserted before or after it is still emitted
+    pub fn remove(&mut self) {
+        self.removed = true
+    }
+
+    pub fn removed(&self) -> bool {
+        self.removed
+    }
+}
+
+/// A text event, which a handler can modify
+pub struct TextChunk<'a> {
+    text: Cow<'a, str>,
+    removed: bool,
+}
+
+impl<'a> TextChunk<'a> {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn replace(&mut self, text: &str) {
+        self.text = Cow::Owned(text.to_owned())
+    }
+
+    pub fn remove(&mut self) {
+        self.removed = true
+    }
+
+    pub fn removed(&self) -> bool {
+        self.removed
+    }
+}
+
+/// The content to insert after an open element, in its context
+struct PendingAfter<Handle> {
+    handle: Handle,
+    context: Vec<HtmlPathElement<'static, Handle>>,
+    fragments: Vec<HtmlFragment>,
+}
+
+/// Applies lol_html style element and text handlers to the stream, for users who find implementing `HtmlSink` too low level.
+///
+/// Content inserted before or after elements is an [`HtmlFragment`], so [`HtmlFragment::text`] is escaped when serialized while
+/// [`HtmlFragment::html`] inserts markup, its elements given synthetic handles.
+pub struct HtmlRewriter<'h, Handle, S> {
+    inner: S,
+    handlers: Vec<ContentHandler<'h, Handle>>,
+    handles: SyntheticHandles,
+    /// The open removed element, with its depth, the length of its context
+    skipped: Option<(Handle, usize)>,
+    /// The open elements with content to insert after them, outermost first, the lengths of their contexts giving their depths
+    pending_after: Vec<PendingAfter<Handle>>,
+}
+
+impl<'h, Handle: SyntheticHandle, S: HtmlSink<Handle>> HtmlRewriter<'h, Handle, S> {
+    pub fn wrap(inner: S, handlers: Vec<ContentHandler<'h, Handle>>) -> Self {
+        Self {
+            inner,
+            handlers,
+            handles: SyntheticHandles::new(),
+            skipped: None,
+            pending_after: vec![],
+        }
+    }
+
+    /// Emits the content inserted after elements which have closed, returns whether the event should be skipped as it is inside a removed element
+    fn close_elements(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        while let Some(pending) = self.pending_after.last() {
+            if context
+                .get(pending.context.len())
+                .is_some_and(|element| element.handle == pending.handle)
+            {
+                break;
+            }
+            let pending = self.pending_after.pop().unwrap();
+            self.insert(&pending.context, &pending.fragments);
+        }
+        if let Some((handle, depth)) = self.skipped {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                return true;
+            }
+            self.skipped = None
+        }
+        false
+    }
+
+    fn insert(&mut self, context: HtmlContext<'_, Handle>, fragments: &[HtmlFragment]) {
+        for fragment in fragments {
+            fragment.append_to(&mut self.inner, context, &mut self.handles)
+        }
+    }
+
+    /// Passes the text through the text handlers, keeping its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if self.close_elements(context) {
+            return;
+        }
+        let mut chunk = TextChunk {
+            text: Cow::Borrowed(text),
+            removed: false,
+        };
+        for handler in &mut self.handlers {
+            if let ContentHandler::Text(handler) = handler {
+                handler(context, &mut chunk);
+                if chunk.removed {
+                    return;
+                }
+            }
+        }
+        append_text_to(&mut self.inner, context, &chunk.text, boundary)
+    }
+}
+
+impl<'h, Handle: SyntheticHandle, S: HtmlSink<Handle>> HtmlSink<Handle>
+    for HtmlRewriter<'h, Handle, S>
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if self.close_elements(context) {
+            return;
+        }
+        let mut element = ElementMut {
+            element: element.clone(),
+            before: vec![],
+            after: vec![],
+            removed: false,
+        };
+        for handler in &mut self.handlers {
+            if let ContentHandler::Element(handler) = handler {
+                handler(context, &mut element);
+                if element.removed {
+                    break;
+                }
+            }
+        }
+        self.insert(context, &element.before);
+        if element.removed {
+            self.skipped = Some((element.element.handle, context.len()));
+            self.insert(context, &element.after);
+            return;
+        }
+        self.inner.append_element(context, &element.element);
+        if !element.after.is_empty() {
+            self.pending_after.push(PendingAfter {
+                handle: element.element.handle,
+                context: context
+                    .iter()
+                    .cloned()
+                    .map(HtmlPathElement::into_owned)
+                    .collect(),
+                fragments: element.after,
+            });
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.close_elements(&text_context(context, element)) {
+            return;
+        }
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.close_elements(context) {
+            return;
+        }
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.close_elements(context) {
+            return;
+        }
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_elements(&[]);
+        self.skipped = None;
+        self.handles.reset();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, test::stream_doc, HtmlFragment, HtmlSerializer};
+    use html5ever::serialize::SerializeOpts;
+
+    #[test]
+    fn lol_html_style_handlers() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let test = r#"<!DOCTYPE html><html><head></head><body><p class="ad">buy</p><p>see <a href="/x">here</a> <span class="shout">loud</span></p></body></html>"#;
+        stream_doc(
+            test,
+            HtmlRewriter::wrap(
+                &mut serializer,
+                vec![
+                    element!(css_select!(."ad"), |el| {
+                        el.remove();
+                        el.after(HtmlFragment::html("<!--ad-->").unwrap());
+                    }),
+                    element!(css_select!("a"), |el| {
+                        el.set_attribute("rel", "nofollow");
+                        el.before(HtmlFragment::text("[<"));
+                        el.after(HtmlFragment::text("]"));
+                        el.after(HtmlFragment::html(r#"<sup class="ext">*</sup>"#).unwrap());
+                    }),
+                    text!(css_select!(."shout"), |text| {
+                        let loud = text.as_str().to_uppercase();
+                        text.replace(&loud)
+                    }),
+                ],
+            ),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><!--ad--><p>see [&lt;<a href="/x" rel="nofollow">here</a><sup class="ext">*</sup>] <span class="shout">LOUD</span></p></body></html>"#
+        );
+    }
+
+    #[test]
+    fn removal_and_nested_after() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let mut seen = Vec::new();
+        let test = r#"<!DOCTYPE html><html><head></head><body><div class="rm">gone <b>bold <i>it</i></b><!--c--></div><section class="n"><p class="n">1<span class="n">2</span></p></section><p class="t">keep <em class="rm">x</em> drop</p></body></html>"#;
+        stream_doc(
+            test,
+            HtmlRewriter::wrap(
+                &mut serializer,
+                vec![
+                    element!(css_select!(."rm"), |el| el.remove()),
+                    element!(css_select!(."n"), |el| {
+                        let name = el.tag_name().to_string();
+                        el.after(HtmlFragment::text(&format!("/{name}")));
+                    }),
+                    text!(css_select!(."t"), |text| {
+                        if text.as_str().contains("drop") {
+                            text.remove()
+                        }
+                    }),
+                    text!(css_select!("i"), |text| seen
+                        .push(text.as_str().to_string())),
+                ],
+            ),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><section class="n"><p class="n">1<span class="n">2</span>/span</p>/p</section>/section<p class="t">keep </p></body></html>"#
+        );
+        // handlers don't see the content of removed elements
+        assert!(seen.is_empty());
+    }
+}
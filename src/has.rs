@@ -0,0 +1,319 @@
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, replay_into, HtmlEvent, OwnedPathElement},
+    raw_text::text_context,
+    selector::ContextualSelector,
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// A filter for css's `:has()`, which holds back the subtree of each candidate element until one of its descendants matches, or it
+/// closes, and only then decides whether to pass it on.
+///
+/// By default it keeps the candidates which have a matching descendant, dropping the others, so the sinks inside it only see the
+/// candidates which `C:has(D)` selects, with the rest of the document passing straight through. With [`HasFilter::removing`] it is an
+/// [`crate::ElementRemover`] of `C:has(D)` instead. Descendants are matched in their whole context, and one which matches counts for
+/// each open candidate it is in.
+///
+/// A dropped candidate is still the previous sibling of the element after it, for `+` and `~`.
+///
+/// Once a descendant matches, the held events are passed on and the rest of the candidate streams, so only the events up to the first
+/// match are held, but a candidate without one is held until it closes, which costs the memory of its subtree.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, HasFilter, SelectToStrings};
+///
+/// let figures = parse_document(
+///     HasFilter::wrap(SelectToStrings::new(css_select!("figure")), css_select!("figure"), css_select!("img")),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><figure><figcaption>None</figcaption></figure><figure><img src="a.png"></figure></body></html>"#)
+/// .unwrap();
+/// assert_eq!(figures, [r#"<figure><img src="a.png"></figure>"#]);
+/// ```
+pub struct HasFilter<Handle, S, C, D> {
+    inner: S,
+    candidates: C,
+    descendants: D,
+    removing: bool,
+    /// The open candidates which have no matching descendant yet, with their depth and the index of their element event in the buffer
+    pending: Vec<(Handle, usize, usize)>,
+    buffer: Vec<HtmlEvent<Handle>>,
+    /// The removed candidate whose subtree is being dropped, with its depth
+    dropping: Option<(Handle, usize)>,
+}
+
+impl<Handle, S, C, D> HasFilter<Handle, S, C, D>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    C: ContextualSelector,
+    D: ContextualSelector,
+{
+    /// Wraps the inner sink, keeping the candidates with a descendant which the second selector matches
+    pub fn wrap(inner: S, candidates: C, descendants: D) -> Self {
+        Self {
+            inner,
+            candidates,
+            descendants,
+            removing: false,
+            pending: vec![],
+            buffer: vec![],
+            dropping: None,
+        }
+    }
+
+    /// Removes the candidates with a matching descendant, keeping the others
+    pub fn removing(self) -> Self {
+        Self {
+            removing: true,
+            ..self
+        }
+    }
+
+    /// Whether the event in the context is in a removed candidate, after which it drops the event
+    fn dropped(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        if let Some((handle, depth)) = self.dropping {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                return true;
+            }
+            self.dropping = None;
+        }
+        false
+    }
+
+    /// Drops, holds or passes on text, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if self.dropped(context) {
+            return;
+        }
+        self.close_candidates(context);
+        if self.pending.is_empty() {
+            append_text_to(&mut self.inner, context, text, boundary)
+        } else {
+            self.buffer.push(HtmlEvent::text(context, text, boundary))
+        }
+    }
+
+    /// Decides the pending candidates which have closed without a matching descendant
+    fn close_candidates(&mut self, context: HtmlContext<'_, Handle>) {
+        while let Some(&(handle, depth, start)) = self.pending.last() {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                break;
+            }
+            self.pending.pop();
+            if !self.removing {
+                self.buffer.truncate(start);
+            }
+        }
+        if self.pending.is_empty() {
+            self.flush();
+        }
+    }
+
+    /// A descendant matched, so each open candidate has one
+    fn confirm(&mut self) {
+        if self.removing {
+            let (handle, depth, start) = self.pending[0];
+            self.buffer.truncate(start);
+            self.dropping = Some((handle, depth));
+        }
+        self.pending.clear();
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        replay_into(&self.buffer, &mut self.inner);
+        self.buffer.clear();
+    }
+}
+
+impl<Handle, S, C, D> HtmlSink<Handle> for HasFilter<Handle, S, C, D>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    C: ContextualSelector,
+    D: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if self.dropped(context) {
+            return;
+        }
+        self.close_candidates(context);
+        if !self.pending.is_empty() && self.descendants.context_match(context, element) {
+            self.confirm();
+            if self.dropped(context) {
+                return;
+            }
+        }
+        if self.candidates.context_match(context, element) {
+            self.pending
+                .push((element.handle, context.len(), self.buffer.len()));
+        }
+        if self.pending.is_empty() {
+            self.inner.append_element(context, element)
+        } else {
+            self.buffer.push(HtmlEvent::Element {
+                context: owned_context(context),
+                element: OwnedPathElement::from(element),
+            })
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        let text_context = text_context(context, element);
+        if self.dropped(&text_context) {
+            return;
+        }
+        self.close_candidates(&text_context);
+        if self.pending.is_empty() {
+            self.inner.append_raw_text(context, element, text)
+        } else {
+            self.buffer
+                .push(HtmlEvent::raw_text(context, element, text))
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.dropped(context) {
+            return;
+        }
+        self.close_candidates(context);
+        if self.pending.is_empty() {
+            self.inner.append_comment(context, text)
+        } else {
+            self.buffer.push(HtmlEvent::Comment {
+                context: owned_context(context),
+                text: text.to_owned(),
+            })
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.dropped(context) {
+            return;
+        }
+        self.close_candidates(context);
+        if self.pending.is_empty() {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        } else {
+            self.buffer.push(HtmlEvent::ProcessingInstruction {
+                context: owned_context(context),
+                target: target.to_owned(),
+                data: data.to_owned(),
+            })
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.dropping = None;
+        self.close_candidates(&[]);
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head></head><body><div id="a"><p>text</p><div id="b"><span class="ad">buy</span></div><p>after</p></div><div id="c"><!-- c --><b>none</b></div></body></html>"#;
+
+    fn serialize<C: ContextualSelector, D: ContextualSelector>(
+        candidates: C,
+        descendants: D,
+        removing: bool,
+    ) -> String {
+        let filter = HasFilter::wrap(
+            HtmlSerializer::new(Vec::new(), Default::default()),
+            candidates,
+            descendants,
+        );
+        let filter = if removing { filter.removing() } else { filter };
+        String::from_utf8(parse_document(filter, Default::default()).one(DOC).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keep_candidates_with_a_match() {
+        assert_eq!(
+            serialize(css_select!("div"), css_select!("p"), false),
+            r#"<!DOCTYPE html><html><head></head><body><div id="a"><p>text</p><p>after</p></div></body></html>"#
+        );
+        assert_eq!(
+            serialize(css_select!("div"), css_select!(."ad"), false),
+            r#"<!DOCTYPE html><html><head></head><body><div id="a"><p>text</p><div id="b"><span class="ad">buy</span></div><p>after</p></div></body></html>"#
+        );
+    }
+
+    #[test]
+    fn remove_candidates_with_a_match() {
+        assert_eq!(
+            serialize(css_select!("div"), css_select!(."ad"), true),
+            r#"<!DOCTYPE html><html><head></head><body><div id="c"><!-- c --><b>none</b></div></body></html>"#
+        );
+        assert_eq!(
+            serialize(css_select!("body" > "div"), css_select!("b"), true),
+            r#"<!DOCTYPE html><html><head></head><body><div id="a"><p>text</p><div id="b"><span class="ad">buy</span></div><p>after</p></div></body></html>"#
+        );
+        assert_eq!(serialize(css_select!("p"), css_select!("span"), true), DOC);
+    }
+}
@@ -0,0 +1,355 @@
+use digest::{Digest, Output};
+use html5ever::{tendril::StrTendril, LocalName};
+
+use crate::{Canonicalizer, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// Feeds the events into a digest, each field is length prefixed so different streams can't feed the same bytes
+struct DigestSink<D> {
+    digest: D,
+    /// Whether text and comments are left out
+    structure_only: bool,
+    ignored_attributes: Vec<LocalName>,
+}
+
+impl<D: Digest> DigestSink<D> {
+    fn new(structure_only: bool) -> Self {
+        Self {
+            digest: D::new(),
+            structure_only,
+            ignored_attributes: vec![],
+        }
+    }
+
+    fn tag(&mut self, tag: u8, depth: usize) {
+        self.digest.update([tag]);
+        self.digest.update((depth as u64).to_le_bytes());
+    }
+
+    fn field(&mut self, value: &str) {
+        self.digest.update((value.len() as u64).to_le_bytes());
+        self.digest.update(value);
+    }
+}
+
+impl<Handle: Eq + Copy, D: Digest> HtmlSink<Handle> for DigestSink<D> {
+    type Output = Output<D>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        self.tag(b'D', 0);
+        self.field(name);
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let ignored_attributes = &self.ignored_attributes;
+        let attrs = element
+            .attrs
+            .iter()
+            .filter(|attr| !ignored_attributes.contains(&attr.name.local))
+            .collect::<Vec<_>>();
+        self.tag(b'E', context.len());
+        self.field(&element.name.ns);
+        self.field(&element.name.local);
+        self.digest.update((attrs.len() as u64).to_le_bytes());
+        for attr in attrs {
+            self.field(&attr.name.ns);
+            self.field(&attr.name.local);
+            self.field(&attr.value);
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.structure_only {
+            self.tag(b'T', context.len());
+            self.field(text);
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.structure_only {
+            self.tag(b'C', context.len());
+            self.field(text);
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.tag(b'P', context.len());
+        self.field(target);
+        self.field(data);
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        std::mem::replace(&mut self.digest, D::new()).finalize()
+    }
+}
+
+/// A sink which outputs a digest of each document, computed over its canonical form so that semantically equal documents hash the same.
+///
+/// The hash function is pluggable, any `digest::Digest` can be used, [`Sha256Hasher`] uses SHA-256.
+/// This detects changed pages without storing or serializing their content.
+pub struct ContentHasher<Handle, D> {
+    inner: Canonicalizer<Handle, DigestSink<D>>,
+}
+
+impl<Handle: Eq + Copy, D: Digest> ContentHasher<Handle, D> {
+    pub fn new() -> Self {
+        Self {
+            inner: Canonicalizer::wrap(DigestSink::new(false)),
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, D: Digest> Default for ContentHasher<Handle, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hashes documents with SHA-256
+#[cfg(feature = "sha2")]
+pub type Sha256Hasher<Handle> = ContentHasher<Handle, sha2::Sha256>;
+
+impl<Handle: Eq + Copy, D: Digest> HtmlSink<Handle> for ContentHasher<Handle, D> {
+    type Output = Output<D>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// Attributes which are ignored by a [`StructureFingerprint`] by default, as they tend to change on every request
+pub const DEFAULT_VOLATILE_ATTRIBUTES: &[&str] = &["nonce", "integrity", "datetime", "csrf-token"];
+
+/// A sink which outputs a digest of each document's structure, ignoring text, comments and volatile attributes,
+/// so pages built from the same template get the same fingerprint and can be clustered by layout.
+///
+/// Element names and the remaining attributes are digested in canonical form, like [`ContentHasher`].
+pub struct StructureFingerprint<Handle, D> {
+    inner: Canonicalizer<Handle, DigestSink<D>>,
+}
+
+impl<Handle: Eq + Copy, D: Digest> StructureFingerprint<Handle, D> {
+    /// Creates a fingerprint ignoring the [`DEFAULT_VOLATILE_ATTRIBUTES`]
+    pub fn new() -> Self {
+        Self::with_ignored_attributes(DEFAULT_VOLATILE_ATTRIBUTES.iter().copied())
+    }
+
+    /// Creates a fingerprint ignoring the attributes with the given local names
+    pub fn with_ignored_attributes<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut sink = DigestSink::new(true);
+        sink.ignored_attributes = names.into_iter().map(LocalName::from).collect();
+        Self {
+            inner: Canonicalizer::wrap(sink),
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, D: Digest> Default for StructureFingerprint<Handle, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fingerprints document structure with SHA-256
+#[cfg(feature = "sha2")]
+pub type Sha256StructureFingerprint<Handle> = StructureFingerprint<Handle, sha2::Sha256>;
+
+impl<Handle: Eq + Copy, D: Digest> HtmlSink<Handle> for StructureFingerprint<Handle, D> {
+    type Output = Output<D>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(all(test, feature = "sha2"))]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    fn hash(doc: &str) -> Output<sha2::Sha256> {
+        parse_document(Sha256Hasher::new(), Default::default())
+            .one(doc)
+            .unwrap()
+    }
+
+    #[test]
+    fn content_hash() {
+        let doc = hash(
+            r#"<!DOCTYPE html><html><head></head><body><p id="a" class="b">Tom &amp; Jerry</p></body></html>"#,
+        );
+        assert_eq!(
+            doc,
+            hash("<!DOCTYPE html><html><head></head><body><p class=b id=a>Tom &#38;\n Jerry</p></body></html>")
+        );
+        assert_ne!(
+            doc,
+            hash(
+                r#"<!DOCTYPE html><html><head></head><body><p id="a" class="b">Tom &amp; Jerry!</p></body></html>"#
+            )
+        );
+        assert_ne!(
+            doc,
+            hash(
+                r#"<!DOCTYPE html><html><head></head><body><p id="a">Tom &amp; Jerry</p></body></html>"#
+            )
+        );
+    }
+
+    #[test]
+    fn structure_fingerprint() {
+        let fingerprint = |doc: &str| {
+            parse_document(Sha256StructureFingerprint::new(), Default::default())
+                .one(doc)
+                .unwrap()
+        };
+        let page = fingerprint(
+            r#"<!DOCTYPE html><html><head><script nonce="abc"></script></head><body><h1 class="title">One</h1><!-- 1 --></body></html>"#,
+        );
+        assert_eq!(
+            page,
+            fingerprint(
+                r#"<!DOCTYPE html><html><head><script nonce="xyz"></script></head><body><h1 class="title">Two</h1><!-- 2 --></body></html>"#
+            )
+        );
+        assert_ne!(
+            page,
+            fingerprint(
+                r#"<!DOCTYPE html><html><head><script nonce="abc"></script></head><body><h2 class="title">One</h2><!-- 1 --></body></html>"#
+            )
+        );
+    }
+}
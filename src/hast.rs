@@ -0,0 +1,258 @@
+use std::io::{self, Write};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, QualName};
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError};
+
+/// Writes a string as a quoted json string
+fn write_json_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    let mut rest = value;
+    while let Some(pos) = rest.find(|c: char| c == '"' || c == '\\' || c < ' ') {
+        writer.write_all(&rest.as_bytes()[..pos])?;
+        let c = rest[pos..].chars().next().unwrap();
+        match c {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' => writer.write_all(b"\\n")?,
+            '\r' => writer.write_all(b"\\r")?,
+            '\t' => writer.write_all(b"\\t")?,
+            c => write!(writer, "\\u{:04x}", c as u32)?,
+        }
+        rest = &rest[pos + 1..];
+    }
+    writer.write_all(rest.as_bytes())?;
+    writer.write_all(b"\"")
+}
+
+/// The hast property name for an attribute, `class` becomes `className`, `for` becomes `htmlFor` and hyphenated names are camel cased
+fn property_name(name: &QualName) -> String {
+    match name.local {
+        local_name!("class") => "className".to_owned(),
+        local_name!("for") => "htmlFor".to_owned(),
+        _ => {
+            let mut property = String::with_capacity(name.local.len());
+            let mut upper = false;
+            for c in name.local.chars() {
+                if c == '-' {
+                    upper = true;
+                } else if upper {
+                    property.push(c.to_ascii_uppercase());
+                    upper = false;
+                } else {
+                    property.push(c);
+                }
+            }
+            property
+        }
+    }
+}
+
+/// Serializes the sink events as a json syntax tree in the [hast](https://github.com/syntax-tree/hast) format, for consumption by rehype / unified tooling.
+///
+/// Each document (up to a `reset`) is written as a `root` node, elements have `tagName`, `properties` and `children`,
+/// `class` is split into a `className` list and other properties are kept as strings.
+/// Elements outside the html namespace are written with their local name.
+///
+/// Write errors are recorded rather than panicking, events are ignored after an error until it is taken with `take_error`.
+pub struct HastSerializer<Wr: Write, Handle> {
+    writer: Wr,
+    open_elements: Vec<Handle>,
+    started: bool,
+    needs_comma: bool,
+    error: Option<HtmlStreamError>,
+}
+
+impl<Wr: Write, Handle: Eq + Copy> HastSerializer<Wr, Handle> {
+    pub fn new(writer: Wr) -> Self {
+        Self {
+            writer,
+            open_elements: vec![],
+            started: false,
+            needs_comma: false,
+            error: None,
+        }
+    }
+
+    /// Consumes the serializer, returning the writer
+    pub fn into_inner(self) -> Wr {
+        self.writer
+    }
+
+    /// Opens the root if needed, closes elements not in the context and starts a new child node
+    fn start_node(&mut self, context: HtmlContext<'_, Handle>) -> io::Result<()> {
+        if !self.started {
+            self.writer.write_all(br#"{"type":"root","children":["#)?;
+            self.started = true;
+            self.needs_comma = false;
+        }
+        let common = context
+            .iter()
+            .zip(&self.open_elements)
+            .take_while(|(a, b)| a.handle == **b)
+            .count();
+        while self.open_elements.len() > common {
+            self.open_elements.pop();
+            self.writer.write_all(b"]}")?;
+            self.needs_comma = true;
+        }
+        if self.needs_comma {
+            self.writer.write_all(b",")?;
+        }
+        self.needs_comma = true;
+        Ok(())
+    }
+
+    fn write_element(&mut self, element: &HtmlPathElement<'_, Handle>) -> io::Result<()> {
+        self.writer.write_all(br#"{"type":"element","tagName":"#)?;
+        write_json_str(&mut self.writer, &element.name.local)?;
+        self.writer.write_all(br#","properties":{"#)?;
+        for (i, attr) in element.attrs.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            write_json_str(&mut self.writer, &property_name(&attr.name))?;
+            self.writer.write_all(b":")?;
+            if attr.name.ns == ns!() && attr.name.local == local_name!("class") {
+                self.writer.write_all(b"[")?;
+                for (i, class) in attr.value.split_whitespace().enumerate() {
+                    if i > 0 {
+                        self.writer.write_all(b",")?;
+                    }
+                    write_json_str(&mut self.writer, class)?;
+                }
+                self.writer.write_all(b"]")?;
+            } else {
+                write_json_str(&mut self.writer, &attr.value)?;
+            }
+        }
+        self.writer.write_all(br#"},"children":["#)?;
+        self.open_elements.push(element.handle);
+        self.needs_comma = false;
+        Ok(())
+    }
+
+    fn write_literal(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        node_type: &str,
+        value: &str,
+    ) -> io::Result<()> {
+        self.start_node(context)?;
+        write!(self.writer, r#"{{"type":"{}","value":"#, node_type)?;
+        write_json_str(&mut self.writer, value)?;
+        self.writer.write_all(b"}")
+    }
+
+    /// Records the first error, events are then ignored until it is taken
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err.into());
+        }
+    }
+}
+
+impl<Wr: Write, Handle: Eq + Copy> HtmlSink<Handle> for &mut HastSerializer<Wr, Handle> {
+    type Output = ();
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .start_node(&[])
+            .and_then(|()| self.writer.write_all(br#"{"type":"doctype"}"#));
+        self.check(result)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .start_node(context)
+            .and_then(|()| self.write_element(element));
+        self.check(result)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.error.is_none() {
+            let result = self.write_literal(context, "text", text);
+            self.check(result)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.error.is_none() {
+            let result = self.write_literal(context, "comment", text);
+            self.check(result)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        if self.error.is_some() {
+            self.open_elements.clear();
+            self.started = false;
+            return;
+        }
+        let mut result = Ok(());
+        if self.started {
+            let closes = self.open_elements.len();
+            self.open_elements.clear();
+            self.started = false;
+            result = (0..closes)
+                .try_for_each(|_| self.writer.write_all(b"]}"))
+                .and_then(|()| self.writer.write_all(b"]}"));
+        }
+        let result = result.and_then(|()| self.writer.flush());
+        self.check(result)
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test::stream_doc;
+
+    #[test]
+    fn hast_output() {
+        let mut sink = HastSerializer::new(Vec::new());
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><!-- "note" --><p class="a b" data-x-y="1">hello<br>world</p></body></html>"#,
+            &mut sink,
+        );
+        let hast: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+        assert_eq!(
+            hast,
+            json!({"type": "root", "children": [
+                {"type": "doctype"},
+                {"type": "element", "tagName": "html", "properties": {}, "children": [
+                    {"type": "element", "tagName": "head", "properties": {}, "children": []},
+                    {"type": "element", "tagName": "body", "properties": {}, "children": [
+                        {"type": "comment", "value": " \"note\" "},
+                        {"type": "element", "tagName": "p", "properties": {"className": ["a", "b"], "dataXY": "1"}, "children": [
+                            {"type": "text", "value": "hello"},
+                            {"type": "element", "tagName": "br", "properties": {}, "children": []},
+                            {"type": "text", "value": "world"},
+                        ]},
+                    ]},
+                ]},
+            ]})
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use html5ever::{local_name, namespace_url, ns};
+
+use crate::{
+    event::{owned_context, replay_into, HtmlEvent, OwnedPathElement, SiblingCopies},
+    HtmlContext, HtmlPathElement, HtmlSink,
+};
+
+/// Holds back the events of a document from the end of its `<head>`, so a sink can still add to the head
+/// once it has seen more of the document, for moving things such as styles or hints into the head.
+///
+/// The sink calls [`HeadBuffer::is_held`] for each event, and [`HeadBuffer::push`]es the event instead of forwarding it
+/// when it is. Once it knows what it is adding, it appends that in [`HeadBuffer::head_context`], then [`HeadBuffer::release`]s the held
+/// events to stream the rest of the document, or [`HeadBuffer::flush`]es them at the end of it. With [`HeadBuffer::hold_head`]
+/// the head's own children are held too, so what is added goes at the start of the head.
+#[derive(Debug)]
+pub struct HeadBuffer<Handle> {
+    /// The context of the head's children, once the head has been appended
+    head: Option<Vec<OwnedPathElement<Handle>>>,
+    hold_head: bool,
+    closed: bool,
+    released: bool,
+    events: Vec<HtmlEvent<Handle>>,
+}
+
+impl<Handle> Default for HeadBuffer<Handle> {
+    fn default() -> Self {
+        Self {
+            head: None,
+            hold_head: false,
+            closed: false,
+            released: false,
+            events: vec![],
+        }
+    }
+}
+
+impl<Handle: Eq + Copy> HeadBuffer<Handle> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to also hold the head's children, rather than only what comes after the head
+    pub fn hold_head(self, hold_head: bool) -> Self {
+        Self { hold_head, ..self }
+    }
+
+    /// Whether the event in the context, of the element if it is one, should be held
+    pub fn is_held(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: Option<&HtmlPathElement<'_, Handle>>,
+    ) -> bool {
+        if self.released {
+            return false;
+        }
+        if self.closed {
+            return true;
+        }
+        match (&self.head, element) {
+            (Some(head), _) => {
+                let head = head.last().unwrap().handle;
+                self.closed = !context.iter().any(|element| element.handle == head);
+                return self.closed || self.hold_head;
+            }
+            (None, Some(element))
+                if context.len() == 1
+                    && element.name.ns == ns!(html)
+                    && element.tag_is(local_name!("head")) =>
+            {
+                let mut head = owned_context(context);
+                head.push(element.into());
+                self.head = Some(head);
+            }
+            _ => {}
+        }
+        self.closed
+    }
+
+    /// Holds an event
+    pub fn push(&mut self, event: HtmlEvent<Handle>) {
+        self.events.push(event)
+    }
+
+    /// The number of events being held
+    pub fn held(&self) -> usize {
+        self.events.len()
+    }
+
+    /// The context to append children to the head in, if the document has one
+    pub fn head_context(&self) -> Option<Vec<HtmlPathElement<'static, Handle>>> {
+        let head = self.head.as_ref()?;
+        Some(SiblingCopies::default().path_context(head))
+    }
+
+    /// Forwards the held events to the sink, and holds no more for this document
+    pub fn release<S: HtmlSink<Handle>>(&mut self, sink: &mut S) {
+        replay_into(&self.events, sink);
+        self.events.clear();
+        self.released = true;
+    }
+
+    /// Whether the held events have been released
+    pub fn is_released(&self) -> bool {
+        self.released
+    }
+
+    /// Forwards the held events to the sink, and starts again for another document
+    pub fn flush<S: HtmlSink<Handle>>(&mut self, sink: &mut S) {
+        self.release(sink);
+        self.reset();
+    }
+
+    /// Drops any held events and starts again for another document
+    pub fn reset(&mut self) {
+        self.head = None;
+        self.closed = false;
+        self.released = false;
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::StrTendril, QualName};
+
+    use super::*;
+    use crate::{test::stream_docs, HtmlSerializer};
+
+    /// Adds a `<meta>` to the head of each document once it has seen all of it
+    struct AddMeta {
+        inner: HtmlSerializer<Vec<u8>, u32>,
+        buffer: HeadBuffer<u32>,
+    }
+
+    impl HtmlSink<u32> for AddMeta {
+        type Output = Vec<u8>;
+
+        fn append_doctype_to_document(
+            &mut self,
+            name: &StrTendril,
+            public_id: &StrTendril,
+            system_id: &StrTendril,
+        ) {
+            self.inner
+                .append_doctype_to_document(name, public_id, system_id)
+        }
+
+        fn append_element(&mut self, context: HtmlContext<u32>, element: &HtmlPathElement<u32>) {
+            if self.buffer.is_held(context, Some(element)) {
+                self.buffer.push(HtmlEvent::Element {
+                    context: owned_context(context),
+                    element: element.into(),
+                })
+            } else {
+                self.inner.append_element(context, element)
+            }
+        }
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            if self.buffer.is_held(context, None) {
+                self.buffer.push(HtmlEvent::text(context, text, None))
+            } else {
+                self.inner.append_text(context, text)
+            }
+        }
+
+        fn append_comment(&mut self, context: HtmlContext<u32>, text: &str) {
+            if self.buffer.is_held(context, None) {
+                self.buffer.push(HtmlEvent::Comment {
+                    context: owned_context(context),
+                    text: text.to_owned(),
+                })
+            } else {
+                self.inner.append_comment(context, text)
+            }
+        }
+
+        fn reset(&mut self) -> Self::Output {
+            if let Some(head) = self.buffer.head_context() {
+                let meta = QualName::new(None, ns!(html), local_name!("meta"));
+                self.inner
+                    .append_element(&head, &HtmlPathElement::new(u32::MAX, meta, vec![]));
+            }
+            self.buffer.flush(&mut self.inner);
+            self.inner.reset()
+        }
+    }
+
+    fn add_meta(buffer: HeadBuffer<u32>) -> Vec<String> {
+        let mut sink = AddMeta {
+            inner: HtmlSerializer::new(Vec::new(), SerializeOpts::default()),
+            buffer,
+        };
+        stream_docs(
+            &[
+                "<!DOCTYPE html><html><head><title>t</title><!-- c --></head><body><p>x</p></body></html>",
+                "<!DOCTYPE html><p>y</p>",
+            ],
+            &mut sink,
+        )
+        .into_iter()
+        .map(|html| String::from_utf8(html).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn add_to_head() {
+        assert_eq!(
+            add_meta(HeadBuffer::new()),
+            [
+                "<!DOCTYPE html><html><head><title>t</title><!-- c --><meta></head><body><p>x</p></body></html>",
+                "<!DOCTYPE html><html><head><meta></head><body><p>y</p></body></html>",
+            ]
+        );
+        assert_eq!(
+            add_meta(HeadBuffer::new().hold_head(true)),
+            [
+                "<!DOCTYPE html><html><head><meta><title>t</title><!-- c --></head><body><p>x</p></body></html>",
+                "<!DOCTYPE html><html><head><meta></head><body><p>y</p></body></html>",
+            ]
+        );
+    }
+}
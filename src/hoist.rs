@@ -0,0 +1,217 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, HtmlEvent, OwnedPathElement},
+    raw_text::text_context,
+    HeadBuffer, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// A `<style>` taken out of the body, with its text
+struct HoistedStyle<Handle> {
+    element: OwnedPathElement<Handle>,
+    text: String,
+    /// Whether a [`crate::RawTextCapture`] gave the style's whole text, to be given again where it is hoisted to
+    raw_text: bool,
+}
+
+/// A filter which moves the `<style>`s in the body of each document, as some content management systems output, to the end of the `<head>`.
+///
+/// The events after the head are held back in a [`HeadBuffer`] until the end of the document, so the styles can be added to the head
+/// before them. Styles in `<template>`s and svg `<style>`s are left where they are, as are all styles of documents without a head.
+pub struct StyleHoister<Handle, S> {
+    inner: S,
+    buffer: HeadBuffer<Handle>,
+    styles: Vec<HoistedStyle<Handle>>,
+    /// Whether the last hoisted style is still open
+    in_style: bool,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> StyleHoister<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            buffer: HeadBuffer::new(),
+            styles: vec![],
+            in_style: false,
+        }
+    }
+
+    /// Whether the event in the context is in the open hoisted style
+    fn is_in_style(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        self.in_style = self.in_style
+            && context.last().map(|element| element.handle)
+                == self.styles.last().map(|style| style.element.handle);
+        self.in_style
+    }
+
+    /// Holds, hoists or passes on text, the text of a hoisted style losing its boundary as it is joined up
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return append_text_to(&mut self.inner, context, text, boundary);
+        }
+        if self.is_in_style(context) {
+            self.styles.last_mut().unwrap().text.push_str(text)
+        } else {
+            self.buffer.push(HtmlEvent::text(context, text, boundary))
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for StyleHoister<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if !self.buffer.is_held(context, Some(element)) {
+            return self.inner.append_element(context, element);
+        }
+        self.is_in_style(context);
+        let is_hoisted = element.name.ns == ns!(html)
+            && element.tag_is(local_name!("style"))
+            && !context.iter().any(|ancestor| {
+                ancestor.name.ns == ns!(html) && ancestor.tag_is(local_name!("template"))
+            });
+        if is_hoisted {
+            self.styles.push(HoistedStyle {
+                element: element.into(),
+                text: String::new(),
+                raw_text: false,
+            });
+            self.in_style = true;
+        } else {
+            self.buffer.push(HtmlEvent::Element {
+                context: owned_context(context),
+                element: element.into(),
+            })
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return self.inner.append_raw_text(context, element, text);
+        }
+        if self.is_in_style(&text_context(context, element)) {
+            self.styles.last_mut().unwrap().raw_text = true
+        } else {
+            self.buffer
+                .push(HtmlEvent::raw_text(context, element, text))
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.buffer.is_held(context, None) {
+            return self.inner.append_comment(context, text);
+        }
+        self.is_in_style(context);
+        self.buffer.push(HtmlEvent::Comment {
+            context: owned_context(context),
+            text: text.to_owned(),
+        })
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return self
+                .inner
+                .append_processing_instruction(context, target, data);
+        }
+        self.is_in_style(context);
+        self.buffer.push(HtmlEvent::ProcessingInstruction {
+            context: owned_context(context),
+            target: target.to_owned(),
+            data: data.to_owned(),
+        })
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        if let Some(mut context) = self.buffer.head_context() {
+            for style in self.styles.drain(..) {
+                let element = style.element.to_child_element(context.last());
+                self.inner.append_element(&context, &element);
+                if !style.text.is_empty() {
+                    context.push(element.clone());
+                    self.inner.append_text(&context, &style.text);
+                    context.pop();
+                }
+                if style.raw_text {
+                    self.inner.append_raw_text(&context, &element, &style.text);
+                }
+            }
+        }
+        self.styles.clear();
+        self.in_style = false;
+        self.buffer.flush(&mut self.inner);
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn hoist_styles() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(StyleHoister::wrap(&mut serializer), Default::default())
+            .one(r#"<!DOCTYPE html><html><head><title>Page</title></head><body><div><style media="print">nav { display: none }</style><p>Text</p></div><style>p { color: red }</style><svg><style>rect {}</style></svg><template><style>b {}</style></template></body></html>"#)
+            .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head><title>Page</title><style media="print">nav { display: none }</style><style>p { color: red }</style></head>"#,
+                r#"<body><div><p>Text</p></div><svg><style>rect {}</style></svg><template><style>b {}</style></template></body></html>"#
+            )
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    attrs::host_matches, BaseUrl, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    TextBoundary,
+};
+
+/// The attributes an [`IframeSandboxer`] adds to the third-party iframes a rule matches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IframePolicy {
+    sandbox: Option<Vec<String>>,
+    lazy: bool,
+    referrer_policy: Option<String>,
+    allow: Option<String>,
+}
+
+impl Default for IframePolicy {
+    /// Sandboxed with `allow-scripts` and `allow-popups`, lazily loaded, and without a referrer beyond the origin
+    fn default() -> Self {
+        Self {
+            sandbox: Some(vec!["allow-scripts".to_owned(), "allow-popups".to_owned()]),
+            lazy: true,
+            referrer_policy: Some("strict-origin-when-cross-origin".to_owned()),
+            allow: None,
+        }
+    }
+}
+
+impl IframePolicy {
+    /// Sets the tokens of the `sandbox` attribute, or `None` not to sandbox
+    pub fn sandbox(self, tokens: Option<&[&str]>) -> Self {
+        Self {
+            sandbox: tokens.map(|tokens| tokens.iter().map(|t| (*t).to_owned()).collect()),
+            ..self
+        }
+    }
+
+    /// Sets whether to add `loading="lazy"`
+    pub fn lazy(self, lazy: bool) -> Self {
+        Self { lazy, ..self }
+    }
+
+    pub fn referrer_policy(self, referrer_policy: Option<&str>) -> Self {
+        Self {
+            referrer_policy: referrer_policy.map(str::to_owned),
+            ..self
+        }
+    }
+
+    /// Sets the permissions policy of the `allow` attribute, such as `fullscreen; picture-in-picture`
+    pub fn allow(self, allow: Option<&str>) -> Self {
+        Self {
+            allow: allow.map(str::to_owned),
+            ..self
+        }
+    }
+
+    fn apply<Handle>(&self, iframe: &mut HtmlPathElement<'_, Handle>) {
+        if let Some(tokens) = &self.sandbox {
+            // an existing sandbox is only narrowed, never loosened
+            let sandbox = match iframe.attr_local("sandbox") {
+                Some(_) => tokens
+                    .iter()
+                    .filter(|token| iframe.has_token("sandbox", token))
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                None => tokens.join(" "),
+            };
+            iframe.set_attr("sandbox", &sandbox);
+        }
+        let missing = [
+            ("loading", self.lazy.then_some("lazy")),
+            ("referrerpolicy", self.referrer_policy.as_deref()),
+            ("allow", self.allow.as_deref()),
+        ];
+        for (attribute, value) in missing {
+            if let (Some(value), false) = (value, iframe.has_attr(attribute)) {
+                iframe.set_attr(attribute, value);
+            }
+        }
+    }
+}
+
+/// A filter which adds `sandbox`, `loading`, `referrerpolicy` and `allow` attributes to third-party `<iframe>`s.
+///
+/// Iframes whose `src` is on the document's origin or an allowed host are left untouched, as are those without a `src`.
+/// Others get the policy of the first [`IframeSandboxer::host`] rule matching their host, or the default policy.
+/// An existing `sandbox` is kept and narrowed to the policy's tokens, the other attributes are only added where they are missing.
+pub struct IframeSandboxer<S> {
+    inner: S,
+    document_url: Url,
+    base: BaseUrl,
+    allowed_hosts: Vec<String>,
+    rules: Vec<(String, IframePolicy)>,
+    default_policy: IframePolicy,
+}
+
+impl<S> IframeSandboxer<S> {
+    pub fn wrap(inner: S, document_url: Url) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            allowed_hosts: vec![],
+            rules: vec![],
+            default_policy: IframePolicy::default(),
+        }
+    }
+
+    /// Sets the hosts whose embeds are trusted and left untouched, such as `www.youtube-nocookie.com` or `*.example.com`
+    pub fn allowed_hosts(self, allowed_hosts: &[&str]) -> Self {
+        Self {
+            allowed_hosts: allowed_hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Adds a rule giving the iframes from hosts matching the pattern, a host or `*.` and a domain, their own policy
+    pub fn host(mut self, pattern: &str, policy: IframePolicy) -> Self {
+        self.rules.push((pattern.to_ascii_lowercase(), policy));
+        self
+    }
+
+    /// Sets the policy of the third-party iframes no rule matches
+    pub fn default_policy(self, default_policy: IframePolicy) -> Self {
+        Self {
+            default_policy,
+            ..self
+        }
+    }
+
+    /// Sets the url of the next document
+    pub fn document_url(&mut self, document_url: Url) {
+        self.base.reset(document_url.clone());
+        self.document_url = document_url;
+    }
+
+    /// The policy for an iframe's url, if it is third-party
+    fn policy(&self, url: &Url) -> Option<&IframePolicy> {
+        let host = url.host_str()?;
+        if url.origin() == self.document_url.origin()
+            || self
+                .allowed_hosts
+                .iter()
+                .any(|pattern| host_matches(pattern, host))
+        {
+            return None;
+        }
+        let rule = self
+            .rules
+            .iter()
+            .find(|(pattern, _)| host_matches(pattern, host));
+        Some(rule.map_or(&self.default_policy, |(_, policy)| policy))
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for IframeSandboxer<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.base.observe(element);
+        let policy = if element.name.ns == ns!(html) && element.tag_is(local_name!("iframe")) {
+            element
+                .attr_url("src", self.base.url())
+                .and_then(|url| self.policy(&url))
+        } else {
+            None
+        };
+        match policy {
+            Some(policy) => {
+                let mut iframe = Cow::Borrowed(element);
+                policy.apply(iframe.to_mut());
+                self.inner.append_element(context, &iframe)
+            }
+            None => self.inner.append_element(context, element),
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.base.reset(self.document_url.clone());
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn sandbox_iframes() {
+        assert!(host_matches("*.example.com", "maps.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            IframeSandboxer::wrap(&mut serializer, "https://example.com/".parse().unwrap())
+                .allowed_hosts(&["*.trusted.example"])
+                .host(
+                    "player.video.example",
+                    IframePolicy::default()
+                        .sandbox(Some(&["allow-scripts", "allow-same-origin"]))
+                        .allow(Some("fullscreen")),
+                ),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><iframe src="/embed"></iframe><iframe src="https://cdn.trusted.example/x"></iframe><iframe src="https://ads.example.net/" sandbox="allow-forms allow-scripts" loading="eager"></iframe><iframe src="https://player.video.example/1"></iframe></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body><iframe src="/embed"></iframe><iframe src="https://cdn.trusted.example/x"></iframe>"#,
+                r#"<iframe src="https://ads.example.net/" sandbox="allow-scripts" loading="eager" referrerpolicy="strict-origin-when-cross-origin"></iframe>"#,
+                r#"<iframe src="https://player.video.example/1" sandbox="allow-scripts allow-same-origin" loading="lazy" referrerpolicy="strict-origin-when-cross-origin" allow="fullscreen"></iframe>"#,
+                r#"</body></html>"#
+            )
+        );
+    }
+}
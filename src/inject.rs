@@ -0,0 +1,640 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use html5ever::{
+    namespace_url, ns,
+    tendril::{StrTendril, TendrilSink},
+    LocalName, QualName,
+};
+
+use crate::{
+    event::{HtmlEvent, OwnedAttribute, OwnedPathElement, Recorder},
+    is_void, parse_fragment,
+    raw_text::text_context,
+    selector::ContextualSelector,
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, SyntheticHandle, SyntheticHandles,
+    TextBoundary,
+};
+
+/// Content for a [`ContentInjector`] or [`ElementReplacer`] to insert into the stream, held as the events of its nodes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HtmlFragment {
+    events: Vec<HtmlEvent<u32>>,
+}
+
+impl HtmlFragment {
+    /// Parses html as the content of a `<body>`, failing at the first parse error, so a mistake in a snippet isn't fixed up silently
+    pub fn html(html: &str) -> Result<Self, HtmlStreamError> {
+        let events = parse_fragment(Recorder::new(), Default::default()).one(html)?;
+        Ok(Self { events })
+    }
+
+    /// Text, which is escaped when serialized
+    pub fn text(text: &str) -> Self {
+        Self {
+            events: vec![HtmlEvent::Text {
+                context: vec![],
+                text: text.to_owned(),
+            }],
+        }
+    }
+
+    /// An empty html element, with attributes without a namespace, such as an anchor to link to
+    pub fn element(local: &str, attrs: &[(&str, &str)]) -> Self {
+        let element = OwnedPathElement::new(
+            0,
+            QualName::new(None, ns!(html), LocalName::from(local)),
+            attrs
+                .iter()
+                .map(|(name, value)| OwnedAttribute {
+                    name: QualName::new(None, ns!(), LocalName::from(*name)),
+                    value: (*value).to_owned(),
+                })
+                .collect(),
+        );
+        Self {
+            events: vec![HtmlEvent::Element {
+                context: vec![],
+                element,
+            }],
+        }
+    }
+
+    /// Appends the nodes to the sink in the context, with a new synthetic handle for each element
+    pub(crate) fn append_to<Handle: SyntheticHandle, S: HtmlSink<Handle>>(
+        &self,
+        sink: &mut S,
+        context: HtmlContext<'_, Handle>,
+        handles: &mut SyntheticHandles,
+    ) {
+        let mut synthetic = HashMap::new();
+        for event in &self.events {
+            let path = |fragment_context: &[OwnedPathElement<u32>],
+                        synthetic: &HashMap<u32, Handle>| {
+                let mut path = context.to_vec();
+                for element in fragment_context {
+                    let element = inserted(element, synthetic[&element.handle], path.last());
+                    path.push(element);
+                }
+                path
+            };
+            match event {
+                HtmlEvent::Doctype { .. } => {}
+                HtmlEvent::Element { context, element } => {
+                    let path = path(context, &synthetic);
+                    let handle = handles.allocate();
+                    synthetic.insert(element.handle, handle);
+                    sink.append_element(&path, &inserted(element, handle, path.last()))
+                }
+                HtmlEvent::Text { context, text } => {
+                    sink.append_text(&path(context, &synthetic), text)
+                }
+                HtmlEvent::TextWithBoundary {
+                    context,
+                    text,
+                    boundary,
+                } => sink.append_text_with_boundary(&path(context, &synthetic), text, *boundary),
+                HtmlEvent::RawText {
+                    context,
+                    element,
+                    text,
+                } => {
+                    let path = path(context, &synthetic);
+                    let element = inserted(element, synthetic[&element.handle], path.last());
+                    sink.append_raw_text(&path, &element, text)
+                }
+                HtmlEvent::Comment { context, text } => {
+                    sink.append_comment(&path(context, &synthetic), text)
+                }
+                HtmlEvent::ProcessingInstruction {
+                    context,
+                    target,
+                    data,
+                } => sink.append_processing_instruction(&path(context, &synthetic), target, data),
+            }
+        }
+    }
+}
+
+/// An element of a fragment as it is inserted, without the position and siblings it had in the fragment, as it isn't from the source
+fn inserted<Handle: Copy>(
+    element: &OwnedPathElement<u32>,
+    handle: Handle,
+    parent: Option<&HtmlPathElement<'_, Handle>>,
+) -> HtmlPathElement<'static, Handle> {
+    OwnedPathElement::new(handle, element.name.clone(), element.attrs.clone())
+        .to_child_element(parent)
+}
+
+/// A filter which inserts fragments before, after, or as the first or last children of each element the selector selects, such as
+/// banners, analytics snippets or anchors.
+///
+/// The fragments are streamed as events, so the sinks inside see their elements as they would those of the source. Each inserted
+/// element gets a new [`SyntheticHandle`], so they are distinct even when a fragment is inserted many times. The content appended to
+/// an element and inserted after it is held until the element closes, which is found from the next event outside it, or the end of the
+/// document. Void elements such as `<img>` can't have children, so only the fragments before and after them are inserted.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, ContentInjector, HtmlFragment, HtmlSerializer};
+///
+/// let html = parse_document(
+///     ContentInjector::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!("body"))
+///         .prepend(HtmlFragment::html(r#"<div class="banner">Sale!</div>"#).unwrap())
+///         .append(HtmlFragment::html(r#"<script src="/analytics.js"></script>"#).unwrap()),
+///     Default::default(),
+/// )
+/// .one("<!DOCTYPE html><html><head></head><body><p>Text</p></body></html>")
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     r#"<!DOCTYPE html><html><head></head><body><div class="banner">Sale!</div><p>Text</p><script src="/analytics.js"></script></body></html>"#
+/// );
+/// ```
+pub struct ContentInjector<Handle, S, M> {
+    inner: S,
+    matcher: M,
+    before: Vec<HtmlFragment>,
+    prepend: Vec<HtmlFragment>,
+    append: Vec<HtmlFragment>,
+    after: Vec<HtmlFragment>,
+    handles: SyntheticHandles,
+    /// The open selected elements which have content to insert when they close, each with its context followed by itself
+    open: Vec<Vec<HtmlPathElement<'static, Handle>>>,
+}
+
+impl<Handle, S, M> ContentInjector<Handle, S, M>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    pub fn wrap(inner: S, matcher: M) -> Self {
+        Self {
+            inner,
+            matcher,
+            before: vec![],
+            prepend: vec![],
+            append: vec![],
+            after: vec![],
+            handles: SyntheticHandles::new(),
+            open: vec![],
+        }
+    }
+
+    /// Inserts the fragment before each selected element, after those added before it
+    pub fn before(mut self, fragment: HtmlFragment) -> Self {
+        self.before.push(fragment);
+        self
+    }
+
+    /// Inserts the fragment as the first children of each selected element, after those added before it
+    pub fn prepend(mut self, fragment: HtmlFragment) -> Self {
+        self.prepend.push(fragment);
+        self
+    }
+
+    /// Inserts the fragment as the last children of each selected element, after those added before it
+    pub fn append(mut self, fragment: HtmlFragment) -> Self {
+        self.append.push(fragment);
+        self
+    }
+
+    /// Inserts the fragment after each selected element, after those added before it
+    pub fn after(mut self, fragment: HtmlFragment) -> Self {
+        self.after.push(fragment);
+        self
+    }
+
+    fn insert(&mut self, placement: Placement, context: HtmlContext<'_, Handle>) {
+        let fragments = match placement {
+            Placement::Before => &self.before,
+            Placement::Prepend => &self.prepend,
+            Placement::Append => &self.append,
+            Placement::After => &self.after,
+        };
+        for fragment in fragments {
+            fragment.append_to(&mut self.inner, context, &mut self.handles);
+        }
+    }
+
+    /// Inserts the content of the selected elements which have closed before an event in the context
+    fn close_elements(&mut self, context: HtmlContext<'_, Handle>) {
+        while let Some(path) = self.open.last() {
+            let depth = path.len() - 1;
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == path[depth].handle)
+            {
+                break;
+            }
+            let path = self.open.pop().unwrap();
+            self.insert(Placement::Append, &path);
+            self.insert(Placement::After, &path[..depth]);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Placement {
+    Before,
+    Prepend,
+    Append,
+    After,
+}
+
+impl<Handle, S, M> HtmlSink<Handle> for ContentInjector<Handle, S, M>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_elements(context);
+        if !self.matcher.context_match(context, element) {
+            return self.inner.append_element(context, element);
+        }
+        self.insert(Placement::Before, context);
+        self.inner.append_element(context, element);
+        if is_void(&element.name) {
+            return self.insert(Placement::After, context);
+        }
+        let mut path = context
+            .iter()
+            .cloned()
+            .map(HtmlPathElement::into_owned)
+            .collect::<Vec<_>>();
+        path.push(element.clone().into_owned());
+        self.insert(Placement::Prepend, &path);
+        if !self.append.is_empty() || !self.after.is_empty() {
+            self.open.push(path);
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_elements(context);
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.close_elements(context);
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.close_elements(&text_context(context, element));
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_elements(context);
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close_elements(context);
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_elements(&[]);
+        self.handles.reset();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// What an [`ElementReplacer`] puts in place of each selected element, a prebuilt fragment or the fragment a callback builds from the
+/// element
+pub trait Replacement<Handle> {
+    fn replacement(&mut self, element: &HtmlPathElement<'_, Handle>) -> Cow<'_, HtmlFragment>;
+}
+
+impl<Handle> Replacement<Handle> for HtmlFragment {
+    fn replacement(&mut self, _element: &HtmlPathElement<'_, Handle>) -> Cow<'_, HtmlFragment> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<Handle, F: FnMut(&HtmlPathElement<'_, Handle>) -> HtmlFragment> Replacement<Handle> for F {
+    fn replacement(&mut self, element: &HtmlPathElement<'_, Handle>) -> Cow<'_, HtmlFragment> {
+        Cow::Owned(self(element))
+    }
+}
+
+/// A filter which drops the subtree of each element the selector selects, as an [`crate::ElementRemover`] does, and inserts a fragment
+/// in its place, such as a placeholder for an embed or an iframe.
+///
+/// The fragment is either the same for each element, or built by a callback from the element, so it can carry over its attributes.
+/// As with a [`ContentInjector`], each inserted element gets a new [`SyntheticHandle`]. The element after a replaced one has the
+/// replaced element as its previous sibling, not the fragment.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, ElementReplacer, HtmlFragment, HtmlSerializer};
+///
+/// let html = parse_document(
+///     ElementReplacer::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!("iframe"), |iframe| {
+///         let src = iframe.attr_local("src").map_or("", |src| &**src);
+///         HtmlFragment::element("a", &[("href", src), ("class", "embed")])
+///     }),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><iframe src="https://example.com/video"><p>No frames</p></iframe></body></html>"#)
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     r#"<!DOCTYPE html><html><head></head><body><a href="https://example.com/video" class="embed"></a></body></html>"#
+/// );
+/// ```
+pub struct ElementReplacer<Handle, S, M, R> {
+    inner: S,
+    matcher: M,
+    replacement: R,
+    handles: SyntheticHandles,
+    /// The replaced element and its depth, the length of its context, so skipping its descendants costs nothing per event
+    skipped: Option<(Handle, usize)>,
+}
+
+impl<Handle, S, M, F> ElementReplacer<Handle, S, M, F>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&HtmlPathElement<'_, Handle>) -> HtmlFragment,
+{
+    /// Replaces each selected element with the fragment the callback builds from it
+    pub fn wrap(inner: S, matcher: M, replace: F) -> Self {
+        Self {
+            inner,
+            matcher,
+            replacement: replace,
+            handles: SyntheticHandles::new(),
+            skipped: None,
+        }
+    }
+}
+
+impl<Handle, S, M> ElementReplacer<Handle, S, M, HtmlFragment>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    /// Replaces each selected element with the fragment
+    pub fn with_fragment(inner: S, matcher: M, fragment: HtmlFragment) -> Self {
+        Self {
+            inner,
+            matcher,
+            replacement: fragment,
+            handles: SyntheticHandles::new(),
+            skipped: None,
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S, M, R> ElementReplacer<Handle, S, M, R> {
+    /// Whether the event in the context is inside the replaced element, otherwise ends the skipping
+    fn is_skipping(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        match self.skipped {
+            Some((handle, depth))
+                if context
+                    .get(depth)
+                    .is_some_and(|element| element.handle == handle) =>
+            {
+                true
+            }
+            _ => {
+                self.skipped = None;
+                false
+            }
+        }
+    }
+}
+
+impl<Handle, S, M, R> HtmlSink<Handle> for ElementReplacer<Handle, S, M, R>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    R: Replacement<Handle>,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if self.is_skipping(context) {
+            return;
+        }
+        if !self.matcher.context_match(context, element) {
+            return self.inner.append_element(context, element);
+        }
+        self.skipped = Some((element.handle, context.len()));
+        self.replacement.replacement(element).append_to(
+            &mut self.inner,
+            context,
+            &mut self.handles,
+        );
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.is_skipping(&text_context(context, element)) {
+            return;
+        }
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.skipped = None;
+        self.handles.reset();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        css_select, parse_document, HtmlSerializer, ParseConfig, SelectToStrings, SiblingTracking,
+    };
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head></head><body><h2>One</h2><p>a <img src="a.png"></p><h2>Two</h2></body></html>"#;
+
+    #[test]
+    fn inject_content() {
+        let html = parse_document(
+            ContentInjector::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("h2", "img"),
+            )
+            .before(HtmlFragment::element("a", &[("id", "section")]))
+            .prepend(HtmlFragment::text("§ "))
+            .append(HtmlFragment::html("<!-- end --><small>x</small>").unwrap())
+            .after(HtmlFragment::html("<hr>").unwrap()),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body>"#,
+                r#"<a id="section"></a><h2>§ One<!-- end --><small>x</small></h2><hr>"#,
+                r#"<p>a <a id="section"></a><img src="a.png"><hr></p>"#,
+                r#"<a id="section"></a><h2>§ Two<!-- end --><small>x</small></h2><hr>"#,
+                r#"</body></html>"#
+            )
+        );
+    }
+
+    #[test]
+    fn injected_elements_are_selectable() {
+        let selected = parse_document(
+            ContentInjector::wrap(
+                SelectToStrings::new(css_select!("h2" > "span"."new")),
+                css_select!("h2"),
+            )
+            .append(HtmlFragment::html(r#"<span class="new">new</span>"#).unwrap()),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            selected,
+            [
+                r#"<span class="new">new</span>"#,
+                r#"<span class="new">new</span>"#
+            ]
+        );
+        assert!(HtmlFragment::html("<p>unclosed <b>bold</p>").is_err());
+    }
+
+    #[test]
+    fn replace_elements() {
+        let html = parse_document(
+            ElementReplacer::with_fragment(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("p"),
+                HtmlFragment::html(r#"<div class="gone">Removed <b>content</b></div>"#).unwrap(),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><h2>One</h2><div class="gone">Removed <b>content</b></div><h2>Two</h2></body></html>"#
+        );
+
+        let selected = ParseConfig::default()
+            .sibling_tracking(SiblingTracking::Adjacent)
+            .parse_document(ElementReplacer::wrap(
+                SelectToStrings::new(css_select!("body" > "h3")),
+                css_select!("h2"),
+                |heading| match heading.previous_sibling {
+                    None => HtmlFragment::element("h3", &[("class", "first")]),
+                    Some(_) => HtmlFragment::element("h3", &[]),
+                },
+            ))
+            .one(DOC)
+            .unwrap();
+        assert_eq!(selected, [r#"<h3 class="first"></h3>"#, "<h3></h3>"]);
+    }
+}
@@ -0,0 +1,203 @@
+use html5ever::tendril::StrTendril;
+use tracing::Span;
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// A wrapper which makes the inner sink's events in a `tracing` span for the stage, so the work of each filter shows up in traces and flamegraphs.
+///
+/// On each reset the number of elements and bytes of text the stage was given is recorded on the span and logged as a debug event.
+pub struct Instrumented<S> {
+    inner: S,
+    span: Span,
+    elements: usize,
+    text_bytes: usize,
+}
+
+impl<S> Instrumented<S> {
+    pub fn wrap(inner: S, stage: &'static str) -> Self {
+        Self {
+            inner,
+            span: tracing::debug_span!(
+                "stage",
+                stage,
+                elements = tracing::field::Empty,
+                text_bytes = tracing::field::Empty
+            ),
+            elements: 0,
+            text_bytes: 0,
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for Instrumented<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        let _entered = self.span.enter();
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let _entered = self.span.enter();
+        self.elements += 1;
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let _entered = self.span.enter();
+        self.text_bytes += text.len();
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        let _entered = self.span.enter();
+        self.text_bytes += text.len();
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        let _entered = self.span.enter();
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let _entered = self.span.enter();
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        let _entered = self.span.enter();
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        let _entered = self.span.enter();
+        self.span.record("elements", self.elements);
+        self.span.record("text_bytes", self.text_bytes);
+        tracing::debug!(
+            elements = self.elements,
+            text_bytes = self.text_bytes,
+            "stage finished document"
+        );
+        self.elements = 0;
+        self.text_bytes = 0;
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    };
+
+    use html5ever::serialize::SerializeOpts;
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    use super::*;
+    use crate::{css_select, test::stream_doc, ElementRemover, HtmlSerializer};
+
+    /// Records the span names and the messages and fields of the events
+    #[derive(Clone, Default)]
+    struct Collector {
+        next_id: Arc<AtomicU64>,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Line<'a>(&'a mut String);
+
+    impl Visit for Line<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut line = format!("span {}", span.metadata().name());
+            span.record(&mut Line(&mut line));
+            self.lines.lock().unwrap().push(line);
+            span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut line = "event".to_owned();
+            event.record(&mut Line(&mut line));
+            self.lines.lock().unwrap().push(line);
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn trace_pipeline() {
+        let collector = Collector::default();
+        tracing::subscriber::with_default(collector.clone(), || {
+            let mut buf = Vec::new();
+            let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+            let sink = Instrumented::wrap(
+                ElementRemover::wrap(&mut serializer, css_select!("p")),
+                "remove",
+            );
+            stream_doc(
+                "<!DOCTYPE html><html><head></head><body><p>gone</p>kept</body></html>",
+                sink,
+            );
+        });
+        assert_eq!(
+            *collector.lines.lock().unwrap(),
+            vec![
+                "span stage stage=\"remove\"".to_owned(),
+                "span parse".to_owned(),
+                "event message=stage finished document elements=4 text_bytes=8".to_owned(),
+                "event message=serialized document bytes_written=58 failed=false".to_owned(),
+                "event message=parsed document elements=4 failed=false".to_owned(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, QualName};
+use whatlang::Lang;
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, replay_into, HtmlEvent, OwnedAttribute, OwnedPathElement},
+    raw_text::text_context,
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// The BCP 47 tag for a detected language, the two-letter ISO 639-1 code where there is one
+pub fn language_tag(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Afr => "af",
+        Lang::Aka => "ak",
+        Lang::Amh => "am",
+        Lang::Ara => "ar",
+        Lang::Aze => "az",
+        Lang::Bel => "be",
+        Lang::Ben => "bn",
+        Lang::Bul => "bg",
+        Lang::Cat => "ca",
+        Lang::Ces => "cs",
+        Lang::Cmn => "zh",
+        Lang::Dan => "da",
+        Lang::Deu => "de",
+        Lang::Ell => "el",
+        Lang::Eng => "en",
+        Lang::Epo => "eo",
+        Lang::Est => "et",
+        Lang::Fin => "fi",
+        Lang::Fra => "fr",
+        Lang::Guj => "gu",
+        Lang::Heb => "he",
+        Lang::Hin => "hi",
+        Lang::Hrv => "hr",
+        Lang::Hun => "hu",
+        Lang::Hye => "hy",
+        Lang::Ind => "id",
+        Lang::Ita => "it",
+        Lang::Jav => "jv",
+        Lang::Jpn => "ja",
+        Lang::Kan => "kn",
+        Lang::Kat => "ka",
+        Lang::Khm => "km",
+        Lang::Kor => "ko",
+        Lang::Lat => "la",
+        Lang::Lav => "lv",
+        Lang::Lit => "lt",
+        Lang::Mal => "ml",
+        Lang::Mar => "mr",
+        Lang::Mkd => "mk",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Nld => "nl",
+        Lang::Nob => "nb",
+        Lang::Ori => "or",
+        Lang::Pan => "pa",
+        Lang::Pes => "fa",
+        Lang::Pol => "pl",
+        Lang::Por => "pt",
+        Lang::Ron => "ro",
+        Lang::Rus => "ru",
+        Lang::Sin => "si",
+        Lang::Slk => "sk",
+        Lang::Slv => "sl",
+        Lang::Sna => "sn",
+        Lang::Spa => "es",
+        Lang::Srp => "sr",
+        Lang::Swe => "sv",
+        Lang::Tam => "ta",
+        Lang::Tel => "te",
+        Lang::Tgl => "tl",
+        Lang::Tha => "th",
+        Lang::Tuk => "tk",
+        Lang::Tur => "tr",
+        Lang::Ukr => "uk",
+        Lang::Urd => "ur",
+        Lang::Uzb => "uz",
+        Lang::Vie => "vi",
+        Lang::Yid => "yi",
+        Lang::Zul => "zu",
+    }
+}
+
+/// Whether a declared language tag, such as `en-GB`, is for the language of a detected tag
+fn is_same_language(declared: &str, tag: &str) -> bool {
+    let primary = declared.split(['-', '_']).next().unwrap_or_default();
+    primary.eq_ignore_ascii_case(tag)
+}
+
+/// The elements whose text is detected as one, the outermost is used where they nest
+fn is_block<Handle>(element: &HtmlPathElement<'_, Handle>) -> bool {
+    element.name.ns == ns!(html)
+        && matches!(
+            element.name.local,
+            local_name!("p")
+                | local_name!("h1")
+                | local_name!("h2")
+                | local_name!("h3")
+                | local_name!("h4")
+                | local_name!("h5")
+                | local_name!("h6")
+                | local_name!("li")
+                | local_name!("dt")
+                | local_name!("dd")
+                | local_name!("td")
+                | local_name!("th")
+                | local_name!("caption")
+                | local_name!("figcaption")
+                | local_name!("blockquote")
+                | local_name!("summary")
+                | local_name!("legend")
+        )
+}
+
+/// The language of a block of text, as found by a [`LanguageDetector`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockLanguage {
+    /// The css path of the block element, as described by [`crate::CssPathTracker`]
+    pub path: String,
+    /// The detected language's tag, see [`language_tag`]
+    pub lang: String,
+    /// The detector's confidence, from 0 to 1
+    pub confidence: f64,
+    /// The language the block inherits from the nearest `lang` attribute, if any
+    pub declared: Option<String>,
+}
+
+impl BlockLanguage {
+    /// Whether the declared language is missing or differs from the detected one
+    pub fn is_mismatched(&self) -> bool {
+        self.declared
+            .as_deref()
+            .map_or(true, |declared| !is_same_language(declared, &self.lang))
+    }
+}
+
+/// A block element whose text is being collected
+struct OpenBlock<Handle> {
+    handle: Handle,
+    path: String,
+    declared: Option<String>,
+    text: String,
+    /// The events in the block, held back when `lang` attributes are injected
+    events: Vec<HtmlEvent<Handle>>,
+}
+
+/// Sets an element's `lang` attribute, replacing any `xml:lang` which would take precedence
+fn set_lang<Handle>(element: &mut OwnedPathElement<Handle>, tag: &str) {
+    element
+        .attrs
+        .retain(|attr| !(attr.name.ns == ns!(xml) && attr.name.local == local_name!("lang")));
+    match element
+        .attrs
+        .iter_mut()
+        .find(|attr| attr.name.ns == ns!() && attr.name.local == local_name!("lang"))
+    {
+        Some(attr) => attr.value = tag.to_owned(),
+        None => element.attrs.push(OwnedAttribute {
+            name: QualName::new(None, ns!(), local_name!("lang")),
+            value: tag.to_owned(),
+        }),
+    }
+}
+
+/// A wrapper which detects the language of the text in each block element, such as a paragraph, heading or list item,
+/// and outputs them along with the inner sink's output. Only the detections which `whatlang` considers reliable are kept.
+///
+/// With [`LanguageDetector::inject_lang`], the events of each block are held back until it ends, then forwarded with a `lang` attribute
+/// on the block element where the language it inherits, as tracked in [`crate::InheritedState`], is missing or is another language.
+pub struct LanguageDetector<Handle, S> {
+    inner: S,
+    inject: bool,
+    paths: CssPathTracker,
+    block: Option<OpenBlock<Handle>>,
+    languages: Vec<BlockLanguage>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> LanguageDetector<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            inject: false,
+            paths: CssPathTracker::new(),
+            block: None,
+            languages: vec![],
+        }
+    }
+
+    /// Sets whether to add `lang` attributes to blocks whose declared language is missing or wrong
+    pub fn inject_lang(self, inject: bool) -> Self {
+        Self { inject, ..self }
+    }
+
+    /// Detects the language of the open block and forwards its events, if the event in the context is outside it
+    fn close_block(&mut self, context: HtmlContext<'_, Handle>) {
+        let block = match &self.block {
+            Some(block) if !context.iter().any(|element| element.handle == block.handle) => {
+                self.block.take().unwrap()
+            }
+            _ => return,
+        };
+        let handle = block.handle;
+        let mut events = block.events;
+        let found = whatlang::detect(&block.text).filter(|info| info.is_reliable());
+        if let Some(info) = found {
+            let language = BlockLanguage {
+                path: block.path,
+                lang: language_tag(info.lang()).to_owned(),
+                confidence: info.confidence(),
+                declared: block.declared,
+            };
+            if self.inject && language.is_mismatched() {
+                for event in &mut events {
+                    let context = match event {
+                        HtmlEvent::Element { context, element }
+                        | HtmlEvent::RawText {
+                            context, element, ..
+                        } => {
+                            if element.handle == handle {
+                                set_lang(element, &language.lang);
+                            }
+                            context
+                        }
+                        HtmlEvent::Text { context, .. }
+                        | HtmlEvent::TextWithBoundary { context, .. }
+                        | HtmlEvent::Comment { context, .. }
+                        | HtmlEvent::ProcessingInstruction { context, .. } => context,
+                        HtmlEvent::Doctype { .. } => continue,
+                    };
+                    if let Some(element) =
+                        context.iter_mut().find(|element| element.handle == handle)
+                    {
+                        set_lang(element, &language.lang);
+                    }
+                }
+            }
+            self.languages.push(language);
+        }
+        replay_into(&events, &mut self.inner);
+    }
+
+    /// Whether events are being held back for the open block
+    fn is_holding(&self) -> bool {
+        self.inject && self.block.is_some()
+    }
+
+    /// Adds text to the open block, holding it back with its boundary if the block is being held
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        self.close_block(context);
+        let Some(block) = &mut self.block else {
+            return append_text_to(&mut self.inner, context, text, boundary);
+        };
+        let in_raw_text = context.last().is_some_and(|parent| {
+            matches!(
+                parent.name.local,
+                local_name!("script") | local_name!("style")
+            )
+        });
+        if !in_raw_text {
+            block.text.push_str(text);
+        }
+        if self.inject {
+            block.events.push(HtmlEvent::text(context, text, boundary));
+        } else {
+            append_text_to(&mut self.inner, context, text, boundary)
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for LanguageDetector<Handle, S> {
+    type Output = (S::Output, Vec<BlockLanguage>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_block(context);
+        self.paths.element(context);
+        if self.block.is_none() && is_block(element) && !element.inherited.in_template {
+            self.block = Some(OpenBlock {
+                handle: element.handle,
+                path: self.paths.element_path(context, element),
+                declared: element.inherited.lang.as_ref().map(|lang| lang.to_string()),
+                text: String::new(),
+                events: vec![],
+            });
+        }
+        if self.is_holding() {
+            let event = HtmlEvent::Element {
+                context: owned_context(context),
+                element: OwnedPathElement::from(element),
+            };
+            self.block.as_mut().unwrap().events.push(event);
+        } else {
+            self.inner.append_element(context, element)
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.close_block(&text_context(context, element));
+        match &mut self.block {
+            Some(block) if self.inject => block
+                .events
+                .push(HtmlEvent::raw_text(context, element, text)),
+            _ => self.inner.append_raw_text(context, element, text),
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_block(context);
+        if self.is_holding() {
+            let event = HtmlEvent::Comment {
+                context: owned_context(context),
+                text: text.to_owned(),
+            };
+            self.block.as_mut().unwrap().events.push(event);
+        } else {
+            self.inner.append_comment(context, text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close_block(context);
+        if self.is_holding() {
+            let event = HtmlEvent::ProcessingInstruction {
+                context: owned_context(context),
+                target: target.to_owned(),
+                data: data.to_owned(),
+            };
+            self.block.as_mut().unwrap().events.push(event);
+        } else {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_block(&[]);
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.languages))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    const DOC: &str = r#"<!DOCTYPE html><html lang="en"><head></head><body><p>The quick brown fox jumps over the lazy dog and runs away into the forest.</p><p lang="en">Le renard brun rapide saute par-dessus le chien paresseux et s'enfuit dans la forêt.</p><ul><li>Der schnelle braune Fuchs springt über den faulen Hund und läuft in den Wald.</li></ul></body></html>"#;
+
+    #[test]
+    fn detect_languages() {
+        let ((), languages) = parse_document(LanguageDetector::wrap(()), Default::default())
+            .one(DOC)
+            .unwrap();
+        let found = languages
+            .iter()
+            .map(|l| (l.path.as_str(), l.lang.as_str(), l.declared.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("html > body > p:nth-child(1)", "en", Some("en")),
+                ("html > body > p:nth-child(2)", "fr", Some("en")),
+                (
+                    "html > body > ul:nth-child(3) > li:nth-child(1)",
+                    "de",
+                    Some("en")
+                ),
+            ]
+        );
+        assert!(!languages[0].is_mismatched());
+        assert!(languages[1].is_mismatched());
+    }
+
+    #[test]
+    fn inject_lang() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            LanguageDetector::wrap(&mut serializer).inject_lang(true),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        drop(serializer);
+        let html = String::from_utf8(buf).unwrap();
+        assert!(html.contains("<p>The quick"));
+        assert!(html.contains(r#"<p lang="fr">Le renard"#));
+        assert!(html.contains(r#"<li lang="de">Der schnelle"#));
+    }
+}
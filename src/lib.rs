@@ -1,42 +1,283 @@
-use std::{borrow::Cow, fmt, io::Write, iter, mem};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+    iter, mem,
+};
 
 use html5ever::{
+    local_name, namespace_url, ns,
     serialize::{self, Serializer},
     tendril::StrTendril,
     Attribute, QualName,
 };
 
+#[cfg(feature = "url")]
+mod allowlist;
+mod attrs;
+mod autofill;
+mod boundary;
+mod canonical;
+mod classes;
+mod coalescer;
+mod config;
+mod content_model;
+mod context;
+mod csrf;
+mod css_path;
+mod dedupe;
+mod defaults;
+mod defer;
+mod diff;
+mod divert;
+mod dump;
+mod entities;
+mod error;
+pub mod event;
+mod expect;
+mod fan_out;
+mod findings;
+mod handlers;
+mod has;
+#[cfg(feature = "digest")]
+mod hash;
+mod hast;
+mod head;
+mod hoist;
+#[cfg(feature = "url")]
+mod iframe;
+mod inject;
+#[cfg(feature = "tracing")]
+mod instrument;
+#[cfg(feature = "whatlang")]
+mod language;
+#[cfg(feature = "url")]
+mod link_graph;
+#[cfg(feature = "url")]
+mod links;
+mod logger;
+mod lookahead;
+mod metrics;
+mod modernize;
+mod namespace;
+mod patch;
+mod picture;
+mod pipeline;
+mod preload;
+mod progress;
+mod provenance;
+mod prune;
+mod raw_text;
+#[cfg(feature = "url")]
+mod redirect;
+mod relocate;
+mod rewrite;
+mod schema;
+mod select;
 pub mod selector;
+mod skeleton;
+mod srcset;
+mod stats;
+mod strip;
+mod synthetic;
 mod traverser;
-
+mod tree;
+mod unwrap;
+#[cfg(feature = "url")]
+mod upgrade;
+mod url_rewrite;
+#[cfg(feature = "url")]
+mod url_scan;
+#[cfg(feature = "quick-xml")]
+mod xml_events;
+mod xpath;
+mod xss_audit;
+
+#[cfg(feature = "url")]
+pub use allowlist::*;
+pub use attrs::*;
+pub use autofill::*;
+pub use boundary::*;
+pub use canonical::*;
+pub use classes::*;
+pub use coalescer::*;
+pub use config::*;
+pub use content_model::*;
+pub use context::*;
+pub use csrf::*;
+pub use css_path::*;
+pub use dedupe::*;
+pub use defaults::*;
+pub use defer::*;
+pub use diff::*;
+pub use divert::*;
+pub use dump::*;
+pub use entities::*;
+pub use error::*;
+pub use expect::*;
+pub use findings::*;
+pub use handlers::*;
+pub use has::*;
+#[cfg(feature = "digest")]
+pub use hash::*;
+pub use hast::*;
+pub use head::*;
+pub use hoist::*;
+#[cfg(feature = "url")]
+pub use iframe::*;
+pub use inject::*;
+#[cfg(feature = "tracing")]
+pub use instrument::*;
+#[cfg(feature = "whatlang")]
+pub use language::*;
+#[cfg(feature = "url")]
+pub use link_graph::*;
+#[cfg(feature = "url")]
+pub use links::*;
+pub use logger::*;
+pub use lookahead::*;
+pub use metrics::*;
+pub use modernize::*;
+pub use namespace::*;
+pub use patch::*;
+pub use picture::*;
+pub use pipeline::*;
+pub use preload::*;
+pub use progress::*;
+pub use provenance::*;
+pub use prune::*;
+pub use raw_text::*;
+#[cfg(feature = "url")]
+pub use redirect::*;
+pub use relocate::*;
+pub use rewrite::*;
+pub use schema::*;
+pub use select::*;
 use selector::{ContextualSelector, Selector};
+pub use skeleton::*;
+pub use srcset::*;
+pub use stats::*;
+pub use strip::*;
+pub use synthetic::*;
 pub use traverser::*;
+pub use tree::*;
+pub use unwrap::*;
+#[cfg(feature = "url")]
+pub use upgrade::*;
+pub use url_rewrite::*;
+#[cfg(feature = "url")]
+pub use url_scan::*;
+#[cfg(feature = "quick-xml")]
+pub use xml_events::*;
+pub use xpath::*;
+pub use xss_audit::*;
+
+/// Whether an element is an html void element, which has no end tag
+pub(crate) fn is_void(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && matches!(
+            name.local,
+            local_name!("area")
+                | local_name!("base")
+                | local_name!("br")
+                | local_name!("col")
+                | local_name!("embed")
+                | local_name!("hr")
+                | local_name!("img")
+                | local_name!("input")
+                | local_name!("link")
+                | local_name!("meta")
+                | local_name!("source")
+                | local_name!("track")
+                | local_name!("wbr")
+        )
+}
 
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct HtmlPathElement<'a, Handle> {
     pub handle: Handle,
     pub name: html5ever::QualName,
     pub attrs: Cow<'a, [Attribute]>,
+    /// The state the element inherits from its ancestors, including what its own attributes set
+    pub inherited: InheritedState,
+    /// The position of the element among its siblings in the source, `None` for elements which aren't from it, such as those a filter adds
+    pub position: Option<SiblingPosition>,
+    /// The element before this one among its siblings in the source, `None` for the first, for elements which aren't from it and unless
+    /// the producer tracks siblings, see [`ParseConfig::sibling_tracking`]
+    pub previous_sibling: Option<PreviousSibling<Handle>>,
+    /// What the element contains, only known once it has closed, so `None` unless a [`ContentBuffer`] held it back
+    pub content: Option<ElementContent>,
 }
 
 impl<'a, Handle> HtmlPathElement<'a, Handle> {
+    /// An element which isn't from the source, so it has no position, siblings or content, and the state only its own attributes set
+    pub fn new(handle: Handle, name: QualName, attrs: impl Into<Cow<'a, [Attribute]>>) -> Self {
+        let attrs = attrs.into();
+        Self {
+            handle,
+            inherited: InheritedState::for_element(None, &name, &attrs),
+            name,
+            attrs,
+            position: None,
+            previous_sibling: None,
+            content: None,
+        }
+    }
+
+    /// Detaches the element from the borrowed attributes, copying them if needed
+    pub fn into_owned(self) -> HtmlPathElement<'static, Handle> {
+        HtmlPathElement {
+            handle: self.handle,
+            name: self.name,
+            attrs: Cow::Owned(self.attrs.into_owned()),
+            inherited: self.inherited,
+            position: self.position,
+            previous_sibling: self.previous_sibling,
+            content: self.content,
+        }
+    }
+
+    /// The elements before this one among its siblings in the source, from the nearest
+    pub fn previous_siblings(&self) -> impl Iterator<Item = &HtmlPathElement<'static, Handle>> {
+        iter::successors(self.previous_sibling.as_deref(), |sibling| {
+            sibling.previous_sibling.as_deref()
+        })
+    }
+
     pub fn attr(&self, name: QualName) -> Option<&StrTendril> {
         self.attrs
             .iter()
-            .find_map(|a| (a.name == name).then(|| &a.value))
+            .find_map(|a| (a.name == name).then_some(&a.value))
     }
 
-    pub fn classes(&self) -> iter::Flatten<std::option::IntoIter<std::str::SplitWhitespace<'_>>> {
-        use html5ever::*;
-        const CLASS: QualName = QualName {
-            prefix: None,
-            ns: ns!(),
-            local: local_name!("class"),
-        };
-        self.attr(CLASS)
-            .map(|value| value.split_whitespace())
-            .into_iter()
-            .flatten()
+    /// Looks up an attribute without a namespace by its local name, which covers all of an html element's own attributes
+    pub fn attr_local(&self, local: &str) -> Option<&StrTendril> {
+        self.attrs
+            .iter()
+            .find_map(|a| (a.name.ns.is_empty() && &*a.name.local == local).then_some(&a.value))
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.attr_local("id").map(|id| &**id)
+    }
+
+    pub fn classes(&self) -> Classes<'_> {
+        Classes::new(self.attr_local("class").map(|value| &**value))
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes().contains(class)
+    }
+
+    /// Edits the class list, copying the attributes if they are borrowed
+    pub fn class_list_mut(&mut self) -> ClassListMut<'_> {
+        ClassListMut::new(self.attrs.to_mut())
+    }
+
+    /// Whether the element's local name is `local`, in any namespace
+    pub fn tag_is(&self, local: html5ever::LocalName) -> bool {
+        self.name.local == local
     }
 }
 
@@ -73,52 +314,331 @@ where
 
     fn append_text(&mut self, context: HtmlContext<Handle>, text: &str);
 
+    /// Appends text flagged with where it falls in its text node and element, this is only called by a [`TextBoundaries`] wrapper and appends it as plain text by default
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        _boundary: TextBoundary,
+    ) {
+        self.append_text(context, text)
+    }
+
+    /// Receives the complete text of a `<script>` or `<style>` element once it has closed, along with the element and its context.
+    /// This is only called by a [`RawTextCapture`] wrapper, after the text has been appended as usual, and does nothing by default
+    fn append_raw_text(
+        &mut self,
+        _context: HtmlContext<Handle>,
+        _element: &HtmlPathElement<'_, Handle>,
+        _text: &str,
+    ) {
+    }
+
     fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str);
 
+    /// Appends a processing instruction, these only come from xml input and are dropped by default
+    fn append_processing_instruction(
+        &mut self,
+        _context: HtmlContext<Handle>,
+        _target: &str,
+        _data: &str,
+    ) {
+    }
+
     fn reset(&mut self) -> Self::Output;
 
-    fn finish(mut self) -> Self::Output {
-        self.reset()
+    /// Takes the first error recorded by this sink or the sinks it wraps, sinks which can fail record errors here rather than panicking
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        None
+    }
+
+    fn finish(mut self) -> Result<Self::Output, HtmlStreamError> {
+        let output = self.reset();
+        match self.take_error() {
+            Some(err) => Err(err),
+            None => Ok(output),
+        }
     }
 }
 
+/// A sink which discards everything, for wrappers which are only wanted for their own output
+impl<Handle: Eq + Copy> HtmlSink<Handle> for () {
+    type Output = ();
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+    }
+
+    fn append_element(
+        &mut self,
+        _context: HtmlContext<'_, Handle>,
+        _element: &HtmlPathElement<'_, Handle>,
+    ) {
+    }
+
+    fn append_text(&mut self, _context: HtmlContext<Handle>, _text: &str) {}
+
+    fn append_comment(&mut self, _context: HtmlContext<Handle>, _text: &str) {}
+
+    fn reset(&mut self) -> Self::Output {}
+}
+
 struct OpenElement<Handle> {
     handle: Handle,
     name: html5ever::QualName,
 }
 
+/// Collects the serializer's many small writes, only writing through to the underlying writer when full or flushed
+struct WriteBuffer<Wr> {
+    writer: Wr,
+    buf: Vec<u8>,
+    /// While set, writes are counted here rather than written
+    discarded: Option<usize>,
+    /// Bytes written since the last document was traced
+    #[cfg(feature = "tracing")]
+    written: usize,
+}
+
+impl<Wr: Write> WriteBuffer<Wr> {
+    fn flush_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.writer.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<Wr: Write> Write for WriteBuffer<Wr> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(discarded) = &mut self.discarded {
+            *discarded += data.len();
+            return Ok(data.len());
+        }
+        #[cfg(feature = "tracing")]
+        {
+            self.written += data.len();
+        }
+        if self.buf.len() + data.len() > self.buf.capacity() {
+            self.flush_buf()?;
+        }
+        if data.len() >= self.buf.capacity() {
+            self.writer.write(data)
+        } else {
+            self.buf.extend_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.writer.flush()
+    }
+}
+
+/// How `HtmlSerializer` handles an event whose context contains elements it hasn't been given, or is missing elements it has open
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextMismatch {
+    /// Record a `HtmlStreamError::ContextMismatch`, this is the default
+    Strict,
+    /// Close the open elements which aren't in the context and open (emitting start tags for) the context elements which aren't open,
+    /// so filters can synthesize elements by only including them in the context of their children
+    Resynchronize,
+}
+
+/// The order `HtmlSerializer` writes the attributes of each element in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttributeOrder {
+    /// The order they were given in, which is the order of the input for parsed documents, this is the default
+    #[default]
+    Preserve,
+    /// `id`, then `class`, then the rest sorted by name, so the output of pipeline runs diffs stably however the attributes were written or added
+    Canonical,
+}
+
+impl AttributeOrder {
+    /// The attributes in this order
+    fn sort<'a>(self, attrs: &'a [Attribute]) -> Cow<'a, [Attribute]> {
+        match self {
+            AttributeOrder::Preserve => Cow::Borrowed(attrs),
+            AttributeOrder::Canonical => {
+                let rank = |attr: &Attribute| match &*attr.name.local {
+                    "id" if attr.name.ns == ns!() => 0,
+                    "class" if attr.name.ns == ns!() => 1,
+                    _ => 2,
+                };
+                let mut attrs = attrs.to_vec();
+                attrs.sort_by(|a, b| {
+                    (rank(a), &*a.name.ns, &*a.name.local).cmp(&(
+                        rank(b),
+                        &*b.name.ns,
+                        &*b.name.local,
+                    ))
+                });
+                Cow::Owned(attrs)
+            }
+        }
+    }
+}
+
+/// Default capacity of the serializer's write buffer, the same as `std::io::BufWriter`
+pub const DEFAULT_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// Serializes the sink events as html to a writer.
+///
+/// Output is buffered internally and written through when the buffer fills, on `reset` / `finish`, or on an explicit `flush`.
+///
+/// Write errors and events in a context that doesn't match the elements appended so far are recorded rather than panicking,
+/// the serializer ignores events after an error until it is taken with `take_error`, which `finish` does.
 pub struct HtmlSerializer<Wr: Write, Handle> {
-    inner: html5ever::serialize::HtmlSerializer<Wr>,
+    inner: html5ever::serialize::HtmlSerializer<WriteBuffer<Wr>>,
     open_element_path: Vec<OpenElement<Handle>>,
+    context_mismatch: ContextMismatch,
+    references: CharacterReferences,
+    attribute_order: AttributeOrder,
+    scripting_enabled: bool,
+    error: Option<HtmlStreamError>,
 }
 
-impl<Wr: Write, Handle: Eq + fmt::Display> HtmlSerializer<Wr, Handle> {
-    fn pop_to_path(&mut self, context: HtmlContext<'_, Handle>) {
-        assert!(context
+impl<Wr: Write, Handle: Eq + Copy + fmt::Display> HtmlSerializer<Wr, Handle> {
+    fn pop_to_path(&mut self, context: HtmlContext<'_, Handle>) -> Result<(), HtmlStreamError> {
+        let common = context
             .iter()
             .zip(&self.open_element_path)
-            .all(|(a, b)| a.handle == b.handle));
-        if context.len() > self.open_element_path.len() {
-            panic!(
-                "Non-appended elements in context : {}",
-                context[self.open_element_path.len()..]
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<String>()
-            );
+            .take_while(|(a, b)| a.handle == b.handle)
+            .count();
+        if self.context_mismatch == ContextMismatch::Strict {
+            if let Some((element, open)) = context[common..]
+                .iter()
+                .zip(&self.open_element_path[common..])
+                .next()
+            {
+                return Err(HtmlStreamError::ContextMismatch(format!(
+                    "Expected open element #{} in context, found {}",
+                    open.handle, element
+                )));
+            }
+            if context.len() > self.open_element_path.len() {
+                return Err(HtmlStreamError::ContextMismatch(format!(
+                    "Non-appended elements in context : {}",
+                    context[self.open_element_path.len()..]
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<String>()
+                )));
+            }
         }
-        while context.len() < self.open_element_path.len() {
+        while common < self.open_element_path.len() {
             let closed = self.open_element_path.pop().unwrap();
-            self.inner.end_elem(closed.name).unwrap();
+            self.inner.end_elem(closed.name)?;
+        }
+        for missing in &context[common..] {
+            self.open_element(missing)?;
+        }
+        Ok(())
+    }
+
+    fn open_element(&mut self, element: &HtmlPathElement<'_, Handle>) -> io::Result<()> {
+        let attrs = self.attribute_order.sort(&element.attrs);
+        if self.references == CharacterReferences::Minimal {
+            self.inner.start_elem(
+                element.name.clone(),
+                attrs.iter().map(|att| (&att.name, &*att.value)),
+            )?;
+        } else {
+            // html5ever keeps track of the element, and writes nothing inside void elements, the tag is written here if it would have
+            self.inner.writer.discarded = Some(0);
+            let result = self.inner.start_elem(element.name.clone(), iter::empty());
+            let discarded = self.inner.writer.discarded.take();
+            result?;
+            if discarded != Some(0) {
+                write_start_tag(
+                    &mut self.inner.writer,
+                    &element.name,
+                    &attrs,
+                    self.references,
+                )?;
+            }
+        }
+        self.open_element_path.push(OpenElement {
+            handle: element.handle,
+            name: element.name.clone(),
+        });
+        Ok(())
+    }
+
+    /// Records the first error, events are then ignored until it is taken
+    fn check(&mut self, result: Result<(), HtmlStreamError>) {
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
         }
     }
 
     pub fn new(writer: Wr, opts: serialize::SerializeOpts) -> Self {
+        Self::with_capacity(DEFAULT_BUFFER_CAPACITY, writer, opts)
+    }
+
+    /// Creates a serializer whose write buffer has at least the given capacity, a capacity of 0 writes straight through
+    pub fn with_capacity(capacity: usize, writer: Wr, opts: serialize::SerializeOpts) -> Self {
+        let writer = WriteBuffer {
+            writer,
+            buf: Vec::with_capacity(capacity),
+            discarded: None,
+            #[cfg(feature = "tracing")]
+            written: 0,
+        };
         Self {
+            scripting_enabled: opts.scripting_enabled,
             inner: html5ever::serialize::HtmlSerializer::new(writer, opts),
             open_element_path: vec![],
+            context_mismatch: ContextMismatch::Strict,
+            references: CharacterReferences::Minimal,
+            attribute_order: AttributeOrder::Preserve,
+            error: None,
+        }
+    }
+
+    /// Sets how to handle events whose context doesn't match the elements appended so far
+    pub fn context_mismatch(self, context_mismatch: ContextMismatch) -> Self {
+        Self {
+            context_mismatch,
+            ..self
         }
     }
+
+    /// Sets which characters of text and attribute values are written as character references
+    pub fn character_references(self, references: CharacterReferences) -> Self {
+        Self { references, ..self }
+    }
+
+    /// Sets the order attributes are written in
+    pub fn attribute_order(self, attribute_order: AttributeOrder) -> Self {
+        Self {
+            attribute_order,
+            ..self
+        }
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let raw = self
+            .open_element_path
+            .last()
+            .is_some_and(|open| is_raw_text(&open.name, self.scripting_enabled));
+        if self.references == CharacterReferences::Minimal || raw {
+            self.inner.write_text(text)
+        } else {
+            write_escaped(&mut self.inner.writer, text, false, self.references)
+        }
+    }
+
+    /// Writes any buffered output through to the underlying writer and flushes it
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.writer.flush()
+    }
 }
 
 impl<Wr: Write, Handle: Eq + Copy + fmt::Display> HtmlSink<Handle>
@@ -131,34 +651,72 @@ impl<Wr: Write, Handle: Eq + Copy + fmt::Display> HtmlSink<Handle>
         context: HtmlContext<'_, Handle>,
         element: &HtmlPathElement<'_, Handle>,
     ) {
-        self.pop_to_path(context);
-
-        self.inner
-            .start_elem(
-                element.name.clone(),
-                element.attrs.iter().map(|att| (&att.name, &*att.value)),
-            )
-            .unwrap();
-        self.open_element_path.push(OpenElement {
-            handle: element.handle,
-            name: element.name.clone(),
-        });
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .pop_to_path(context)
+            .and_then(|()| Ok(self.open_element(element)?));
+        self.check(result)
     }
 
     fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
-        self.pop_to_path(context);
-
-        self.inner.write_text(text).unwrap();
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .pop_to_path(context)
+            .and_then(|()| Ok(self.write_text(text)?));
+        self.check(result)
     }
 
     fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
-        self.pop_to_path(context);
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .pop_to_path(context)
+            .and_then(|()| Ok(self.inner.write_comment(text)?));
+        self.check(result)
+    }
 
-        self.inner.write_comment(text).unwrap();
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .pop_to_path(context)
+            .and_then(|()| Ok(self.inner.write_processing_instruction(target, data)?));
+        self.check(result)
     }
 
     fn reset(&mut self) -> Self::Output {
-        self.pop_to_path(&[])
+        let result = if self.error.is_some() {
+            self.open_element_path.clear();
+            Ok(())
+        } else {
+            self.pop_to_path(&[])
+        };
+        let result = result.and_then(|()| Ok(self.flush()?));
+        #[cfg(feature = "tracing")]
+        {
+            let bytes_written = mem::take(&mut self.inner.writer.written);
+            tracing::debug!(
+                bytes_written,
+                failed = result.is_err(),
+                "serialized document"
+            );
+        }
+        self.check(result)
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.error.take()
     }
 
     fn append_doctype_to_document(
@@ -167,14 +725,99 @@ impl<Wr: Write, Handle: Eq + Copy + fmt::Display> HtmlSink<Handle>
         _public_id: &html5ever::tendril::StrTendril,
         _system_id: &html5ever::tendril::StrTendril,
     ) {
-        self.inner.write_doctype(name).unwrap()
+        if self.error.is_some() {
+            return;
+        }
+        let result = self
+            .inner
+            .write_doctype(name)
+            .map_err(HtmlStreamError::from);
+        self.check(result)
+    }
+}
+
+/// An owned serializer outputs the writer at the end of each document, with a new one taking its place, so a `Vec<u8>` gives each
+/// document's bytes. This makes it usable in owned pipelines, and as the inner sink of a [`RootFilter`] collecting each selection
+impl<Wr: Write + Default, Handle: Eq + Copy + fmt::Display> HtmlSink<Handle>
+    for HtmlSerializer<Wr, Handle>
+{
+    type Output = Wr;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &html5ever::tendril::StrTendril,
+        public_id: &html5ever::tendril::StrTendril,
+        system_id: &html5ever::tendril::StrTendril,
+    ) {
+        <&mut Self as HtmlSink<Handle>>::append_doctype_to_document(
+            &mut &mut *self,
+            name,
+            public_id,
+            system_id,
+        )
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        <&mut Self as HtmlSink<Handle>>::append_element(&mut &mut *self, context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        <&mut Self as HtmlSink<Handle>>::append_text(&mut &mut *self, context, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        <&mut Self as HtmlSink<Handle>>::append_comment(&mut &mut *self, context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        <&mut Self as HtmlSink<Handle>>::append_processing_instruction(
+            &mut &mut *self,
+            context,
+            target,
+            data,
+        )
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        <&mut Self as HtmlSink<Handle>>::reset(&mut &mut *self);
+        mem::take(&mut self.inner.writer.writer)
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.error.take()
     }
 }
 
+/// A marker appended in place of each subtree an [`ElementRemover`] drops, so consumers can see what was elided
+#[derive(Clone, Debug)]
+pub enum Placeholder {
+    /// A comment with this text, such as `removed: .ad`
+    Comment(String),
+    /// An empty element, which takes the handle of the removed element
+    Element {
+        name: QualName,
+        attrs: Vec<Attribute>,
+    },
+}
+
+/// A filter which drops the subtree of each element the selector selects, optionally leaving a [`Placeholder`] in its place.
+///
+/// The elements after a dropped one keep it as their previous sibling, see [`SiblingTracking`]
 pub struct ElementRemover<Handle: Eq + Copy, S: HtmlSink<Handle>, M: Selector> {
     inner: S,
     matcher: M,
-    skip_handle: Option<Handle>,
+    /// The removed element and its depth, the length of its context, so skipping its descendants costs nothing per event
+    skipped: Option<(Handle, usize)>,
+    placeholder: Option<Placeholder>,
 }
 
 impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: Selector> ElementRemover<Handle, S, M> {
@@ -182,7 +825,33 @@ impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: Selector> ElementRemover<Handle,
         Self {
             inner: sink,
             matcher,
-            skip_handle: None,
+            skipped: None,
+            placeholder: None,
+        }
+    }
+
+    /// Appends the placeholder where each subtree is removed
+    pub fn placeholder(self, placeholder: Placeholder) -> Self {
+        Self {
+            placeholder: Some(placeholder),
+            ..self
+        }
+    }
+
+    /// Whether the event in the context is inside the removed element, otherwise ends the skipping
+    fn is_skipping(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        match self.skipped {
+            Some((skip_handle, depth))
+                if context
+                    .get(depth)
+                    .is_some_and(|elem| elem.handle == skip_handle) =>
+            {
+                true
+            }
+            _ => {
+                self.skipped = None;
+                false
+            }
         }
     }
 }
@@ -207,53 +876,111 @@ impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: Selector> HtmlSink<Handle>
         context: HtmlContext<'_, Handle>,
         element: &HtmlPathElement<'_, Handle>,
     ) {
-        if let Some(skip_handle) = self.skip_handle {
-            if context.iter().any(|elem| elem.handle == skip_handle) {
-                return;
-            } else {
-                self.skip_handle = None
-            }
+        if self.is_skipping(context) {
+            return;
         }
         let skip = self.matcher.context_match(context, element);
         if skip {
-            self.skip_handle = Some(element.handle);
+            self.skipped = Some((element.handle, context.len()));
+            match &self.placeholder {
+                Some(Placeholder::Comment(text)) => self.inner.append_comment(context, text),
+                Some(Placeholder::Element { name, attrs }) => self.inner.append_element(
+                    context,
+                    &HtmlPathElement {
+                        handle: element.handle,
+                        name: name.clone(),
+                        attrs: Cow::Borrowed(attrs),
+                        inherited: InheritedState::for_element(
+                            context.last().map(|parent| &parent.inherited),
+                            name,
+                            attrs,
+                        ),
+                        position: None,
+                        previous_sibling: None,
+                        content: None,
+                    },
+                ),
+                None => {}
+            }
             return;
         }
         self.inner.append_element(context, element)
     }
 
     fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
-        if let Some(skip_handle) = self.skip_handle {
-            if context.iter().any(|elem| elem.handle == skip_handle) {
-                return;
-            } else {
-                self.skip_handle = None
-            }
+        if self.is_skipping(context) {
+            return;
         }
         self.inner.append_text(context, text)
     }
 
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.is_skipping(&raw_text::text_context(context, element)) {
+            return;
+        }
+        self.inner.append_raw_text(context, element, text)
+    }
+
     fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
-        if let Some(skip_handle) = self.skip_handle {
-            if context.iter().any(|elem| elem.handle == skip_handle) {
-                return;
-            } else {
-                self.skip_handle = None
-            }
+        if self.is_skipping(context) {
+            return;
         }
         self.inner.append_comment(context, text)
     }
 
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.is_skipping(context) {
+            return;
+        }
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
     fn reset(&mut self) -> Self::Output {
-        self.skip_handle = None;
+        self.skipped = None;
         self.inner.reset()
     }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
 }
 
+/// A filter which passes on only the subtrees whose root the selector matches, each as if it were a document, collecting the inner
+/// sink's output for each of them.
+///
+/// Contexts are rebased to the selected element: it is appended with an empty context, and its descendants with contexts starting
+/// from it. Sinks and selectors inside the filter, including further `RootFilter`s and [`ElementRemover`]s, only see the selected
+/// subtree, so [`crate::selector::Scope`] and relative [`crate::XPath`]s resolve to the selected element, while descendant chains
+/// can't match its ancestors.
 pub struct RootFilter<Handle: Eq + Copy, S: HtmlSink<Handle>, M: ContextualSelector, O = ()> {
     inner: S,
     matcher: M,
-    select_handle: Option<Handle>,
+    /// The selected element and its depth, the length of its context, so the selection is checked in constant time whatever the depth
+    selected: Option<(Handle, usize)>,
     output: O,
 }
 
@@ -264,12 +991,37 @@ impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: ContextualSelector, O: Default>
         Self {
             inner,
             matcher,
-            select_handle: None,
+            selected: None,
             output: O::default(),
         }
     }
 }
 
+impl<Handle, S, M, O> RootFilter<Handle, S, M, O>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    O: Extend<S::Output>,
+{
+    /// The index of the selected element in the context if the selection continues, otherwise ends any selection
+    fn select_index(&mut self, context: HtmlContext<'_, Handle>) -> Option<usize> {
+        let (select_handle, select_index) = self.selected?;
+        if context
+            .get(select_index)
+            .is_some_and(|elem| elem.handle == select_handle)
+        {
+            // select continues
+            Some(select_index)
+        } else {
+            // select ends
+            self.selected = None;
+            self.output.extend(iter::once(self.inner.reset()));
+            None
+        }
+    }
+}
+
 impl<Handle, S, M: ContextualSelector, O> HtmlSink<Handle> for RootFilter<Handle, S, M, O>
 where
     Handle: Eq + Copy,
@@ -291,84 +1043,92 @@ where
         context: HtmlContext<'_, Handle>,
         element: &HtmlPathElement<'_, Handle>,
     ) {
-        if let Some(select_handle) = self.select_handle {
-            if let Some(select_index) = context
-                .iter()
-                .enumerate()
-                .find_map(|(index, elem)| (elem.handle == select_handle).then(|| index))
-            {
-                // select continues
-                self.inner.append_element(&context[select_index..], element);
-                return;
-            } else {
-                // select ends
-                self.select_handle = None;
-                self.output.extend(iter::once(self.inner.reset()));
-            }
+        if let Some(select_index) = self.select_index(context) {
+            self.inner.append_element(&context[select_index..], element);
+            return;
         }
         let select = self.matcher.context_match(context, element);
         if select {
             // select starts
-            let select_handle = element.handle;
             self.inner.append_element(&[], element);
-            self.select_handle = Some(select_handle);
+            self.selected = Some((element.handle, context.len()));
         }
     }
 
     fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
-        if let Some(select_handle) = self.select_handle {
-            if let Some(select_index) = context
-                .iter()
-                .enumerate()
-                .find_map(|(index, elem)| (elem.handle == select_handle).then(|| index))
-            {
-                // select continues
-                self.inner.append_text(&context[select_index..], text)
-            } else {
-                // select ends
-                self.select_handle = None;
-                self.output.extend(iter::once(self.inner.reset()));
-            }
+        if let Some(select_index) = self.select_index(context) {
+            self.inner.append_text(&context[select_index..], text)
+        }
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        if let Some(select_index) = self.select_index(context) {
+            self.inner
+                .append_text_with_boundary(&context[select_index..], text, boundary)
+        }
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if let Some(select_index) = self.select_index(&raw_text::text_context(context, element)) {
+            self.inner
+                .append_raw_text(&context[select_index..], element, text)
         }
     }
 
     fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
-        if let Some(select_handle) = self.select_handle {
-            if let Some(select_index) = context
-                .iter()
-                .enumerate()
-                .find_map(|(index, elem)| (elem.handle == select_handle).then(|| index))
-            {
-                // select continues
-                self.inner.append_comment(&context[select_index..], text)
-            } else {
-                // select ends
-                self.select_handle = None;
-                self.output.extend(iter::once(self.inner.reset()));
-            }
+        if let Some(select_index) = self.select_index(context) {
+            self.inner.append_comment(&context[select_index..], text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if let Some(select_index) = self.select_index(context) {
+            self.inner
+                .append_processing_instruction(&context[select_index..], target, data)
         }
     }
 
     fn reset(&mut self) -> Self::Output {
-        if self.select_handle.take().is_some() {
+        if self.selected.take().is_some() {
             self.output.extend(iter::once(self.inner.reset()));
-            self.select_handle = None
         }
         mem::take(&mut self.output)
     }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
 }
 
+#[deprecated(note = "use `ElementUnwrapper`")]
 pub struct ElementSkipper<S, M> {
     inner: S,
     matcher: M,
 }
 
+#[allow(deprecated)]
 impl<S, M: Selector> ElementSkipper<S, M> {
     pub fn wrap(inner: S, matcher: M) -> Self {
         Self { inner, matcher }
     }
 }
 
+#[allow(deprecated)]
 impl<Handle, S, M: Selector> HtmlSink<Handle> for ElementSkipper<S, M>
 where
     Handle: Eq + Copy,
@@ -411,57 +1171,73 @@ where
         self.inner.append_text(filtered_path.as_slice(), text);
     }
 
-    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
         // TODO optimise when not hitting
         let filtered_path = context
             .iter()
             .filter(|element| !self.matcher.is_match(element))
             .cloned()
             .collect::<Vec<_>>();
-        self.inner.append_comment(filtered_path.as_slice(), text);
-    }
-
-    fn reset(&mut self) -> Self::Output {
-        self.inner.reset()
-    }
-}
-
-impl<Handle: Copy + Eq, A: HtmlSink<Handle>, B: HtmlSink<Handle>> HtmlSink<Handle> for (A, B) {
-    type Output = (A::Output, B::Output);
-
-    fn append_doctype_to_document(
-        &mut self,
-        name: &html5ever::tendril::StrTendril,
-        public_id: &html5ever::tendril::StrTendril,
-        system_id: &html5ever::tendril::StrTendril,
-    ) {
-        self.0
-            .append_doctype_to_document(name, public_id, system_id);
-        self.1
-            .append_doctype_to_document(name, public_id, system_id);
+        self.inner
+            .append_text_with_boundary(filtered_path.as_slice(), text, boundary);
     }
 
-    fn append_element(
+    fn append_raw_text(
         &mut self,
-        context: HtmlContext<'_, Handle>,
+        context: HtmlContext<Handle>,
         element: &HtmlPathElement<'_, Handle>,
+        text: &str,
     ) {
-        self.0.append_element(context, element);
-        self.1.append_element(context, element);
+        if self.matcher.is_match(element) {
+            return;
+        }
+        // TODO optimise when not hitting
+        let filtered_path = context
+            .iter()
+            .filter(|element| !self.matcher.is_match(element))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.inner
+            .append_raw_text(filtered_path.as_slice(), element, text);
     }
 
-    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
-        self.0.append_text(context, text);
-        self.1.append_text(context, text);
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        // TODO optimise when not hitting
+        let filtered_path = context
+            .iter()
+            .filter(|element| !self.matcher.is_match(element))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.inner.append_comment(filtered_path.as_slice(), text);
     }
 
-    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
-        self.0.append_comment(context, text);
-        self.1.append_comment(context, text);
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        // TODO optimise when not hitting
+        let filtered_path = context
+            .iter()
+            .filter(|element| !self.matcher.is_match(element))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.inner
+            .append_processing_instruction(filtered_path.as_slice(), target, data);
     }
 
     fn reset(&mut self) -> Self::Output {
-        (self.0.reset(), self.1.reset())
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
     }
 }
 
@@ -469,18 +1245,34 @@ impl<Handle: Copy + Eq, A: HtmlSink<Handle>, B: HtmlSink<Handle>> HtmlSink<Handl
 mod test {
     use super::*;
     use html5ever::{
+        local_name, namespace_url, ns,
         serialize::{SerializeOpts, TraversalScope},
         tendril::TendrilSink,
         ParseOpts,
     };
 
-    fn stream_doc(test: &str, sink: impl HtmlSink<u32>) {
+    pub(crate) fn stream_doc(test: &str, sink: impl HtmlSink<u32>) {
         let mut opts = ParseOpts::default();
         opts.tree_builder.exact_errors = true;
         let parser = parse_document(sink, opts);
         parser.one(test).unwrap();
     }
 
+    /// Streams each document through the same sink, resetting it after each as a sink reused for a run of documents is
+    pub(crate) fn stream_docs<S: HtmlSink<u32>>(docs: &[&str], sink: &mut S) -> Vec<S::Output> {
+        let mut opts = ParseOpts::default();
+        opts.tree_builder.exact_errors = true;
+        docs.iter()
+            .map(|doc| {
+                let events = parse_document(crate::event::Recorder::new(), opts.clone())
+                    .one(*doc)
+                    .unwrap();
+                crate::event::replay_into(&events, sink);
+                sink.reset()
+            })
+            .collect()
+    }
+
     fn serialiser(buf: &mut Vec<u8>) -> HtmlSerializer<&mut Vec<u8>, u32> {
         let opts = SerializeOpts::default();
         HtmlSerializer::new(buf, opts)
@@ -495,12 +1287,134 @@ mod test {
         assert_eq!(String::from_utf8(buf).unwrap(), test);
     }
 
+    #[test]
+    fn buffered_writes() {
+        struct CountingWriter<'a>(&'a mut Vec<u8>, usize);
+        impl Write for CountingWriter<'_> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1 += 1;
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut buf = Vec::new();
+        let mut writer = CountingWriter(&mut buf, 0);
+        let mut sink = HtmlSerializer::new(&mut writer, SerializeOpts::default());
+        let test = "<!DOCTYPE html><html><head></head><body><p><b>hello</b></p><p>world!</p></body></html>";
+        stream_doc(test, &mut sink);
+        assert_eq!(writer.1, 1);
+        assert_eq!(String::from_utf8(buf).unwrap(), test);
+    }
+
+    #[test]
+    fn character_references() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf).character_references(CharacterReferences::Named);
+        stream_doc(
+            r#"<!DOCTYPE html><html><head><style>p::after { content: "…" }</style></head><body><p title="Café &#8220;x&#8221;">Tom &amp; Jérôme&hellip; &#9731;<br></p></body></html>"#,
+            &mut sink,
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><style>p::after { content: "…" }</style></head><body><p title="Caf&eacute; &ldquo;x&rdquo;">Tom &amp; J&eacute;r&ocirc;me&hellip; &#9731;<br></p></body></html>"#
+        );
+    }
+
+    #[test]
+    fn element_accessors() {
+        let attrs = [
+            Attribute {
+                name: QualName::new(None, ns!(), local_name!("id")),
+                value: "main".into(),
+            },
+            Attribute {
+                name: QualName::new(None, ns!(), local_name!("class")),
+                value: " a  b ".into(),
+            },
+            Attribute {
+                name: QualName::new(None, ns!(xlink), local_name!("href")),
+                value: "#x".into(),
+            },
+        ];
+        let element = HtmlPathElement::new(
+            1,
+            QualName::new(None, ns!(html), local_name!("a")),
+            &attrs[..],
+        );
+        assert_eq!(element.id(), Some("main"));
+        assert_eq!(element.attr_local("class").map(|v| &**v), Some(" a  b "));
+        assert_eq!(element.attr_local("href"), None);
+        assert!(element.has_class("b"));
+        assert!(!element.has_class("a b"));
+        assert!(element.tag_is(local_name!("a")));
+        assert!(!element.tag_is(local_name!("p")));
+    }
+
+    #[test]
+    fn edit_classes() {
+        let attrs = [Attribute {
+            name: QualName::new(None, ns!(), local_name!("class")),
+            value: "a\tb  c".into(),
+        }];
+        let mut element = HtmlPathElement::new(
+            1,
+            QualName::new(None, ns!(html), local_name!("p")),
+            &attrs[..],
+        );
+        let mut classes = element.classes();
+        assert_eq!(classes.len(), 3);
+        assert_eq!(classes.next_back(), Some("c"));
+        assert_eq!(classes.len(), 2);
+        assert!(!classes.contains("c"));
+
+        let mut list = element.class_list_mut();
+        assert!(list.add("d"));
+        assert!(!list.add("a"));
+        assert!(list.remove("b"));
+        assert!(!list.toggle("c"));
+        assert_eq!(element.attr_local("class").map(|v| &**v), Some("a d"));
+        let mut list = element.class_list_mut();
+        assert!(list.retain(|_| false));
+        assert_eq!(element.attr_local("class"), None);
+        assert!(element.class_list_mut().toggle("e"));
+        assert_eq!(element.classes().collect::<Vec<_>>(), ["e"]);
+    }
+
+    #[test]
+    fn pipelined_identity() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf);
+        let test = "<!DOCTYPE html><html><head></head><body><!-- comment --><p><b>hello</b></p><p>world!</p></body></html>";
+        pipelined(2, &mut sink, |sink| stream_doc(test, sink));
+        drop(sink);
+        assert_eq!(String::from_utf8(buf).unwrap(), test);
+    }
+
+    #[test]
+    fn canonical_attribute_order() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf).attribute_order(AttributeOrder::Canonical);
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><a title="t" class="c" href="/" id="i">link</a><svg><use xlink:href="/s" fill="red"></use></svg></body></html>"#,
+            &mut sink,
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><a id="i" class="c" href="/" title="t">link</a><svg><use fill="red" xlink:href="/s"></use></svg></body></html>"#
+        );
+    }
+
     #[test]
     // #[ignore = "html5ever mysteriously adds a <html> root"]
     fn fragment_identity() {
         let mut buf = Vec::new();
-        let mut opts = SerializeOpts::default();
-        opts.traversal_scope = TraversalScope::ChildrenOnly(None);
+        let opts = SerializeOpts {
+            traversal_scope: TraversalScope::ChildrenOnly(None),
+            ..Default::default()
+        };
         let mut sink = HtmlSerializer::new(&mut buf, opts);
         let mut opts = ParseOpts::default();
         opts.tree_builder.exact_errors = true;
@@ -525,6 +1439,56 @@ mod test {
         );
     }
 
+    #[test]
+    fn removed_elements_stay_siblings() {
+        let selected = ParseConfig::default()
+            .sibling_tracking(SiblingTracking::Adjacent)
+            .parse_document(ElementRemover::wrap(
+                SelectToStrings::new(css_select!("h2" + "p")),
+                css_select!(."ad"),
+            ))
+            .one(
+                "<!DOCTYPE html><html><head></head><body><h2 class=ad>x</h2><p>a</p></body></html>",
+            )
+            .unwrap();
+        assert_eq!(selected, ["<p>a</p>"]);
+    }
+
+    #[test]
+    fn remove_with_placeholders() {
+        let test = r#"<!DOCTYPE html><html><head></head><body><p class="ad"><b>buy</b></p><p>world!</p><div class="ad">now</div></body></html>"#;
+        let mut buf = Vec::new();
+        let mut serializer = serialiser(&mut buf);
+        stream_doc(
+            test,
+            ElementRemover::wrap(&mut serializer, css_select!(."ad"))
+                .placeholder(Placeholder::Comment(" removed: .ad ".to_owned())),
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><!-- removed: .ad --><p>world!</p><!-- removed: .ad --></body></html>"#
+        );
+
+        let mut buf = Vec::new();
+        let mut serializer = serialiser(&mut buf);
+        stream_doc(
+            test,
+            ElementRemover::wrap(&mut serializer, css_select!(."ad")).placeholder(
+                Placeholder::Element {
+                    name: QualName::new(None, ns!(html), local_name!("div")),
+                    attrs: vec![Attribute {
+                        name: QualName::new(None, ns!(), local_name!("class")),
+                        value: "removed-placeholder".into(),
+                    }],
+                },
+            ),
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><div class="removed-placeholder"></div><p>world!</p><div class="removed-placeholder"></div></body></html>"#
+        );
+    }
+
     #[test] // for selection, a selected node needs to be appended to the document, if it is not already part of a selected tree. i think for this all to work, either each processor needs to have it's own traversal tree, or maybe, the traversal tree builder from a Sink is only the first step and the processing actually happens using a different interface, probably entirely triggered by appends, but also having a (filtered) access to the tracversal scope
     fn select_element() {
         let mut buf = Vec::new();
@@ -535,6 +1499,115 @@ mod test {
         assert_eq!(buf, b"<p><!-- comment --><b>hello</b></p><p>world!</p>");
     }
 
+    #[test]
+    fn scope_relative_selectors() {
+        let select = |doc: &str, matcher| {
+            let sink = RootFilter::<_, _, _, Vec<Vec<String>>>::wrap(
+                SelectToStrings::new(matcher),
+                css_select!("section"),
+            );
+            parse_document(sink, ParseOpts::default()).one(doc).unwrap()
+        };
+        let doc = "<!DOCTYPE html><html><head></head><body><section><p>a</p><div><p>b</p></div></section><p>c</p></body></html>";
+        assert_eq!(
+            select(doc, XPath::parse("p").unwrap()),
+            [vec!["<p>a</p>".to_owned()]]
+        );
+        assert_eq!(
+            select(doc, XPath::parse(".//p").unwrap()),
+            [vec!["<p>a</p>".to_owned(), "<p>b</p>".to_owned()]]
+        );
+        assert_eq!(
+            select(doc, XPath::parse("/section/div").unwrap()),
+            [vec!["<div><p>b</p></div>".to_owned()]]
+        );
+
+        let sink = RootFilter::<_, _, _, Vec<Vec<String>>>::wrap(
+            SelectToStrings::new(css_select!(:scope)),
+            css_select!("section"),
+        );
+        let sections = parse_document(sink, ParseOpts::default())
+            .one("<!DOCTYPE html><html><head></head><body><section><section>nested</section></section></body></html>")
+            .unwrap();
+        assert_eq!(
+            sections,
+            [vec![
+                "<section><section>nested</section></section>".to_owned()
+            ]]
+        );
+
+        let sink = RootFilter::<_, _, _, Vec<Vec<String>>>::wrap(
+            SelectToStrings::new(css_select!((:scope) ("section"))),
+            css_select!("section"),
+        );
+        let sections = parse_document(sink, ParseOpts::default())
+            .one("<!DOCTYPE html><html><head></head><body><section><section>nested</section></section></body></html>")
+            .unwrap();
+        assert_eq!(sections, [vec!["<section>nested</section>".to_owned()]]);
+    }
+
+    #[test]
+    fn owned_serializer_output() {
+        let sink = RootFilter::<_, _, _, Vec<Vec<u8>>>::wrap(
+            HtmlSerializer::new(Vec::new(), SerializeOpts::default()),
+            css_select!("p"),
+        );
+        let selections = parse_document(sink, ParseOpts::default())
+            .one("<!DOCTYPE html><html><head></head><body><p><b>hello</b></p><p>world!</p></body></html>")
+            .unwrap();
+        assert_eq!(
+            selections,
+            [b"<p><b>hello</b></p>".to_vec(), b"<p>world!</p>".to_vec()]
+        );
+
+        let html = parse_document(
+            HtmlSerializer::new(Vec::new(), SerializeOpts::default()),
+            ParseOpts::default(),
+        )
+        .one("<!DOCTYPE html><html><head></head><body></body></html>")
+        .unwrap();
+        assert_eq!(
+            html,
+            b"<!DOCTYPE html><html><head></head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn malformed_input_error() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf);
+        let parser = parse_document(&mut sink, ParseOpts::default());
+        let result = parser.one(
+            "<!DOCTYPE html><html><head></head><body><table><b>misnested</table></body></html>",
+        );
+        assert!(matches!(result, Err(HtmlStreamError::Parse(_))));
+    }
+
+    #[test]
+    fn context_mismatch_error() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf);
+        let element =
+            HtmlPathElement::new(1, QualName::new(None, ns!(html), local_name!("p")), vec![]);
+        (&mut sink).append_text(&[element], "orphan");
+        assert!(matches!(
+            (&mut sink).finish(),
+            Err(HtmlStreamError::ContextMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn context_mismatch_resynchronize() {
+        let mut buf = Vec::new();
+        let mut sink = serialiser(&mut buf).context_mismatch(ContextMismatch::Resynchronize);
+        let p = HtmlPathElement::new(1, QualName::new(None, ns!(html), local_name!("p")), vec![]);
+        let b = HtmlPathElement::new(2, QualName::new(None, ns!(html), local_name!("b")), vec![]);
+        (&mut sink).append_text(&[p.clone(), b], "synthesized");
+        (&mut sink).append_text(&[p], " wrapper");
+        (&mut sink).finish().unwrap();
+        assert_eq!(buf, b"<p><b>synthesized</b> wrapper</p>");
+    }
+
     #[test]
     fn extract_data() {}
 }
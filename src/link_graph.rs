@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{BaseUrl, HtmlContext, HtmlPathElement, HtmlSink};
+
+/// A link from one page of a site to another, in a [`LinkGraph`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkEdge {
+    /// The site-relative path and query of the linked page, without any fragment
+    pub to: String,
+    /// The link's text with whitespace collapsed, including the `alt` text of images in it
+    pub text: String,
+    /// The tokens of the link's `rel` attribute, such as `nofollow`
+    pub rel: Vec<String>,
+}
+
+/// The links between the pages of a site, as built by a [`LinkGraphBuilder`]. Pages are site-relative paths with any query, such as `/docs/intro`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkGraph {
+    /// The links of each parsed page, in document order
+    pub pages: BTreeMap<String, Vec<LinkEdge>>,
+}
+
+impl LinkGraph {
+    /// The links to a page from other pages, with the page they are on
+    pub fn incoming<'a>(&'a self, page: &'a str) -> impl Iterator<Item = (&'a str, &'a LinkEdge)> {
+        self.pages.iter().flat_map(move |(from, edges)| {
+            edges
+                .iter()
+                .filter(move |edge| edge.to == page && from != page)
+                .map(move |edge| (from.as_str(), edge))
+        })
+    }
+
+    /// Every page which was parsed or linked to, for a sitemap
+    pub fn all_pages(&self) -> BTreeSet<&str> {
+        self.pages
+            .iter()
+            .flat_map(|(from, edges)| {
+                Some(from.as_str())
+                    .into_iter()
+                    .chain(edges.iter().map(|edge| edge.to.as_str()))
+            })
+            .collect()
+    }
+
+    /// The parsed pages which no other page links to, other than the `roots` such as the home page
+    pub fn orphans<'a>(&'a self, roots: &'a [&str]) -> Vec<&'a str> {
+        self.pages
+            .keys()
+            .map(String::as_str)
+            .filter(|page| !roots.contains(page) && self.incoming(page).next().is_none())
+            .collect()
+    }
+
+    /// The parsed pages' links to pages which weren't parsed, which may be missing
+    pub fn unparsed_targets(&self) -> BTreeSet<&str> {
+        self.all_pages()
+            .into_iter()
+            .filter(|page| !self.pages.contains_key(*page))
+            .collect()
+    }
+}
+
+/// An `<a>` or `<area>` whose text is being collected
+struct OpenLink<Handle> {
+    handle: Handle,
+    edge: LinkEdge,
+}
+
+/// A sink which accumulates the links between the pages of a site across the documents parsed with it into a [`LinkGraph`].
+///
+/// Set the url of each page with [`LinkGraphBuilder::page`] before parsing it. Links from `<a>` and `<area>` are resolved against the
+/// page's base url and kept when they are within the site, links to other sites and to the page itself by fragment are ignored.
+pub struct LinkGraphBuilder<Handle> {
+    site: Url,
+    page: Option<(String, BaseUrl)>,
+    graph: LinkGraph,
+    open: Option<OpenLink<Handle>>,
+}
+
+impl<Handle: Eq + Copy> LinkGraphBuilder<Handle> {
+    /// A builder for the site at the url, pages must share its origin and be under its path
+    pub fn new(site: Url) -> Self {
+        Self {
+            site,
+            page: None,
+            graph: LinkGraph::default(),
+            open: None,
+        }
+    }
+
+    /// The site-relative page of the url, if it is within the site
+    fn site_page(&self, url: &Url) -> Option<String> {
+        if url.origin() != self.site.origin() || !url.path().starts_with(self.site.path()) {
+            return None;
+        }
+        Some(match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned(),
+        })
+    }
+
+    /// Sets the url of the next page to be parsed
+    pub fn page(&mut self, url: Url) {
+        self.page = self.site_page(&url).map(|page| (page, BaseUrl::new(url)));
+        if let Some((page, _)) = &self.page {
+            self.graph.pages.entry(page.clone()).or_default();
+        }
+    }
+
+    pub fn graph(&self) -> &LinkGraph {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> LinkGraph {
+        self.graph
+    }
+
+    /// Adds the open link to the graph if the event in the context is outside it
+    fn close_link(&mut self, context: HtmlContext<'_, Handle>) {
+        let open = match &self.open {
+            Some(open) if !context.iter().any(|element| element.handle == open.handle) => {
+                self.open.take().unwrap()
+            }
+            _ => return,
+        };
+        let Some((page, _)) = &self.page else {
+            return;
+        };
+        let mut edge = open.edge;
+        edge.text = edge.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.graph.pages.entry(page.clone()).or_default().push(edge);
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for &mut LinkGraphBuilder<Handle> {
+    type Output = ();
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_link(context);
+        let Some((page, base)) = &mut self.page else {
+            return;
+        };
+        base.observe(element);
+        if element.name.ns != ns!(html) {
+            return;
+        }
+        match element.name.local {
+            local_name!("a") | local_name!("area") if self.open.is_none() => {
+                let Some(mut url) = element.attr_url("href", base.url()) else {
+                    return;
+                };
+                url.set_fragment(None);
+                let page = page.clone();
+                if let Some(to) = self.site_page(&url).filter(|to| *to != page) {
+                    self.open = Some(OpenLink {
+                        handle: element.handle,
+                        edge: LinkEdge {
+                            to,
+                            text: element
+                                .attr_local("alt")
+                                .map_or_else(String::new, |alt| alt.to_string()),
+                            rel: element.attr_tokens("rel").map(str::to_owned).collect(),
+                        },
+                    });
+                }
+            }
+            local_name!("img") => {
+                if let (Some(open), Some(alt)) = (&mut self.open, element.attr_local("alt")) {
+                    open.edge.text.push(' ');
+                    open.edge.text.push_str(alt);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_link(context);
+        if let Some(open) = &mut self.open {
+            open.edge.text.push_str(text);
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, _text: &str) {
+        self.close_link(context);
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_link(&[]);
+        self.page = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    #[test]
+    fn build_link_graph() {
+        let mut builder = LinkGraphBuilder::new("https://example.com/".parse().unwrap());
+        let pages = [
+            (
+                "https://example.com/",
+                r##"<a href="/docs/">Docs</a> <a href="about" rel="nofollow"><img alt="About"> us</a> <a href="https://other.com/">elsewhere</a> <a href="#top">top</a>"##,
+            ),
+            (
+                "https://example.com/docs/",
+                r##"<a href="intro?lang=en#start">
+                    Getting   started
+                </a><a href="/">Home</a>"##,
+            ),
+            ("https://example.com/old", "<p>No links here</p>"),
+        ];
+        for (url, doc) in pages.iter() {
+            builder.page(url.parse().unwrap());
+            parse_document(&mut builder, Default::default())
+                .one(format!("<!DOCTYPE html>{}", doc))
+                .unwrap();
+        }
+        let graph = builder.into_graph();
+        let edge = |to: &str, text: &str, rel: &[&str]| LinkEdge {
+            to: to.to_owned(),
+            text: text.to_owned(),
+            rel: rel.iter().map(|rel| (*rel).to_owned()).collect(),
+        };
+        assert_eq!(
+            graph.pages["/"],
+            [
+                edge("/docs/", "Docs", &[]),
+                edge("/about", "About us", &["nofollow"])
+            ]
+        );
+        assert_eq!(
+            graph.pages["/docs/"],
+            [
+                edge("/docs/intro?lang=en", "Getting started", &[]),
+                edge("/", "Home", &[])
+            ]
+        );
+        assert_eq!(graph.orphans(&["/"]), ["/old"]);
+        assert_eq!(
+            graph.unparsed_targets().into_iter().collect::<Vec<_>>(),
+            ["/about", "/docs/intro?lang=en"]
+        );
+        assert_eq!(
+            graph
+                .incoming("/docs/")
+                .map(|(from, _)| from)
+                .collect::<Vec<_>>(),
+            ["/"]
+        );
+    }
+}
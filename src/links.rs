@@ -0,0 +1,436 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::Poll,
+};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    BaseUrl, CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// A link found by a [`LinkCollector`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// The resolved url, including any fragment
+    pub url: Url,
+    /// The local name of the element, such as `a` or `img`
+    pub element: String,
+    /// The attribute the url is in, such as `href` or `src`
+    pub attribute: String,
+    /// The css path of the element, as described by [`CssPathTracker`]
+    pub path: String,
+}
+
+/// The outcome of checking a url, as given by the checker passed to [`check_links`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkStatus {
+    /// The http status code
+    pub status: u16,
+    /// Where the url redirects to, if it does
+    pub redirect: Option<Url>,
+}
+
+/// The links of the documents a [`LinkCollector`] has seen, along with the results of [`check_links`]
+#[derive(Clone, Debug, Default)]
+pub struct LinkReport {
+    pub links: Vec<Link>,
+    /// The result of checking each unique url, keyed by the url without its fragment
+    pub results: HashMap<Url, Result<LinkStatus, String>>,
+}
+
+/// The url a link's resource is at, which is the link without its fragment
+fn resource(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+impl LinkReport {
+    /// The urls of the linked resources, without fragments or duplicates, in the order they were first seen
+    pub fn unique_urls(&self) -> Vec<Url> {
+        let mut seen = HashSet::new();
+        self.links
+            .iter()
+            .map(|link| resource(&link.url))
+            .filter(|url| seen.insert(url.clone()))
+            .collect()
+    }
+
+    /// The result of checking the link's resource, if it has been checked
+    pub fn result(&self, link: &Link) -> Option<&Result<LinkStatus, String>> {
+        self.results.get(&resource(&link.url))
+    }
+
+    /// Adds the links of another report, for checking the links of many documents together
+    pub fn extend(&mut self, other: LinkReport) {
+        self.links.extend(other.links);
+        self.results.extend(other.results);
+    }
+}
+
+/// Checks each unique url of the report which hasn't been checked yet with `checker`, running up to `concurrency` checks at once,
+/// and records the results in the report.
+///
+/// The checker is typically an http client making `HEAD` requests without following redirects,
+/// this doesn't depend on an async runtime so works with whichever the checker uses.
+pub async fn check_links<F, Fut>(report: &mut LinkReport, concurrency: usize, mut checker: F)
+where
+    F: FnMut(Url) -> Fut,
+    Fut: Future<Output = Result<LinkStatus, String>>,
+{
+    let mut pending = report
+        .unique_urls()
+        .into_iter()
+        .filter(|url| !report.results.contains_key(url))
+        .collect::<Vec<_>>()
+        .into_iter();
+    let mut in_flight: Vec<(Url, Pin<Box<Fut>>)> = vec![];
+    let results = &mut report.results;
+    poll_fn(|cx| loop {
+        while in_flight.len() < concurrency.max(1) {
+            match pending.next() {
+                Some(url) => {
+                    let check = Box::pin(checker(url.clone()));
+                    in_flight.push((url, check));
+                }
+                None => break,
+            }
+        }
+        if in_flight.is_empty() {
+            return Poll::Ready(());
+        }
+        let before = in_flight.len();
+        let mut i = 0;
+        while i < in_flight.len() {
+            match in_flight[i].1.as_mut().poll(cx) {
+                Poll::Ready(result) => {
+                    let (url, _) = in_flight.swap_remove(i);
+                    results.insert(url, result);
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+        if in_flight.len() == before {
+            return Poll::Pending;
+        }
+    })
+    .await
+}
+
+/// A wrapper which collects the http and https links of each document, resolved against its base url, and outputs them along with the inner sink's output.
+///
+/// Links are taken from `href` on `<a>`, `<area>` and `<link>`, and `src` on embedded content such as `<img>` and `<script>`.
+/// The collected [`LinkReport`] can then be checked with [`check_links`].
+pub struct LinkCollector<S> {
+    inner: S,
+    document_url: Url,
+    base: BaseUrl,
+    paths: CssPathTracker,
+    report: LinkReport,
+}
+
+impl<S> LinkCollector<S> {
+    pub fn wrap(inner: S, document_url: Url) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            paths: CssPathTracker::new(),
+            report: LinkReport::default(),
+        }
+    }
+
+    /// Sets the url of the next document, relative links are resolved against it
+    pub fn document_url(&mut self, document_url: Url) {
+        self.base.reset(document_url.clone());
+        self.document_url = document_url;
+    }
+}
+
+/// The attribute of an element which links to a resource
+fn link_attribute<Handle>(element: &HtmlPathElement<'_, Handle>) -> Option<&'static str> {
+    if element.name.ns != ns!(html) {
+        return None;
+    }
+    match element.name.local {
+        local_name!("a") | local_name!("area") | local_name!("link") => Some("href"),
+        local_name!("audio")
+        | local_name!("embed")
+        | local_name!("iframe")
+        | local_name!("img")
+        | local_name!("script")
+        | local_name!("source")
+        | local_name!("track")
+        | local_name!("video") => Some("src"),
+        _ => None,
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for LinkCollector<S> {
+    type Output = (S::Output, LinkReport);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.base.observe(element);
+        if let Some(attribute) = link_attribute(element) {
+            let url = element
+                .attr_url(attribute, self.base.url())
+                .filter(|url| matches!(url.scheme(), "http" | "https"));
+            if let Some(url) = url {
+                self.report.links.push(Link {
+                    url,
+                    element: element.name.local.to_string(),
+                    attribute: attribute.to_owned(),
+                    path: self.paths.element_path(context, element),
+                });
+            }
+        }
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.base.reset(self.document_url.clone());
+        (self.inner.reset(), std::mem::take(&mut self.report))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        future::Future,
+        pin::pin,
+        rc::Rc,
+        task::{Context, Poll, Waker},
+    };
+
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, test::stream_docs};
+
+    /// A check which is pending once before completing, tracking how many checks are running
+    struct Check {
+        url: Url,
+        polled: bool,
+        running: Rc<Cell<usize>>,
+        most_running: Rc<Cell<usize>>,
+    }
+
+    impl Future for Check {
+        type Output = Result<LinkStatus, String>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if !self.polled {
+                self.polled = true;
+                self.running.set(self.running.get() + 1);
+                self.most_running
+                    .set(self.most_running.get().max(self.running.get()));
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.running.set(self.running.get() - 1);
+            Poll::Ready(match self.url.path() {
+                "/docs/old" => Ok(LinkStatus {
+                    status: 301,
+                    redirect: Some(self.url.join("/new").unwrap()),
+                }),
+                "/missing.png" => Err("connection refused".to_owned()),
+                _ => Ok(LinkStatus {
+                    status: 200,
+                    redirect: None,
+                }),
+            })
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn collect_and_check_links() {
+        let (_, mut report) = parse_document(
+            LinkCollector::wrap((), "https://example.com/docs/page.html".parse().unwrap()),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="/site.css"></head><body><a href="old">old</a> <a href="old#part">part</a> <a href="mailto:a@example.com">mail</a><img src="https://cdn.example.com/missing.png"><a href="/site.css">css</a></body></html>"#)
+        .unwrap();
+        assert_eq!(
+            report
+                .links
+                .iter()
+                .map(|link| (link.url.as_str(), link.path.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "https://example.com/site.css",
+                    "html > head > link:nth-child(1)"
+                ),
+                (
+                    "https://example.com/docs/old",
+                    "html > body > a:nth-child(1)"
+                ),
+                (
+                    "https://example.com/docs/old#part",
+                    "html > body > a:nth-child(2)"
+                ),
+                (
+                    "https://cdn.example.com/missing.png",
+                    "html > body > img:nth-child(4)"
+                ),
+                (
+                    "https://example.com/site.css",
+                    "html > body > a:nth-child(5)"
+                ),
+            ]
+        );
+
+        let checked = Rc::new(Cell::new(0));
+        let running = Rc::new(Cell::new(0));
+        let most_running = Rc::new(Cell::new(0));
+        block_on(check_links(&mut report, 2, |url| {
+            checked.set(checked.get() + 1);
+            Check {
+                url,
+                polled: false,
+                running: running.clone(),
+                most_running: most_running.clone(),
+            }
+        }));
+        assert_eq!(checked.get(), 3);
+        assert_eq!(most_running.get(), 2);
+        assert_eq!(
+            report.result(&report.links[2]),
+            Some(&Ok(LinkStatus {
+                status: 301,
+                redirect: Some("https://example.com/new".parse().unwrap())
+            }))
+        );
+        assert_eq!(
+            report.result(&report.links[3]),
+            Some(&Err("connection refused".to_owned()))
+        );
+    }
+
+    #[test]
+    fn collect_each_document() {
+        let mut collector =
+            LinkCollector::wrap((), "https://example.com/docs/page.html".parse().unwrap());
+        let mut reports = stream_docs(
+            &[
+                "<!DOCTYPE html>",
+                r#"<!DOCTYPE html><html><head><base href="https://other.example.com/dir/"></head><body><a href="a"><img src="i.png"></a></body></html>"#,
+                r#"<!DOCTYPE html><html><head></head><body><a href="b">b</a></body></html>"#,
+            ],
+            &mut collector,
+        )
+        .into_iter()
+        .map(|((), report)| report)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| {
+                    report
+                        .links
+                        .iter()
+                        .map(|link| (link.url.as_str(), link.path.as_str()))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>(),
+            [
+                vec![],
+                vec![
+                    (
+                        "https://other.example.com/dir/a",
+                        "html > body > a:nth-child(1)"
+                    ),
+                    (
+                        "https://other.example.com/dir/i.png",
+                        "html > body > a:nth-child(1) > img:nth-child(1)"
+                    ),
+                ],
+                vec![("https://example.com/docs/b", "html > body > a:nth-child(1)")],
+            ]
+        );
+
+        let checked = Cell::new(0);
+        block_on(check_links(&mut reports[0], 2, |_| {
+            checked.set(checked.get() + 1);
+            async {
+                Ok(LinkStatus {
+                    status: 200,
+                    redirect: None,
+                })
+            }
+        }));
+        assert_eq!(checked.get(), 0);
+    }
+}
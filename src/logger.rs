@@ -0,0 +1,240 @@
+use std::{fmt, io::Write};
+
+use html5ever::tendril::StrTendril;
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// Text longer than this is truncated in the log by default
+pub const DEFAULT_MAX_LOGGED_TEXT: usize = 40;
+
+/// A wrapper which writes a line for each event passing through it to a writer, then forwards the event to the inner sink.
+///
+/// Lines are prefixed with the logger's label and indented by the depth of the event's context, elements are written with their
+/// handle and attributes and text is truncated, so a logger placed between two stages shows what reaches the second.
+/// A disabled logger forwards the events without writing anything.
+///
+/// Write errors are recorded and returned from `take_error` before the inner sink's, the events are forwarded regardless.
+pub struct DebugLogger<S, W> {
+    inner: S,
+    writer: W,
+    label: &'static str,
+    enabled: bool,
+    max_text: usize,
+    error: Option<HtmlStreamError>,
+}
+
+impl<S, W: Write> DebugLogger<S, W> {
+    pub fn wrap(inner: S, writer: W) -> Self {
+        Self {
+            inner,
+            writer,
+            label: "",
+            enabled: true,
+            max_text: DEFAULT_MAX_LOGGED_TEXT,
+            error: None,
+        }
+    }
+
+    /// Sets the label to prefix the lines with, to tell apart the loggers of different stages
+    pub fn label(self, label: &'static str) -> Self {
+        Self { label, ..self }
+    }
+
+    /// Turns the logging on or off, the events are still forwarded
+    pub fn enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    /// Sets the number of characters of text and comments to log
+    pub fn max_text(self, max_text: usize) -> Self {
+        Self { max_text, ..self }
+    }
+
+    fn log(&mut self, depth: usize, event: fmt::Arguments<'_>) {
+        if !self.enabled || self.error.is_some() {
+            return;
+        }
+        let result = if self.label.is_empty() {
+            writeln!(self.writer, "{:indent$}{}", "", event, indent = depth * 2)
+        } else {
+            writeln!(
+                self.writer,
+                "{}: {:indent$}{}",
+                self.label,
+                "",
+                event,
+                indent = depth * 2
+            )
+        };
+        if let Err(err) = result {
+            self.error = Some(err.into());
+        }
+    }
+
+    fn truncate<'t>(&self, text: &'t str) -> (&'t str, &'static str) {
+        match text.char_indices().nth(self.max_text) {
+            Some((end, _)) => (&text[..end], "…"),
+            None => (text, ""),
+        }
+    }
+}
+
+impl<Handle, S, W> HtmlSink<Handle> for DebugLogger<S, W>
+where
+    Handle: Eq + Copy + fmt::Display,
+    S: HtmlSink<Handle>,
+    W: Write,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.log(0, format_args!("<!DOCTYPE {}>", name));
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.log(context.len(), format_args!("{}", element));
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let (text_start, ellipsis) = self.truncate(text);
+        self.log(context.len(), format_args!("{:?}{}", text_start, ellipsis));
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        let (text_start, ellipsis) = self.truncate(text);
+        self.log(context.len(), format_args!("{:?}{}", text_start, ellipsis));
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        let (text_start, ellipsis) = self.truncate(text);
+        self.log(
+            context.len(),
+            format_args!("{} whole text {:?}{}", element, text_start, ellipsis),
+        );
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let (text_start, ellipsis) = self.truncate(text);
+        self.log(
+            context.len(),
+            format_args!("<!--{}{}-->", text_start, ellipsis),
+        );
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        let (data_start, ellipsis) = self.truncate(data);
+        self.log(
+            context.len(),
+            format_args!("<?{} {}{}?>", target, data_start, ellipsis),
+        );
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.log(0, format_args!("reset"));
+        if self.enabled && self.error.is_none() {
+            if let Err(err) = self.writer.flush() {
+                self.error = Some(err.into());
+            }
+        }
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        let inner = self.inner.take_error();
+        self.error.take().or(inner)
+    }
+}
+
+/// A writer which logs each line written to it as a debug record of the `log` crate, for use as a `DebugLogger`'s writer
+#[cfg(feature = "log")]
+#[derive(Default)]
+pub struct LogWriter {
+    line: Vec<u8>,
+}
+
+#[cfg(feature = "log")]
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            if byte == b'\n' {
+                log::debug!(target: "html5streams", "{}", String::from_utf8_lossy(&self.line));
+                self.line.clear();
+            } else {
+                self.line.push(byte);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, event::Recorder, test::stream_doc, ElementRemover};
+
+    #[test]
+    fn log_stages() {
+        let mut log = Vec::new();
+        let sink = ElementRemover::wrap(
+            DebugLogger::wrap(Recorder::new(), &mut log)
+                .label("kept")
+                .max_text(5),
+            css_select!("p"),
+        );
+        let sink = DebugLogger::wrap(sink, std::io::sink()).enabled(false);
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><p>skipped</p><div id="main">hello world<!--note--></div></body></html>"#,
+            sink,
+        );
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            r#"kept: <!DOCTYPE html>
+kept: #1 <html>
+kept:   #2 <head>
+kept:   #3 <body>
+kept:     #5 <div id="main">
+kept:       "hello"…
+kept:       <!--note-->
+kept: reset
+"#
+        );
+    }
+}
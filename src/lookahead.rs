@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+
+use html5ever::{tendril::StrTendril, QualName};
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, replay_into, HtmlEvent, OwnedContent, OwnedPathElement},
+    raw_text::text_context,
+    selector::ContextualSelector,
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// Updates the path of a held event, its context followed by its element if it is one
+fn complete<Handle: Clone>(
+    event: &mut HtmlEvent<Handle>,
+    update: impl Fn(&mut [OwnedPathElement<Handle>]),
+) {
+    match event {
+        HtmlEvent::Element { context, element }
+        | HtmlEvent::RawText {
+            context, element, ..
+        } => {
+            context.push(element.clone());
+            update(context);
+            *element = context.pop().unwrap();
+        }
+        HtmlEvent::Text { context, .. }
+        | HtmlEvent::TextWithBoundary { context, .. }
+        | HtmlEvent::Comment { context, .. }
+        | HtmlEvent::ProcessingInstruction { context, .. } => update(context),
+        HtmlEvent::Doctype { .. } => {}
+    }
+}
+
+/// The element children of a selected parent, counted as they are held
+#[derive(Default)]
+struct ChildCounts {
+    total: usize,
+    by_name: HashMap<QualName, usize>,
+}
+
+/// A filter which holds back the subtrees of the parents the selector selects until each closes, so that it can complete the
+/// [`crate::SiblingPosition::count`] of their children before passing them on.
+///
+/// Selectors which depend on what comes after an element, such as `:last-child` and `:last-of-type`, can't match as the element
+/// streams, as its later siblings haven't been parsed yet. Put this in front of the sinks which use them, selecting the parents whose
+/// children they are interested in: it costs the memory of each held subtree, so a narrow selector keeps a pipeline streaming. The rest
+/// of the document passes straight through, and the parents themselves aren't held, only their contents. The previous siblings linked
+/// to an element are copies taken before it was held, so they don't have counts.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, LookaheadBuffer, SelectToStrings};
+///
+/// let last_items = parse_document(
+///     LookaheadBuffer::wrap(SelectToStrings::new(css_select!("li":last-child)), css_select!("ul")),
+///     Default::default(),
+/// )
+/// .one("<!DOCTYPE html><html><head></head><body><ul><li>One</li><li>Two</li></ul><ul><li>Three</li></ul></body></html>")
+/// .unwrap();
+/// assert_eq!(last_items, ["<li>Two</li>", "<li>Three</li>"]);
+/// ```
+pub struct LookaheadBuffer<Handle, S, P> {
+    inner: S,
+    parents: P,
+    /// The outermost selected parent whose subtree is being held, with its depth
+    holding: Option<(Handle, usize)>,
+    events: Vec<HtmlEvent<Handle>>,
+    /// The children of each selected parent in the held subtree
+    counts: Vec<(Handle, ChildCounts)>,
+}
+
+impl<Handle, S, P> LookaheadBuffer<Handle, S, P>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    P: ContextualSelector,
+{
+    /// Wraps the inner sink, holding the children of the parents the selector selects
+    pub fn wrap(inner: S, parents: P) -> Self {
+        Self {
+            inner,
+            parents,
+            holding: None,
+            events: vec![],
+            counts: vec![],
+        }
+    }
+
+    /// Whether the event in the context is to be held, passing on the held events first if it is after the held parent
+    fn hold(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        if let Some((handle, depth)) = self.holding {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                return true;
+            }
+            self.flush();
+        }
+        false
+    }
+
+    /// Holds or passes on text, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if self.hold(context) {
+            self.events.push(HtmlEvent::text(context, text, boundary));
+        } else {
+            append_text_to(&mut self.inner, context, text, boundary)
+        }
+    }
+
+    /// Completes the counts of the held elements and passes on their events
+    fn flush(&mut self) {
+        self.holding = None;
+        let counts = std::mem::take(&mut self.counts);
+        let completion = |path: &mut [OwnedPathElement<Handle>]| {
+            for i in 1..path.len() {
+                let parent = path[i - 1].handle;
+                let element = &mut path[i];
+                if let (Some(position), Some((_, children))) = (
+                    &mut element.position,
+                    counts.iter().find(|(handle, _)| *handle == parent),
+                ) {
+                    position.count = Some(children.total);
+                    position.count_of_type = children.by_name.get(&element.name).copied();
+                }
+            }
+        };
+        for event in &mut self.events {
+            complete(event, completion);
+        }
+        replay_into(&self.events, &mut self.inner);
+        self.events.clear();
+    }
+}
+
+impl<Handle, S, P> HtmlSink<Handle> for LookaheadBuffer<Handle, S, P>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    P: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.flush();
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let selected = self.parents.context_match(context, element);
+        if self.hold(context) {
+            let parent = context.last().map(|parent| parent.handle);
+            if let Some((_, children)) = self
+                .counts
+                .iter_mut()
+                .find(|(handle, _)| Some(*handle) == parent)
+            {
+                children.total += 1;
+                *children.by_name.entry(element.name.clone()).or_default() += 1;
+            }
+            self.events.push(HtmlEvent::Element {
+                context: owned_context(context),
+                element: OwnedPathElement::from(element),
+            });
+        } else {
+            self.inner.append_element(context, element);
+            if selected {
+                self.holding = Some((element.handle, context.len()));
+            }
+        }
+        if selected {
+            self.counts.push((element.handle, ChildCounts::default()));
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.hold(&text_context(context, element)) {
+            self.events
+                .push(HtmlEvent::raw_text(context, element, text))
+        } else {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.hold(context) {
+            self.events.push(HtmlEvent::Comment {
+                context: owned_context(context),
+                text: text.to_owned(),
+            });
+        } else {
+            self.inner.append_comment(context, text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.hold(context) {
+            self.events.push(HtmlEvent::ProcessingInstruction {
+                context: owned_context(context),
+                target: target.to_owned(),
+                data: data.to_owned(),
+            });
+        } else {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.flush();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// A filter which holds back each element the selector selects until it closes, so that it can record the [`crate::ElementContent`] of the
+/// element before passing it on.
+///
+/// Selectors of what an element contains, such as `:empty` and `:text-contains()`, can't match as the element streams, as its
+/// contents haven't been parsed yet. Put this in front of the sinks which use them, selecting the elements they test, such as every
+/// `p` for a cleanup which drops empty paragraphs: like a [`LookaheadBuffer`] it costs the memory of each held subtree, and the rest of
+/// the document passes straight through. The content is also set on the held element in the context of its descendants.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, ContentBuffer, ElementRemover, HtmlSerializer};
+///
+/// let html = parse_document(
+///     ContentBuffer::wrap(
+///         ElementRemover::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!("p":empty)),
+///         css_select!("p"),
+///     ),
+///     Default::default(),
+/// )
+/// .one("<!DOCTYPE html><html><head></head><body><p>Text</p><p></p><p><!-- note --></p></body></html>")
+/// .unwrap();
+/// assert_eq!(String::from_utf8(html).unwrap(), "<!DOCTYPE html><html><head></head><body><p>Text</p></body></html>");
+/// ```
+pub struct ContentBuffer<Handle, S, E> {
+    inner: S,
+    elements: E,
+    /// The outermost selected element whose subtree is being held, with its depth
+    holding: Option<(Handle, usize)>,
+    events: Vec<HtmlEvent<Handle>>,
+    /// Whether each selected element in the held subtree is empty so far, and its text
+    contents: Vec<(Handle, bool, String)>,
+}
+
+impl<Handle, S, E> ContentBuffer<Handle, S, E>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    E: ContextualSelector,
+{
+    /// Wraps the inner sink, holding the elements the selector selects
+    pub fn wrap(inner: S, elements: E) -> Self {
+        Self {
+            inner,
+            elements,
+            holding: None,
+            events: vec![],
+            contents: vec![],
+        }
+    }
+
+    /// Whether the event in the context is to be held, passing on the held events first if it is after the held element
+    fn hold(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        if let Some((handle, depth)) = self.holding {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                return true;
+            }
+            self.flush();
+        }
+        false
+    }
+
+    /// Adds a child to the selected elements it is in, with its text
+    fn add_content(&mut self, context: HtmlContext<'_, Handle>, text: &str) {
+        for (handle, empty, content) in &mut self.contents {
+            if context.iter().any(|element| element.handle == *handle) {
+                *empty = false;
+                content.push_str(text);
+            }
+        }
+    }
+
+    /// Holds or passes on text, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if self.hold(context) {
+            self.add_content(context, text);
+            self.events.push(HtmlEvent::text(context, text, boundary));
+        } else {
+            append_text_to(&mut self.inner, context, text, boundary)
+        }
+    }
+
+    /// Sets the content of the held elements and passes on their events
+    fn flush(&mut self) {
+        self.holding = None;
+        let contents = std::mem::take(&mut self.contents)
+            .into_iter()
+            .map(|(handle, empty, text)| (handle, OwnedContent { empty, text }))
+            .collect::<Vec<_>>();
+        let completion = |path: &mut [OwnedPathElement<Handle>]| {
+            for element in path {
+                if let Some((_, content)) = contents
+                    .iter()
+                    .find(|(handle, _)| *handle == element.handle)
+                {
+                    element.content = Some(content.clone());
+                }
+            }
+        };
+        for event in &mut self.events {
+            complete(event, completion);
+        }
+        replay_into(&self.events, &mut self.inner);
+        self.events.clear();
+    }
+}
+
+impl<Handle, S, E> HtmlSink<Handle> for ContentBuffer<Handle, S, E>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    E: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.flush();
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let held = self.hold(context);
+        if held {
+            self.add_content(context, "");
+        }
+        if self.elements.context_match(context, element) {
+            if !held {
+                self.holding = Some((element.handle, context.len()));
+            }
+            self.contents.push((element.handle, true, String::new()));
+        }
+        if self.holding.is_some() {
+            self.events.push(HtmlEvent::Element {
+                context: owned_context(context),
+                element: OwnedPathElement::from(element),
+            });
+        } else {
+            self.inner.append_element(context, element)
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.hold(&text_context(context, element)) {
+            self.events
+                .push(HtmlEvent::raw_text(context, element, text))
+        } else {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.hold(context) {
+            self.events.push(HtmlEvent::Comment {
+                context: owned_context(context),
+                text: text.to_owned(),
+            });
+        } else {
+            self.inner.append_comment(context, text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.hold(context) {
+            self.events.push(HtmlEvent::ProcessingInstruction {
+                context: owned_context(context),
+                target: target.to_owned(),
+                data: data.to_owned(),
+            });
+        } else {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.flush();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{
+        css_select, parse_document, selector::CssSelector, HtmlSerializer, SelectToStrings,
+    };
+
+    const DOC: &str = "<!DOCTYPE html><html><head></head><body><ol><li>1<ol><li>1.1</li><li>1.2</li></ol></li><!-- c --><li>2</li><p>after</p></ol><p>last</p></body></html>";
+
+    fn select<P: ContextualSelector>(parents: P) -> Vec<String> {
+        parse_document(
+            LookaheadBuffer::wrap(
+                SelectToStrings::new(css_select!("ol" > "li":last-of-type)),
+                parents,
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap()
+    }
+
+    #[test]
+    fn select_last_children() {
+        assert_eq!(select(css_select!("ol")), ["<li>1.2</li>", "<li>2</li>"]);
+        // the nested list is in the held subtree, but its children aren't counted
+        assert_eq!(select(css_select!("body" > "ol")), ["<li>2</li>"]);
+    }
+
+    #[test]
+    fn pass_events_through_in_order() {
+        let html = parse_document(
+            LookaheadBuffer::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("ol"),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), DOC);
+    }
+
+    const CONTENT_DOC: &str = "<!DOCTYPE html><html><head></head><body><div><p>Buy <b>now</b></p><p></p></div><div><p><!-- c --></p><span>now</span></div></body></html>";
+
+    fn select_by_content<S: ContextualSelector>(selector: S) -> Vec<String> {
+        parse_document(
+            ContentBuffer::wrap(SelectToStrings::new(selector), css_select!("div", "p")),
+            Default::default(),
+        )
+        .one(CONTENT_DOC)
+        .unwrap()
+    }
+
+    #[test]
+    fn select_by_content_of_elements() {
+        assert_eq!(
+            select_by_content(css_select!("p":empty)),
+            ["<p></p>", "<p><!-- c --></p>"]
+        );
+        assert_eq!(
+            select_by_content(css_select!("div":text-contains("now") > "p":text-contains("Buy"))),
+            ["<p>Buy <b>now</b></p>"]
+        );
+        assert_eq!(
+            select_by_content(
+                "div:text-contains('now') > p:not(:empty)"
+                    .parse::<CssSelector>()
+                    .unwrap()
+            ),
+            ["<p>Buy <b>now</b></p>"]
+        );
+        // the span isn't held, so its content isn't known
+        assert_eq!(
+            select_by_content(css_select!("body" > * > *:text-contains("now"))),
+            ["<p>Buy <b>now</b></p>"]
+        );
+
+        let html = parse_document(
+            ContentBuffer::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("div"),
+            ),
+            Default::default(),
+        )
+        .one(CONTENT_DOC)
+        .unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), CONTENT_DOC);
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// The peak sizes reached by the buffers of a parse, see [`MemoryMetrics`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HighWaterMarks {
+    /// The deepest the traversal stack of open elements got
+    pub traversal_depth: usize,
+    /// The most nodes which were created but not yet appended, the parser holds on to these for misnested markup
+    pub free_nodes: usize,
+    /// The most text held back by a buffering adapter such as [`crate::TextCoalescer`]
+    pub buffered_text_bytes: usize,
+}
+
+#[derive(Default)]
+struct Peaks {
+    traversal_depth: AtomicUsize,
+    free_nodes: AtomicUsize,
+    buffered_text_bytes: AtomicUsize,
+}
+
+/// A handle collecting the [`HighWaterMarks`] of parses and adapters it is given to, see [`crate::parse_document_with_metrics`].
+///
+/// Clones share the same marks, so one can be kept to read them while others are moved into the parse or sinks.
+/// These give real numbers to base limits for untrusted input on.
+#[derive(Clone, Default)]
+pub struct MemoryMetrics {
+    peaks: Arc<Peaks>,
+}
+
+impl MemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The peaks seen since creation or the last reset
+    pub fn high_water_marks(&self) -> HighWaterMarks {
+        HighWaterMarks {
+            traversal_depth: self.peaks.traversal_depth.load(Ordering::Relaxed),
+            free_nodes: self.peaks.free_nodes.load(Ordering::Relaxed),
+            buffered_text_bytes: self.peaks.buffered_text_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the peaks and starts again from zero, to get them per parse
+    pub fn reset(&self) -> HighWaterMarks {
+        HighWaterMarks {
+            traversal_depth: self.peaks.traversal_depth.swap(0, Ordering::Relaxed),
+            free_nodes: self.peaks.free_nodes.swap(0, Ordering::Relaxed),
+            buffered_text_bytes: self.peaks.buffered_text_bytes.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn traversal_depth(&self, depth: usize) {
+        self.peaks
+            .traversal_depth
+            .fetch_max(depth, Ordering::Relaxed);
+    }
+
+    pub(crate) fn free_nodes(&self, count: usize) {
+        self.peaks.free_nodes.fetch_max(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn buffered_text_bytes(&self, bytes: usize) {
+        self.peaks
+            .buffered_text_bytes
+            .fetch_max(bytes, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document_with_metrics, StatsCollector, TextCoalescer};
+
+    #[test]
+    fn high_water_marks() {
+        let metrics = MemoryMetrics::new();
+        parse_document_with_metrics(
+            TextCoalescer::wrap(StatsCollector::new()).metrics(metrics.clone()),
+            Default::default(),
+            metrics.clone(),
+        )
+        .one("<!DOCTYPE html><html><head></head><body><div><p>Tom &amp; Jerry</p></div><p>x</p></body></html>")
+        .unwrap();
+        assert_eq!(
+            metrics.reset(),
+            HighWaterMarks {
+                traversal_depth: 4,
+                free_nodes: 1,
+                buffered_text_bytes: 11,
+            }
+        );
+        assert_eq!(metrics.high_water_marks(), HighWaterMarks::default());
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    attrs::is_legacy_type, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    SyntheticHandle, SyntheticHandles, TextBoundary,
+};
+
+/// How a [`ScriptModernizer`] sets the loading of external classic scripts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScriptLoading {
+    /// Leave `defer` and `async` as they are
+    #[default]
+    Keep,
+    /// Set `defer` and remove `async`
+    Defer,
+    /// Set `async` and remove `defer`
+    Async,
+    /// Remove `defer` and `async`, so the scripts block the parser
+    Blocking,
+}
+
+/// A filter which rewrites `<script>` elements for a bundler's output.
+///
+/// External scripts with a module build in the [`ScriptModernizer::module_scripts`] mapping get a `<script type="module">` for it
+/// inserted before them, and `nomodule` added, so each browser loads one of them. The `src`s are then rewritten to hashed filenames from
+/// the [`ScriptModernizer::asset_manifest`], redundant `type="text/javascript"`s can be stripped, and `defer` or `async` set on classic scripts.
+/// Inline scripts are only affected by stripping the `type`.
+pub struct ScriptModernizer<S> {
+    inner: S,
+    modules: HashMap<String, String>,
+    manifest: HashMap<String, String>,
+    strip_legacy_type: bool,
+    loading: ScriptLoading,
+    handles: SyntheticHandles,
+}
+
+impl<S> ScriptModernizer<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            modules: HashMap::new(),
+            manifest: HashMap::new(),
+            strip_legacy_type: false,
+            loading: ScriptLoading::Keep,
+            handles: SyntheticHandles::new(),
+        }
+    }
+
+    /// Sets the module build of each classic script, by their `src`s before any rewriting by the manifest
+    pub fn module_scripts(self, modules: HashMap<String, String>) -> Self {
+        Self { modules, ..self }
+    }
+
+    /// Sets the manifest of asset paths to hashed filenames, as written by bundlers, such as `app.js` to `app.3f2a9c.js`.
+    /// A `src` of `/app.js` also matches `app.js`, keeping its leading slash
+    pub fn asset_manifest(self, manifest: HashMap<String, String>) -> Self {
+        Self { manifest, ..self }
+    }
+
+    /// Sets whether to remove `type="text/javascript"` and `type="application/javascript"`
+    pub fn strip_legacy_type(self, strip_legacy_type: bool) -> Self {
+        Self {
+            strip_legacy_type,
+            ..self
+        }
+    }
+
+    /// Sets the loading of external classic scripts, those without `nomodule`
+    pub fn loading(self, loading: ScriptLoading) -> Self {
+        Self { loading, ..self }
+    }
+
+    /// The hashed filename of a `src` from the manifest, or the `src` itself
+    fn hashed(&self, src: &str) -> String {
+        if let Some(hashed) = self.manifest.get(src) {
+            return hashed.clone();
+        }
+        match src
+            .strip_prefix('/')
+            .and_then(|path| self.manifest.get(path))
+        {
+            Some(hashed) => format!("/{}", hashed.trim_start_matches('/')),
+            None => src.to_owned(),
+        }
+    }
+
+    fn set_loading<Handle>(&self, element: &mut HtmlPathElement<'_, Handle>) {
+        match self.loading {
+            ScriptLoading::Keep => {}
+            ScriptLoading::Defer => {
+                element.remove_attr("async");
+                element.set_attr("defer", "");
+            }
+            ScriptLoading::Async => {
+                element.remove_attr("defer");
+                element.set_attr("async", "");
+            }
+            ScriptLoading::Blocking => {
+                element.remove_attr("defer");
+                element.remove_attr("async");
+            }
+        }
+    }
+}
+
+impl<Handle: SyntheticHandle, S: HtmlSink<Handle>> HtmlSink<Handle> for ScriptModernizer<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if element.name.ns != ns!(html) || !element.tag_is(local_name!("script")) {
+            return self.inner.append_element(context, element);
+        }
+        let mut script = element.clone();
+        if self.strip_legacy_type && script.attr_local("type").is_some_and(|t| is_legacy_type(t)) {
+            script.remove_attr("type");
+        }
+        let Some(src) = element.attr_local("src").map(|src| src.trim()) else {
+            return self.inner.append_element(context, &script);
+        };
+        let is_classic = script
+            .attr_local("type")
+            .map_or(true, |t| t.is_empty() || is_legacy_type(t));
+        if let Some(module) = self.modules.get(src).filter(|_| is_classic) {
+            let mut module_script = HtmlPathElement::new_child(
+                self.handles.allocate(),
+                context.last(),
+                script.name.clone(),
+                script.attrs.to_vec(),
+            );
+            module_script.set_attr("type", "module");
+            module_script.set_attr("src", &self.hashed(module));
+            module_script.remove_attr("defer");
+            module_script.remove_attr("nomodule");
+            self.inner.append_element(context, &module_script);
+            script.set_attr("nomodule", "");
+        }
+        if is_classic && !script.has_attr("nomodule") {
+            self.set_loading(&mut script);
+        }
+        script.set_attr("src", &self.hashed(src));
+        self.inner.append_element(context, &script)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.handles.reset();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn modernize_scripts() {
+        let map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(from, to)| ((*from).to_owned(), (*to).to_owned()))
+                .collect::<HashMap<_, _>>()
+        };
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            ScriptModernizer::wrap(&mut serializer)
+                .module_scripts(map(&[("/app.js", "/app.mjs")]))
+                .asset_manifest(map(&[
+                    ("app.js", "app.3f2a.js"),
+                    ("app.mjs", "app.9c1d.mjs"),
+                    ("vendor.js", "vendor.77b0.js"),
+                ]))
+                .strip_legacy_type(true)
+                .loading(ScriptLoading::Defer),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><script type="text/javascript" src="/app.js" async></script><script src="vendor.js"></script><script type="module" src="/widget.mjs"></script><script type="text/javascript">init()</script></head><body></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><script src="/app.9c1d.mjs" async="" type="module"></script><script src="/app.3f2a.js" async="" nomodule=""></script><script src="vendor.77b0.js" defer=""></script><script type="module" src="/widget.mjs"></script><script>init()</script></head><body></body></html>"#
+        );
+    }
+}
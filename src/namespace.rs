@@ -0,0 +1,166 @@
+use std::borrow::Cow;
+
+use html5ever::{namespace_prefix, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+use crate::{HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// Whether the attribute is namespace cruft, an `xmlns` declaration or in a namespace html doesn't know about
+fn is_cruft(attr: &Attribute) -> bool {
+    let name = &attr.name;
+    if name.ns == ns!(xml) || name.ns == ns!(xlink) {
+        return false;
+    }
+    if name.ns != ns!() {
+        return true;
+    }
+    // the html parser leaves the prefix in the local name, as in `xmlns:o` or `o:gfxdata`
+    &*name.local == "xmlns"
+        || name
+            .local
+            .split_once(':')
+            .is_some_and(|(prefix, _)| prefix != "xml" && prefix != "xlink")
+}
+
+/// The attribute name with the conventional prefix for its namespace
+fn normalize_attr(name: &QualName) -> QualName {
+    let prefix = if name.ns == ns!(xml) {
+        Some(namespace_prefix!("xml"))
+    } else if name.ns == ns!(xlink) {
+        Some(namespace_prefix!("xlink"))
+    } else {
+        None
+    };
+    QualName::new(prefix, name.ns.clone(), name.local.clone())
+}
+
+/// A filter which cleans up namespace cruft from XHTML and word processor exports, for clean html5 output.
+///
+/// `xmlns` declarations are dropped, as are attributes in namespaces html doesn't know, such as office's `o:` attributes.
+/// Element prefixes are dropped and `xml` and `xlink` attributes get their conventional prefixes, so foreign content such as svg uses the html5 forms.
+pub struct NamespaceNormalizer<S> {
+    inner: S,
+}
+
+impl<S> NamespaceNormalizer<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for NamespaceNormalizer<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let clean = element.name.prefix.is_none()
+            && element
+                .attrs
+                .iter()
+                .all(|attr| !is_cruft(attr) && attr.name == normalize_attr(&attr.name));
+        if clean {
+            return self.inner.append_element(context, element);
+        }
+        let attrs = element
+            .attrs
+            .iter()
+            .filter(|attr| !is_cruft(attr))
+            .map(|attr| Attribute {
+                name: normalize_attr(&attr.name),
+                value: attr.value.clone(),
+            })
+            .collect::<Vec<_>>();
+        self.inner.append_element(
+            context,
+            &HtmlPathElement {
+                handle: element.handle,
+                name: QualName::new(None, element.name.ns.clone(), element.name.local.clone()),
+                attrs: Cow::Owned(attrs),
+                inherited: element.inherited.clone(),
+                position: element.position,
+                previous_sibling: element.previous_sibling.clone(),
+                content: element.content.clone(),
+            },
+        )
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::serialize::SerializeOpts;
+
+    use super::*;
+    use crate::{test::stream_doc, HtmlSerializer};
+
+    #[test]
+    fn normalize_namespaces() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(
+            r##"<!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml" xmlns:o="urn:schemas-microsoft-com:office:office" xml:lang="en"><head></head><body><p class="MsoNormal" o:gfxdata="x">Hi</p><svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><use xlink:href="#a"></use></svg></body></html>"##,
+            NamespaceNormalizer::wrap(&mut serializer),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r##"<!DOCTYPE html><html xml:lang="en"><head></head><body><p class="MsoNormal">Hi</p><svg><use xlink:href="#a"></use></svg></body></html>"##
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use html5ever::{
+    serialize::{HtmlSerializer, SerializeOpts, Serializer},
+    tendril::StrTendril,
+    QualName,
+};
+
+use crate::{
+    event::{replay_into, HtmlEvent, OwnedAttribute},
+    HtmlContext, HtmlPathElement, HtmlSink,
+};
+
+/// A change to make to a document to turn it into the next one, as generated by [`PatchGenerator`].
+///
+/// Paths are child indices from the document root, doctypes aren't counted. Patches are applied in order and each path is
+/// relative to the document as left by the patches before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "op", rename_all = "snake_case")
+)]
+pub enum Patch {
+    /// Inserts the html as the child at the path
+    Insert {
+        path: Vec<usize>,
+        html: String,
+    },
+    /// Removes the node at the path
+    Remove {
+        path: Vec<usize>,
+    },
+    SetAttribute {
+        path: Vec<usize>,
+        name: String,
+        value: String,
+    },
+    RemoveAttribute {
+        path: Vec<usize>,
+        name: String,
+    },
+    /// Sets the text of the text or comment node at the path
+    SetText {
+        path: Vec<usize>,
+        text: String,
+    },
+}
+
+#[derive(Debug)]
+enum Node {
+    Element {
+        name: QualName,
+        attrs: Vec<OwnedAttribute>,
+        children: Vec<Node>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+impl Node {
+    /// Nodes with the same key are patched in place rather than replaced
+    fn key(&self) -> Option<(&QualName, Option<&str>)> {
+        match self {
+            Node::Element { name, attrs, .. } => Some((
+                name,
+                attrs
+                    .iter()
+                    .find(|attr| &*attr.name.local == "id")
+                    .map(|attr| &*attr.value),
+            )),
+            Node::Text(_) | Node::Comment(_) => None,
+        }
+    }
+
+    fn same_kind(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Element { .. }, Node::Element { .. }) => self.key() == other.key(),
+            (Node::Text(_), Node::Text(_)) | (Node::Comment(_), Node::Comment(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn serialize(&self, serializer: &mut HtmlSerializer<&mut Vec<u8>>) {
+        const WRITE: &str = "writing to a Vec can't fail";
+        match self {
+            Node::Element {
+                name,
+                attrs,
+                children,
+            } => {
+                serializer
+                    .start_elem(
+                        name.clone(),
+                        attrs.iter().map(|attr| (&attr.name, &*attr.value)),
+                    )
+                    .expect(WRITE);
+                for child in children {
+                    child.serialize(serializer);
+                }
+                serializer.end_elem(name.clone()).expect(WRITE);
+            }
+            Node::Text(text) => serializer.write_text(text).expect(WRITE),
+            Node::Comment(text) => serializer.write_comment(text).expect(WRITE),
+        }
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = Vec::new();
+        self.serialize(&mut HtmlSerializer::new(
+            &mut html,
+            SerializeOpts::default(),
+        ));
+        String::from_utf8(html).expect("serializer writes utf-8")
+    }
+}
+
+/// The name of an attribute as it is written, with its prefix if it has one
+pub(crate) fn attribute_name(name: &QualName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local),
+        None => name.local.to_string(),
+    }
+}
+
+/// The most cells of the table of [`align_middle`], above which the changed children are replaced rather than aligned
+const MAX_ALIGNED_CELLS: usize = 1 << 16;
+
+/// Pairs the indices of the old and new children which are kept: the common prefix and suffix of node kinds, and the longest common
+/// subsequence of those between them, unless there are too many of them to compare each pair
+fn align(old: &[Node], new: &[Node]) -> Vec<(usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old.same_kind(new))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old.same_kind(new))
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut pairs = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+    if old_middle.len().saturating_mul(new_middle.len()) <= MAX_ALIGNED_CELLS {
+        pairs.extend(
+            align_middle(old_middle, new_middle)
+                .into_iter()
+                .map(|(i, j)| (prefix + i, prefix + j)),
+        );
+    }
+    pairs.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    pairs
+}
+
+/// Pairs the indices of the old and new children using the longest common subsequence of node kinds
+fn align_middle(old: &[Node], new: &[Node]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i].same_kind(&new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = vec![];
+    while i < old.len() && j < new.len() {
+        if old[i].same_kind(&new[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+fn child_path(parent: &[usize], index: usize) -> Vec<usize> {
+    let mut path = parent.to_vec();
+    path.push(index);
+    path
+}
+
+/// Patches the children of the node at `path`, removals are made first so the insertions and the updates can use indices in the new document
+fn diff_children(path: &[usize], old: &[Node], new: &[Node], patches: &mut Vec<Patch>) {
+    let pairs = align(old, new);
+    let mut kept = pairs.iter().map(|(i, _)| *i).peekable();
+    let removed = (0..old.len())
+        .filter(|i| {
+            if kept.peek() == Some(i) {
+                kept.next();
+                false
+            } else {
+                true
+            }
+        })
+        .collect::<Vec<_>>();
+    for i in removed.into_iter().rev() {
+        patches.push(Patch::Remove {
+            path: child_path(path, i),
+        });
+    }
+    let mut kept = pairs.iter().map(|(_, j)| *j).peekable();
+    for (j, node) in new.iter().enumerate() {
+        if kept.peek() == Some(&j) {
+            kept.next();
+        } else {
+            patches.push(Patch::Insert {
+                path: child_path(path, j),
+                html: node.to_html(),
+            });
+        }
+    }
+    for (i, j) in pairs {
+        diff_node(&child_path(path, j), &old[i], &new[j], patches);
+    }
+}
+
+fn diff_node(path: &[usize], old: &Node, new: &Node, patches: &mut Vec<Patch>) {
+    match (old, new) {
+        (
+            Node::Element {
+                attrs: old_attrs,
+                children: old_children,
+                ..
+            },
+            Node::Element {
+                attrs: new_attrs,
+                children: new_children,
+                ..
+            },
+        ) => {
+            for old_attr in old_attrs {
+                if !new_attrs.iter().any(|attr| attr.name == old_attr.name) {
+                    patches.push(Patch::RemoveAttribute {
+                        path: path.to_vec(),
+                        name: attribute_name(&old_attr.name),
+                    });
+                }
+            }
+            for new_attr in new_attrs {
+                if !old_attrs.contains(new_attr) {
+                    patches.push(Patch::SetAttribute {
+                        path: path.to_vec(),
+                        name: attribute_name(&new_attr.name),
+                        value: new_attr.value.clone(),
+                    });
+                }
+            }
+            diff_children(path, old_children, new_children, patches);
+        }
+        (Node::Text(old), Node::Text(new)) | (Node::Comment(old), Node::Comment(new)) => {
+            if old != new {
+                patches.push(Patch::SetText {
+                    path: path.to_vec(),
+                    text: new.clone(),
+                });
+            }
+        }
+        _ => unreachable!("only nodes of the same kind are aligned"),
+    }
+}
+
+/// Builds the node tree of a document from the sink events
+struct TreeBuilder<Handle> {
+    roots: Vec<Node>,
+    open_elements: Vec<Handle>,
+}
+
+impl<Handle: Eq + Copy> TreeBuilder<Handle> {
+    fn new() -> Self {
+        Self {
+            roots: vec![],
+            open_elements: vec![],
+        }
+    }
+
+    /// Closes the elements not in the context and returns the children of the innermost open element
+    fn children(&mut self, context: HtmlContext<'_, Handle>) -> &mut Vec<Node> {
+        let common = context
+            .iter()
+            .zip(&self.open_elements)
+            .take_while(|(a, b)| a.handle == **b)
+            .count();
+        self.open_elements.truncate(common);
+        let mut children = &mut self.roots;
+        for _ in 0..self.open_elements.len() {
+            children = match children.last_mut() {
+                Some(Node::Element { children, .. }) => children,
+                _ => unreachable!("open elements are the last children"),
+            };
+        }
+        children
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for &mut TreeBuilder<Handle> {
+    type Output = Vec<Node>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        _name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.children(context).push(Node::Element {
+            name: element.name.clone(),
+            attrs: element
+                .attrs
+                .iter()
+                .map(|attr| OwnedAttribute {
+                    name: attr.name.clone(),
+                    value: attr.value.to_string(),
+                })
+                .collect(),
+            children: vec![],
+        });
+        self.open_elements.push(element.handle);
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let children = self.children(context);
+        // the parser can split a text node over many events
+        if let Some(Node::Text(last)) = children.last_mut() {
+            last.push_str(text);
+        } else {
+            children.push(Node::Text(text.to_owned()));
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.children(context).push(Node::Comment(text.to_owned()));
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.open_elements.clear();
+        std::mem::take(&mut self.roots)
+    }
+}
+
+/// Compares each document with a reference document, outputting the [`Patch`]es which turn the reference into it, for a client side morphing library to apply.
+///
+/// After each document the reference becomes that document, so a stream of documents produces a stream of incremental patches.
+/// Children are aligned on their element name and id, so elements are kept and updated where possible rather than replaced. Past
+/// their common start and end, lists of hundreds of changed children are replaced rather than aligned, to bound the work.
+pub struct PatchGenerator<Handle> {
+    reference: Vec<Node>,
+    builder: TreeBuilder<Handle>,
+}
+
+impl<Handle: Eq + Copy> PatchGenerator<Handle> {
+    /// Creates a generator with an empty reference document, the first patches insert the whole document
+    pub fn new() -> Self {
+        Self {
+            reference: vec![],
+            builder: TreeBuilder::new(),
+        }
+    }
+
+    /// Creates a generator with a previously recorded document as the reference
+    pub fn with_reference<R: Eq + Copy>(reference: &[HtmlEvent<R>]) -> Self {
+        let mut builder = TreeBuilder::new();
+        let mut sink = &mut builder;
+        replay_into(reference, &mut sink);
+        Self {
+            reference: sink.reset(),
+            builder: TreeBuilder::new(),
+        }
+    }
+}
+
+impl<Handle: Eq + Copy> Default for PatchGenerator<Handle> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Handle: Eq + Copy> HtmlSink<Handle> for &mut PatchGenerator<Handle> {
+    type Output = Vec<Patch>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        (&mut self.builder).append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        (&mut self.builder).append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        (&mut self.builder).append_text(context, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        (&mut self.builder).append_comment(context, text)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        let document = (&mut self.builder).reset();
+        let mut patches = vec![];
+        diff_children(&[], &self.reference, &document, &mut patches);
+        self.reference = document;
+        patches
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    #[test]
+    fn patch_documents() {
+        let mut generator = PatchGenerator::new();
+        let mut patch = |doc: &str| {
+            parse_document(&mut generator, Default::default())
+                .one(doc)
+                .unwrap()
+        };
+
+        assert_eq!(
+            patch("<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>"),
+            vec![Patch::Insert {
+                path: vec![0],
+                html: "<html><head></head><body><p>hello</p></body></html>".to_owned()
+            }]
+        );
+        assert_eq!(
+            patch(
+                r#"<!DOCTYPE html><html><head></head><body><h1 class="title">hi</h1><p>hello world</p></body></html>"#
+            ),
+            vec![
+                Patch::Insert {
+                    path: vec![0, 1, 0],
+                    html: r#"<h1 class="title">hi</h1>"#.to_owned()
+                },
+                Patch::SetText {
+                    path: vec![0, 1, 1, 0],
+                    text: "hello world".to_owned()
+                },
+            ]
+        );
+        assert_eq!(
+            patch(r#"<!DOCTYPE html><html><head></head><body><h1>hi</h1></body></html>"#),
+            vec![
+                Patch::Remove {
+                    path: vec![0, 1, 1]
+                },
+                Patch::RemoveAttribute {
+                    path: vec![0, 1, 0],
+                    name: "class".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_long_changed_lists() {
+        let list = |ids: &mut dyn Iterator<Item = usize>| {
+            format!(
+                "<!DOCTYPE html><html><head></head><body><ul>{}</ul></body></html>",
+                ids.map(|id| format!(r#"<li id="i{}"></li>"#, id))
+                    .collect::<String>()
+            )
+        };
+        let mut generator = PatchGenerator::new();
+        let mut patch = |doc: &str| {
+            parse_document(&mut generator, Default::default())
+                .one(doc)
+                .unwrap()
+        };
+        patch(&list(&mut (0..300)));
+
+        // the common prefix and suffix are kept however long the list
+        assert_eq!(
+            patch(&list(&mut (0..150).chain(151..300))),
+            vec![Patch::Remove {
+                path: vec![0, 1, 0, 150]
+            }]
+        );
+        // moving the first item to the end leaves too many items to align
+        let patches = patch(&list(&mut (1..300).chain(0..1)));
+        assert_eq!(patches.len(), 299 + 300);
+        assert!(patches[..299]
+            .iter()
+            .all(|patch| matches!(patch, Patch::Remove { .. })));
+    }
+}
@@ -0,0 +1,280 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+use crate::{
+    parse_srcset, selector::ContextualSelector, HtmlContext, HtmlPathElement, HtmlSink,
+    HtmlStreamError, SyntheticHandle, SyntheticHandles, TextBoundary,
+};
+
+/// A `<picture>` whose `<source>`s have been seen, so sources of the same types aren't added to it again
+struct OpenPicture<Handle> {
+    handle: Handle,
+    types: Vec<String>,
+}
+
+fn attribute(local: &str, value: &str) -> Attribute {
+    Attribute {
+        name: QualName::new(None, ns!(), local.into()),
+        value: value.into(),
+    }
+}
+
+/// A filter which wraps matched `<img>`s in a `<picture>` with `<source>`s for modern image formats, keeping the `<img>` as the fallback.
+///
+/// The resolver is called with each url of the image, from its `srcset` or else its `src`, and each type, such as `image/avif`,
+/// and gives the url of that variant. A `<source>` is only added for a type when every url has a variant, and copies the image's
+/// `srcset` descriptors and `sizes`. An image already in a `<picture>` isn't wrapped again, the sources for the types it is missing
+/// are added before it.
+pub struct PictureWrapper<Handle, S, M, F> {
+    inner: S,
+    matcher: M,
+    resolver: F,
+    types: Vec<String>,
+    handles: SyntheticHandles,
+    picture: Option<OpenPicture<Handle>>,
+}
+
+impl<Handle, S, M, F> PictureWrapper<Handle, S, M, F>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    pub fn wrap(inner: S, matcher: M, resolver: F) -> Self {
+        Self {
+            inner,
+            matcher,
+            resolver,
+            types: vec!["image/avif".to_owned(), "image/webp".to_owned()],
+            handles: SyntheticHandles::new(),
+            picture: None,
+        }
+    }
+
+    /// Sets the types to add sources for, in order of preference, `image/avif` and `image/webp` by default
+    pub fn types(self, types: &[&str]) -> Self {
+        Self {
+            types: types.iter().map(|t| (*t).to_owned()).collect(),
+            ..self
+        }
+    }
+
+    /// The attributes of the `<source>`s for the image, skipping the types in `existing`
+    fn sources(
+        &mut self,
+        img: &HtmlPathElement<'_, Handle>,
+        existing: &[String],
+    ) -> Vec<Vec<Attribute>> {
+        let candidates = match (img.attr_local("srcset"), img.attr_local("src")) {
+            (Some(srcset), _) => parse_srcset(srcset),
+            (None, Some(src)) => vec![(src.trim(), "")],
+            (None, None) => return vec![],
+        };
+        let Self {
+            types, resolver, ..
+        } = self;
+        let mut sources = vec![];
+        for image_type in types.iter() {
+            if existing.iter().any(|t| t.eq_ignore_ascii_case(image_type)) {
+                continue;
+            }
+            let srcset = candidates
+                .iter()
+                .map(|(url, descriptor)| {
+                    let variant = resolver(url, image_type)?;
+                    Some(if descriptor.is_empty() {
+                        variant
+                    } else {
+                        format!("{} {}", variant, descriptor)
+                    })
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(srcset) = srcset {
+                let mut attrs = vec![
+                    attribute("type", image_type),
+                    attribute("srcset", &srcset.join(", ")),
+                ];
+                if let Some(sizes) = img.attr_local("sizes") {
+                    attrs.push(attribute("sizes", sizes));
+                }
+                sources.push(attrs);
+            }
+        }
+        sources
+    }
+
+    /// Appends the sources as children of the last element of the context
+    fn append_sources(&mut self, context: HtmlContext<'_, Handle>, sources: Vec<Vec<Attribute>>) {
+        for attrs in sources {
+            let source = HtmlPathElement::new_child(
+                self.handles.allocate(),
+                context.last(),
+                QualName::new(None, ns!(html), local_name!("source")),
+                attrs,
+            );
+            self.inner.append_element(context, &source);
+        }
+    }
+}
+
+impl<Handle, S, M, F> HtmlSink<Handle> for PictureWrapper<Handle, S, M, F>
+where
+    Handle: SyntheticHandle,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if element.name.ns != ns!(html) {
+            return self.inner.append_element(context, element);
+        }
+        let in_picture = self.picture.as_ref().filter(|picture| {
+            context
+                .last()
+                .is_some_and(|parent| parent.handle == picture.handle)
+        });
+        match element.name.local {
+            local_name!("picture") => {
+                self.picture = Some(OpenPicture {
+                    handle: element.handle,
+                    types: vec![],
+                });
+            }
+            local_name!("source") => {
+                if let (Some(_), Some(source_type)) = (in_picture, element.attr_local("type")) {
+                    let source_type = source_type.trim().to_owned();
+                    self.picture.as_mut().unwrap().types.push(source_type);
+                }
+            }
+            local_name!("img") if self.matcher.context_match(context, element) => {
+                if let Some(picture) = in_picture {
+                    let existing = picture.types.clone();
+                    let sources = self.sources(element, &existing);
+                    self.append_sources(context, sources);
+                    return self.inner.append_element(context, element);
+                }
+                let sources = self.sources(element, &[]);
+                if sources.is_empty() {
+                    return self.inner.append_element(context, element);
+                }
+                let picture = HtmlPathElement::new_child(
+                    self.handles.allocate(),
+                    context.last(),
+                    QualName::new(None, ns!(html), local_name!("picture")),
+                    vec![],
+                );
+                self.inner.append_element(context, &picture);
+                let mut picture_context = context.to_vec();
+                picture_context.push(picture);
+                self.append_sources(&picture_context, sources);
+                let img = HtmlPathElement::new_child(
+                    element.handle,
+                    picture_context.last(),
+                    element.name.clone(),
+                    element.attrs.to_vec(),
+                );
+                return self.inner.append_element(&picture_context, &img);
+            }
+            _ => {}
+        }
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.handles.reset();
+        self.picture = None;
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer};
+
+    #[test]
+    fn wrap_in_picture() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            PictureWrapper::wrap(&mut serializer, css_select!("img"), |url, image_type| {
+                let extension = image_type.strip_prefix("image/")?;
+                let stem = url.strip_suffix(".jpg")?;
+                Some(format!("{}.{}", stem, extension))
+            }),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><img src="a.jpg" alt="A"><img srcset="b.jpg 1x, b@2x.jpg 2x" sizes="50vw"><picture><source type="image/webp" srcset="c.webp"><img src="c.jpg"></picture><img src="d.png"><p>after</p></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body>"#,
+                r#"<picture><source type="image/avif" srcset="a.avif"><source type="image/webp" srcset="a.webp"><img src="a.jpg" alt="A"></picture>"#,
+                r#"<picture><source type="image/avif" srcset="b.avif 1x, b@2x.avif 2x" sizes="50vw"><source type="image/webp" srcset="b.webp 1x, b@2x.webp 2x" sizes="50vw"><img srcset="b.jpg 1x, b@2x.jpg 2x" sizes="50vw"></picture>"#,
+                r#"<picture><source type="image/webp" srcset="c.webp"><source type="image/avif" srcset="c.avif"><img src="c.jpg"></picture>"#,
+                r#"<img src="d.png"><p>after</p></body></html>"#
+            )
+        );
+    }
+}
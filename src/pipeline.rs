@@ -0,0 +1,267 @@
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+use crate::{
+    event::{owned_context, HtmlEvent, OwnedPathElement, SiblingCopies},
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+enum Message<Handle> {
+    Event(HtmlEvent<Handle>),
+    Reset,
+    TakeError,
+}
+
+fn stopped() -> HtmlStreamError {
+    HtmlStreamError::Io(io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "pipeline worker has stopped",
+    ))
+}
+
+/// The sending half of a pipeline, see [`pipelined`].
+///
+/// If the inner sink panics, the worker stops and the events after are dropped. `reset` then outputs `None`, while
+/// `take_error` and `finish` report an [`HtmlStreamError::Io`].
+pub struct PipelinedSink<Handle, O> {
+    events: SyncSender<Message<Handle>>,
+    outputs: Receiver<O>,
+    errors: Receiver<Option<HtmlStreamError>>,
+    /// Whether the worker has stopped
+    stopped: bool,
+}
+
+impl<Handle, O> PipelinedSink<Handle, O> {
+    fn send(&mut self, message: Message<Handle>) {
+        if !self.stopped && self.events.send(message).is_err() {
+            self.stopped = true;
+        }
+    }
+
+    /// Sends a message and waits for its reply, `None` if the worker has stopped
+    fn request<T>(
+        &mut self,
+        message: Message<Handle>,
+        replies: impl Fn(&Self) -> &Receiver<T>,
+    ) -> Option<T> {
+        self.send(message);
+        if self.stopped {
+            return None;
+        }
+        let reply = replies(self).recv().ok();
+        self.stopped = reply.is_none();
+        reply
+    }
+}
+
+impl<Handle: Eq + Copy, O> HtmlSink<Handle> for PipelinedSink<Handle, O> {
+    type Output = Option<O>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &html5ever::tendril::StrTendril,
+        public_id: &html5ever::tendril::StrTendril,
+        system_id: &html5ever::tendril::StrTendril,
+    ) {
+        self.send(Message::Event(HtmlEvent::Doctype {
+            name: name.to_string(),
+            public_id: public_id.to_string(),
+            system_id: system_id.to_string(),
+        }))
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.send(Message::Event(HtmlEvent::Element {
+            context: owned_context(context),
+            element: OwnedPathElement::from(element),
+        }))
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.send(Message::Event(HtmlEvent::text(context, text, None)))
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.send(Message::Event(HtmlEvent::text(
+            context,
+            text,
+            Some(boundary),
+        )))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.send(Message::Event(HtmlEvent::raw_text(context, element, text)))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.send(Message::Event(HtmlEvent::Comment {
+            context: owned_context(context),
+            text: text.to_owned(),
+        }))
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.send(Message::Event(HtmlEvent::ProcessingInstruction {
+            context: owned_context(context),
+            target: target.to_owned(),
+            data: data.to_owned(),
+        }))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.request(Message::Reset, |sink| &sink.outputs)
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.request(Message::TakeError, |sink| &sink.errors)
+            .unwrap_or_else(|| Some(stopped()))
+    }
+}
+
+/// Runs `inner` on a dedicated worker thread for the duration of `f`, which is given a sink feeding it through a queue of at most `capacity` events.
+///
+/// This overlaps the parsing and filtering done on the calling thread with the work done by `inner`, typically serialization and its IO.
+///
+/// ```
+/// use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+/// use html5streams::{parse_document, pipelined, HtmlSerializer};
+///
+/// let mut buf = Vec::new();
+/// let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+/// let doc = "<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>";
+/// pipelined(64, &mut serializer, |sink| {
+///     parse_document(sink, Default::default()).one(doc).unwrap()
+/// });
+/// drop(serializer);
+/// assert_eq!(buf, doc.as_bytes());
+/// ```
+pub fn pipelined<Handle, S, R, F>(capacity: usize, mut inner: S, f: F) -> R
+where
+    Handle: Eq + Copy + Send + Sync,
+    S: HtmlSink<Handle> + Send,
+    S::Output: Send,
+    F: FnOnce(PipelinedSink<Handle, S::Output>) -> R,
+{
+    let (event_sender, event_receiver) = mpsc::sync_channel(capacity);
+    let (output_sender, output_receiver) = mpsc::channel();
+    let (error_sender, error_receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            // a panic of the inner sink stops the worker, dropping the channels, which the pipelined sink reports as an error
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut siblings = SiblingCopies::default();
+                for message in event_receiver {
+                    let sent = match message {
+                        Message::Event(event) => {
+                            event.replay_with(&mut inner, &mut siblings);
+                            Ok(())
+                        }
+                        Message::Reset => {
+                            siblings = SiblingCopies::default();
+                            output_sender.send(inner.reset()).map_err(drop)
+                        }
+                        Message::TakeError => error_sender.send(inner.take_error()).map_err(drop),
+                    };
+                    if sent.is_err() {
+                        break;
+                    }
+                }
+            }));
+        });
+        f(PipelinedSink {
+            events: event_sender,
+            outputs: output_receiver,
+            errors: error_receiver,
+            stopped: false,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, StatsCollector};
+
+    const DOC: &str = "<!DOCTYPE html><html><head></head><body><p>hello</p></body></html>";
+
+    /// Panics at the first paragraph or comment
+    struct Panicking;
+
+    impl HtmlSink<u32> for Panicking {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &html5ever::tendril::StrTendril,
+            _public_id: &html5ever::tendril::StrTendril,
+            _system_id: &html5ever::tendril::StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            element: &HtmlPathElement<'_, u32>,
+        ) {
+            assert_ne!(&*element.name.local, "p", "sink failed");
+        }
+
+        fn append_text(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {
+            panic!("sink failed");
+        }
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn report_a_stopped_worker() {
+        let stats = pipelined(4, StatsCollector::new(), |sink| {
+            parse_document(sink, Default::default()).one(DOC)
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(stats.elements, 4);
+
+        let result = pipelined(4, Panicking, |sink| {
+            parse_document(sink, Default::default()).one(DOC)
+        });
+        assert!(matches!(
+            result,
+            Err(HtmlStreamError::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe
+        ));
+
+        let (output, error) = pipelined(4, Panicking, |mut sink| {
+            sink.append_comment(&[], "stop");
+            (sink.reset(), sink.take_error())
+        });
+        assert!(output.is_none());
+        assert!(error.is_some());
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute, QualName};
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, HtmlEvent},
+    HeadBuffer, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, SyntheticHandle,
+    SyntheticHandles, TextBoundary,
+};
+
+/// A render-critical resource found by a [`PreloadHints`]
+struct Resource {
+    href: String,
+    /// The `as`, `type` and, for images, the `imagesrcset` and `imagesizes` of the preload
+    attrs: Vec<(&'static str, String)>,
+}
+
+/// The `type` of a font by its url's extension
+fn font_type(href: &str) -> Option<&'static str> {
+    let path = href.split(['?', '#']).next().unwrap_or_default();
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match &*extension {
+        "woff2" => Some("font/woff2"),
+        "woff" => Some("font/woff"),
+        "ttf" => Some("font/ttf"),
+        "otf" => Some("font/otf"),
+        _ => None,
+    }
+}
+
+/// A filter which adds `<link rel="preload">`s to the start of the `<head>` of each document for the first render-critical resources in it:
+/// stylesheets, fonts referenced by `<link>`s and images which aren't lazily loaded, in the order they are found.
+///
+/// The head and what follows it are held back in a [`HeadBuffer`] until the [`PreloadHints::limit`] of resources has been found,
+/// or the [`PreloadHints::max_held`] number of events has been held, then the hints are added and the rest of the document streams.
+/// Resources which already have a preload aren't hinted again.
+pub struct PreloadHints<Handle, S> {
+    inner: S,
+    limit: usize,
+    max_held: usize,
+    buffer: HeadBuffer<Handle>,
+    handles: SyntheticHandles,
+    preloaded: HashSet<String>,
+    resources: Vec<Resource>,
+}
+
+impl<Handle: SyntheticHandle, S: HtmlSink<Handle>> PreloadHints<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            limit: 4,
+            max_held: 1000,
+            buffer: HeadBuffer::new().hold_head(true),
+            handles: SyntheticHandles::new(),
+            preloaded: HashSet::new(),
+            resources: vec![],
+        }
+    }
+
+    /// Sets the number of resources to add hints for, 4 by default
+    pub fn limit(self, limit: usize) -> Self {
+        Self { limit, ..self }
+    }
+
+    /// Sets the number of events to hold back while looking for resources, 1000 by default
+    pub fn max_held(self, max_held: usize) -> Self {
+        Self { max_held, ..self }
+    }
+
+    /// Notes the resource an element loads, or that a preload already exists
+    fn find(&mut self, element: &HtmlPathElement<'_, Handle>) {
+        if element.name.ns != ns!(html) {
+            return;
+        }
+        let resource = match element.name.local {
+            local_name!("link") => {
+                let Some(href) = element.attr_local("href").map(|href| href.trim()) else {
+                    return;
+                };
+                if element.has_token("rel", "preload") || element.has_token("rel", "modulepreload")
+                {
+                    self.preloaded.insert(href.to_owned());
+                    return;
+                }
+                if element.has_token("rel", "stylesheet") {
+                    Resource {
+                        href: href.to_owned(),
+                        attrs: vec![("as", "style".to_owned())],
+                    }
+                } else if let Some(font_type) = font_type(href) {
+                    Resource {
+                        href: href.to_owned(),
+                        attrs: vec![
+                            ("as", "font".to_owned()),
+                            ("type", font_type.to_owned()),
+                            ("crossorigin", String::new()),
+                        ],
+                    }
+                } else {
+                    return;
+                }
+            }
+            local_name!("img") => {
+                let lazy = element
+                    .attr_local("loading")
+                    .is_some_and(|loading| loading.trim().eq_ignore_ascii_case("lazy"));
+                let Some(src) = element.attr_local("src").filter(|_| !lazy) else {
+                    return;
+                };
+                let mut attrs = vec![("as", "image".to_owned())];
+                for (attribute, preload_attribute) in
+                    [("srcset", "imagesrcset"), ("sizes", "imagesizes")]
+                {
+                    if let Some(value) = element.attr_local(attribute) {
+                        attrs.push((preload_attribute, value.to_string()));
+                    }
+                }
+                Resource {
+                    href: src.trim().to_owned(),
+                    attrs,
+                }
+            }
+            _ => return,
+        };
+        if !self
+            .resources
+            .iter()
+            .any(|found| found.href == resource.href)
+        {
+            self.resources.push(resource);
+        }
+    }
+
+    /// The attributes of the preloads for the first resources found which don't have one yet
+    fn hints(&self) -> Vec<Vec<Attribute>> {
+        let attribute = |local: &str, value: &str| Attribute {
+            name: QualName::new(None, ns!(), local.into()),
+            value: value.into(),
+        };
+        self.resources
+            .iter()
+            .filter(|resource| !self.preloaded.contains(&resource.href))
+            .take(self.limit)
+            .map(|resource| {
+                let mut attrs = vec![
+                    attribute("rel", "preload"),
+                    attribute("href", &resource.href),
+                ];
+                attrs.extend(
+                    resource
+                        .attrs
+                        .iter()
+                        .map(|(local, value)| attribute(local, value)),
+                );
+                attrs
+            })
+            .collect()
+    }
+
+    /// Adds the hints to the head and releases the held events, once enough has been found or held
+    fn decide(&mut self, at_end: bool) {
+        if self.buffer.is_released() {
+            return;
+        }
+        let found = self
+            .resources
+            .iter()
+            .filter(|resource| !self.preloaded.contains(&resource.href))
+            .count();
+        if !(at_end || found >= self.limit || self.buffer.held() >= self.max_held) {
+            return;
+        }
+        if let Some(context) = self.buffer.head_context() {
+            for attrs in self.hints() {
+                let link = HtmlPathElement::new_child(
+                    self.handles.allocate(),
+                    context.last(),
+                    QualName::new(None, ns!(html), local_name!("link")),
+                    attrs,
+                );
+                self.inner.append_element(&context, &link);
+            }
+        }
+        self.buffer.release(&mut self.inner);
+    }
+
+    /// Holds or passes on text, with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return append_text_to(&mut self.inner, context, text, boundary);
+        }
+        self.buffer.push(HtmlEvent::text(context, text, boundary));
+        self.decide(false)
+    }
+}
+
+impl<Handle: SyntheticHandle, S: HtmlSink<Handle>> HtmlSink<Handle> for PreloadHints<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if !self.buffer.is_held(context, Some(element)) {
+            return self.inner.append_element(context, element);
+        }
+        self.find(element);
+        self.buffer.push(HtmlEvent::Element {
+            context: owned_context(context),
+            element: element.into(),
+        });
+        self.decide(false)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return self.inner.append_raw_text(context, element, text);
+        }
+        self.buffer
+            .push(HtmlEvent::raw_text(context, element, text));
+        self.decide(false)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if !self.buffer.is_held(context, None) {
+            return self.inner.append_comment(context, text);
+        }
+        self.buffer.push(HtmlEvent::Comment {
+            context: owned_context(context),
+            text: text.to_owned(),
+        });
+        self.decide(false)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if !self.buffer.is_held(context, None) {
+            return self
+                .inner
+                .append_processing_instruction(context, target, data);
+        }
+        self.buffer.push(HtmlEvent::ProcessingInstruction {
+            context: owned_context(context),
+            target: target.to_owned(),
+            data: data.to_owned(),
+        });
+        self.decide(false)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.decide(true);
+        self.buffer.reset();
+        self.handles.reset();
+        self.preloaded.clear();
+        self.resources.clear();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn add_preload_hints() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(PreloadHints::wrap(&mut serializer).limit(3), Default::default())
+            .one(r#"<!DOCTYPE html><html><head><link rel="preload" href="/app.css" as="style"><link rel="stylesheet" href="/app.css"><link rel="stylesheet" href="/print.css"></head><body><img src="/lazy.jpg" loading="lazy"><img src="/hero.jpg" srcset="/hero-2x.jpg 2x"><img src="/other.jpg"><img src="/late.jpg"></body></html>"#)
+            .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head><link rel="preload" href="/print.css" as="style"><link rel="preload" href="/hero.jpg" as="image" imagesrcset="/hero-2x.jpg 2x">"#,
+                r#"<link rel="preload" href="/other.jpg" as="image">"#,
+                r#"<link rel="preload" href="/app.css" as="style"><link rel="stylesheet" href="/app.css"><link rel="stylesheet" href="/print.css"></head>"#,
+                r#"<body><img src="/lazy.jpg" loading="lazy"><img src="/hero.jpg" srcset="/hero-2x.jpg 2x"><img src="/other.jpg"><img src="/late.jpg"></body></html>"#
+            )
+        );
+    }
+
+    #[test]
+    fn bound_held_events() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(PreloadHints::wrap(&mut serializer).max_held(2), Default::default())
+            .one(r#"<!DOCTYPE html><html><head><title>Page</title><link rel="stylesheet" href="/app.css"></head><body></body></html>"#)
+            .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><title>Page</title><link rel="stylesheet" href="/app.css"></head><body></body></html>"#
+        );
+    }
+}
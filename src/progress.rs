@@ -0,0 +1,201 @@
+use std::{cell::Cell, io::Read, ops::ControlFlow, rc::Rc};
+
+use html5ever::{
+    tendril::{ByteTendril, StrTendril, TendrilSink},
+    ParseOpts,
+};
+
+use crate::{
+    parse_document, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+    DEFAULT_BUFFER_CAPACITY,
+};
+
+/// How far a parse has got, as reported to the callback of [`parse_document_with_progress`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub bytes_consumed: usize,
+    pub elements_emitted: usize,
+    /// Whether the input has all been consumed, the last report is always made once it has
+    pub finished: bool,
+}
+
+/// Counts the elements passing through to the inner sink
+struct ElementCounter<S> {
+    inner: S,
+    elements: Rc<Cell<usize>>,
+}
+
+impl<S: HtmlSink<u32>> HtmlSink<u32> for ElementCounter<S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, u32>,
+        element: &HtmlPathElement<'_, u32>,
+    ) {
+        self.elements.set(self.elements.get() + 1);
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<u32>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<u32>,
+        element: &HtmlPathElement<'_, u32>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<u32>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<u32>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// Parses a utf-8 document read from `input` into the sink, reporting the [`Progress`] to a callback each time at least `report_every` more bytes have been consumed.
+///
+/// The callback can cancel the parse by returning `ControlFlow::Break`, which stops reading and returns `HtmlStreamError::Cancelled`.
+pub fn parse_document_with_progress<Sink, R, F>(
+    sink: Sink,
+    opts: ParseOpts,
+    mut input: R,
+    report_every: usize,
+    mut progress: F,
+) -> Result<Sink::Output, HtmlStreamError>
+where
+    Sink: HtmlSink<u32>,
+    R: Read,
+    F: FnMut(&Progress) -> ControlFlow<()>,
+{
+    let elements = Rc::new(Cell::new(0));
+    let sink = ElementCounter {
+        inner: sink,
+        elements: elements.clone(),
+    };
+    let mut parser = parse_document(sink, opts).from_utf8();
+    let mut buf = vec![0; DEFAULT_BUFFER_CAPACITY];
+    let mut bytes_consumed = 0;
+    let mut reported = 0;
+    loop {
+        let read = match input.read(&mut buf) {
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let finished = read == 0;
+        if !finished {
+            parser.process(ByteTendril::from_slice(&buf[..read]));
+            bytes_consumed += read;
+        }
+        if finished || bytes_consumed - reported >= report_every {
+            reported = bytes_consumed;
+            let report = Progress {
+                bytes_consumed,
+                elements_emitted: elements.get(),
+                finished,
+            };
+            if progress(&report).is_break() {
+                return Err(HtmlStreamError::Cancelled);
+            }
+        }
+        if finished {
+            return parser.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StatsCollector;
+
+    #[test]
+    fn report_progress() {
+        let doc = format!(
+            "<!DOCTYPE html><html><head></head><body>{}</body></html>",
+            "<p>paragraph</p>".repeat(2000)
+        );
+        let mut reports = vec![];
+        let stats = parse_document_with_progress(
+            StatsCollector::new(),
+            Default::default(),
+            doc.as_bytes(),
+            10_000,
+            |progress| {
+                reports.push(*progress);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(stats.elements, 2003);
+        assert!(reports.len() > 2);
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].bytes_consumed <= pair[1].bytes_consumed
+                && pair[0].elements_emitted <= pair[1].elements_emitted));
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                bytes_consumed: doc.len(),
+                elements_emitted: 2003,
+                finished: true
+            })
+        );
+
+        let cancelled = parse_document_with_progress(
+            StatsCollector::new(),
+            Default::default(),
+            doc.as_bytes(),
+            10_000,
+            |progress| {
+                if progress.elements_emitted > 100 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        );
+        assert!(matches!(cancelled, Err(HtmlStreamError::Cancelled)));
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+#[derive(Debug, Default)]
+struct LineState {
+    current: u64,
+    elements: HashMap<u32, u64>,
+}
+
+/// A handle recording the source lines of a parse, set with [`crate::ParseConfig::source_lines`], for a [`ProvenanceTracker`].
+///
+/// html5ever reports the line of each token it processes, not byte offsets, so lines are as precise as the source can be located.
+/// Clones share the same lines, so one can be kept while another is moved into the parse.
+#[derive(Clone, Debug, Default)]
+pub struct SourceLines {
+    state: Arc<Mutex<LineState>>,
+}
+
+impl SourceLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The line being parsed, counting from 1
+    pub fn current(&self) -> u64 {
+        self.state.lock().unwrap().current
+    }
+
+    /// The line of the start tag of the element with the parser's handle
+    pub fn element(&self, handle: u32) -> Option<u64> {
+        self.state.lock().unwrap().elements.get(&handle).copied()
+    }
+
+    pub(crate) fn set_current(&self, line: u64) {
+        self.state.lock().unwrap().current = line;
+    }
+
+    /// Records that the element with the handle starts on the current line
+    pub(crate) fn element_created(&self, handle: u32) {
+        let mut state = self.state.lock().unwrap();
+        let line = state.current;
+        state.elements.insert(handle, line);
+    }
+
+    /// Forgets the lines of the last document
+    pub fn clear(&self) {
+        *self.state.lock().unwrap() = LineState::default();
+    }
+}
+
+/// The kind of event an [`Origin`] describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OriginKind {
+    Element,
+    Text,
+    Comment,
+    ProcessingInstruction,
+}
+
+/// Where an event which reached the sink inside a [`ProvenanceTracker`] came from in the input
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Origin {
+    pub kind: OriginKind,
+    /// The handle of the element, or of the element the text, comment or instruction is in, `None` at the document level
+    pub handle: Option<u32>,
+    /// The line in the input, of an element's start tag or where other events were parsed, `None` for elements which aren't from
+    /// the input, such as those a filter adds
+    pub line: Option<u64>,
+    /// The path of the event in the output
+    pub path: String,
+}
+
+/// A sink which records the [`Origin`] of each event it passes on to the inner sink, outputting them alongside the inner sink's output
+/// in the order the inner sink received them, so the nth origin is of the nth event in the output.
+///
+/// Put it last in a pipeline, around the serializer, to map the output back to the input: handles survive filters, and the lines come
+/// from the parse's [`SourceLines`]. Elements are located by their handle, so they keep their line when a filter holds them back or
+/// moves them, while other events take the line being parsed when they are passed on, which is later for those a filter held back.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{HtmlSerializer, OriginKind, ParseConfig, ProvenanceTracker, SourceLines};
+///
+/// let lines = SourceLines::new();
+/// let (_html, origins) = ParseConfig::default()
+///     .source_lines(lines.clone())
+///     .parse_document(ProvenanceTracker::wrap(
+///         HtmlSerializer::new(Vec::new(), Default::default()),
+///         lines,
+///     ))
+///     .one("<!DOCTYPE html>\n<html>\n<head></head>\n<body>\n<p>Hello</p>\n</body>\n</html>")
+///     .unwrap();
+/// let p = origins.iter().find(|origin| origin.path.ends_with("> p:nth-child(1)")).unwrap();
+/// assert_eq!((p.kind, p.line), (OriginKind::Element, Some(5)));
+/// ```
+pub struct ProvenanceTracker<S> {
+    inner: S,
+    lines: SourceLines,
+    paths: CssPathTracker,
+    origins: Vec<Origin>,
+}
+
+impl<S: HtmlSink<u32>> ProvenanceTracker<S> {
+    /// Wraps the inner sink, with the lines recorded by the parse
+    pub fn wrap(inner: S, lines: SourceLines) -> Self {
+        Self {
+            inner,
+            lines,
+            paths: CssPathTracker::new(),
+            origins: vec![],
+        }
+    }
+
+    fn record(&mut self, kind: OriginKind, context: HtmlContext<'_, u32>) {
+        self.origins.push(Origin {
+            kind,
+            handle: context.last().map(|parent| parent.handle),
+            line: Some(self.lines.current()),
+            path: self.paths.context_path(context),
+        });
+    }
+}
+
+impl<S: HtmlSink<u32>> HtmlSink<u32> for ProvenanceTracker<S> {
+    type Output = (S::Output, Vec<Origin>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, u32>,
+        element: &HtmlPathElement<'_, u32>,
+    ) {
+        self.paths.element(context);
+        self.origins.push(Origin {
+            kind: OriginKind::Element,
+            handle: Some(element.handle),
+            line: self.lines.element(element.handle),
+            path: self.paths.element_path(context, element),
+        });
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+        self.record(OriginKind::Text, context);
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<u32>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.record(OriginKind::Text, context);
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<u32>,
+        element: &HtmlPathElement<'_, u32>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<u32>, text: &str) {
+        self.record(OriginKind::Comment, context);
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<u32>,
+        target: &str,
+        data: &str,
+    ) {
+        self.record(OriginKind::ProcessingInstruction, context);
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.origins))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{selector::NameSelector, ElementRemover, ParseConfig};
+
+    #[test]
+    fn track_origins_through_filters() {
+        let lines = SourceLines::new();
+        let ((), origins) = ParseConfig::default()
+            .source_lines(lines.clone())
+            .parse_document(ElementRemover::wrap(
+                ProvenanceTracker::wrap((), lines),
+                NameSelector("aside"),
+            ))
+            .one("<!DOCTYPE html>\n<html><head></head><body>\n<aside>ad</aside>\n<p>one\ntwo</p><!-- c -->\n</body></html>")
+            .unwrap();
+        assert_eq!(
+            origins
+                .iter()
+                .map(|origin| (origin.kind, origin.line, &*origin.path))
+                .collect::<Vec<_>>(),
+            [
+                (OriginKind::Element, Some(2), "html"),
+                (OriginKind::Element, Some(2), "html > head"),
+                (OriginKind::Element, Some(2), "html > body"),
+                (OriginKind::Text, Some(3), "html > body"),
+                (OriginKind::Text, Some(4), "html > body"),
+                (OriginKind::Element, Some(4), "html > body > p:nth-child(1)"),
+                (OriginKind::Text, Some(4), "html > body > p:nth-child(1)"),
+                (OriginKind::Text, Some(5), "html > body > p:nth-child(1)"),
+                (OriginKind::Text, Some(5), "html > body > p:nth-child(1)"),
+                (OriginKind::Comment, Some(5), "html > body"),
+                (OriginKind::Text, Some(6), "html > body"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use html5ever::{tendril::StrTendril, LocalName};
+
+use crate::{
+    boundary::append_text_to,
+    event::{owned_context, replay_into, HtmlEvent, OwnedPathElement},
+    raw_text::text_context,
+    selector::ContextualSelector,
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// Elements which are never pruned by default, as they are content without any children
+pub const DEFAULT_PRESERVED_ELEMENTS: &[&str] = &[
+    "area", "audio", "br", "canvas", "col", "embed", "hr", "iframe", "img", "input", "object",
+    "select", "source", "svg", "td", "textarea", "th", "track", "video", "wbr",
+];
+
+/// A filter which drops the elements matching a selector which turn out to contain no content, such as the `<div></div>` and `<p> </p>`
+/// wrappers left behind by removing or sanitizing their contents.
+///
+/// Content is text which isn't only html whitespace, so a `&nbsp;` is content, or an element which isn't prunable, either because it doesn't match the selector or because it
+/// is in the preserve list, [`DEFAULT_PRESERVED_ELEMENTS`] by default. Comments don't count as content and are dropped along with an empty element.
+/// The events within a prunable element are buffered until content is found or it closes. A pruned element is still the previous
+/// sibling of the element after it, as it was in the source.
+pub struct EmptyElementPruner<Handle, S, M> {
+    inner: S,
+    matcher: M,
+    preserved: Vec<LocalName>,
+    /// The open prunable elements which have no content yet, with their depths, the lengths of their contexts, and the index of their
+    /// element event in the buffer
+    pending: Vec<(Handle, usize, usize)>,
+    buffer: Vec<HtmlEvent<Handle>>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: ContextualSelector>
+    EmptyElementPruner<Handle, S, M>
+{
+    pub fn wrap(inner: S, matcher: M) -> Self {
+        Self {
+            inner,
+            matcher,
+            preserved: DEFAULT_PRESERVED_ELEMENTS
+                .iter()
+                .map(|name| LocalName::from(*name))
+                .collect(),
+            pending: vec![],
+            buffer: vec![],
+        }
+    }
+
+    /// Sets the local names of the elements which are never pruned
+    pub fn preserve<'a>(self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            preserved: names.into_iter().map(LocalName::from).collect(),
+            ..self
+        }
+    }
+
+    /// Drops the pending elements which have closed without content
+    fn close_elements(&mut self, context: HtmlContext<'_, Handle>) {
+        while let Some(&(handle, depth, start)) = self.pending.last() {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                break;
+            }
+            self.pending.pop();
+            self.buffer.truncate(start);
+        }
+    }
+
+    /// Content has been found, so the pending elements are kept
+    fn flush(&mut self) {
+        self.pending.clear();
+        replay_into(&self.buffer, &mut self.inner);
+        self.buffer.clear();
+    }
+
+    /// Holds whitespace in pending elements, other text being content which keeps them
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        self.close_elements(context);
+        if self.pending.is_empty() {
+            append_text_to(&mut self.inner, context, text, boundary)
+        } else if text
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .is_empty()
+        {
+            self.buffer.push(HtmlEvent::text(context, text, boundary));
+        } else {
+            self.flush();
+            append_text_to(&mut self.inner, context, text, boundary)
+        }
+    }
+}
+
+impl<Handle, S, M> HtmlSink<Handle> for EmptyElementPruner<Handle, S, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_elements(context);
+        if self.preserved.contains(&element.name.local)
+            || !self.matcher.context_match(context, element)
+        {
+            self.flush();
+            self.inner.append_element(context, element);
+        } else {
+            self.pending
+                .push((element.handle, context.len(), self.buffer.len()));
+            self.buffer.push(HtmlEvent::Element {
+                context: owned_context(context),
+                element: OwnedPathElement::from(element),
+            });
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.close_elements(&text_context(context, element));
+        if self.pending.is_empty() {
+            self.inner.append_raw_text(context, element, text)
+        } else {
+            self.buffer
+                .push(HtmlEvent::raw_text(context, element, text))
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_elements(context);
+        if self.pending.is_empty() {
+            self.inner.append_comment(context, text)
+        } else {
+            self.buffer.push(HtmlEvent::Comment {
+                context: owned_context(context),
+                text: text.to_owned(),
+            });
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close_elements(context);
+        if self.pending.is_empty() {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        } else {
+            self.buffer.push(HtmlEvent::ProcessingInstruction {
+                context: owned_context(context),
+                target: target.to_owned(),
+                data: data.to_owned(),
+            });
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_elements(&[]);
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::serialize::SerializeOpts;
+
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{
+        css_select, selector::ContextualSelector, test::stream_doc, ElementRemover, HtmlSerializer,
+        ParseConfig, SelectToStrings, SiblingTracking,
+    };
+
+    #[test]
+    fn prune_empty_elements() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><div class="wrap"><p> <span class="ad">buy</span> </p><!-- c --></div><p>kept <b></b></p><div><img src="a.png"></div><p>&nbsp;</p><table><tbody><tr><td></td></tr></tbody></table></body></html>"#,
+            ElementRemover::wrap(
+                EmptyElementPruner::wrap(
+                    &mut serializer,
+                    css_select!("div").or(css_select!("p")).or(css_select!("b")),
+                ),
+                css_select!(."ad"),
+            ),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><p>kept </p><div><img src="a.png"></div><p>&nbsp;</p><table><tbody><tr><td></td></tr></tbody></table></body></html>"#
+        );
+    }
+
+    #[test]
+    fn buffered_elements_keep_their_siblings() {
+        let selected = ParseConfig::default()
+            .sibling_tracking(SiblingTracking::Adjacent)
+            .parse_document(EmptyElementPruner::wrap(
+                SelectToStrings::new(css_select!("div" + "div")),
+                css_select!("div"),
+            ))
+            .one("<!DOCTYPE html><html><head></head><body><div><p>a</p></div><div><p>b</p></div></body></html>")
+            .unwrap();
+        assert_eq!(selected, ["<div><p>b</p></div>"]);
+
+        let selected = ParseConfig::default()
+            .sibling_tracking(SiblingTracking::Adjacent)
+            .parse_document(EmptyElementPruner::wrap(
+                SelectToStrings::new(css_select!("div" + "p")),
+                css_select!("div"),
+            ))
+            .one("<!DOCTYPE html><html><head></head><body><div> </div><p>a</p></body></html>")
+            .unwrap();
+        assert_eq!(selected, ["<p>a</p>"]);
+    }
+}
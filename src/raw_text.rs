@@ -0,0 +1,246 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    boundary::append_text_to, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// The context of the text of a raw text element, for filters which pass its whole text on by the same rules as the chunks of it
+pub(crate) fn text_context<'a, Handle: Copy>(
+    context: HtmlContext<'a, Handle>,
+    element: &HtmlPathElement<'a, Handle>,
+) -> Vec<HtmlPathElement<'a, Handle>> {
+    let mut text_context = context.to_vec();
+    text_context.push(element.clone());
+    text_context
+}
+
+/// A `<script>` or `<style>` element whose text is being collected
+struct OpenRawText<Handle> {
+    context: Vec<HtmlPathElement<'static, Handle>>,
+    element: HtmlPathElement<'static, Handle>,
+    text: String,
+}
+
+/// A wrapper which collects the text of each `<script>` and `<style>` element, in html or svg, and gives it whole to `append_raw_text` on the inner sink.
+///
+/// The text is still appended as usual as it arrives, the complete text follows once the element closes,
+/// so JSON-LD extraction or css auditing can work on it without reassembling chunks.
+pub struct RawTextCapture<Handle, S> {
+    inner: S,
+    open: Option<OpenRawText<Handle>>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> RawTextCapture<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self { inner, open: None }
+    }
+
+    /// Gives the collected text to the inner sink, the next event is outside the element
+    fn close(&mut self) {
+        if let Some(open) = self.open.take() {
+            self.inner
+                .append_raw_text(&open.context, &open.element, &open.text);
+        }
+    }
+
+    /// Collects the text of the open raw text element, passing it on with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        match (&mut self.open, context.last()) {
+            (Some(open), Some(parent)) if parent.handle == open.element.handle => {
+                open.text.push_str(text)
+            }
+            _ => self.close(),
+        }
+        append_text_to(&mut self.inner, context, text, boundary)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for RawTextCapture<Handle, S> {
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.close();
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close();
+        self.inner.append_element(context, element);
+        if matches!(element.name.ns, ns!(html) | ns!(svg))
+            && matches!(
+                element.name.local,
+                local_name!("script") | local_name!("style")
+            )
+        {
+            self.open = Some(OpenRawText {
+                context: context
+                    .iter()
+                    .cloned()
+                    .map(HtmlPathElement::into_owned)
+                    .collect(),
+                element: element.clone().into_owned(),
+                text: String::new(),
+            });
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.close();
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close();
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close();
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, test::stream_doc, ElementRemover, LookaheadBuffer};
+
+    #[derive(Default)]
+    struct RawTexts {
+        texts: Vec<(String, Option<String>, String)>,
+        chunks: usize,
+    }
+
+    impl HtmlSink<u32> for &mut RawTexts {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            _context: HtmlContext<'_, u32>,
+            _element: &HtmlPathElement<'_, u32>,
+        ) {
+        }
+
+        fn append_text(&mut self, _context: HtmlContext<u32>, _text: &str) {
+            self.chunks += 1;
+        }
+
+        fn append_raw_text(
+            &mut self,
+            context: HtmlContext<u32>,
+            element: &HtmlPathElement<'_, u32>,
+            text: &str,
+        ) {
+            self.texts.push((
+                format!(
+                    "{}>{}",
+                    context.last().unwrap().name.local,
+                    element.name.local
+                ),
+                element.attr_local("type").map(|t| t.to_string()),
+                text.to_owned(),
+            ));
+        }
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn capture_raw_text() {
+        let mut raw = RawTexts::default();
+        stream_doc(
+            r#"<!DOCTYPE html><html><head><style>p { color: red }</style><script type="application/ld+json">{"name": "a &amp; b"}</script></head><body><p>text</p><script></script></body></html>"#,
+            RawTextCapture::wrap(&mut raw),
+        );
+        assert_eq!(
+            raw.texts,
+            [
+                ("head>style".to_owned(), None, "p { color: red }".to_owned()),
+                (
+                    "head>script".to_owned(),
+                    Some("application/ld+json".to_owned()),
+                    r#"{"name": "a &amp; b"}"#.to_owned()
+                ),
+                ("body>script".to_owned(), None, String::new()),
+            ]
+        );
+        assert_eq!(raw.chunks, 3);
+    }
+
+    #[test]
+    fn raw_text_passes_through_filters() {
+        let mut raw = RawTexts::default();
+        stream_doc(
+            r#"<!DOCTYPE html><html><head><style>p { color: red }</style><script>a()</script></head><body><p>text</p><script>b()</script></body></html>"#,
+            RawTextCapture::wrap(ElementRemover::wrap(
+                LookaheadBuffer::wrap(&mut raw, css_select!("head")),
+                css_select!("style"),
+            )),
+        );
+        assert_eq!(
+            raw.texts,
+            [
+                ("head>script".to_owned(), None, "a()".to_owned()),
+                ("body>script".to_owned(), None, "b()".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::sync::{Arc, OnceLock};
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    boundary::append_text_to, BaseUrl, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    TextBoundary,
+};
+
+/// A way a document sends its reader elsewhere, found by a [`RedirectDetector`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Redirect {
+    /// A `<meta http-equiv="refresh">` with a url, after the delay in seconds
+    MetaRefresh { delay: f64, url: Url },
+    /// A `<link rel="canonical">` to another host
+    OffSiteCanonical { url: Url },
+    /// An inline script assigning to `location`, or calling `location.replace` or `location.assign`, with a string
+    ScriptLocation { url: Url },
+}
+
+impl Redirect {
+    pub fn url(&self) -> &Url {
+        match self {
+            Redirect::MetaRefresh { url, .. }
+            | Redirect::OffSiteCanonical { url }
+            | Redirect::ScriptLocation { url } => url,
+        }
+    }
+}
+
+/// Parses the `content` of a `<meta http-equiv="refresh">` into its delay in seconds and url, such as `5; url='/next'`, by the html rules
+pub fn parse_refresh(content: &str) -> Option<(f64, Option<&str>)> {
+    let content = content.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let end = content
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(content.len());
+    let digits = &content[..end];
+    let integer = &digits[..digits.find('.').unwrap_or(digits.len())];
+    if integer.is_empty() {
+        return None;
+    }
+    let delay = digits.trim_end_matches('.').parse::<f64>().ok()?;
+    let rest = content[end..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let rest = match rest.strip_prefix(|c| c == ';' || c == ',') {
+        Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_whitespace()),
+        None if rest.is_empty() => return Some((delay, None)),
+        None => return None,
+    };
+    let url = match rest.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+            let after = rest[3..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+            match after.strip_prefix('=') {
+                Some(after) => after.trim_start_matches(|c: char| c.is_ascii_whitespace()),
+                None => rest,
+            }
+        }
+        _ => rest,
+    };
+    let url = match url.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let url = &url[1..];
+            &url[..url.find(quote).unwrap_or(url.len())]
+        }
+        _ => url.trim_end_matches(|c: char| c.is_ascii_whitespace()),
+    };
+    Some((delay, Some(url).filter(|url| !url.is_empty())))
+}
+
+/// Finds the string assigned to `location` or `location.href`, or passed to `location.replace` or `location.assign`, in a script
+fn script_location(script: &str) -> Option<&str> {
+    let mut rest = script;
+    while let Some(found) = rest.find("location") {
+        let after = &rest[found + "location".len()..];
+        rest = after;
+        let after = after.strip_prefix(".href").unwrap_or(after);
+        let after = after.trim_start();
+        let after = if let Some(after) = after.strip_prefix('=') {
+            if after.starts_with('=') {
+                continue;
+            }
+            after
+        } else if let Some(after) = after
+            .strip_prefix(".replace(")
+            .or_else(|| after.strip_prefix(".assign("))
+        {
+            after
+        } else {
+            continue;
+        };
+        let after = after.trim_start();
+        let Some(quote @ ('"' | '\'' | '`')) = after.chars().next() else {
+            continue;
+        };
+        let after = &after[1..];
+        if let Some(end) = after.find(quote) {
+            return Some(&after[..end]);
+        }
+    }
+    None
+}
+
+/// A handle to the first redirect a [`RedirectDetector`] finds, which can be checked while the document is still being parsed,
+/// such as in the callback of [`crate::parse_document_with_progress`] to cancel the parse once a crawler knows the page redirects
+#[derive(Clone, Debug, Default)]
+pub struct RedirectVerdict {
+    first: Arc<OnceLock<Redirect>>,
+}
+
+impl RedirectVerdict {
+    pub fn get(&self) -> Option<&Redirect> {
+        self.first.get()
+    }
+}
+
+/// A wrapper which detects the ways a document redirects, outputting them along with the inner sink's output.
+///
+/// Detected are `<meta http-equiv="refresh">` with a url, `<link rel="canonical">` to another host, and inline scripts assigning a string to `location`.
+/// Urls are resolved against the document's base url. The first redirect is also available from [`RedirectDetector::verdict`] as soon as it is seen.
+pub struct RedirectDetector<Handle, S> {
+    inner: S,
+    document_url: Url,
+    base: BaseUrl,
+    script: Option<(Handle, String)>,
+    redirects: Vec<Redirect>,
+    verdict: RedirectVerdict,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> RedirectDetector<Handle, S> {
+    pub fn wrap(inner: S, document_url: Url) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            script: None,
+            redirects: vec![],
+            verdict: RedirectVerdict::default(),
+        }
+    }
+
+    /// A handle to the first redirect of the current document, a new handle is needed for each document after a reset
+    pub fn verdict(&self) -> RedirectVerdict {
+        self.verdict.clone()
+    }
+
+    fn found(&mut self, redirect: Redirect) {
+        let _ = self.verdict.first.set(redirect.clone());
+        self.redirects.push(redirect);
+    }
+
+    /// Looks for a redirect in the collected script, if the event in the context is outside it
+    fn close_script(&mut self, context: HtmlContext<'_, Handle>) {
+        match &self.script {
+            Some((handle, _)) if !context.iter().any(|element| element.handle == *handle) => {}
+            _ => return,
+        }
+        let (_, script) = self.script.take().unwrap();
+        let url = script_location(&script).and_then(|url| self.base.url().join(url).ok());
+        if let Some(url) = url {
+            self.found(Redirect::ScriptLocation { url });
+        }
+    }
+
+    fn check_element(&mut self, element: &HtmlPathElement<'_, Handle>) {
+        if element.name.ns != ns!(html) {
+            return;
+        }
+        match element.name.local {
+            local_name!("meta")
+                if element
+                    .attr_local("http-equiv")
+                    .is_some_and(|value| value.eq_ignore_ascii_case("refresh")) =>
+            {
+                let refresh = element.attr_local("content").and_then(|c| parse_refresh(c));
+                if let Some((delay, Some(url))) = refresh {
+                    if let Ok(url) = self.base.url().join(url) {
+                        self.found(Redirect::MetaRefresh { delay, url });
+                    }
+                }
+            }
+            local_name!("link") if element.has_token("rel", "canonical") => {
+                let url = element.attr_url("href", self.base.url());
+                if let Some(url) = url.filter(|url| url.host() != self.document_url.host()) {
+                    self.found(Redirect::OffSiteCanonical { url });
+                }
+            }
+            local_name!("script") if !element.has_attr("src") => {
+                let is_javascript = element.attr_local("type").map_or(true, |t| {
+                    t.is_empty()
+                        || t.eq_ignore_ascii_case("text/javascript")
+                        || t.eq_ignore_ascii_case("module")
+                });
+                if is_javascript {
+                    self.script = Some((element.handle, String::new()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Collects the text of the open script, passing it on with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        self.close_script(context);
+        if let Some((_, script)) = &mut self.script {
+            script.push_str(text);
+        }
+        append_text_to(&mut self.inner, context, text, boundary)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for RedirectDetector<Handle, S> {
+    type Output = (S::Output, Vec<Redirect>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_script(context);
+        self.base.observe(element);
+        self.check_element(element);
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_script(context);
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close_script(context);
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_script(&[]);
+        self.base.reset(self.document_url.clone());
+        self.verdict = RedirectVerdict::default();
+        (self.inner.reset(), std::mem::take(&mut self.redirects))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::ControlFlow;
+
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, parse_document_with_progress};
+
+    #[test]
+    fn refresh_content() {
+        assert_eq!(parse_refresh("0"), Some((0.0, None)));
+        assert_eq!(
+            parse_refresh(" 5; url='/next page'"),
+            Some((5.0, Some("/next page")))
+        );
+        assert_eq!(parse_refresh("1.5,URL = /a"), Some((1.5, Some("/a"))));
+        assert_eq!(parse_refresh("3;/b "), Some((3.0, Some("/b"))));
+        assert_eq!(parse_refresh("soon"), None);
+        assert_eq!(script_location("if (a == location) {}"), None);
+        assert_eq!(
+            script_location("window.location.href = \"/x\";"),
+            Some("/x")
+        );
+        assert_eq!(script_location("location.replace('/y')"), Some("/y"));
+    }
+
+    #[test]
+    fn detect_redirects() {
+        let ((), redirects) = parse_document(
+            RedirectDetector::wrap((), "https://example.com/a/page".parse().unwrap()),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><meta http-equiv="Refresh" content="2; url=moved"><link rel="canonical" href="https://example.com/a/page"><link rel="canonical" href="https://mirror.example.net/page"><script>if (old) { window.location = '/new'; }</script><script src="/app.js"></script></head><body></body></html>"#)
+        .unwrap();
+        let url = |url: &str| url.parse::<Url>().unwrap();
+        assert_eq!(
+            redirects,
+            [
+                Redirect::MetaRefresh {
+                    delay: 2.0,
+                    url: url("https://example.com/a/moved")
+                },
+                Redirect::OffSiteCanonical {
+                    url: url("https://mirror.example.net/page")
+                },
+                Redirect::ScriptLocation {
+                    url: url("https://example.com/new")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cut_parse_short() {
+        let detector = RedirectDetector::wrap((), "https://example.com/".parse().unwrap());
+        let verdict = detector.verdict();
+        let doc = format!(
+            r#"<!DOCTYPE html><html><head><meta http-equiv="refresh" content="0;url=/elsewhere"></head><body>{}</body></html>"#,
+            "<p>paragraph</p>".repeat(2000)
+        );
+        let result =
+            parse_document_with_progress(detector, Default::default(), doc.as_bytes(), 1, |_| {
+                match verdict.get() {
+                    Some(_) => ControlFlow::Break(()),
+                    None => ControlFlow::Continue(()),
+                }
+            });
+        assert!(matches!(result, Err(HtmlStreamError::Cancelled)));
+        assert_eq!(verdict.get().unwrap().url().path(), "/elsewhere");
+    }
+}
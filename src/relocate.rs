@@ -0,0 +1,205 @@
+use html5ever::{local_name, namespace_url, ns};
+
+use crate::{
+    event::{owned_context, replay_into, HtmlEvent, OwnedPathElement},
+    HtmlContext, HtmlPathElement, HtmlSink,
+};
+
+/// Holds back subtrees which a sink is moving to the end of the `<body>`, and forwards them there.
+///
+/// The sink calls [`RelocationBuffer::observe`] before forwarding each event, which forwards the held subtrees once the body ends,
+/// and [`RelocationBuffer::push`]es the events of the subtrees it moves instead of forwarding them. At the end of the document it calls
+/// [`RelocationBuffer::flush`] for the subtrees still held. Only the moved subtrees are held, the rest of the document streams as usual.
+#[derive(Debug)]
+pub struct RelocationBuffer<Handle> {
+    /// The context of the body's children, once the body has been appended
+    body: Option<Vec<OwnedPathElement<Handle>>>,
+    /// The held events, with the depth of the moved subtree's root in their context
+    events: Vec<(usize, HtmlEvent<Handle>)>,
+}
+
+impl<Handle> Default for RelocationBuffer<Handle> {
+    fn default() -> Self {
+        Self {
+            body: None,
+            events: vec![],
+        }
+    }
+}
+
+impl<Handle: Eq + Copy> RelocationBuffer<Handle> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracks the body, and forwards the held subtrees to the sink if the event in the context, of the element if it is one, comes after the body
+    pub fn observe<S: HtmlSink<Handle>>(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: Option<&HtmlPathElement<'_, Handle>>,
+        sink: &mut S,
+    ) {
+        match (&self.body, element) {
+            (Some(body), _) => {
+                let body = body.last().unwrap().handle;
+                if !context.iter().any(|element| element.handle == body) {
+                    self.flush(sink)
+                }
+            }
+            (None, Some(element))
+                if context.len() == 1
+                    && element.name.ns == ns!(html)
+                    && element.tag_is(local_name!("body")) =>
+            {
+                let mut body = owned_context(context);
+                body.push(element.into());
+                self.body = Some(body);
+            }
+            _ => {}
+        }
+    }
+
+    /// Holds an event of a subtree being moved, whose root is at `depth` in the event's context
+    pub fn push(&mut self, depth: usize, event: HtmlEvent<Handle>) {
+        self.events.push((depth, event))
+    }
+
+    /// Forwards the held subtrees to the sink at the end of the body, or in their own places if there has been no body
+    pub fn flush<S: HtmlSink<Handle>>(&mut self, sink: &mut S) {
+        if let Some(body) = &self.body {
+            for (depth, event) in &mut self.events {
+                match event {
+                    HtmlEvent::Element { context, .. }
+                    | HtmlEvent::Text { context, .. }
+                    | HtmlEvent::TextWithBoundary { context, .. }
+                    | HtmlEvent::RawText { context, .. }
+                    | HtmlEvent::Comment { context, .. }
+                    | HtmlEvent::ProcessingInstruction { context, .. } => {
+                        context.splice(..*depth, body.iter().cloned());
+                    }
+                    HtmlEvent::Doctype { .. } => {}
+                }
+            }
+        }
+        replay_into(self.events.iter().map(|(_, event)| event), sink);
+        self.events.clear();
+    }
+
+    /// Drops any held subtrees and starts again for another document
+    pub fn reset(&mut self) {
+        self.body = None;
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::StrTendril};
+
+    use super::*;
+    use crate::{test::stream_docs, HtmlSerializer};
+
+    /// Moves each `<aside>` to the end of the body
+    struct MoveAsides {
+        inner: HtmlSerializer<Vec<u8>, u32>,
+        buffer: RelocationBuffer<u32>,
+        /// The aside being moved, with its depth
+        moving: Option<(u32, usize)>,
+    }
+
+    impl MoveAsides {
+        /// The depth of the aside the event in the context is in, if it is being moved
+        fn moving(&mut self, context: HtmlContext<u32>) -> Option<usize> {
+            let (handle, depth) = self.moving?;
+            if context.get(depth).is_some_and(|e| e.handle == handle) {
+                Some(depth)
+            } else {
+                self.moving = None;
+                None
+            }
+        }
+    }
+
+    impl HtmlSink<u32> for MoveAsides {
+        type Output = Vec<u8>;
+
+        fn append_doctype_to_document(
+            &mut self,
+            name: &StrTendril,
+            public_id: &StrTendril,
+            system_id: &StrTendril,
+        ) {
+            self.inner
+                .append_doctype_to_document(name, public_id, system_id)
+        }
+
+        fn append_element(&mut self, context: HtmlContext<u32>, element: &HtmlPathElement<u32>) {
+            self.buffer.observe(context, Some(element), &mut self.inner);
+            let depth = self.moving(context).or_else(|| {
+                element.tag_is(local_name!("aside")).then(|| {
+                    self.moving = Some((element.handle, context.len()));
+                    context.len()
+                })
+            });
+            match depth {
+                Some(depth) => self.buffer.push(
+                    depth,
+                    HtmlEvent::Element {
+                        context: owned_context(context),
+                        element: element.into(),
+                    },
+                ),
+                None => self.inner.append_element(context, element),
+            }
+        }
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            self.buffer.observe(context, None, &mut self.inner);
+            match self.moving(context) {
+                Some(depth) => self
+                    .buffer
+                    .push(depth, HtmlEvent::text(context, text, None)),
+                None => self.inner.append_text(context, text),
+            }
+        }
+
+        fn append_comment(&mut self, context: HtmlContext<u32>, text: &str) {
+            self.buffer.observe(context, None, &mut self.inner);
+            self.inner.append_comment(context, text)
+        }
+
+        fn reset(&mut self) -> Self::Output {
+            self.moving = None;
+            self.buffer.flush(&mut self.inner);
+            self.buffer.reset();
+            self.inner.reset()
+        }
+    }
+
+    #[test]
+    fn move_to_end_of_body() {
+        let mut sink = MoveAsides {
+            inner: HtmlSerializer::new(Vec::new(), SerializeOpts::default()),
+            buffer: RelocationBuffer::new(),
+            moving: None,
+        };
+        let html = stream_docs(
+            &[
+                "<!DOCTYPE html><html><head></head><body><aside>a<b>1</b></aside><p>x</p><aside>2</aside><div>y</div></body></html><!-- after -->",
+                "<!DOCTYPE html><html><head></head><body><aside>out<aside>in</aside></aside><p>z</p></body></html>",
+                "<!DOCTYPE html><html><head></head><body></body></html>",
+            ],
+            &mut sink,
+        );
+        assert_eq!(
+            html.into_iter()
+                .map(|html| String::from_utf8(html).unwrap())
+                .collect::<Vec<_>>(),
+            [
+                "<!DOCTYPE html><html><head></head><body><p>x</p><div>y</div><aside>a<b>1</b></aside><aside>2</aside></body></html><!-- after -->",
+                "<!DOCTYPE html><html><head></head><body><p>z</p><aside>out<aside>in</aside></aside></body></html>",
+                "<!DOCTYPE html><html><head></head><body></body></html>",
+            ]
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::{borrow::Cow, ops::Deref};
+
+use html5ever::{tendril::StrTendril, Attribute, LocalName, QualName};
+
+use crate::{
+    boundary::append_text_to, raw_text::text_context, selector::ContextualSelector, HtmlContext,
+    HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// An element an [`ElementRewriter`] is rewriting, which can be read as the element, and renamed or have its attributes changed
+pub struct ElementEdit<'e, 'a, Handle> {
+    element: &'e mut HtmlPathElement<'a, Handle>,
+}
+
+impl<Handle> ElementEdit<'_, '_, Handle> {
+    /// Renames the element, keeping its namespace
+    pub fn set_name(&mut self, local: &str) {
+        self.element.name =
+            QualName::new(None, self.element.name.ns.clone(), LocalName::from(local));
+    }
+
+    /// Sets an attribute without a namespace, adding it if it is absent
+    pub fn set_attr(&mut self, local: &str, value: &str) {
+        self.element.set_attr(local, value)
+    }
+
+    /// Removes an attribute without a namespace, returning whether it was present
+    pub fn remove_attr(&mut self, local: &str) -> bool {
+        self.element.remove_attr(local)
+    }
+
+    /// The attributes, to change them in other ways, such as those with a namespace
+    pub fn attrs_mut(&mut self) -> &mut Vec<Attribute> {
+        self.element.attrs.to_mut()
+    }
+}
+
+impl<'a, Handle> Deref for ElementEdit<'_, 'a, Handle> {
+    type Target = HtmlPathElement<'a, Handle>;
+
+    fn deref(&self) -> &Self::Target {
+        self.element
+    }
+}
+
+/// A filter which calls the callback with each element the selector selects, to rename it or change its attributes before the inner sink
+/// sees it.
+///
+/// The descendants of a rewritten element see it rewritten in their context, so the selectors of the inner sinks match against the
+/// new names and attributes. The inherited state of the element and its position are those of the source.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, ElementRewriter, HtmlSerializer};
+///
+/// let html = parse_document(
+///     ElementRewriter::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!("b"), |element| {
+///         element.set_name("strong");
+///         element.remove_attr("style");
+///     }),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><p><b style="color: red">Bold</b></p></body></html>"#)
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     "<!DOCTYPE html><html><head></head><body><p><strong>Bold</strong></p></body></html>"
+/// );
+/// ```
+pub struct ElementRewriter<Handle, S, M, F> {
+    inner: S,
+    matcher: M,
+    rewrite: F,
+    /// The open elements which were rewritten, outermost first, with their depths, the lengths of their contexts
+    rewritten: Vec<(HtmlPathElement<'static, Handle>, usize)>,
+}
+
+impl<Handle, S, M, F> ElementRewriter<Handle, S, M, F>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&mut ElementEdit<'_, '_, Handle>),
+{
+    pub fn wrap(inner: S, matcher: M, rewrite: F) -> Self {
+        Self {
+            inner,
+            matcher,
+            rewrite,
+            rewritten: vec![],
+        }
+    }
+
+    /// The context with the open rewritten elements in place of those from the source
+    fn context<'c>(
+        &mut self,
+        context: HtmlContext<'c, Handle>,
+    ) -> Cow<'c, [HtmlPathElement<'c, Handle>]> {
+        while let Some((last, depth)) = self.rewritten.last() {
+            if context
+                .get(*depth)
+                .is_some_and(|element| element.handle == last.handle)
+            {
+                break;
+            }
+            self.rewritten.pop();
+        }
+        if self.rewritten.is_empty() {
+            return Cow::Borrowed(context);
+        }
+        let mut rewritten_context = context.to_vec();
+        for (rewritten, depth) in &self.rewritten {
+            rewritten_context[*depth] = rewritten.clone();
+        }
+        Cow::Owned(rewritten_context)
+    }
+}
+
+impl<Handle, S, M, F> HtmlSink<Handle> for ElementRewriter<Handle, S, M, F>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&mut ElementEdit<'_, '_, Handle>),
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let context = self.context(context);
+        if !self.matcher.context_match(&context, element) {
+            return self.inner.append_element(&context, element);
+        }
+        let mut rewritten = element.clone();
+        (self.rewrite)(&mut ElementEdit {
+            element: &mut rewritten,
+        });
+        if rewritten.name == element.name && rewritten.attrs == element.attrs {
+            return self.inner.append_element(&context, element);
+        }
+        self.inner.append_element(&context, &rewritten);
+        self.rewritten.push((rewritten.into_owned(), context.len()));
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let context = self.context(context);
+        self.inner.append_text(&context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        let context = self.context(context);
+        self.inner
+            .append_text_with_boundary(&context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        let text_context = text_context(context, element);
+        let context = self.context(&text_context);
+        let (element, context) = context.split_last().unwrap();
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let context = self.context(context);
+        self.inner.append_comment(&context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        let context = self.context(context);
+        self.inner
+            .append_processing_instruction(&context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.rewritten.clear();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+/// A filter which passes the text directly inside the elements the selector selects through the callback, such as to uppercase the
+/// text in `.shout`, or to censor words everywhere with `css_select!(*)`.
+///
+/// As with the [`crate::text!`] handlers, the selector is matched against the parent of the text, so the text of descendants is
+/// rewritten by also selecting them, as `css_select!(."shout", (."shout") *)` does. Selecting every element includes the contents of
+/// `<script>` and `<style>`, which `css_select!(:not("script"))` leaves out. html5ever can split a text node into many events, so a
+/// callback which looks for words across them should be behind a [`crate::TextCoalescer`].
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, HtmlSerializer, TextRewriter};
+///
+/// let html = parse_document(
+///     TextRewriter::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!(."shout"), str::to_uppercase),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><p class="shout">Hey you</p><p>quiet</p></body></html>"#)
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     r#"<!DOCTYPE html><html><head></head><body><p class="shout">HEY YOU</p><p>quiet</p></body></html>"#
+/// );
+/// ```
+pub struct TextRewriter<S, M, F> {
+    inner: S,
+    matcher: M,
+    rewrite: F,
+}
+
+impl<S, M: ContextualSelector, F: FnMut(&str) -> String> TextRewriter<S, M, F> {
+    pub fn wrap(inner: S, matcher: M, rewrite: F) -> Self {
+        Self {
+            inner,
+            matcher,
+            rewrite,
+        }
+    }
+
+    /// Rewrites the text of the selected elements, keeping its boundary if it has one
+    fn text<Handle: Eq + Copy>(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) where
+        S: HtmlSink<Handle>,
+    {
+        let selected = context
+            .split_last()
+            .is_some_and(|(parent, context)| self.matcher.context_match(context, parent));
+        if selected {
+            append_text_to(&mut self.inner, context, &(self.rewrite)(text), boundary)
+        } else {
+            append_text_to(&mut self.inner, context, text, boundary)
+        }
+    }
+}
+
+impl<Handle, S, M, F> HtmlSink<Handle> for TextRewriter<S, M, F>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&str) -> String,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.matcher.context_match(context, element) {
+            self.inner
+                .append_raw_text(context, element, &(self.rewrite)(text))
+        } else {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer, SelectToStrings};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head></head><body><div class="note" style="x"><b>Note</b> <a href="/a">a</a></div><a href="https://example.com" target="_blank">b</a></body></html>"#;
+
+    #[test]
+    fn rewrite_elements() {
+        let html = parse_document(
+            ElementRewriter::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("div", "a"["target"]),
+                |element| {
+                    if element.name.local == *"div" {
+                        element.set_name("aside");
+                        element.remove_attr("style");
+                    } else {
+                        if element.attr_local("rel").is_none() {
+                            element.set_attr("rel", "noopener");
+                        }
+                        element
+                            .attrs_mut()
+                            .retain(|attr| &*attr.name.local != "target");
+                    }
+                },
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><aside class="note"><b>Note</b> <a href="/a">a</a></aside><a href="https://example.com" rel="noopener">b</a></body></html>"#
+        );
+    }
+
+    #[test]
+    fn rewritten_elements_in_context() {
+        let selected = parse_document(
+            ElementRewriter::wrap(
+                SelectToStrings::new(css_select!("aside"."note" > "a")),
+                css_select!("div"),
+                |element| element.set_name("aside"),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(selected, [r#"<a href="/a">a</a>"#]);
+    }
+
+    #[test]
+    fn rewrite_text() {
+        let html = parse_document(
+            TextRewriter::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!(:not("script")),
+                |text| text.replace("darn", "****"),
+            ),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><title>darn</title><script>var darn;</script></head><body>darn <p>a darn <b>darn</b></p><!-- darn --></body></html>"#)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            r#"<!DOCTYPE html><html><head><title>****</title><script>var darn;</script></head><body>**** <p>a **** <b>****</b></p><!-- darn --></body></html>"#
+        );
+
+        let html = parse_document(
+            TextRewriter::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!(."shout", (."shout") *),
+                str::to_uppercase,
+            ),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><p class="shout">Hey <b>you</b></p><p>quiet</p></body></html>"#)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><p class="shout">HEY <b>YOU</b></p><p>quiet</p></body></html>"#
+        );
+    }
+}
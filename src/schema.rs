@@ -0,0 +1,437 @@
+use std::collections::BTreeMap;
+
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// What a [`Violation`] found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ViolationKind {
+    /// An element which the schema doesn't list
+    UnknownElement,
+    /// An attribute which isn't allowed on its element
+    UnknownAttribute,
+    /// A required attribute is absent
+    MissingAttribute,
+    /// An element which isn't allowed in its parent
+    DisallowedChild,
+    /// An element nested where html's content model doesn't allow it, found by a [`crate::ContentModelChecker`]
+    ContentModel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The rules for one element of a [`Schema`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ElementRule {
+    /// The attributes allowed on the element besides the schema's global attributes
+    pub attributes: Vec<String>,
+    /// The attributes the element must have, these are allowed without being listed in `attributes`
+    pub required_attributes: Vec<String>,
+    /// The elements allowed as children, any element of the schema is allowed when `None`
+    pub children: Option<Vec<String>>,
+}
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| (*name).to_owned()).collect()
+}
+
+impl ElementRule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attributes(self, attributes: &[&str]) -> Self {
+        Self {
+            attributes: strings(attributes),
+            ..self
+        }
+    }
+
+    pub fn required_attributes(self, required_attributes: &[&str]) -> Self {
+        Self {
+            required_attributes: strings(required_attributes),
+            ..self
+        }
+    }
+
+    pub fn children(self, children: &[&str]) -> Self {
+        Self {
+            children: Some(strings(children)),
+            ..self
+        }
+    }
+}
+
+/// A declarative description of the markup a [`SchemaValidator`] accepts, element and attribute names are local names in lowercase.
+///
+/// With the `serde` feature it can be loaded from configuration, absent fields take their defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Schema {
+    /// The allowed elements
+    pub elements: BTreeMap<String, ElementRule>,
+    /// The attributes allowed on every element
+    pub global_attributes: Vec<String>,
+    /// The prefixes of attributes allowed on every element, such as `data-` and `aria-`
+    pub global_attribute_prefixes: Vec<String>,
+    /// The severity of each kind of violation, those not listed are errors
+    pub severities: BTreeMap<ViolationKind, Severity>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn element(mut self, name: &str, rule: ElementRule) -> Self {
+        self.elements.insert(name.to_owned(), rule);
+        self
+    }
+
+    pub fn global_attributes(self, global_attributes: &[&str]) -> Self {
+        Self {
+            global_attributes: strings(global_attributes),
+            ..self
+        }
+    }
+
+    pub fn global_attribute_prefixes(self, global_attribute_prefixes: &[&str]) -> Self {
+        Self {
+            global_attribute_prefixes: strings(global_attribute_prefixes),
+            ..self
+        }
+    }
+
+    pub fn severity(mut self, kind: ViolationKind, severity: Severity) -> Self {
+        self.severities.insert(kind, severity);
+        self
+    }
+
+    fn severity_of(&self, kind: ViolationKind) -> Severity {
+        self.severities
+            .get(&kind)
+            .copied()
+            .unwrap_or(Severity::Error)
+    }
+
+    fn allows_attribute(&self, rule: &ElementRule, attribute: &str) -> bool {
+        rule.attributes
+            .iter()
+            .chain(&rule.required_attributes)
+            .chain(&self.global_attributes)
+            .any(|allowed| allowed == attribute)
+            || self
+                .global_attribute_prefixes
+                .iter()
+                .any(|prefix| attribute.starts_with(prefix.as_str()))
+    }
+}
+
+/// A breach of a [`Schema`], as reported by a [`SchemaValidator`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub severity: Severity,
+    /// The css path of the element, as described by [`CssPathTracker`]
+    pub path: String,
+    pub message: String,
+}
+
+/// A wrapper which checks each element against a [`Schema`] and outputs the violations along with the inner sink's output.
+///
+/// Content is forwarded unchanged, use [`SchemaValidator::new`] to only validate.
+/// The children of an element the schema doesn't list aren't checked against a parent rule.
+pub struct SchemaValidator<S> {
+    inner: S,
+    schema: Schema,
+    paths: CssPathTracker,
+    violations: Vec<Violation>,
+}
+
+impl SchemaValidator<()> {
+    /// A validator which doesn't forward the content anywhere
+    pub fn new(schema: Schema) -> Self {
+        Self::wrap((), schema)
+    }
+}
+
+impl<S> SchemaValidator<S> {
+    pub fn wrap(inner: S, schema: Schema) -> Self {
+        Self {
+            inner,
+            schema,
+            paths: CssPathTracker::new(),
+            violations: vec![],
+        }
+    }
+
+    fn validate<Handle>(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let schema = &self.schema;
+        let name = &*element.name.local;
+        let mut found = vec![];
+        if let Some(parent) = context.last() {
+            let parent_rule = schema.elements.get(&*parent.name.local);
+            if let Some(children) = parent_rule.and_then(|rule| rule.children.as_ref()) {
+                if !children.iter().any(|child| child == name) {
+                    found.push((
+                        ViolationKind::DisallowedChild,
+                        format!("<{}> is not allowed in <{}>", name, parent.name.local),
+                    ));
+                }
+            }
+        }
+        match schema.elements.get(name) {
+            None => found.push((
+                ViolationKind::UnknownElement,
+                format!("<{}> is not allowed", name),
+            )),
+            Some(rule) => {
+                for attr in element.attrs.iter() {
+                    let attribute = &*attr.name.local;
+                    if !schema.allows_attribute(rule, attribute) {
+                        found.push((
+                            ViolationKind::UnknownAttribute,
+                            format!("attribute {} is not allowed on <{}>", attribute, name),
+                        ));
+                    }
+                }
+                for required in &rule.required_attributes {
+                    if !element.has_attr(required) {
+                        found.push((
+                            ViolationKind::MissingAttribute,
+                            format!("<{}> is missing attribute {}", name, required),
+                        ));
+                    }
+                }
+            }
+        }
+        if found.is_empty() {
+            return;
+        }
+        let path = self.paths.element_path(context, element);
+        for (kind, message) in found {
+            self.violations.push(Violation {
+                kind,
+                severity: self.schema.severity_of(kind),
+                path: path.clone(),
+                message,
+            });
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for SchemaValidator<S> {
+    type Output = (S::Output, Vec<Violation>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.validate(context, element);
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        (self.inner.reset(), std::mem::take(&mut self.violations))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, test::stream_docs};
+
+    #[test]
+    fn validate_against_schema() {
+        let schema = Schema::new()
+            .global_attributes(&["id", "class"])
+            .global_attribute_prefixes(&["data-"])
+            .element("html", ElementRule::new().children(&["head", "body"]))
+            .element("head", ElementRule::new())
+            .element("body", ElementRule::new())
+            .element("p", ElementRule::new())
+            .element("ul", ElementRule::new().children(&["li"]))
+            .element("li", ElementRule::new())
+            .element(
+                "img",
+                ElementRule::new().required_attributes(&["alt", "src"]),
+            )
+            .severity(ViolationKind::UnknownAttribute, Severity::Warning);
+        let ((), violations) = parse_document(SchemaValidator::new(schema), Default::default())
+            .one(r#"<!DOCTYPE html><html><head></head><body><p class="a" data-x="1" onclick="go()">text</p><ul><li>a</li><p>b</p></ul><img src="x.png"><blink>!</blink></body></html>"#)
+            .unwrap();
+        let found = |kind, severity, path: &str, message: &str| Violation {
+            kind,
+            severity,
+            path: path.to_owned(),
+            message: message.to_owned(),
+        };
+        assert_eq!(
+            violations,
+            [
+                found(
+                    ViolationKind::UnknownAttribute,
+                    Severity::Warning,
+                    "html > body > p.a:nth-child(1)",
+                    "attribute onclick is not allowed on <p>"
+                ),
+                found(
+                    ViolationKind::DisallowedChild,
+                    Severity::Error,
+                    "html > body > ul:nth-child(2) > p:nth-child(2)",
+                    "<p> is not allowed in <ul>"
+                ),
+                found(
+                    ViolationKind::MissingAttribute,
+                    Severity::Error,
+                    "html > body > img:nth-child(3)",
+                    "<img> is missing attribute alt"
+                ),
+                found(
+                    ViolationKind::UnknownElement,
+                    Severity::Error,
+                    "html > body > blink:nth-child(4)",
+                    "<blink> is not allowed"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_each_document() {
+        let schema = Schema::new()
+            .element("html", ElementRule::new())
+            .element("head", ElementRule::new())
+            .element("body", ElementRule::new())
+            .element("ul", ElementRule::new().children(&["li"]))
+            .element("li", ElementRule::new());
+        let mut validator = SchemaValidator::new(schema);
+        let found = stream_docs(
+            &[
+                "<!DOCTYPE html>",
+                "<!DOCTYPE html><html><head></head><body><blink><blink>!</blink></blink><ul><li><ul><p>x</p></ul></li></ul></body></html>",
+                "<!DOCTYPE html><html><head></head><body><ul><li>a</li></ul><blink></blink></body></html>",
+            ],
+            &mut validator,
+        )
+        .into_iter()
+        .map(|((), violations)| {
+            violations
+                .into_iter()
+                .map(|violation| (violation.kind, violation.path))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+        let nested_p =
+            "html > body > ul:nth-child(2) > li:nth-child(1) > ul:nth-child(1) > p:nth-child(1)";
+        assert_eq!(
+            found,
+            [
+                vec![],
+                vec![
+                    (
+                        ViolationKind::UnknownElement,
+                        "html > body > blink:nth-child(1)".to_owned()
+                    ),
+                    (
+                        ViolationKind::UnknownElement,
+                        "html > body > blink:nth-child(1) > blink:nth-child(1)".to_owned()
+                    ),
+                    (ViolationKind::DisallowedChild, nested_p.to_owned()),
+                    (ViolationKind::UnknownElement, nested_p.to_owned()),
+                ],
+                vec![(
+                    ViolationKind::UnknownElement,
+                    "html > body > blink:nth-child(2)".to_owned()
+                )],
+            ]
+        );
+    }
+}
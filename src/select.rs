@@ -0,0 +1,145 @@
+use std::fmt;
+
+use html5ever::{serialize::SerializeOpts, tendril::StrTendril};
+
+use crate::{
+    selector::ContextualSelector, HtmlContext, HtmlPathElement, HtmlSerializer, HtmlSink,
+    HtmlStreamError, RootFilter, TextBoundary,
+};
+
+/// The serializer of each match, outputting its bytes
+type MatchSerializer<Handle> = HtmlSerializer<Vec<u8>, Handle>;
+
+/// A sink which outputs the html of each subtree the selector matches, a [`RootFilter`] around an owned [`HtmlSerializer`].
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, SelectToStrings};
+///
+/// let paragraphs = parse_document(SelectToStrings::new(css_select!("p")), Default::default())
+///     .one("<!DOCTYPE html><html><head></head><body><p>One</p><div><p>Two</p></div></body></html>")
+///     .unwrap();
+/// assert_eq!(paragraphs, ["<p>One</p>", "<p>Two</p>"]);
+/// ```
+pub struct SelectToStrings<Handle: Eq + Copy + fmt::Display, M: ContextualSelector> {
+    inner: RootFilter<Handle, MatchSerializer<Handle>, M, Vec<Vec<u8>>>,
+}
+
+impl<Handle: Eq + Copy + fmt::Display, M: ContextualSelector> SelectToStrings<Handle, M> {
+    pub fn new(matcher: M) -> Self {
+        Self::with_opts(matcher, SerializeOpts::default())
+    }
+
+    /// Serializes the matches with the options
+    pub fn with_opts(matcher: M, opts: SerializeOpts) -> Self {
+        Self {
+            inner: RootFilter::wrap(HtmlSerializer::new(Vec::new(), opts), matcher),
+        }
+    }
+}
+
+impl<Handle, M> HtmlSink<Handle> for SelectToStrings<Handle, M>
+where
+    Handle: Eq + Copy + fmt::Display,
+    M: ContextualSelector,
+{
+    type Output = Vec<String>;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner
+            .reset()
+            .into_iter()
+            // the serializer only writes utf-8
+            .map(|html| String::from_utf8(html).unwrap())
+            .collect()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{css_select, test::stream_docs};
+
+    #[test]
+    fn select_to_strings() {
+        let mut sink = SelectToStrings::new(css_select!("div"));
+        let selected = stream_docs(
+            &[
+                r#"<!DOCTYPE html><html><head></head><body><div>One <div class="inner">nested</div></div><p>no</p><div>Tom &amp; Jerry<!-- c --></div></body></html>"#,
+                "<!DOCTYPE html>",
+                "<!DOCTYPE html><html><head></head><body><div>Two</div></body></html>",
+            ],
+            &mut sink,
+        );
+        assert_eq!(
+            selected,
+            [
+                vec![
+                    r#"<div>One <div class="inner">nested</div></div>"#.to_owned(),
+                    "<div>Tom &amp; Jerry<!-- c --></div>".to_owned(),
+                ],
+                vec![],
+                vec!["<div>Two</div>".to_owned()],
+            ]
+        );
+    }
+}
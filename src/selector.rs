@@ -1,7 +1,25 @@
+use std::{borrow::Cow, error::Error, fmt};
+
 use html5ever::{tendril::StrTendril, *};
 
 use crate::{HtmlContext, HtmlPathElement};
 
+mod boxed;
+mod closure;
+mod css;
+mod nth;
+mod rules;
+#[cfg(feature = "selectors")]
+mod servo;
+
+pub use boxed::*;
+pub use closure::*;
+pub use css::*;
+pub use nth::*;
+pub use rules::*;
+#[cfg(feature = "selectors")]
+pub use servo::*;
+
 /// Selects elements using a syntax similar to css 1 selectors, supporting css 1 selectors except pseudo-elements and pseudo classes
 ///
 /// ```
@@ -12,15 +30,77 @@ use crate::{HtmlContext, HtmlPathElement};
 /// css_select!(."quote");
 /// css_select!(#"main");
 /// css_select!((#"main") ("p"."quote"));
+/// css_select!("div"#"main" "p"."quote");
+/// css_select!((#"main") "p"."quote");
+/// css_select!("ul" > "li");
+/// css_select!((#"main") > "ul" > "li"."active");
+/// css_select!("h2" + "p");
+/// css_select!("div" > "h2" ~ "p"."note" "a");
+/// css_select!(["href"]);
+/// css_select!("a"["href"]);
+/// css_select!(["data-kind" = "note"]);
+/// css_select!("aside"["data-kind" = "note"]);
+/// css_select!("a"["href" ^= "https://"]);
+/// css_select!(["class" ~= "warning"]["src" $= ".png"]["title" *= "draft"]["lang" |= "en"]);
+/// css_select!("input"["type" = "text" i]);
+/// css_select!(:scope);
+/// css_select!((:scope) ("p"));
+/// css_select!("li":nth-child("2n+1"));
+/// css_select!("ul" > "li":nth-of-type("odd") "a");
+/// css_select!("li":first-child);
+/// css_select!("tr":last-of-type "td":last-child);
+/// css_select!("p":empty);
+/// css_select!("li":text-contains("sponsored"));
+/// css_select!("p":not(."keep"));
+/// css_select!(:not("a"["href"]));
+/// css_select!("h1", "h2", ."title");
+/// css_select!("svg" | "a"["href"]);
+/// css_select!(*);
+/// css_select!("ul" > * > "a");
+/// css_select!(*["data-" *]);
+/// css_select!("svg" | * > *:first-child);
+/// css_select!("html" | "a" > "svg" | "svg");
 /// ```
+///
+/// Selectors separated by whitespace are combined with the descendant combinator, as are parenthesised selectors, and those separated by
+/// `>` with the child combinator. Macros can't see whitespace, so each name starts a selector, and a selector without a name needs
+/// parentheses to be separate from the one before.
+///
+/// The sibling combinators `+` and `~` compare against the [`HtmlPathElement::previous_siblings`] of the source, so they don't select
+/// elements which a filter added, and they need a name or other selector after them rather than a parenthesised one. The producer only
+/// keeps the previous siblings when asked to with [`crate::ParseConfig::sibling_tracking`], the adjacent one for `+` and all of them for
+/// `~`, otherwise they select nothing.
+///
+/// `:nth-child` and `:nth-of-type` take their `an+b` argument as a string, which is checked as the macro compiles, and compare against
+/// the [`crate::SiblingPosition`] of the source, so they don't select elements which a filter added, as do `:first-child` and
+/// `:first-of-type`. `:last-child` and `:last-of-type` depend on the siblings after the element, so they only select anything behind a
+/// [`crate::LookaheadBuffer`]. `:empty` and `:text-contains("...")` depend on the content of the element, so they only select anything
+/// behind a [`crate::ContentBuffer`].
+///
+/// `*` selects any element, such as every element at a depth between combinators, and `["data-" *]` any element with an attribute
+/// whose name starts with `data-`, see [`AttrPrefixSelector`].
+///
+/// A name can have a namespace prefix, `html`, `svg`, `mathml`, or `""` for no namespace, as in `"svg" | "a"`, to tell elements of the
+/// same name in different namespaces apart; a name without a prefix selects the name in any namespace, see [`NamespaceSelector`].
+///
+/// Selectors separated by commas make a list, an [`AnyOf`] which selects what any of them selects, of up to 12 selectors.
+///
+/// `:scope` is the root of the contexts the selector sees, the document's root element, or under a [`crate::RootFilter`] the selected
+/// element, see [`Scope`].
 #[macro_export]
 macro_rules! css_select {
     (@inner [($($head:tt)+)] -> [$selector:expr]) => {
         ($crate::selector::ContextualSelectCons($selector , css_select!($($head)+)))
     };
+    (@inner [($($head:tt)+) > $($tail:tt)*] -> [$selector:expr]) => {
+        css_select!(@inner [$($tail)*] -> [$crate::selector::ParentSelectCons($selector , css_select!($($head)+))])
+    };
     (@inner [($($head:tt)+) $($tail:tt)*] -> [$selector:expr]) => {
         css_select!(@inner [$($tail)*] -> [$crate::selector::ContextSelectCons($selector , css_select!($($head)+))])
     };
+    ((:scope) $(($($selectors:tt)+))+) => {
+        css_select!(@inner [$(($($selectors)+))+] -> [$crate::selector::Scope])
+    };
     ($(($($selectors:tt)+))+) => {
         css_select!(@inner [$(($($selectors)+))+] -> [$crate::selector::MatchAll])
     };
@@ -36,17 +116,336 @@ macro_rules! css_select {
             $crate::selector::IdSelector($id),
         )
     };
+    ($name:literal[$($attr:tt)+]) => {
+        $crate::selector::Selector::and(
+            $crate::selector::NameSelector($name),
+            css_select!(@attr $($attr)+),
+        )
+    };
     ($name:literal) => {
         $crate::selector::NameSelector($name)
     };
+    ([$($attr:tt)+]) => {
+        css_select!(@attr $($attr)+)
+    };
     (.$class:literal) => {
         $crate::selector::ClassSelector($class)
     };
     (#$id:literal) => {
         $crate::selector::IdSelector($id)
     };
+    (:scope) => {
+        $crate::selector::Scope
+    };
+    (*) => {
+        $crate::selector::MatchAll
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] . $class:literal $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* . $class] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] # $id:literal $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* # $id] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] [$($attr:tt)+] $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* [$($attr)+]] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] ($($group:tt)+) $($rest:tt)*) => {
+        css_select!(@chain [] [$($ancestors)* ($($group)+)] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] ($($group:tt)+) $($rest:tt)*) => {
+        css_select!(@chain [] [$($ancestors)* ($($compound)+) ($($group)+)] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] > $($rest:tt)*) => {
+        css_select!(@chain [] [$($ancestors)* >] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] > $($rest:tt)*) => {
+        css_select!(@chain [] [$($ancestors)* ($($compound)+) >] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] + $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@chain [$($compound)+] [$($ancestors)*])] [+] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] ~ $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@chain [$($compound)+] [$($ancestors)*])] [~] $($rest)*)
+    };
+    (@chain [] [] : scope $($rest:tt)*) => {
+        css_select!(@chain [] [(:scope)] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] : $($pseudo:ident)-+ ($($args:tt)*) $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* : $($pseudo)-+ ($($args)*)] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] : $a:ident - $b:ident - $c:ident $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* : $a - $b - $c] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] : $a:ident - $b:ident $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* : $a - $b] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)*] [$($ancestors:tt)*] : $a:ident $($rest:tt)*) => {
+        css_select!(@chain [$($compound)* : $a] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] $prefix:literal | * $($rest:tt)*) => {
+        css_select!(@chain [$prefix | *] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] $prefix:literal | * $($rest:tt)*) => {
+        css_select!(@chain [$prefix | *] [$($ancestors)* ($($compound)+)] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] * $($rest:tt)*) => {
+        css_select!(@chain [*] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] * $($rest:tt)*) => {
+        css_select!(@chain [*] [$($ancestors)* ($($compound)+)] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] $prefix:literal | $name:literal $($rest:tt)*) => {
+        css_select!(@chain [$prefix | $name] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] $prefix:literal | $name:literal $($rest:tt)*) => {
+        css_select!(@chain [$prefix | $name] [$($ancestors)* ($($compound)+)] $($rest)*)
+    };
+    (@chain [] [$($ancestors:tt)*] $name:literal $($rest:tt)*) => {
+        css_select!(@chain [$name] [$($ancestors)*] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)*] $name:literal $($rest:tt)*) => {
+        css_select!(@chain [$name] [$($ancestors)* ($($compound)+)] $($rest)*)
+    };
+    (@chain [$($compound:tt)+] [$($ancestors:tt)+]) => {
+        css_select!(@chain [] [$($ancestors)+ ($($compound)+)])
+    };
+    (@chain [] [(:scope) $($ancestors:tt)+]) => {
+        css_select!(@inner [$($ancestors)+] -> [$crate::selector::Scope])
+    };
+    (@chain [] [$($ancestors:tt)+]) => {
+        css_select!(@inner [$($ancestors)+] -> [$crate::selector::MatchAll])
+    };
+    (@chain [$($compound:tt)+] []) => {
+        css_select!(@compound [$crate::selector::MatchAll] $($compound)+)
+    };
+    (@chain $($unsupported:tt)*) => {
+        compile_error!("unsupported selector")
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] . $class:literal $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ . $class] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] # $id:literal $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ # $id] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] [$($attr:tt)+] $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ [$($attr)+]] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] : $($pseudo:ident)-+ ($($args:tt)*) $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ : $($pseudo)-+ ($($args)*)] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] : $a:ident - $b:ident - $c:ident $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ : $a - $b - $c] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] : $a:ident - $b:ident $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ : $a - $b] $($rest)*)
+    };
+    (@fold [$selector:expr] [$($pending:tt)+] : $a:ident $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$($pending)+ : $a] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt] $prefix:literal | * $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$combinator $prefix | *] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+] $prefix:literal | * $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@combine $combinator [$selector] $($compound)+)] [descendant $prefix | *] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt] * $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$combinator *] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+] * $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@combine $combinator [$selector] $($compound)+)] [descendant *] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt] $prefix:literal | $name:literal $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$combinator $prefix | $name] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+] $prefix:literal | $name:literal $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@combine $combinator [$selector] $($compound)+)] [descendant $prefix | $name] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt] $name:literal $($rest:tt)*) => {
+        css_select!(@fold [$selector] [$combinator $name] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+] $name:literal $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@combine $combinator [$selector] $($compound)+)] [descendant $name] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+] $next:tt $($rest:tt)*) => {
+        css_select!(@fold [css_select!(@combine $combinator [$selector] $($compound)+)] [$next] $($rest)*)
+    };
+    (@fold [$selector:expr] [$combinator:tt $($compound:tt)+]) => {
+        css_select!(@combine $combinator [$selector] $($compound)+)
+    };
+    (@fold $($unsupported:tt)*) => {
+        compile_error!("unsupported selector")
+    };
+    (@combine descendant [$selector:expr] $($compound:tt)+) => {
+        $crate::selector::ContextualSelector::descendant_of(css_select!(@compound [$crate::selector::MatchAll] $($compound)+), $selector)
+    };
+    (@combine > [$selector:expr] $($compound:tt)+) => {
+        $crate::selector::ContextualSelector::child_of(css_select!(@compound [$crate::selector::MatchAll] $($compound)+), $selector)
+    };
+    (@combine + [$selector:expr] $($compound:tt)+) => {
+        $crate::selector::ContextualSelector::adjacent_sibling_of(css_select!(@compound [$crate::selector::MatchAll] $($compound)+), $selector)
+    };
+    (@combine ~ [$selector:expr] $($compound:tt)+) => {
+        $crate::selector::ContextualSelector::general_sibling_of(css_select!(@compound [$crate::selector::MatchAll] $($compound)+), $selector)
+    };
+    (@combine $($unsupported:tt)*) => {
+        compile_error!("unsupported selector")
+    };
+    (@compound [$selector:expr] $prefix:literal | * $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, css_select!(@namespace $prefix))] $($rest)*)
+    };
+    (@compound [$selector:expr] * $($rest:tt)*) => {
+        css_select!(@compound [$selector] $($rest)*)
+    };
+    (@compound [$selector:expr] $prefix:literal | $name:literal $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($crate::selector::Selector::and($selector, css_select!(@namespace $prefix)), $crate::selector::NameSelector($name))] $($rest)*)
+    };
+    (@compound [$selector:expr] $name:literal $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::NameSelector($name))] $($rest)*)
+    };
+    (@compound [$selector:expr] . $class:literal $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::ClassSelector($class))] $($rest)*)
+    };
+    (@compound [$selector:expr] # $id:literal $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::IdSelector($id))] $($rest)*)
+    };
+    (@compound [$selector:expr] [$($attr:tt)+] $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, css_select!(@attr $($attr)+))] $($rest)*)
+    };
+    (@compound [$selector:expr] : nth-child($nth:literal) $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::NthChildSelector(css_select!(@nth $nth)))] $($rest)*)
+    };
+    (@compound [$selector:expr] : nth-of-type($nth:literal) $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::NthOfTypeSelector(css_select!(@nth $nth)))] $($rest)*)
+    };
+    (@compound [$selector:expr] : not($($negated:tt)+) $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::Not(css_select!(@compound [$crate::selector::MatchAll] $($negated)+)))] $($rest)*)
+    };
+    (@compound [$selector:expr] : first-child $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::FirstChildSelector)] $($rest)*)
+    };
+    (@compound [$selector:expr] : first-of-type $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::FirstOfTypeSelector)] $($rest)*)
+    };
+    (@compound [$selector:expr] : last-child $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::LastChildSelector)] $($rest)*)
+    };
+    (@compound [$selector:expr] : last-of-type $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::LastOfTypeSelector)] $($rest)*)
+    };
+    (@compound [$selector:expr] : empty $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::EmptySelector)] $($rest)*)
+    };
+    (@compound [$selector:expr] : text-contains($text:literal) $($rest:tt)*) => {
+        css_select!(@compound [$crate::selector::Selector::and($selector, $crate::selector::TextContainsSelector($text))] $($rest)*)
+    };
+    (@compound [$selector:expr]) => {
+        $selector
+    };
+    (@compound $($unsupported:tt)*) => {
+        compile_error!("unsupported selector")
+    };
+    (@attr $attr:literal = $value:literal i) => {
+        css_select!(@attr $attr = $value).ignore_case()
+    };
+    (@attr $attr:literal ^= $value:literal i) => {
+        css_select!(@attr $attr ^= $value).ignore_case()
+    };
+    (@attr $attr:literal *= $value:literal i) => {
+        css_select!(@attr $attr *= $value).ignore_case()
+    };
+    (@attr $attr:literal |= $value:literal i) => {
+        css_select!(@attr $attr |= $value).ignore_case()
+    };
+    (@attr $attr:literal ~ = $value:literal i) => {
+        css_select!(@attr $attr ~ = $value).ignore_case()
+    };
+    (@attr $attr:literal $dollar:tt = $value:literal i) => {
+        css_select!(@attr $attr $dollar = $value).ignore_case()
+    };
+    (@attr $attr:literal) => {
+        $crate::selector::AttrSelector($attr)
+    };
+    (@attr $prefix:literal *) => {
+        $crate::selector::AttrPrefixSelector($prefix)
+    };
+    (@attr $attr:literal = $value:literal) => {
+        $crate::selector::AttrValueSelector($attr, $value)
+    };
+    (@attr $attr:literal ^= $value:literal) => {
+        $crate::selector::AttrOperatorSelector($attr, $crate::selector::AttrOperator::Prefix, $value)
+    };
+    (@attr $attr:literal *= $value:literal) => {
+        $crate::selector::AttrOperatorSelector($attr, $crate::selector::AttrOperator::Substring, $value)
+    };
+    (@attr $attr:literal |= $value:literal) => {
+        $crate::selector::AttrOperatorSelector($attr, $crate::selector::AttrOperator::DashMatch, $value)
+    };
+    (@attr $attr:literal ~ = $value:literal) => {
+        $crate::selector::AttrOperatorSelector($attr, $crate::selector::AttrOperator::Word, $value)
+    };
+    // a macro can't match a `$`, so this is `$=` as the other operators have been matched
+    (@attr $attr:literal $dollar:tt = $value:literal) => {
+        $crate::selector::AttrOperatorSelector($attr, $crate::selector::AttrOperator::Suffix, $value)
+    };
+    (@attr $($unsupported:tt)*) => {
+        compile_error!("unsupported attribute selector")
+    };
+    (@nth $nth:literal) => {{
+        const NTH: $crate::selector::Nth = match $crate::selector::Nth::parse($nth) {
+            Some(nth) => nth,
+            None => panic!("expected an+b, odd or even"),
+        };
+        NTH
+    }};
+    (@namespace $prefix:literal) => {{
+        const NAMESPACE: $crate::selector::NamespaceSelector = match $crate::selector::NamespaceSelector::prefix($prefix) {
+            Some(namespace) => namespace,
+            None => panic!("unknown namespace prefix"),
+        };
+        NAMESPACE
+    }};
+    (@list [$($branches:tt)*] [$($branch:tt)+] , $($rest:tt)+) => {
+        css_select!(@list [$($branches)* ($($branch)+)] [] $($rest)+)
+    };
+    (@list [$($branches:tt)*] [$($branch:tt)*] $next:tt $($rest:tt)*) => {
+        css_select!(@list [$($branches)*] [$($branch)* $next] $($rest)*)
+    };
+    (@list [] [$($branch:tt)+]) => {
+        css_select!(@chain [] [] $($branch)+)
+    };
+    (@list [$($branches:tt)+] [$($branch:tt)+]) => {
+        $crate::selector::AnyOf(($(css_select! $branches,)+ css_select!($($branch)+),))
+    };
+    ($($selector:tt)+) => {
+        css_select!(@list [] [] $($selector)+)
+    };
+}
+
+/// An error in the syntax of a selector expression parsed at runtime
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorParseError {
+    /// The byte offset in the expression where the error was found
+    pub position: usize,
+    pub message: Cow<'static, str>,
 }
 
+impl SelectorParseError {
+    pub(crate) fn new(position: usize, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SelectorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+impl Error for SelectorParseError {}
+
 pub trait Selector {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool;
 
@@ -56,6 +455,11 @@ pub trait Selector {
     {
         AndSelector(self, other)
     }
+
+    /// An id, class or name which every element this selects has, so that a [`RuleSet`] only tries it on the elements with the key
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        None
+    }
 }
 
 pub trait ContextualSelector {
@@ -65,14 +469,81 @@ pub trait ContextualSelector {
         element: &HtmlPathElement<'_, Handle>,
     ) -> bool;
 
+    /// An id, class or name which every element this selects has, as [`Selector::element_key`]
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        None
+    }
+
     fn or<O: ContextualSelector>(self, other: O) -> GroupSelector<Self, O>
     where
         Self: Sized,
     {
         GroupSelector(self, other)
     }
+
+    /// Also requires an ancestor of the element to match, the descendant combinator
+    fn descendant_of<A: ContextualSelector>(self, ancestor: A) -> DescendantSelector<A, Self>
+    where
+        Self: Sized,
+    {
+        DescendantSelector(ancestor, self)
+    }
+
+    /// Also requires the parent of the element to match, the child combinator
+    fn child_of<P: ContextualSelector>(self, parent: P) -> ChildSelector<P, Self>
+    where
+        Self: Sized,
+    {
+        ChildSelector(parent, self)
+    }
+
+    /// Also requires the previous sibling of the element to match, in the same context, the `+` combinator
+    fn adjacent_sibling_of<P: ContextualSelector>(
+        self,
+        previous: P,
+    ) -> AdjacentSiblingSelector<P, Self>
+    where
+        Self: Sized,
+    {
+        AdjacentSiblingSelector(previous, self)
+    }
+
+    /// Also requires a sibling before the element to match, in the same context, the `~` combinator
+    fn general_sibling_of<P: ContextualSelector>(
+        self,
+        previous: P,
+    ) -> GeneralSiblingSelector<P, Self>
+    where
+        Self: Sized,
+    {
+        GeneralSiblingSelector(previous, self)
+    }
+}
+
+/// More logical combinators of selectors, alongside [`Selector::and`] and [`ContextualSelector::or`], to build conditions fluently,
+/// such as `NameSelector("p").and(ClassSelector("note").not())`.
+///
+/// They only apply to selectors which look at the element alone, so the results are selectors themselves and can be combined further.
+pub trait SelectorExt: ContextualSelector + Sized {
+    /// Selects what either selects, as a selector of the element, for selectors which only look at it
+    fn or_element<O: Selector>(self, other: O) -> OrSelector<Self, O>
+    where
+        Self: Selector,
+    {
+        OrSelector(self, other)
+    }
+
+    /// Selects what this doesn't select, as `:not()`
+    fn not(self) -> Not<Self>
+    where
+        Self: Selector,
+    {
+        Not(self)
+    }
 }
 
+impl<S: ContextualSelector> SelectorExt for S {}
+
 pub trait OnlyContextualSelector {
     fn context_match<Handle>(&self, context: HtmlContext<'_, Handle>) -> bool;
 }
@@ -88,6 +559,10 @@ where
     ) -> bool {
         self.is_match(element)
     }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.element_key()
+    }
 }
 
 pub struct NameSelector(pub &'static str);
@@ -96,13 +571,60 @@ impl Selector for NameSelector {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
         *self.0 == *element.name.local
     }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        Some(RuleKey::Name(self.0))
+    }
+}
+
+/// Selects elements in a namespace, by its url, as does the prefix in `svg|a`. Html elements are in the html namespace, and the
+/// elements of inline `<svg>` and `<math>` in the svg and mathml namespaces.
+pub struct NamespaceSelector(pub &'static str);
+
+impl NamespaceSelector {
+    pub const HTML: Self = Self("http://www.w3.org/1999/xhtml");
+    pub const SVG: Self = Self("http://www.w3.org/2000/svg");
+    pub const MATHML: Self = Self("http://www.w3.org/1998/Math/MathML");
+    /// Elements without a namespace, which only occur in xml
+    pub const NONE: Self = Self("");
+
+    /// The namespace of a prefix, `html`, `svg`, `mathml`, or the empty prefix for no namespace, ignoring case
+    pub const fn prefix(prefix: &str) -> Option<Self> {
+        let mut lower = [0; 6];
+        let bytes = prefix.as_bytes();
+        if bytes.len() > lower.len() {
+            return None;
+        }
+        let mut i = 0;
+        while i < bytes.len() {
+            lower[i] = bytes[i].to_ascii_lowercase();
+            i += 1;
+        }
+        match lower.split_at(bytes.len()).0 {
+            b"html" => Some(Self::HTML),
+            b"svg" => Some(Self::SVG),
+            b"mathml" => Some(Self::MATHML),
+            b"" => Some(Self::NONE),
+            _ => None,
+        }
+    }
+}
+
+impl Selector for NamespaceSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        *self.0 == *element.name.ns
+    }
 }
 
 pub struct ClassSelector(pub &'static str);
 
 impl Selector for ClassSelector {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
-        element.classes().any(|class| class == self.0)
+        element.has_class(self.0)
+    }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        Some(RuleKey::Class(self.0))
     }
 }
 
@@ -110,18 +632,151 @@ pub struct IdSelector(pub &'static str);
 
 impl Selector for IdSelector {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
-        const ID: QualName = QualName {
-            prefix: None,
-            ns: ns!(),
-            local: local_name!("id"),
-        };
-        if let Some(id) = element.attr(ID) {
-            let var_name: &str = &*id;
-            self.0 == var_name
-        } else {
-            false
+        element.id() == Some(self.0)
+    }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        Some(RuleKey::Id(self.0))
+    }
+}
+
+/// Selects elements which have the attribute, as in `[href]`
+pub struct AttrSelector(pub &'static str);
+
+impl Selector for AttrSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element.has_attr(self.0)
+    }
+}
+
+/// Selects elements with any attribute whose name starts with the prefix, as does the wildcard `[data-*]`
+pub struct AttrPrefixSelector(pub &'static str);
+
+impl Selector for AttrPrefixSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .attrs
+            .iter()
+            .any(|attr| attr.name.local.starts_with(self.0))
+    }
+}
+
+/// Selects elements whose attribute has exactly the value, as in `[data-kind="note"]`
+pub struct AttrValueSelector(pub &'static str, pub &'static str);
+
+impl AttrValueSelector {
+    /// Compares the value ignoring ascii case, as in `[type="text" i]`
+    pub fn ignore_case(self) -> AttrIgnoreCaseSelector {
+        AttrIgnoreCaseSelector(self.0, AttrOperator::Equals, self.1)
+    }
+}
+
+impl Selector for AttrValueSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .attr_local(self.0)
+            .is_some_and(|value| **value == *self.1)
+    }
+}
+
+/// How an [`AttrOperatorSelector`] compares the attribute's value with its value, as the css attribute selector operators do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttrOperator {
+    /// `=`, the whole value
+    Equals,
+    /// `^=`, a prefix of the value
+    Prefix,
+    /// `$=`, a suffix of the value
+    Suffix,
+    /// `*=`, a substring of the value
+    Substring,
+    /// `~=`, one of the whitespace-separated words of the value
+    Word,
+    /// `|=`, the whole value or its prefix before a `-`, as for language codes
+    DashMatch,
+}
+
+impl AttrOperator {
+    /// Whether the attribute's actual value matches the expected value, the empty value only matching `=` and `|=` as in css
+    pub fn matches(self, actual: &str, expected: &str) -> bool {
+        match self {
+            AttrOperator::Equals => actual == expected,
+            AttrOperator::Prefix => !expected.is_empty() && actual.starts_with(expected),
+            AttrOperator::Suffix => !expected.is_empty() && actual.ends_with(expected),
+            AttrOperator::Substring => !expected.is_empty() && actual.contains(expected),
+            AttrOperator::Word => {
+                !expected.is_empty()
+                    && !expected.contains(|c: char| c.is_ascii_whitespace())
+                    && actual
+                        .split(|c: char| c.is_ascii_whitespace())
+                        .any(|word| word == expected)
+            }
+            AttrOperator::DashMatch => {
+                actual == expected
+                    || actual
+                        .strip_prefix(expected)
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
         }
     }
+
+    /// Whether the values match ignoring ascii case, as with the css `i` flag, for html whose casing is inconsistent
+    pub fn matches_ignore_case(self, actual: &str, expected: &str) -> bool {
+        self.matches(&actual.to_ascii_lowercase(), &expected.to_ascii_lowercase())
+    }
+}
+
+/// Selects elements whose attribute's value the operator matches with the value, as in `[href^="https://"]`
+pub struct AttrOperatorSelector(pub &'static str, pub AttrOperator, pub &'static str);
+
+impl AttrOperatorSelector {
+    /// Compares the value ignoring ascii case, as in `[href^="HTTPS:" i]`
+    pub fn ignore_case(self) -> AttrIgnoreCaseSelector {
+        AttrIgnoreCaseSelector(self.0, self.1, self.2)
+    }
+}
+
+impl Selector for AttrOperatorSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .attr_local(self.0)
+            .is_some_and(|value| self.1.matches(value, self.2))
+    }
+}
+
+/// Selects elements whose attribute's value the operator matches with the value ignoring ascii case, as in `[type="text" i]`
+pub struct AttrIgnoreCaseSelector(pub &'static str, pub AttrOperator, pub &'static str);
+
+impl Selector for AttrIgnoreCaseSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .attr_local(self.0)
+            .is_some_and(|value| self.1.matches_ignore_case(value, self.2))
+    }
+}
+
+/// Selects elements with an attribute whose value matches a regular expression, such as a `href` pattern
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+pub struct AttrMatchSelector {
+    local: LocalName,
+    regex: regex::Regex,
+}
+
+#[cfg(feature = "regex")]
+impl AttrMatchSelector {
+    pub fn new(local: LocalName, regex: regex::Regex) -> Self {
+        Self { local, regex }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Selector for AttrMatchSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .attr_local(&self.local)
+            .is_some_and(|value| self.regex.is_match(value))
+    }
 }
 
 #[derive(Debug, Default)]
@@ -129,34 +784,52 @@ pub struct ElementSelector {
     name: Option<QualName>,
     id: Option<StrTendril>,
     classes: Vec<StrTendril>,
+    #[cfg(feature = "regex")]
+    attr_matches: Vec<AttrMatchSelector>,
 }
 
 impl ElementSelector {
     pub fn element_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
-        const ID: QualName = QualName {
-            prefix: None,
-            ns: ns!(),
-            local: local_name!("id"),
-        };
         self.name
             .as_ref()
             .map_or(true, |match_name| *match_name == element.name)
-            && self.id.as_ref().map_or(true, |match_id| {
-                element.attr(ID).map_or(false, |id| match_id == id)
-            })
+            && self
+                .id
+                .as_ref()
+                .map_or(true, |match_id| element.id() == Some(&**match_id))
             && self
                 .classes
                 .iter()
-                .all(|match_class| element.classes().any(|class| **match_class == *class))
+                .all(|match_class| element.has_class(match_class))
+            && self.attrs_match(element)
+    }
+
+    #[cfg(feature = "regex")]
+    fn attrs_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        self.attr_matches
+            .iter()
+            .all(|attr_match| attr_match.is_match(element))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn attrs_match<Handle>(&self, _element: &HtmlPathElement<'_, Handle>) -> bool {
+        true
     }
 
     pub fn class(self, class: StrTendril) -> Self {
         let mut classes = self.classes;
         classes.push(class);
+        Self { classes, ..self }
+    }
+
+    /// Also requires the attribute's value to match the regular expression
+    #[cfg(feature = "regex")]
+    pub fn attr_matches(self, local: LocalName, regex: regex::Regex) -> Self {
+        let mut attr_matches = self.attr_matches;
+        attr_matches.push(AttrMatchSelector::new(local, regex));
         Self {
-            name: self.name,
-            id: self.id,
-            classes,
+            attr_matches,
+            ..self
         }
     }
 
@@ -167,8 +840,7 @@ impl ElementSelector {
                 ns: ns!(html),
                 local: local_name,
             }),
-            id: self.id,
-            classes: self.classes,
+            ..self
         }
     }
 }
@@ -177,6 +849,13 @@ impl Selector for ElementSelector {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
         self.element_match(element)
     }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        let id = self.id.as_deref().map(RuleKey::Id);
+        let class = self.classes.first().map(|class| RuleKey::Class(class));
+        let name = self.name.as_ref().map(|name| RuleKey::Name(&name.local));
+        id.or(class).or(name)
+    }
 }
 
 /// A contextual selector, the last selector must match the element exactly and the preceding must match elements in the context in that order
@@ -205,9 +884,39 @@ impl<S: Selector> ContextualSelector for [S] {
         }
         true
     }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.last().and_then(Selector::element_key)
+    }
+}
+
+/// Selects elements without element or text children, `:empty`. It needs the element's content to have been recorded by a
+/// [`crate::ContentBuffer`], and doesn't select anything otherwise.
+pub struct EmptySelector;
+
+impl Selector for EmptySelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .content
+            .as_ref()
+            .is_some_and(|content| content.empty)
+    }
 }
 
-/// Always matches
+/// Selects elements whose text contains the string, the non-standard `:text-contains("...")`. Like [`EmptySelector`] it needs a
+/// [`crate::ContentBuffer`].
+pub struct TextContainsSelector(pub &'static str);
+
+impl Selector for TextContainsSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .content
+            .as_ref()
+            .is_some_and(|content| content.text.contains(self.0))
+    }
+}
+
+/// Always matches, css's universal selector `*`
 pub struct MatchAll;
 
 impl Selector for MatchAll {
@@ -222,6 +931,30 @@ impl OnlyContextualSelector for MatchAll {
     }
 }
 
+/// Matches the root of the contexts the selector is evaluated in, as css's `:scope`.
+///
+/// Contexts are relative to the sink which receives them: the root is the document's root element, but a [`crate::RootFilter`]
+/// passes each selected subtree on as if it were a document, so the sinks inside it see the selected element as the root, with an empty
+/// context. On its own, `Scope` matches that root, and leading a descendant chain it requires the rest of the chain to be inside it.
+pub struct Scope;
+
+impl ContextualSelector for Scope {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        _element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        context.is_empty()
+    }
+}
+
+impl OnlyContextualSelector for Scope {
+    /// The context left above what the chain matched, which is inside the root if it isn't empty
+    fn context_match<Handle>(&self, context: HtmlContext<'_, Handle>) -> bool {
+        !context.is_empty()
+    }
+}
+
 /// Matches something in the context, then continues by using the second matcher for the remaining context
 pub struct ContextSelectCons<C, A>(pub C, pub A);
 
@@ -230,14 +963,26 @@ impl<C: OnlyContextualSelector, A: Selector> OnlyContextualSelector for ContextS
         while let Some((last, rest)) = context.split_last() {
             let element = last;
             context = rest;
-            if self.1.is_match(element) {
-                return self.0.context_match(context);
+            // an outer match may satisfy the rest when the innermost doesn't, such as for a child combinator further out
+            if self.1.is_match(element) && self.0.context_match(context) {
+                return true;
             }
         }
         false
     }
 }
 
+/// Matches the last element of the context, the parent, then continues by using the second matcher for the remaining context
+pub struct ParentSelectCons<C, A>(pub C, pub A);
+
+impl<C: OnlyContextualSelector, A: Selector> OnlyContextualSelector for ParentSelectCons<C, A> {
+    fn context_match<Handle>(&self, context: HtmlContext<'_, Handle>) -> bool {
+        context
+            .split_last()
+            .is_some_and(|(parent, rest)| self.1.is_match(parent) && self.0.context_match(rest))
+    }
+}
+
 /// Matches the element, then continues by using the second matcher for the remaining context
 pub struct ContextualSelectCons<C: OnlyContextualSelector, A: Selector>(pub C, pub A);
 
@@ -249,6 +994,10 @@ impl<C: OnlyContextualSelector, A: Selector> ContextualSelector for ContextualSe
     ) -> bool {
         self.1.is_match(element) && self.0.context_match(context)
     }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.1.element_key()
+    }
 }
 
 /// Groups together 2 selectors, selects elements that either would select
@@ -264,6 +1013,197 @@ impl<A: ContextualSelector, B: ContextualSelector> ContextualSelector for GroupS
     }
 }
 
+/// A list of selectors for an [`AnyOf`], implemented for tuples of up to 12 selectors, which can be of different types, and for arrays
+/// and vectors of them
+pub trait SelectorList {
+    /// The index of the first selector in the list which selects the element
+    fn first_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize>;
+}
+
+macro_rules! tuple_selector_list {
+    ($($index:tt $selector:ident),+) => {
+        impl<$($selector: ContextualSelector),+> SelectorList for ($($selector,)+) {
+            fn first_match<Handle>(
+                &self,
+                context: HtmlContext<'_, Handle>,
+                element: &HtmlPathElement<'_, Handle>,
+            ) -> Option<usize> {
+                $(if self.$index.context_match(context, element) {
+                    return Some($index);
+                })+
+                None
+            }
+        }
+    };
+}
+
+tuple_selector_list!(0 A);
+tuple_selector_list!(0 A, 1 B);
+tuple_selector_list!(0 A, 1 B, 2 C);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K);
+tuple_selector_list!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L);
+
+impl<S: ContextualSelector> SelectorList for [S] {
+    fn first_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize> {
+        self.iter()
+            .position(|selector| selector.context_match(context, element))
+    }
+}
+
+impl<S: ContextualSelector, const N: usize> SelectorList for [S; N] {
+    fn first_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize> {
+        self[..].first_match(context, element)
+    }
+}
+
+impl<S: ContextualSelector> SelectorList for Vec<S> {
+    fn first_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize> {
+        self[..].first_match(context, element)
+    }
+}
+
+/// Selects elements which any selector in the list selects, a selector list as made by `css_select!("h1", "h2")`. Sinks which want to
+/// treat the selectors differently can ask which one selected an element with [`AnyOf::branch`].
+pub struct AnyOf<L: SelectorList>(pub L);
+
+impl<L: SelectorList> AnyOf<L> {
+    /// The index in the list of the first selector which selects the element, `None` if none do
+    pub fn branch<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize> {
+        self.0.first_match(context, element)
+    }
+}
+
+impl<L: SelectorList> ContextualSelector for AnyOf<L> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.branch(context, element).is_some()
+    }
+}
+
+/// Selects elements the second selector selects which are descendants of an element the first selects, in its own context
+pub struct DescendantSelector<A: ContextualSelector, B: ContextualSelector>(A, B);
+
+impl<A: ContextualSelector, B: ContextualSelector> ContextualSelector for DescendantSelector<A, B> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.1.context_match(context, element)
+            && (0..context.len())
+                .rev()
+                .any(|depth| self.0.context_match(&context[..depth], &context[depth]))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.1.subject_key()
+    }
+}
+
+/// Selects elements the second selector selects whose parent the first selects, in its own context
+pub struct ChildSelector<A: ContextualSelector, B: ContextualSelector>(A, B);
+
+impl<A: ContextualSelector, B: ContextualSelector> ContextualSelector for ChildSelector<A, B> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.1.context_match(context, element)
+            && context
+                .split_last()
+                .is_some_and(|(parent, rest)| self.0.context_match(rest, parent))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.1.subject_key()
+    }
+}
+
+/// Selects elements the second selector selects whose previous sibling the first selects, in the same context
+pub struct AdjacentSiblingSelector<A: ContextualSelector, B: ContextualSelector>(A, B);
+
+impl<A: ContextualSelector, B: ContextualSelector> ContextualSelector
+    for AdjacentSiblingSelector<A, B>
+{
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.1.context_match(context, element)
+            && element
+                .previous_sibling
+                .as_deref()
+                .is_some_and(|previous| self.0.context_match(context, previous))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.1.subject_key()
+    }
+}
+
+/// Selects elements the second selector selects which have a sibling before them which the first selects, in the same context
+pub struct GeneralSiblingSelector<A: ContextualSelector, B: ContextualSelector>(A, B);
+
+impl<A: ContextualSelector, B: ContextualSelector> ContextualSelector
+    for GeneralSiblingSelector<A, B>
+{
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.1.context_match(context, element)
+            && element
+                .previous_siblings()
+                .any(|previous| self.0.context_match(context, previous))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.1.subject_key()
+    }
+}
+
+/// Selects what the selector doesn't, `:not()`
+pub struct Not<S: Selector>(pub S);
+
+impl<S: Selector> Selector for Not<S> {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        !self.0.is_match(element)
+    }
+}
+
 /// COmbines 2 selectors, selecting something taht both would select
 pub struct AndSelector<A: Selector, B: Selector>(A, B);
 
@@ -271,6 +1211,19 @@ impl<A: Selector, B: Selector> Selector for AndSelector<A, B> {
     fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
         self.0.is_match(element) && self.1.is_match(element)
     }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        RuleKey::narrower(self.0.element_key(), self.1.element_key())
+    }
+}
+
+/// Combines 2 selectors of the element, selecting something that either would select, see [`SelectorExt::or_element`]
+pub struct OrSelector<A: Selector, B: Selector>(A, B);
+
+impl<A: Selector, B: Selector> Selector for OrSelector<A, B> {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        self.0.is_match(element) || self.1.is_match(element)
+    }
 }
 
 #[test]
@@ -278,15 +1231,15 @@ fn test_matchers() {
     let mut handle = 0;
     let mut el = |local, attrs: Vec<Attribute>| {
         handle += 1;
-        HtmlPathElement {
+        HtmlPathElement::new(
             handle,
-            name: QualName {
+            QualName {
                 prefix: None,
                 ns: ns!(html),
                 local,
             },
-            attrs: attrs.into(),
-        }
+            attrs,
+        )
     };
     let attr = |local, value: &str| Attribute {
         name: QualName {
@@ -339,4 +1292,234 @@ fn test_matchers() {
     assert!(css_select!((#"main") ("p"."quote")).context_match(&path_main, &el_quote));
     assert!(!css_select!((#"main") ("p"."quote")).context_match(&path_body, &el_main));
     assert!(!css_select!((#"main") ("p"."quote")).context_match(&path_body, &el_quote));
+
+    assert!(css_select!(["class"]).context_match(&path_main, &el_quote));
+    assert!(!css_select!(["class"]).context_match(&path_main, &el_p));
+    assert!(css_select!("div"["id"]).context_match(&path_body, &el_main));
+    assert!(!css_select!("p"["id"]).context_match(&path_body, &el_main));
+    assert!(css_select!(["id" = "main"]).context_match(&path_body, &el_main));
+    assert!(!css_select!(["class" = "quote"]).context_match(&path_main, &el_quote));
+    assert!(css_select!("p"["class" = "fixed quote"]).context_match(&path_main, &el_quote));
+    assert!(css_select!((["id" = "main"])("p"["class"])).context_match(&path_main, &el_quote));
+
+    assert!(css_select!("html" "div"#"main" "p"."quote").context_match(&path_main, &el_quote));
+    assert!(!css_select!("html" "div"#"main" "p"."quote").context_match(&path_main, &el_p));
+    assert!(css_select!("div"["id"] "p"."fixed"."quote").context_match(&path_main, &el_quote));
+    assert!(!css_select!("div"["id"] "p"."fixed"."quote").context_match(&path_main, &el_p));
+    assert!(css_select!((#"main") "p").context_match(&path_main, &el_p));
+    assert!(!css_select!("div" "html" "p").context_match(&path_main, &el_p));
+    assert!(css_select!(:scope "div" "p").context_match(&path_main, &el_p));
+
+    assert!(css_select!("div" > "p").context_match(&path_main, &el_p));
+    assert!(css_select!("html" > "html" > "div" > "p").context_match(&path_main, &el_p));
+    assert!(!css_select!("html" > "p").context_match(&path_main, &el_p));
+    assert!(css_select!("html" > "html" "p").context_match(&path_main, &el_p));
+    assert!(css_select!((#"main") > "p"."quote").context_match(&path_main, &el_quote));
+    assert!(!css_select!("p" > "p").context_match(&path_body, &el_p));
+    // the innermost div's parent isn't the html, but an outer div's is
+    let path_nested = [
+        el(local_name!("html"), vec![]),
+        el(local_name!("div"), vec![]),
+        el(local_name!("p"), vec![]),
+        el(local_name!("div"), vec![]),
+    ];
+    assert!(css_select!("html" > "div" "p").context_match(&path_nested, &el_p));
+    assert!(css_select!("p")
+        .child_of(css_select!("div"))
+        .context_match(&path_nested, &el_p));
+    assert!(!css_select!("p")
+        .child_of(css_select!("html" > "div"))
+        .context_match(&path_nested, &el_p));
+
+    // siblings are matched in the context of the element
+    let mut chain = crate::SiblingChain::new(crate::SiblingTracking::All);
+    let mut el_h2 = el(local_name!("h2"), vec![]);
+    el_h2.previous_sibling = chain.element(path_main.len(), &el_h2);
+    let mut el_note = el(local_name!("p"), vec![attr(local_name!("class"), "note")]);
+    el_note.previous_sibling = chain.element(path_main.len(), &el_note);
+    let mut el_p2 = el(local_name!("p"), vec![]);
+    el_p2.previous_sibling = chain.element(path_main.len(), &el_p2);
+    assert!(css_select!("h2" + "p").context_match(&path_main, &el_note));
+    assert!(!css_select!("h2" + "p").context_match(&path_main, &el_p2));
+    assert!(css_select!("h2" ~ "p").context_match(&path_main, &el_p2));
+    assert!(css_select!("h2" + "p"."note" + "p").context_match(&path_main, &el_p2));
+    assert!(css_select!("div" > "h2" ~ "p").context_match(&path_main, &el_p2));
+    assert!(!css_select!("html" > "h2" ~ "p").context_match(&path_main, &el_p2));
+    assert!(css_select!("h2" ~ ."note").context_match(&path_main, &el_note));
+    assert!(!css_select!("p" ~ "h2").context_match(&path_main, &el_h2));
+    assert!(css_select!("p")
+        .adjacent_sibling_of(css_select!(."note"))
+        .context_match(&path_main, &el_p2));
+    assert!(css_select!("p")
+        .general_sibling_of(css_select!("div" "h2"))
+        .context_match(&path_main, &el_p2));
+
+    // positions are those in the source
+    let el_third = HtmlPathElement {
+        position: Some(crate::SiblingPosition {
+            index: 3,
+            index_of_type: 2,
+            count: None,
+            count_of_type: None,
+        }),
+        ..el(local_name!("li"), vec![])
+    };
+    assert!(css_select!(:nth-child("odd")).context_match(&path_main, &el_third));
+    assert!(css_select!("li":nth-child("3n")).context_match(&path_main, &el_third));
+    assert!(!css_select!("li":nth-of-type("3n")).context_match(&path_main, &el_third));
+    assert!(
+        css_select!("div" > "li":nth-of-type("-n+2"):nth-child("2n+1"))
+            .context_match(&path_main, &el_third)
+    );
+    assert!(!css_select!("h2" ~ :nth-of-type("even")).context_match(&path_main, &el_third));
+    assert!(!css_select!("li":nth-child("2")).context_match(&path_main, &el_p));
+    assert!(!css_select!("li":first-child).context_match(&path_main, &el_third));
+    assert!(!css_select!("div" > :first-of-type).context_match(&path_main, &el_third));
+    // the siblings after haven't been counted
+    assert!(!css_select!("li":last-child).context_match(&path_main, &el_third));
+    let el_last = HtmlPathElement {
+        position: Some(crate::SiblingPosition {
+            index: 3,
+            index_of_type: 1,
+            count: Some(3),
+            count_of_type: Some(2),
+        }),
+        ..el(local_name!("li"), vec![])
+    };
+    assert!(css_select!("li":first-of-type:last-child).context_match(&path_main, &el_last));
+    assert!(!css_select!("h2" + "li":last-of-type).context_match(&path_main, &el_last));
+
+    assert!(Not(ClassSelector("quote")).is_match(&el_p));
+    assert!(!Not(ClassSelector("quote")).is_match(&el_quote));
+    assert!(css_select!("p":not(."fixed"."other")).context_match(&path_main, &el_quote));
+    assert!(!css_select!("p":not(."fixed")).context_match(&path_main, &el_quote));
+    assert!(css_select!("div" > :not("div"):not(#"x")).context_match(&path_main, &el_p));
+    let link = el(
+        local_name!("a"),
+        vec![
+            attr(local_name!("href"), "https://example.com/logo.png"),
+            attr(local_name!("class"), "button  warning"),
+            attr(local_name!("lang"), "en-GB"),
+        ],
+    );
+    assert!(css_select!("a"["href" ^= "https://"]).context_match(&path_main, &link));
+    assert!(!css_select!(["href" ^= "http://"]).context_match(&path_main, &link));
+    assert!(css_select!(["href" $= ".png"]["href" *= "example"]).context_match(&path_main, &link));
+    assert!(css_select!("div" > "a"["class" ~= "warning"]).context_match(&path_main, &link));
+    assert!(!css_select!(["class" ~= "butt"]).context_match(&path_main, &link));
+    assert!(css_select!(["lang" |= "en"]).context_match(&path_main, &link));
+    assert!(!css_select!(["lang" |= "e"]).context_match(&path_main, &link));
+    assert!(!css_select!("h2" ~ ["href" $= "png"], "a"["lang" ^= "de"])
+        .context_match(&path_main, &link));
+    assert!(!css_select!(["href" $= ".PNG"]).context_match(&path_main, &link));
+    assert!(css_select!(["href" $= ".PNG" i]).context_match(&path_main, &link));
+    assert!(css_select!("a"["class" ~= "WARNING" i]["lang" = "EN-gb" i])
+        .context_match(&path_main, &link));
+    assert!(
+        css_select!(["href" ^= "HTTPS" i]["href" *= "Example" i]["lang" |= "EN" i])
+            .context_match(&path_main, &link)
+    );
+    assert!(AttrValueSelector("lang", "en-gb")
+        .ignore_case()
+        .is_match(&link));
+    let svg_link = HtmlPathElement {
+        name: QualName::new(None, ns!(svg), local_name!("a")),
+        ..link.clone()
+    };
+    assert!(css_select!("html" | "a").context_match(&path_main, &link));
+    assert!(!css_select!("html" | "a").context_match(&path_main, &svg_link));
+    assert!(css_select!("div" > "svg" | "a"["href"]).context_match(&path_main, &svg_link));
+    assert!(css_select!(("div") "SVG" | "a", "p").context_match(&path_main, &svg_link));
+    assert!(css_select!("a":not("svg" | "a")).context_match(&path_main, &link));
+    assert!(!css_select!("" | "a").context_match(&path_main, &link));
+    assert!(css_select!("h2" ~ "html" | "p").context_match(&path_main, &el_p2));
+    assert_eq!(
+        NamespaceSelector::prefix("MathML").map(|namespace| namespace.0),
+        Some(NamespaceSelector::MATHML.0)
+    );
+    assert!(NamespaceSelector::prefix("xlink").is_none());
+
+    let button = NameSelector("a").and(ClassSelector("button"));
+    assert!(button.is_match(&link));
+    assert!(!button.not().is_match(&link));
+    assert!(NameSelector("p")
+        .or_element(ClassSelector("warning"))
+        .and(AttrSelector("href"))
+        .is_match(&link));
+    assert!(!NameSelector("p")
+        .or_element(IdSelector("x"))
+        .not()
+        .not()
+        .is_match(&link));
+    assert!(NameSelector("p")
+        .or(css_select!("div" > "a"))
+        .context_match(&path_main, &link));
+    assert!(!css_select!("html" > "a")
+        .or(NameSelector("p").and(ClassSelector("button")))
+        .context_match(&path_main, &link));
+    assert!(ContextualSelector::context_match(
+        &css_select!(*),
+        &path_main,
+        &svg_link
+    ));
+    assert!(css_select!("html" > "html" > * > "a").context_match(&path_main, &link));
+    assert!(!css_select!("div" > * > "a").context_match(&path_main, &link));
+    assert!(css_select!(* > *."button").context_match(&path_main, &link));
+    assert!(!css_select!("svg" | * ~ *, "h1").context_match(&path_main, &el_p2));
+    assert!(css_select!("h2" ~ *:not("h1")).context_match(&path_main, &el_p2));
+    assert!(css_select!("div" > "svg" | *).context_match(&path_main, &svg_link));
+    assert!(css_select!(["la" *]).context_match(&path_main, &link));
+    assert!(!css_select!(*["data-" *]).context_match(&path_main, &link));
+    assert!(!AttrOperator::Substring.matches("anything", ""));
+    assert!(!AttrOperator::Word.matches("a b", "a b"));
+
+    let headings = css_select!("h1", "div" > "p"."quote", (#"main") ("p"));
+    assert_eq!(headings.branch(&path_main, &el_quote), Some(1));
+    assert_eq!(headings.branch(&path_main, &el_p), Some(2));
+    assert_eq!(headings.branch(&path_body, &el_p), None);
+    assert!(headings.context_match(&path_main, &el_p));
+    assert!(css_select!("h1", "p").context_match(&path_body, &el_p));
+    let names = AnyOf(vec![NameSelector("h1"), NameSelector("p")]);
+    assert_eq!(names.branch(&path_body, &el_p), Some(1));
+    assert_eq!(AnyOf([NameSelector("h1")]).branch(&path_body, &el_p), None);
+
+    // without a position in the source it isn't known to be the first child
+    assert!(css_select!(:not(:first-child)).context_match(&path_main, &el_p));
+
+    // the ancestor is matched in its own context, not only by itself
+    let in_main = css_select!("p").descendant_of(css_select!("html" "div"));
+    assert!(in_main.context_match(&path_main, &el_p));
+    assert!(!in_main.context_match(&path_body, &el_p));
+    assert!(!css_select!("p")
+        .descendant_of(css_select!("p"))
+        .context_match(&path_main, &el_p));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_attr_matches() {
+    let attrs = [Attribute {
+        name: QualName::new(None, ns!(), local_name!("href")),
+        value: "https://www.example.com/page".into(),
+    }];
+    let link = HtmlPathElement::new(
+        1,
+        QualName::new(None, ns!(html), local_name!("a")),
+        &attrs[..],
+    );
+    let pattern = |pattern| regex::Regex::new(pattern).unwrap();
+
+    assert!(AttrMatchSelector::new(
+        local_name!("href"),
+        pattern(r"^https://(www\.)?example\.com")
+    )
+    .is_match(&link));
+    assert!(!AttrMatchSelector::new(local_name!("src"), pattern("")).is_match(&link));
+    assert!(ElementSelector::default()
+        .name(local_name!("a"))
+        .attr_matches(local_name!("href"), pattern("/page$"))
+        .is_match(&link));
+    assert!(!ElementSelector::default()
+        .attr_matches(local_name!("href"), pattern("^http:"))
+        .is_match(&link));
 }
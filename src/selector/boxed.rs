@@ -0,0 +1,153 @@
+use super::{closure::view, ContextualSelector, ElementView, RuleKey, Selector};
+use crate::{HtmlContext, HtmlPathElement};
+
+/// The object-safe mirror of [`Selector`], implemented for every selector, so that selectors of different types, such as those
+/// assembled from configuration, can be boxed and stored together.
+///
+/// `Box<dyn DynSelector>` is a [`Selector`], so it can be passed to sinks such as [`crate::ElementRemover`]. Like a [`super::FnSelector`]
+/// it sees each element as an [`ElementView`], without its handle or previous siblings.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{
+///     css_select, parse_document,
+///     selector::{ClassSelector, DynSelector, NameSelector},
+///     ElementRemover, SelectToStrings,
+/// };
+///
+/// let configured = "class";
+/// let remove: Box<dyn DynSelector> = match configured {
+///     "class" => Box::new(ClassSelector("ad")),
+///     _ => Box::new(NameSelector("aside")),
+/// };
+/// let body = parse_document(
+///     ElementRemover::wrap(SelectToStrings::new(css_select!("body")), remove),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><p class="ad">Ad</p><p>Text</p></body></html>"#)
+/// .unwrap();
+/// assert_eq!(body, ["<body><p>Text</p></body>"]);
+/// ```
+pub trait DynSelector {
+    fn dyn_is_match(&self, element: &ElementView<'_>) -> bool;
+
+    fn dyn_element_key(&self) -> Option<RuleKey<'_>>;
+}
+
+impl<S: Selector> DynSelector for S {
+    fn dyn_is_match(&self, element: &ElementView<'_>) -> bool {
+        self.is_match(element)
+    }
+
+    fn dyn_element_key(&self) -> Option<RuleKey<'_>> {
+        self.element_key()
+    }
+}
+
+impl<T: DynSelector + ?Sized> Selector for Box<T> {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        (**self).dyn_is_match(&view(element))
+    }
+
+    fn element_key(&self) -> Option<RuleKey<'_>> {
+        (**self).dyn_element_key()
+    }
+}
+
+/// The object-safe mirror of [`ContextualSelector`], implemented for every contextual selector, as [`DynSelector`] is of [`Selector`].
+///
+/// `Box<dyn DynContextualSelector>` is a [`ContextualSelector`], so it can be passed to sinks such as [`crate::RootFilter`], and a
+/// `Vec` of them to [`super::AnyOf`]. Each match copies the context into [`ElementView`]s, which don't have previous siblings, so the
+/// sibling combinators inside a boxed selector don't select anything.
+pub trait DynContextualSelector {
+    fn dyn_context_match(&self, context: &[ElementView<'_>], element: &ElementView<'_>) -> bool;
+
+    fn dyn_subject_key(&self) -> Option<RuleKey<'_>>;
+}
+
+impl<S: ContextualSelector> DynContextualSelector for S {
+    fn dyn_context_match(&self, context: &[ElementView<'_>], element: &ElementView<'_>) -> bool {
+        self.context_match(context, element)
+    }
+
+    fn dyn_subject_key(&self) -> Option<RuleKey<'_>> {
+        self.subject_key()
+    }
+}
+
+impl ContextualSelector for Box<dyn DynContextualSelector + '_> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        let context = context.iter().map(view).collect::<Vec<_>>();
+        (**self).dyn_context_match(&context, &view(element))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        (**self).dyn_subject_key()
+    }
+}
+
+impl ContextualSelector for Box<dyn DynContextualSelector + Send + Sync + '_> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        let context = context.iter().map(view).collect::<Vec<_>>();
+        (**self).dyn_context_match(&context, &view(element))
+    }
+
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        (**self).dyn_subject_key()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{
+        css_select, parse_document,
+        selector::{AnyOf, CssSelector, IdSelector, NameSelector, SelectorExt},
+        SelectToStrings,
+    };
+
+    fn select<S: ContextualSelector>(selector: S) -> Vec<String> {
+        parse_document(SelectToStrings::new(selector), Default::default())
+            .one(r#"<!DOCTYPE html><html><head></head><body><ol><li>One</li></ol><ul><li>Two</li></ul><p id="main">Three</p></body></html>"#)
+            .unwrap()
+    }
+
+    #[test]
+    fn select_with_boxed_selectors() {
+        let configured = ["ol > li", "#main"]
+            .iter()
+            .map(|selector| {
+                Box::new(selector.parse::<CssSelector>().unwrap())
+                    as Box<dyn DynContextualSelector + Send + Sync>
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            select(AnyOf(configured)),
+            ["<li>One</li>", r#"<p id="main">Three</p>"#]
+        );
+
+        let mixed: Vec<Box<dyn DynContextualSelector>> = vec![
+            Box::new(css_select!("ul" > "li")),
+            Box::new(css_select!(#"main")),
+        ];
+        assert_eq!(
+            select(AnyOf(mixed)),
+            ["<li>Two</li>", r#"<p id="main">Three</p>"#]
+        );
+        let element: Box<dyn DynSelector> = Box::new(NameSelector("li"));
+        assert_eq!(
+            select(element.and(IdSelector("x").not())),
+            ["<li>One</li>", "<li>Two</li>"]
+        );
+    }
+}
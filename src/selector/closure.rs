@@ -0,0 +1,118 @@
+use std::borrow::Cow;
+
+use super::{ContextualSelector, Selector};
+use crate::{HtmlContext, HtmlPathElement};
+
+/// An element as a closure selector sees it, without its handle or previous siblings.
+///
+/// Selectors are generic over the handle, which closures can't be, so [`FnSelector`] and [`FnContextualSelector`] pass a copy of each
+/// element with the handle erased. Selectors don't see handles anyway, but the previous siblings are typed by them, so they aren't
+/// copied: use [`crate::selector::AdjacentSiblingSelector`] or [`crate::selector::GeneralSiblingSelector`] around the closure instead.
+pub type ElementView<'a> = HtmlPathElement<'a, ()>;
+
+pub(super) fn view<'e, Handle>(element: &'e HtmlPathElement<'_, Handle>) -> ElementView<'e> {
+    HtmlPathElement {
+        handle: (),
+        name: element.name.clone(),
+        attrs: Cow::Borrowed(&element.attrs),
+        inherited: element.inherited.clone(),
+        position: element.position,
+        previous_sibling: None,
+        content: element.content.clone(),
+    }
+}
+
+/// A selector which calls the closure with each element, for one-off conditions which don't need a type of their own.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, selector::{FnSelector, Selector}, css_select, SelectToStrings};
+///
+/// let wide = FnSelector::new(|element| element.attr_integer("width").is_some_and(|width| width > 100));
+/// let images = parse_document(SelectToStrings::new(wide.and(css_select!("img"))), Default::default())
+///     .one(r#"<!DOCTYPE html><html><head></head><body><img width="50"><img width="200"></body></html>"#)
+///     .unwrap();
+/// assert_eq!(images, [r#"<img width="200">"#]);
+/// ```
+pub struct FnSelector<F>(F);
+
+impl<F> FnSelector<F>
+where
+    F: Fn(&ElementView<'_>) -> bool,
+{
+    pub fn new(is_match: F) -> Self {
+        Self(is_match)
+    }
+}
+
+impl<F> Selector for FnSelector<F>
+where
+    F: Fn(&ElementView<'_>) -> bool,
+{
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        (self.0)(&view(element))
+    }
+}
+
+/// A contextual selector which calls the closure with each element and its ancestors, from the root, like [`FnSelector`]. Each call
+/// copies the context, so prefer [`FnSelector`] combined with other selectors where the condition is on the element alone.
+pub struct FnContextualSelector<F>(F);
+
+impl<F> FnContextualSelector<F>
+where
+    F: Fn(&[ElementView<'_>], &ElementView<'_>) -> bool,
+{
+    pub fn new(context_match: F) -> Self {
+        Self(context_match)
+    }
+}
+
+impl<F> ContextualSelector for FnContextualSelector<F>
+where
+    F: Fn(&[ElementView<'_>], &ElementView<'_>) -> bool,
+{
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        let context = context.iter().map(view).collect::<Vec<_>>();
+        (self.0)(&context, &view(element))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{css_select, parse_document, SelectToStrings};
+
+    fn select<S: ContextualSelector>(selector: S) -> Vec<String> {
+        parse_document(SelectToStrings::new(selector), Default::default())
+            .one(r#"<!DOCTYPE html><html><head></head><body><ul lang="en"><li>One</li><li lang="fr">Deux</li></ul><ol><li>Three</li></ol></body></html>"#)
+            .unwrap()
+    }
+
+    #[test]
+    fn select_with_closures() {
+        let french = FnContextualSelector::new(|context, element| {
+            context.len() == 3 && element.inherited.lang.as_deref() == Some("fr")
+        });
+        assert_eq!(select(french), [r#"<li lang="fr">Deux</li>"#]);
+        let in_list = |list| {
+            FnContextualSelector::new(move |context, _element| {
+                context
+                    .last()
+                    .is_some_and(|parent| &*parent.name.local == list)
+            })
+        };
+        assert_eq!(select(in_list("ol")), ["<li>Three</li>"]);
+        let first =
+            FnSelector::new(|element| element.position.is_some_and(|position| position.index == 1));
+        assert_eq!(
+            select(css_select!("li").and(first).child_of(css_select!("ul"))),
+            ["<li>One</li>"]
+        );
+    }
+}
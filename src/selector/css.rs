@@ -0,0 +1,878 @@
+use std::str::FromStr;
+
+use html5ever::{namespace_url, ns, LocalName, Namespace};
+
+use super::{
+    AttrOperator, ContextualSelector, NamespaceSelector, Nth, RuleKey, SelectorParseError,
+};
+use crate::{HtmlContext, HtmlPathElement};
+
+/// A type or attribute name, as written and lowercased, as html names are compared whatever their case and foreign names as written
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Name {
+    written: LocalName,
+    lowercase: LocalName,
+}
+
+impl Name {
+    fn new(name: &str) -> Self {
+        Self {
+            written: LocalName::from(name),
+            lowercase: LocalName::from(name.to_ascii_lowercase()),
+        }
+    }
+
+    /// The name to compare with the names of the element, those of html elements being lowercase
+    fn of<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> &LocalName {
+        if element.name.ns == ns!(html) {
+            &self.lowercase
+        } else {
+            &self.written
+        }
+    }
+}
+
+/// A simple selector, one condition of a compound selector
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Simple {
+    /// `*`
+    Universal,
+    /// A type selector, matching the local name
+    Type(Name),
+    /// `#id`
+    Id(String),
+    /// `.class`
+    Class(String),
+    /// `[name]`, or with the operator, value and whether to ignore case, such as `[name=value i]`
+    Attr(Name, Option<(AttrOperator, String, bool)>),
+    /// `[prefix*]`, any attribute whose name starts with the prefix
+    AttrPrefix(Name),
+    /// `:scope`
+    Scope,
+    /// `:nth-child(an+b)`
+    NthChild(Nth),
+    /// `:nth-of-type(an+b)`
+    NthOfType(Nth),
+    /// `:last-child`
+    LastChild,
+    /// `:last-of-type`
+    LastOfType,
+    /// `:empty`
+    Empty,
+    /// `:text-contains("text")`
+    TextContains(String),
+    /// `:not()` of a compound selector
+    Not(Box<Compound>),
+}
+
+impl Simple {
+    /// Whether the element at the depth, the length of its context, matches
+    fn is_match<Handle>(
+        &self,
+        depth: usize,
+        element: &HtmlPathElement<'_, Handle>,
+        default: Option<&Namespace>,
+    ) -> bool {
+        match self {
+            Simple::Universal => true,
+            Simple::Type(name) => *name.of(element) == element.name.local,
+            Simple::Id(id) => element.id() == Some(id),
+            Simple::Class(class) => element.has_class(class),
+            Simple::Attr(name, None) => element.has_attr(name.of(element)),
+            Simple::Attr(name, Some((operator, value, false))) => element
+                .attr_local(name.of(element))
+                .is_some_and(|actual| operator.matches(actual, value)),
+            Simple::Attr(name, Some((operator, value, true))) => element
+                .attr_local(name.of(element))
+                .is_some_and(|actual| operator.matches_ignore_case(actual, value)),
+            Simple::AttrPrefix(prefix) => {
+                let prefix = prefix.of(element);
+                element
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.name.local.starts_with(&**prefix))
+            }
+            Simple::Scope => depth == 0,
+            Simple::NthChild(nth) => element
+                .position
+                .is_some_and(|position| nth.matches(position.index)),
+            Simple::NthOfType(nth) => element
+                .position
+                .is_some_and(|position| nth.matches(position.index_of_type)),
+            Simple::LastChild => element
+                .position
+                .is_some_and(|position| position.count == Some(position.index)),
+            Simple::Empty => element
+                .content
+                .as_ref()
+                .is_some_and(|content| content.empty),
+            Simple::TextContains(text) => element
+                .content
+                .as_ref()
+                .is_some_and(|content| content.text.contains(&**text)),
+            Simple::Not(compound) => !compound.is_match(depth, element, default),
+            Simple::LastOfType => element
+                .position
+                .is_some_and(|position| position.count_of_type == Some(position.index_of_type)),
+        }
+    }
+}
+
+/// How a compound selector relates to the one before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Combinator {
+    /// Whitespace
+    Descendant,
+    /// `>`
+    Child,
+    /// `+`
+    Adjacent,
+    /// `~`
+    General,
+}
+
+/// The namespace prefix of a compound selector
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Prefix {
+    /// No prefix, the default namespace if there is one
+    Default,
+    /// `*|`
+    Any,
+    /// `svg|`, or `|` for no namespace
+    Namespace(Namespace),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Compound {
+    /// The relation to the previous compound, the first's is ignored
+    combinator: Combinator,
+    prefix: Prefix,
+    simples: Vec<Simple>,
+}
+
+impl Compound {
+    fn is_match<Handle>(
+        &self,
+        depth: usize,
+        element: &HtmlPathElement<'_, Handle>,
+        default: Option<&Namespace>,
+    ) -> bool {
+        let namespace = match &self.prefix {
+            Prefix::Default => default,
+            Prefix::Any => None,
+            Prefix::Namespace(namespace) => Some(namespace),
+        };
+        namespace.map_or(true, |namespace| *namespace == element.name.ns)
+            && self
+                .simples
+                .iter()
+                .all(|simple| simple.is_match(depth, element, default))
+    }
+}
+
+/// A complex selector, compound selectors joined by combinators
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Complex {
+    compounds: Vec<Compound>,
+}
+
+impl Complex {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        default: Option<&Namespace>,
+    ) -> bool {
+        // for each compound, whether the selector up to it matches at the parent, and at any ancestor
+        let mut at_parent = vec![false; self.compounds.len()];
+        let mut at_ancestor = vec![false; self.compounds.len()];
+        for (depth, node) in context.iter().chain(Some(element)).enumerate() {
+            let here = self.here(depth, node, &at_parent, &at_ancestor, default);
+            for (ancestor, matched) in at_ancestor.iter_mut().zip(&here) {
+                *ancestor |= matched;
+            }
+            at_parent = here;
+        }
+        at_parent.last().copied().unwrap_or(false)
+    }
+
+    /// An id, class or name which every element the last compound matches has, html names are only a key if they are lowercase, as
+    /// they could match foreign elements in another case
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        self.compounds
+            .last()?
+            .simples
+            .iter()
+            .fold(None, |key, simple| {
+                let simple = match simple {
+                    Simple::Id(id) => Some(RuleKey::Id(id)),
+                    Simple::Class(class) => Some(RuleKey::Class(class)),
+                    Simple::Type(name) if name.written == name.lowercase => {
+                        Some(RuleKey::Name(&name.lowercase))
+                    }
+                    _ => None,
+                };
+                RuleKey::narrower(key, simple)
+            })
+    }
+}
+
+/// A css selector parsed at runtime, for selectors which come from configuration or arguments rather than [`crate::css_select!`].
+///
+/// Supports type selectors, `*`, `.class`, `#id`, `[attr]`, `[attr=value]` and the other attribute operators, `[data-*]` for any attribute
+/// with the prefix, `:scope`, `:nth-child(an+b)`, `:nth-of-type(an+b)`,
+/// `:first-child`, `:first-of-type`, `:last-child`, `:last-of-type`, `:empty`,
+/// `:text-contains("text")` and `:not()` of a compound selector, combined with the descendant, `>` child, and
+/// `+` and `~` sibling combinators, which need the previous siblings kept by [`crate::ParseConfig::sibling_tracking`], in a comma separated
+/// list of selectors, any of which selects an element. Type selectors match the local name, and they and attribute names match those of
+/// html elements whatever their case, and those of svg and mathml elements as they are written, such as `clipPath`. Names can have
+/// a namespace prefix, `html|`, `svg|`, `mathml|`, `|` for no namespace or `*|` for any, and those without one are in any namespace unless
+/// a [`CssSelector::default_namespace`] is set.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, selector::CssSelector, SelectToStrings};
+///
+/// let selector = CssSelector::parse("div.article > p").unwrap();
+/// let paragraphs = parse_document(SelectToStrings::new(selector), Default::default())
+///     .one(r#"<!DOCTYPE html><html><head></head><body><div class="article"><p>One</p><blockquote><p>Quoted</p></blockquote></div></body></html>"#)
+///     .unwrap();
+/// assert_eq!(paragraphs, ["<p>One</p>"]);
+///
+/// let error = CssSelector::parse("div >").unwrap_err();
+/// assert_eq!(error.to_string(), "expected a selector after '>' at offset 5");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CssSelector {
+    /// The comma separated selectors, any of which selects an element
+    complexes: Vec<Complex>,
+    default_namespace: Option<Namespace>,
+}
+
+impl CssSelector {
+    pub fn parse(selector: &str) -> Result<Self, SelectorParseError> {
+        let mut parser = Parser {
+            selector,
+            offset: 0,
+        };
+        let mut complexes = vec![];
+        loop {
+            parser.skip_whitespace();
+            complexes.push(parser.complex()?);
+            if !parser.eat(',') {
+                break;
+            }
+            parser.skip_whitespace();
+            if parser.rest().is_empty() {
+                return Err(parser.error("expected a selector after ','"));
+            }
+        }
+        Ok(Self {
+            complexes,
+            default_namespace: None,
+        })
+    }
+
+    /// Restricts the names without a namespace prefix, and compounds without a name, to the namespace, as a css `@namespace` rule
+    /// without a prefix does, so that `a` doesn't select an svg `<a>`
+    ///
+    /// ```
+    /// use html5ever::tendril::TendrilSink;
+    /// use html5streams::{parse_document, selector::{CssSelector, NamespaceSelector}, SelectToStrings};
+    ///
+    /// let links = CssSelector::parse("a[href]").unwrap().default_namespace(NamespaceSelector::HTML);
+    /// let links = parse_document(SelectToStrings::new(links), Default::default())
+    ///     .one(r#"<!DOCTYPE html><html><head></head><body><a href="/">Home</a><svg><a href="/map"></a></svg></body></html>"#)
+    ///     .unwrap();
+    /// assert_eq!(links, [r#"<a href="/">Home</a>"#]);
+    /// ```
+    pub fn default_namespace(self, namespace: NamespaceSelector) -> Self {
+        Self {
+            default_namespace: Some(Namespace::from(namespace.0)),
+            ..self
+        }
+    }
+}
+
+impl FromStr for CssSelector {
+    type Err = SelectorParseError;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        Self::parse(selector)
+    }
+}
+
+impl ContextualSelector for CssSelector {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.complexes
+            .iter()
+            .any(|complex| complex.context_match(context, element, self.default_namespace.as_ref()))
+    }
+
+    /// The key of the selector, if there is only one, as a list has no key which all the elements it selects have
+    fn subject_key(&self) -> Option<RuleKey<'_>> {
+        match &*self.complexes {
+            [complex] => complex.subject_key(),
+            _ => None,
+        }
+    }
+}
+
+impl Complex {
+    /// For each compound, whether the selector up to it matches the element at the depth, from whether it matches at the parent and
+    /// at any ancestor
+    fn here<Handle>(
+        &self,
+        depth: usize,
+        element: &HtmlPathElement<'_, Handle>,
+        at_parent: &[bool],
+        at_ancestor: &[bool],
+        default: Option<&Namespace>,
+    ) -> Vec<bool> {
+        // the siblings have the same context, so they are matched in order, from the first, up to the element
+        let mut siblings = vec![element];
+        if self.compounds.iter().any(|compound| {
+            matches!(
+                compound.combinator,
+                Combinator::Adjacent | Combinator::General
+            )
+        }) {
+            for sibling in element.previous_siblings() {
+                siblings.push(sibling);
+            }
+        }
+        let mut previous: Option<Vec<bool>> = None;
+        let mut at_earlier = vec![false; self.compounds.len()];
+        for sibling in siblings.into_iter().rev() {
+            let here = self
+                .compounds
+                .iter()
+                .enumerate()
+                .map(|(index, compound)| {
+                    let follows = match (index.checked_sub(1), compound.combinator) {
+                        (None, _) => true,
+                        (Some(before), Combinator::Child) => at_parent[before],
+                        (Some(before), Combinator::Descendant) => at_ancestor[before],
+                        (Some(before), Combinator::Adjacent) => {
+                            previous.as_ref().is_some_and(|previous| previous[before])
+                        }
+                        (Some(before), Combinator::General) => at_earlier[before],
+                    };
+                    follows && compound.is_match(depth, sibling, default)
+                })
+                .collect::<Vec<_>>();
+            for (earlier, matched) in at_earlier.iter_mut().zip(&here) {
+                *earlier |= matched;
+            }
+            previous = Some(here);
+        }
+        previous.unwrap_or_default()
+    }
+}
+
+struct Parser<'a> {
+    selector: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.selector[self.offset..]
+    }
+
+    fn error(&self, message: &'static str) -> SelectorParseError {
+        SelectorParseError::new(self.offset, message)
+    }
+
+    /// Skips whitespace, returning whether there was any
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let len = rest.len() - rest.trim_start().len();
+        self.offset += len;
+        len > 0
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.offset += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str, SelectorParseError> {
+        let rest = self.rest();
+        if rest.starts_with('\\') {
+            return Err(self.error("escapes aren't supported"));
+        }
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_') || !c.is_ascii()))
+            .unwrap_or(rest.len());
+        let ident = &rest[..len];
+        if ident.is_empty()
+            || ident
+                .trim_start_matches('-')
+                .starts_with(|c: char| c.is_ascii_digit())
+        {
+            return Err(self.error("expected an identifier"));
+        }
+        self.offset += len;
+        Ok(ident)
+    }
+
+    /// A quoted string, or an identifier
+    fn value(&mut self) -> Result<String, SelectorParseError> {
+        let rest = self.rest();
+        let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return Ok(self.ident()?.to_owned());
+        };
+        let Some(len) = rest[1..].find(quote) else {
+            return Err(self.error("unterminated string"));
+        };
+        if rest[1..=len].contains('\\') {
+            return Err(self.error("escapes aren't supported"));
+        }
+        self.offset += len + 2;
+        Ok(rest[1..=len].to_owned())
+    }
+
+    /// An attribute selector, after the `[`
+    fn attr(&mut self) -> Result<Simple, SelectorParseError> {
+        self.skip_whitespace();
+        let name = Name::new(self.ident()?);
+        if !self.rest().starts_with("*=") && self.eat('*') {
+            self.skip_whitespace();
+            if !self.eat(']') {
+                return Err(self.error("expected ']'"));
+            }
+            return Ok(Simple::AttrPrefix(name));
+        }
+        self.skip_whitespace();
+        let operator = [
+            ("=", AttrOperator::Equals),
+            ("^=", AttrOperator::Prefix),
+            ("$=", AttrOperator::Suffix),
+            ("*=", AttrOperator::Substring),
+            ("~=", AttrOperator::Word),
+            ("|=", AttrOperator::DashMatch),
+        ]
+        .iter()
+        .copied()
+        .find(|(operator, _)| self.rest().starts_with(operator));
+        let value = if let Some((symbol, operator)) = operator {
+            self.offset += symbol.len();
+            self.skip_whitespace();
+            let value = self.value()?;
+            self.skip_whitespace();
+            // an unquoted value takes a flag written without whitespace as part of its ident
+            let ignore_case = match self.rest().chars().next() {
+                Some(flag @ ('i' | 'I' | 's' | 'S')) => {
+                    self.offset += 1;
+                    self.skip_whitespace();
+                    flag.eq_ignore_ascii_case(&'i')
+                }
+                _ => false,
+            };
+            Some((operator, value, ignore_case))
+        } else {
+            None
+        };
+        if !self.eat(']') {
+            return Err(self.error("expected ']'"));
+        }
+        Ok(Simple::Attr(name, value))
+    }
+
+    /// The parenthesised `an+b` argument of a pseudo-class
+    fn nth(&mut self) -> Result<Nth, SelectorParseError> {
+        if !self.eat('(') {
+            return Err(self.error("expected '('"));
+        }
+        let rest = self.rest();
+        let Some(len) = rest.find(')') else {
+            return Err(self.error("expected ')'"));
+        };
+        let Some(nth) = Nth::parse(&rest[..len]) else {
+            return Err(self.error("expected an+b, odd or even"));
+        };
+        self.offset += len + 1;
+        Ok(nth)
+    }
+
+    /// The parenthesised string of `:text-contains()`
+    fn text_contains(&mut self) -> Result<Simple, SelectorParseError> {
+        if !self.eat('(') {
+            return Err(self.error("expected '('"));
+        }
+        self.skip_whitespace();
+        let text = self.value()?;
+        self.skip_whitespace();
+        if !self.eat(')') {
+            return Err(self.error("expected ')'"));
+        }
+        Ok(Simple::TextContains(text))
+    }
+
+    /// The parenthesised compound selector of `:not()`
+    fn not(&mut self) -> Result<Simple, SelectorParseError> {
+        if !self.eat('(') {
+            return Err(self.error("expected '('"));
+        }
+        self.skip_whitespace();
+        let negated = self.compound(Combinator::Descendant)?;
+        self.skip_whitespace();
+        if !self.eat(')') {
+            return Err(self.error("expected ')'"));
+        }
+        Ok(Simple::Not(Box::new(negated)))
+    }
+
+    /// The namespace prefix before a name, if there is one
+    fn prefix(&mut self) -> Result<Prefix, SelectorParseError> {
+        let start = self.offset;
+        let rest = self.rest();
+        let (prefix, len) = if rest.starts_with("*|") {
+            (Prefix::Any, 1)
+        } else if rest.starts_with('|') && !rest.starts_with("|=") {
+            (
+                Prefix::Namespace(Namespace::from(NamespaceSelector::NONE.0)),
+                0,
+            )
+        } else if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            let len = self.ident()?.len();
+            self.offset = start;
+            if !self.rest()[len..].starts_with('|') || self.rest()[len..].starts_with("|=") {
+                return Ok(Prefix::Default);
+            }
+            let Some(namespace) = NamespaceSelector::prefix(&rest[..len]) else {
+                return Err(self.error("unknown namespace prefix"));
+            };
+            (Prefix::Namespace(Namespace::from(namespace.0)), len)
+        } else {
+            return Ok(Prefix::Default);
+        };
+        self.offset += len + 1;
+        Ok(prefix)
+    }
+
+    /// A complex selector, up to the end or a comma
+    fn complex(&mut self) -> Result<Complex, SelectorParseError> {
+        let mut compounds = vec![self.compound(Combinator::Descendant)?];
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = [
+                ('>', Combinator::Child, "expected a selector after '>'"),
+                ('+', Combinator::Adjacent, "expected a selector after '+'"),
+                ('~', Combinator::General, "expected a selector after '~'"),
+            ]
+            .iter()
+            .copied()
+            .find(|(c, ..)| self.eat(*c));
+            let combinator = if let Some((_, combinator, missing)) = combinator {
+                self.skip_whitespace();
+                if self.rest().is_empty() {
+                    return Err(self.error(missing));
+                }
+                combinator
+            } else if self.rest().is_empty() || self.rest().starts_with(',') {
+                break;
+            } else if whitespace {
+                Combinator::Descendant
+            } else {
+                return Err(self.error("unexpected character"));
+            };
+            compounds.push(self.compound(combinator)?);
+        }
+        Ok(Complex { compounds })
+    }
+
+    fn compound(&mut self, combinator: Combinator) -> Result<Compound, SelectorParseError> {
+        let mut simples = vec![];
+        let prefix = self.prefix()?;
+        if self.eat('*') {
+            simples.push(Simple::Universal);
+        } else if self
+            .rest()
+            .starts_with(|c: char| c.is_alphabetic() || c == '_')
+        {
+            simples.push(Simple::Type(Name::new(self.ident()?)));
+        } else if prefix != Prefix::Default {
+            return Err(self.error("expected a name or '*'"));
+        }
+        loop {
+            if self.eat('.') {
+                simples.push(Simple::Class(self.ident()?.to_owned()));
+            } else if self.eat('#') {
+                simples.push(Simple::Id(self.ident()?.to_owned()));
+            } else if self.eat('[') {
+                simples.push(self.attr()?);
+            } else if self.eat(':') {
+                let start = self.offset;
+                match &*self.ident()?.to_ascii_lowercase() {
+                    "scope" => simples.push(Simple::Scope),
+                    "nth-child" => simples.push(Simple::NthChild(self.nth()?)),
+                    "nth-of-type" => simples.push(Simple::NthOfType(self.nth()?)),
+                    "first-child" => simples.push(Simple::NthChild(Nth::new(0, 1))),
+                    "first-of-type" => simples.push(Simple::NthOfType(Nth::new(0, 1))),
+                    "last-child" => simples.push(Simple::LastChild),
+                    "last-of-type" => simples.push(Simple::LastOfType),
+                    "empty" => simples.push(Simple::Empty),
+                    "text-contains" => simples.push(self.text_contains()?),
+                    "not" => simples.push(self.not()?),
+                    _ => return Err(SelectorParseError::new(start, "unsupported pseudo-class")),
+                }
+            } else {
+                break;
+            }
+        }
+        if simples.is_empty() {
+            return Err(self.error("expected a selector"));
+        }
+        Ok(Compound {
+            combinator,
+            prefix,
+            simples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, ParseConfig, SelectToStrings, SiblingTracking};
+
+    #[test]
+    fn select_with_parsed_selector() {
+        let select = |selector: &str| {
+            parse_document(
+                SelectToStrings::new(selector.parse::<CssSelector>().unwrap()),
+                Default::default(),
+            )
+            .one(r#"<!DOCTYPE html><html><head></head><body><div id="main" class="article wide"><p>One</p><section><p class="note">Two</p></section></div><p>Three</p></body></html>"#)
+            .unwrap()
+        };
+        assert_eq!(
+            select("#main p"),
+            ["<p>One</p>", r#"<p class="note">Two</p>"#]
+        );
+        assert_eq!(select("DIV.wide.article>p"), ["<p>One</p>"]);
+        assert_eq!(
+            select("body > * > section  .note"),
+            [r#"<p class="note">Two</p>"#]
+        );
+        assert_eq!(select(":scope > body > p"), ["<p>Three</p>"]);
+        assert_eq!(select("section p.article"), Vec::<String>::new());
+        assert_eq!(select("[ID=main] [class]"), [r#"<p class="note">Two</p>"#]);
+        assert_eq!(select("p:not(.note)"), ["<p>One</p>", "<p>Three</p>"]);
+        assert_eq!(
+            select("#main > :not( p )"),
+            [r#"<section><p class="note">Two</p></section>"#]
+        );
+        assert_eq!(
+            select("body > :not(div):not(:first-child)"),
+            ["<p>Three</p>"]
+        );
+        assert_eq!(select(r#"div[class="article wide"] > p"#), ["<p>One</p>"]);
+        assert_eq!(select("p[class='note']"), [r#"<p class="note">Two</p>"#]);
+        assert_eq!(select("[class^=art] > p"), ["<p>One</p>"]);
+        assert_eq!(
+            select("[class ~= 'wide'] [class$=te]"),
+            [r#"<p class="note">Two</p>"#]
+        );
+        assert_eq!(select("div[class*='e w'] > p"), ["<p>One</p>"]);
+        assert_eq!(select("[id=MAIN] > p"), Vec::<String>::new());
+        assert_eq!(select("[id=MAIN i] > p"), ["<p>One</p>"]);
+        assert_eq!(select("[class~='WIDE'I]>p"), ["<p>One</p>"]);
+        assert_eq!(select("[id='MAIN' s] > p"), Vec::<String>::new());
+        assert_eq!(select("[id=MAINi] > p"), Vec::<String>::new());
+        assert_eq!(
+            select("* > * > * > *"),
+            [
+                "<p>One</p>",
+                r#"<section><p class="note">Two</p></section>"#
+            ]
+        );
+        assert_eq!(
+            select("[CLASS*] > *"),
+            ["<p>One</p>", "<section><p class=\"note\">Two</p></section>"]
+        );
+        assert_eq!(
+            select("[i*]"),
+            [
+                r#"<div id="main" class="article wide"><p>One</p><section><p class="note">Two</p></section></div>"#
+            ]
+        );
+    }
+
+    #[test]
+    fn select_namespaces() {
+        let select = |selector: CssSelector| {
+            parse_document(SelectToStrings::new(selector), Default::default())
+                .one(r#"<!DOCTYPE html><html><head></head><body><a href="/">Home</a><svg><a href="/map"><title>Map</title></a></svg><math><mi>x</mi></math></body></html>"#)
+                .unwrap()
+        };
+        let parse = |selector: &str| selector.parse::<CssSelector>().unwrap();
+        let svg_link = r#"<a href="/map"><title>Map</title></a>"#;
+        assert_eq!(select(parse("a")), [r#"<a href="/">Home</a>"#, svg_link]);
+        assert_eq!(select(parse("svg|a")), [svg_link]);
+        assert_eq!(select(parse("body > HTML|*")), [r#"<a href="/">Home</a>"#]);
+        assert_eq!(select(parse("svg a > *|title")), ["<title>Map</title>"]);
+        assert_eq!(select(parse("mathml|math > mathml|mi")), ["<mi>x</mi>"]);
+        assert_eq!(select(parse("|a")), Vec::<String>::new());
+        assert_eq!(
+            select(parse("svg a").default_namespace(NamespaceSelector::HTML)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            select(parse("*|svg *|a").default_namespace(NamespaceSelector::HTML)),
+            [svg_link]
+        );
+        assert_eq!(
+            select(parse("svg|svg > :not(title)").default_namespace(NamespaceSelector::SVG)),
+            [svg_link]
+        );
+        assert_eq!(select(parse("[href|=x]")), Vec::<String>::new());
+    }
+
+    #[test]
+    fn match_foreign_names_as_written() {
+        let select = |selector: &str| {
+            parse_document(
+                SelectToStrings::new(selector.parse::<CssSelector>().unwrap()),
+                Default::default(),
+            )
+            .one(r#"<!DOCTYPE html><html><head></head><body><DIV ID="d">d</DIV><svg viewBox="0 0 1 1"><clipPath id="c"></clipPath><foreignObject><p>f</p></foreignObject></svg></body></html>"#)
+            .unwrap()
+        };
+        let clip = r#"<clipPath id="c"></clipPath>"#;
+        assert_eq!(select("clipPath"), [clip]);
+        assert_eq!(select("svg|clipPath"), [clip]);
+        assert_eq!(select("clippath"), Vec::<String>::new());
+        assert_eq!(select("CLIPPATH"), Vec::<String>::new());
+        assert_eq!(select("foreignObject > P"), ["<p>f</p>"]);
+        assert_eq!(select("Div[Id=d]"), [r#"<div id="d">d</div>"#]);
+        assert_eq!(select("[viewBox] > [id]"), [clip]);
+        assert_eq!(select("[viewbox]"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn select_lists() {
+        let select = |selector: &str| {
+            parse_document(
+                SelectToStrings::new(selector.parse::<CssSelector>().unwrap()),
+                Default::default(),
+            )
+            .one(r#"<!DOCTYPE html><html><head></head><body><h1>Title</h1><p class="a">1</p><p>2</p><h2>Sub</h2></body></html>"#)
+            .unwrap()
+        };
+        assert_eq!(
+            select("h1, p.a,h2"),
+            ["<h1>Title</h1>", r#"<p class="a">1</p>"#, "<h2>Sub</h2>"]
+        );
+        assert_eq!(select("p , p.a"), [r#"<p class="a">1</p>"#, "<p>2</p>"]);
+        assert_eq!(CssSelector::parse("h1, p").unwrap().subject_key(), None);
+        assert_eq!(
+            CssSelector::parse("div h1").unwrap().subject_key(),
+            Some(RuleKey::Name("h1"))
+        );
+    }
+
+    #[test]
+    fn select_siblings() {
+        let select = |selector: &str| {
+            ParseConfig::default()
+                .sibling_tracking(SiblingTracking::All)
+                .parse_document(SelectToStrings::new(selector.parse::<CssSelector>().unwrap()))
+                .one("<!DOCTYPE html><html><head></head><body><h2>A</h2><p>1</p><p>2</p><div><h2>B</h2>text<p>3</p></div><h3>C</h3><p>4</p></body></html>")
+            .unwrap()
+        };
+        assert_eq!(select("h2 + p"), ["<p>1</p>", "<p>3</p>"]);
+        assert_eq!(
+            select("h2 ~ p"),
+            ["<p>1</p>", "<p>2</p>", "<p>3</p>", "<p>4</p>"]
+        );
+        assert_eq!(
+            select("body > h2 ~ p"),
+            ["<p>1</p>", "<p>2</p>", "<p>4</p>"]
+        );
+        assert_eq!(select("h2+p+p"), ["<p>2</p>"]);
+        assert_eq!(select("h2 ~ div p"), ["<p>3</p>"]);
+        assert_eq!(select("h2 ~ h3 + p"), ["<p>4</p>"]);
+        assert_eq!(select("p + h2"), Vec::<String>::new());
+        assert_eq!(
+            select("body > :nth-child(2n+1)"),
+            ["<h2>A</h2>", "<p>2</p>", "<h3>C</h3>"]
+        );
+        assert_eq!(
+            select("body > p:nth-of-type( odd )"),
+            ["<p>1</p>", "<p>4</p>"]
+        );
+        assert_eq!(select("p:NTH-CHILD(-n+2)"), ["<p>1</p>", "<p>3</p>"]);
+        assert_eq!(
+            select("body :first-child:first-of-type"),
+            ["<h2>A</h2>", "<h2>B</h2>"]
+        );
+        // without a lookahead buffer nothing is known to be last
+        assert_eq!(select("p:last-child"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |selector| CssSelector::parse(selector).unwrap_err();
+        assert_eq!(error(""), SelectorParseError::new(0, "expected a selector"));
+        assert_eq!(
+            error("p..x"),
+            SelectorParseError::new(2, "expected an identifier")
+        );
+        assert_eq!(error("p > > a").position, 4);
+        assert_eq!(
+            error("p ~"),
+            SelectorParseError::new(3, "expected a selector after '~'")
+        );
+        assert_eq!(error("p:hover").message, "unsupported pseudo-class");
+        assert_eq!(
+            error("li:nth-child(2x)"),
+            SelectorParseError::new(13, "expected an+b, odd or even")
+        );
+        assert_eq!(error("li:nth-child(2").message, "expected ')'");
+        assert_eq!(
+            error("p:not(a b)"),
+            SelectorParseError::new(8, "expected ')'")
+        );
+        assert_eq!(error("p:not()").message, "expected a selector");
+        assert_eq!(
+            error("p; a"),
+            SelectorParseError::new(1, "unexpected character")
+        );
+        assert_eq!(
+            error("p, "),
+            SelectorParseError::new(3, "expected a selector after ','")
+        );
+        assert_eq!(
+            error("p,,a"),
+            SelectorParseError::new(2, "expected a selector")
+        );
+        assert_eq!(error(".2col").position, 1);
+        assert_eq!(error("[href").message, "expected ']'");
+        assert_eq!(error("[href='x]").message, "unterminated string");
+        assert_eq!(
+            error("[data-* x]"),
+            SelectorParseError::new(8, "expected ']'")
+        );
+        assert_eq!(
+            error("p xlink|a"),
+            SelectorParseError::new(2, "unknown namespace prefix")
+        );
+        assert_eq!(
+            error("svg|.x"),
+            SelectorParseError::new(4, "expected a name or '*'")
+        );
+    }
+}
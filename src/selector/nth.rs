@@ -0,0 +1,257 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use super::{Selector, SelectorParseError};
+use crate::HtmlPathElement;
+
+/// The `an+b` argument of `:nth-child` and `:nth-of-type`, matching the positions `a * n + b` for any `n` from 0, counting from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nth {
+    a: i32,
+    b: i32,
+}
+
+impl Nth {
+    /// `odd`, `2n+1`
+    pub const ODD: Nth = Nth::new(2, 1);
+    /// `even`, `2n`
+    pub const EVEN: Nth = Nth::new(2, 0);
+
+    pub const fn new(a: i32, b: i32) -> Self {
+        Self { a, b }
+    }
+
+    /// Parses the `an+b` syntax, also accepting `odd` and `even`, `None` if it isn't valid. It is a const fn so that
+    /// [`crate::css_select!`] checks its arguments as it compiles.
+    pub const fn parse(nth: &str) -> Option<Self> {
+        let bytes = nth.as_bytes();
+        let mut i = skip_whitespace(bytes, 0);
+        if let Some(end) = keyword(bytes, i, b"odd") {
+            return if skip_whitespace(bytes, end) == bytes.len() {
+                Some(Self::ODD)
+            } else {
+                None
+            };
+        }
+        if let Some(end) = keyword(bytes, i, b"even") {
+            return if skip_whitespace(bytes, end) == bytes.len() {
+                Some(Self::EVEN)
+            } else {
+                None
+            };
+        }
+        let negative = i < bytes.len() && bytes[i] == b'-';
+        if i < bytes.len() && (bytes[i] == b'-' || bytes[i] == b'+') {
+            i += 1;
+        }
+        let (digits, end) = match number(bytes, i) {
+            Some((digits, end)) => (Some(digits), end),
+            None => (None, i),
+        };
+        i = end;
+        let (a, b) = if i < bytes.len() && (bytes[i] == b'n' || bytes[i] == b'N') {
+            let a = match digits {
+                Some(a) => a,
+                None => 1,
+            };
+            i = skip_whitespace(bytes, i + 1);
+            let b = if i == bytes.len() {
+                0
+            } else if bytes[i] == b'+' || bytes[i] == b'-' {
+                let negative = bytes[i] == b'-';
+                i = skip_whitespace(bytes, i + 1);
+                match number(bytes, i) {
+                    Some((b, end)) => {
+                        i = end;
+                        if negative {
+                            -b
+                        } else {
+                            b
+                        }
+                    }
+                    None => return None,
+                }
+            } else {
+                return None;
+            };
+            (if negative { -a } else { a }, b)
+        } else {
+            match digits {
+                Some(b) => (0, if negative { -b } else { b }),
+                None => return None,
+            }
+        };
+        if skip_whitespace(bytes, i) == bytes.len() {
+            Some(Self::new(a, b))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the position, counting from 1, is one of those selected
+    pub fn matches(&self, position: usize) -> bool {
+        let (a, b) = (i64::from(self.a), i64::from(self.b));
+        let Ok(position) = i64::try_from(position) else {
+            return false;
+        };
+        if a == 0 {
+            position == b
+        } else {
+            let steps = position - b;
+            steps % a == 0 && steps / a >= 0
+        }
+    }
+}
+
+impl FromStr for Nth {
+    type Err = SelectorParseError;
+
+    fn from_str(nth: &str) -> Result<Self, Self::Err> {
+        Self::parse(nth).ok_or_else(|| SelectorParseError::new(0, "expected an+b, odd or even"))
+    }
+}
+
+const fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// The end of the keyword if it is at `i`, ignoring case
+const fn keyword(bytes: &[u8], i: usize, keyword: &[u8]) -> Option<usize> {
+    if bytes.len() - i < keyword.len() {
+        return None;
+    }
+    let mut k = 0;
+    while k < keyword.len() {
+        if bytes[i + k].to_ascii_lowercase() != keyword[k] {
+            return None;
+        }
+        k += 1;
+    }
+    Some(i + k)
+}
+
+/// The number of the digits at `i` and their end, `None` if there are none or it overflows
+const fn number(bytes: &[u8], mut i: usize) -> Option<(i32, usize)> {
+    let start = i;
+    let mut number: i32 = 0;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        number = match number.checked_mul(10) {
+            Some(number) => match number.checked_add((bytes[i] - b'0') as i32) {
+                Some(number) => number,
+                None => return None,
+            },
+            None => return None,
+        };
+        i += 1;
+    }
+    if i == start {
+        None
+    } else {
+        Some((number, i))
+    }
+}
+
+/// Selects elements by their position among all their element siblings in the source, `:nth-child`
+pub struct NthChildSelector(pub Nth);
+
+impl Selector for NthChildSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .position
+            .is_some_and(|position| self.0.matches(position.index))
+    }
+}
+
+/// Selects elements by their position among their element siblings with the same name in the source, `:nth-of-type`
+pub struct NthOfTypeSelector(pub Nth);
+
+impl Selector for NthOfTypeSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .position
+            .is_some_and(|position| self.0.matches(position.index_of_type))
+    }
+}
+
+/// Selects elements which are the first of their element siblings, `:first-child`
+pub struct FirstChildSelector;
+
+impl Selector for FirstChildSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element.position.is_some_and(|position| position.index == 1)
+    }
+}
+
+/// Selects elements which are the first of their element siblings with the same name, `:first-of-type`
+pub struct FirstOfTypeSelector;
+
+impl Selector for FirstOfTypeSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .position
+            .is_some_and(|position| position.index_of_type == 1)
+    }
+}
+
+/// Selects elements which are the last of their element siblings, `:last-child`. It needs the siblings to have been counted by a
+/// [`crate::LookaheadBuffer`], and doesn't select anything otherwise.
+pub struct LastChildSelector;
+
+impl Selector for LastChildSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .position
+            .is_some_and(|position| position.count == Some(position.index))
+    }
+}
+
+/// Selects elements which are the last of their element siblings with the same name, `:last-of-type`. Like [`LastChildSelector`] it
+/// needs a [`crate::LookaheadBuffer`].
+pub struct LastOfTypeSelector;
+
+impl Selector for LastOfTypeSelector {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        element
+            .position
+            .is_some_and(|position| position.count_of_type == Some(position.index_of_type))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_nth() {
+        assert_eq!(Nth::parse("odd"), Some(Nth::ODD));
+        assert_eq!(Nth::parse(" EVEN "), Some(Nth::EVEN));
+        assert_eq!(Nth::parse("2n+1"), Some(Nth::new(2, 1)));
+        assert_eq!(Nth::parse("-n + 3"), Some(Nth::new(-1, 3)));
+        assert_eq!(Nth::parse("+5"), Some(Nth::new(0, 5)));
+        assert_eq!(Nth::parse("n"), Some(Nth::new(1, 0)));
+        assert_eq!(Nth::parse("3n- 2"), Some(Nth::new(3, -2)));
+        assert_eq!(Nth::parse(""), None);
+        assert_eq!(Nth::parse("2n+"), None);
+        assert_eq!(Nth::parse("odd2"), None);
+        assert_eq!(Nth::parse("n2"), None);
+        assert_eq!(Nth::parse("99999999999"), None);
+        assert_eq!(
+            "x".parse::<Nth>(),
+            Err(SelectorParseError::new(0, "expected an+b, odd or even"))
+        );
+    }
+
+    #[test]
+    fn match_positions() {
+        let matching = |nth: Nth| (1..=10).filter(|i| nth.matches(*i)).collect::<Vec<_>>();
+        assert_eq!(matching(Nth::ODD), [1, 3, 5, 7, 9]);
+        assert_eq!(matching(Nth::EVEN), [2, 4, 6, 8, 10]);
+        assert_eq!(matching(Nth::new(0, 3)), [3]);
+        assert_eq!(matching(Nth::new(-1, 3)), [1, 2, 3]);
+        assert_eq!(matching(Nth::new(3, -2)), [1, 4, 7, 10]);
+        assert_eq!(matching(Nth::new(1, 8)), [8, 9, 10]);
+        assert_eq!(matching(Nth::new(0, 0)), Vec::<usize>::new());
+    }
+}
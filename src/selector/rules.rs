@@ -0,0 +1,285 @@
+use std::{collections::HashMap, iter::FromIterator};
+
+use super::{ContextualSelector, SelectorList};
+use crate::{HtmlContext, HtmlPathElement};
+
+/// An id, class or name which every element a selector selects has, see [`super::ContextualSelector::subject_key`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RuleKey<'a> {
+    /// `#id`
+    Id(&'a str),
+    /// `.class`
+    Class(&'a str),
+    /// The local name of a type selector
+    Name(&'a str),
+}
+
+impl<'a> RuleKey<'a> {
+    /// The key which fewer elements have, of two conditions which both hold: an id before a class before a name
+    pub fn narrower(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Many labelled selectors, which reports which of them select each element, for sinks which dispatch on dozens of rules.
+///
+/// Rules are bucketed by their [`ContextualSelector::subject_key`], as browsers do with style rules, so each element is only matched
+/// against the rules for its id, its classes and its name, and those without a key. The selectors built by [`crate::css_select!`] and
+/// [`super::CssSelector`] have keys where their last compound has an id, class or type selector. Matches are reported in the order the
+/// rules were added.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, selector::{CssSelector, RuleSet}, SelectToStrings};
+///
+/// let rules: RuleSet<&str, CssSelector> = [("nav", "nav a"), ("ads", ".ad"), ("images", "img[src]")]
+///     .iter()
+///     .map(|(label, selector)| (*label, selector.parse().unwrap()))
+///     .collect();
+/// // any rule selects an element, as a selector
+/// let selected = parse_document(SelectToStrings::new(rules), Default::default())
+///     .one(r#"<!DOCTYPE html><html><head></head><body><nav><a href="/">Home</a></nav><p class="ad">Buy</p><p>Text</p></body></html>"#)
+///     .unwrap();
+/// assert_eq!(selected, [r#"<a href="/">Home</a>"#, r#"<p class="ad">Buy</p>"#]);
+/// ```
+pub struct RuleSet<L, S> {
+    rules: Vec<(L, S)>,
+    by_id: HashMap<String, Vec<usize>>,
+    by_class: HashMap<String, Vec<usize>>,
+    by_name: HashMap<String, Vec<usize>>,
+    /// The rules without a key, which are matched against every element
+    unkeyed: Vec<usize>,
+}
+
+impl<L, S: ContextualSelector> RuleSet<L, S> {
+    pub fn new() -> Self {
+        Self {
+            rules: vec![],
+            by_id: HashMap::new(),
+            by_class: HashMap::new(),
+            by_name: HashMap::new(),
+            unkeyed: vec![],
+        }
+    }
+
+    /// Adds a rule
+    pub fn rule(mut self, label: L, selector: S) -> Self {
+        self.insert(label, selector);
+        self
+    }
+
+    /// Adds a rule, returning its index
+    pub fn insert(&mut self, label: L, selector: S) -> usize {
+        let index = self.rules.len();
+        let bucket = match selector.subject_key() {
+            Some(RuleKey::Id(id)) => self.by_id.entry(id.to_owned()).or_default(),
+            Some(RuleKey::Class(class)) => self.by_class.entry(class.to_owned()).or_default(),
+            Some(RuleKey::Name(name)) => self.by_name.entry(name.to_owned()).or_default(),
+            None => &mut self.unkeyed,
+        };
+        bucket.push(index);
+        self.rules.push((label, selector));
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The label and selector of the rule at the index
+    pub fn get(&self, index: usize) -> Option<(&L, &S)> {
+        self.rules
+            .get(index)
+            .map(|(label, selector)| (label, selector))
+    }
+
+    /// The index and label of each rule which selects the element, in the order they were added
+    pub fn matches<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Vec<(usize, &L)> {
+        self.candidates(element)
+            .into_iter()
+            .filter(|&index| self.rules[index].1.context_match(context, element))
+            .map(|index| (index, &self.rules[index].0))
+            .collect()
+    }
+
+    /// The rules which can select the element by its key, in order
+    fn candidates<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> Vec<usize> {
+        let mut candidates = self.unkeyed.clone();
+        let mut add = |bucket: Option<&Vec<usize>>| candidates.extend(bucket.into_iter().flatten());
+        add(self.by_name.get(&*element.name.local));
+        if let Some(id) = element.id() {
+            add(self.by_id.get(id));
+        }
+        for class in element.classes() {
+            add(self.by_class.get(class));
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl<L, S: ContextualSelector> Default for RuleSet<L, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L, S: ContextualSelector> FromIterator<(L, S)> for RuleSet<L, S> {
+    fn from_iter<I: IntoIterator<Item = (L, S)>>(rules: I) -> Self {
+        let mut set = Self::new();
+        set.extend(rules);
+        set
+    }
+}
+
+impl<L, S: ContextualSelector> Extend<(L, S)> for RuleSet<L, S> {
+    fn extend<I: IntoIterator<Item = (L, S)>>(&mut self, rules: I) {
+        for (label, selector) in rules {
+            self.insert(label, selector);
+        }
+    }
+}
+
+/// The first rule which selects the element, so a rule set can be the list of an [`super::AnyOf`]
+impl<L, S: ContextualSelector> SelectorList for RuleSet<L, S> {
+    fn first_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> Option<usize> {
+        self.candidates(element)
+            .into_iter()
+            .find(|&index| self.rules[index].1.context_match(context, element))
+    }
+}
+
+impl<L, S: ContextualSelector> ContextualSelector for RuleSet<L, S> {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        self.first_match(context, element).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::StrTendril;
+
+    use super::*;
+    use crate::{
+        css_select,
+        selector::{CssSelector, DynContextualSelector, ElementSelector},
+        test::stream_doc,
+        HtmlSink,
+    };
+
+    struct Dispatch {
+        rules: RuleSet<&'static str, Box<dyn DynContextualSelector>>,
+        matched: Vec<(String, Vec<&'static str>)>,
+    }
+
+    impl HtmlSink<u32> for &mut Dispatch {
+        type Output = ();
+
+        fn append_doctype_to_document(
+            &mut self,
+            _name: &StrTendril,
+            _public_id: &StrTendril,
+            _system_id: &StrTendril,
+        ) {
+        }
+
+        fn append_element(
+            &mut self,
+            context: HtmlContext<'_, u32>,
+            element: &HtmlPathElement<'_, u32>,
+        ) {
+            let labels = self
+                .rules
+                .matches(context, element)
+                .into_iter()
+                .map(|(_, label)| *label)
+                .collect::<Vec<_>>();
+            if !labels.is_empty() {
+                self.matched.push((element.name.local.to_string(), labels));
+            }
+        }
+
+        fn append_text(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn append_comment(&mut self, _context: HtmlContext<u32>, _text: &str) {}
+
+        fn reset(&mut self) -> Self::Output {}
+    }
+
+    #[test]
+    fn report_matching_rules() {
+        let mut dispatch = Dispatch {
+            rules: RuleSet::new()
+                .rule(
+                    "links",
+                    Box::new(css_select!("a")) as Box<dyn DynContextualSelector>,
+                )
+                .rule("main", Box::new(css_select!(#"main")))
+                .rule(
+                    "nav links",
+                    Box::new("nav a.home".parse::<CssSelector>().unwrap()),
+                )
+                .rule("first", Box::new(css_select!(:first-child)))
+                .rule("notes", Box::new(css_select!(."note")))
+                .rule("scope", Box::new(css_select!(:scope))),
+            matched: vec![],
+        };
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><nav><a class="home note" href="/">Home</a></nav><p id="main" class="note note">Text <a href="/x">x</a></p></body></html>"#,
+            &mut dispatch,
+        );
+        assert_eq!(
+            dispatch.matched,
+            [
+                ("html".to_owned(), vec!["first", "scope"]),
+                ("head".to_owned(), vec!["first"]),
+                ("nav".to_owned(), vec!["first"]),
+                ("a".to_owned(), vec!["links", "nav links", "first", "notes"]),
+                ("p".to_owned(), vec!["main", "notes"]),
+                ("a".to_owned(), vec!["links", "first"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn selector_keys() {
+        assert_eq!(css_select!("a").subject_key(), Some(RuleKey::Name("a")));
+        assert_eq!(
+            css_select!("div" > "a"."x").subject_key(),
+            Some(RuleKey::Class("x"))
+        );
+        assert_eq!(
+            "ul li#top.x".parse::<CssSelector>().unwrap().subject_key(),
+            Some(RuleKey::Id("top"))
+        );
+        assert_eq!(
+            "p:not(.x)".parse::<CssSelector>().unwrap().subject_key(),
+            Some(RuleKey::Name("p"))
+        );
+        assert_eq!(css_select!("a", "b").subject_key(), None);
+        assert_eq!(
+            ElementSelector::default().class("x".into()).subject_key(),
+            Some(RuleKey::Class("x"))
+        );
+    }
+}
@@ -0,0 +1,707 @@
+use std::{cell::Cell, fmt, iter, ptr, str::FromStr};
+
+use cssparser::{
+    BasicParseErrorKind, CowRcStr, ParseError, ParseErrorKind, ParserInput, SourceLocation, ToCss,
+};
+use html5ever::{namespace_url, ns, LocalName, Namespace};
+use selectors::{
+    attr::{AttrSelectorOperation, CaseSensitivity, NamespaceConstraint},
+    context::{MatchingContext, MatchingMode, QuirksMode},
+    matching::{matches_selector, ElementSelectorFlags},
+    parser::{self, SelectorList, SelectorParseErrorKind},
+    visitor::{SelectorVisitor, Visit},
+    OpaqueElement,
+};
+
+use super::{ContextualSelector, NamespaceSelector, SelectorParseError};
+use crate::{HtmlContext, HtmlPathElement};
+
+/// A selector list parsed and matched by servo's `selectors` crate, the engine behind browsers' css, for the spec-complete syntax
+/// where [`super::CssSelector`]'s subset isn't enough, such as escapes, `:root`, `:link` and attribute namespaces.
+///
+/// The element sees its ancestors and previous siblings. The pseudo-classes which depend on the siblings after it, `:last-child`,
+/// `:only-child`, `:last-of-type`, `:only-of-type` and `:nth-last-*`, count them from [`crate::SiblingPosition::count`], so they
+/// need a [`crate::LookaheadBuffer`]: a selector which needs the later siblings of an element without the count selects nothing,
+/// even under `:not()`. `:empty` and the non-standard `:text-contains("text")` only select anything behind a [`crate::ContentBuffer`]. The sibling combinators need the
+/// previous siblings kept by [`crate::ParseConfig::sibling_tracking`], while `:nth-child()` and the like count from the element's
+/// position without them. The namespace prefixes are those of [`NamespaceSelector::prefix`].
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, selector::ServoSelector, SelectToStrings};
+///
+/// let selector: ServoSelector = "ul:not(.draft) > li:nth-child(odd)".parse().unwrap();
+/// let items = parse_document(SelectToStrings::new(selector), Default::default())
+///     .one("<!DOCTYPE html><html><head></head><body><ul class=draft><li>A</li></ul><ul><li>B</li><li>C</li><li>D</li></ul></body></html>")
+///     .unwrap();
+/// assert_eq!(items, ["<li>B</li>", "<li>D</li>"]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServoSelector {
+    selectors: SelectorList<Impl>,
+}
+
+impl ServoSelector {
+    pub fn parse(selector: &str) -> Result<Self, SelectorParseError> {
+        let mut input = ParserInput::new(selector);
+        let selectors = SelectorList::parse(&Parser, &mut cssparser::Parser::new(&mut input))
+            .map_err(|error| {
+                SelectorParseError::new(offset(selector, error.location), message(error.kind))
+            })?;
+        Ok(Self { selectors })
+    }
+}
+
+impl FromStr for ServoSelector {
+    type Err = SelectorParseError;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        Self::parse(selector)
+    }
+}
+
+impl ContextualSelector for ServoSelector {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        let mut matching =
+            MatchingContext::new(MatchingMode::Normal, None, None, QuirksMode::NoQuirks);
+        let uncounted = Cell::new(false);
+        let node = Node::new(context, element, &uncounted);
+        self.selectors.0.iter().any(|selector| {
+            uncounted.set(false);
+            matches_selector(selector, 0, None, &node, &mut matching, &mut |_, _| {})
+                && !uncounted.get()
+        })
+    }
+}
+
+/// The byte offset of the location, which counts utf-16 code units from 1 within its line
+fn offset(selector: &str, location: SourceLocation) -> usize {
+    let line = selector
+        .split_inclusive('\n')
+        .take(location.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+    let mut units = location.column.saturating_sub(1) as usize;
+    let column = selector[line..]
+        .char_indices()
+        .find(|(_, c)| {
+            let found = units == 0;
+            units = units.saturating_sub(c.len_utf16());
+            found
+        })
+        .map_or(selector.len() - line, |(i, _)| i);
+    line + column
+}
+
+fn message(kind: ParseErrorKind<'_, SelectorParseErrorKind<'_>>) -> String {
+    match kind {
+        ParseErrorKind::Basic(BasicParseErrorKind::EndOfInput) => {
+            "unexpected end of selector".into()
+        }
+        ParseErrorKind::Basic(BasicParseErrorKind::UnexpectedToken(token))
+        | ParseErrorKind::Custom(SelectorParseErrorKind::BadValueInAttr(token))
+        | ParseErrorKind::Custom(SelectorParseErrorKind::NoIdentForPseudo(token)) => {
+            format!("unexpected '{}'", token.to_css_string())
+        }
+        ParseErrorKind::Custom(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name)) => {
+            format!("unsupported pseudo-class or element '{}'", name)
+        }
+        ParseErrorKind::Custom(SelectorParseErrorKind::ExpectedNamespace(_)) => {
+            "unknown namespace prefix".into()
+        }
+        ParseErrorKind::Custom(SelectorParseErrorKind::EmptySelector) => {
+            "expected a selector".into()
+        }
+        ParseErrorKind::Custom(SelectorParseErrorKind::DanglingCombinator) => {
+            "expected a selector after the combinator".into()
+        }
+        kind => format!("{:?}", kind),
+    }
+}
+
+struct Parser;
+
+impl<'i> parser::Parser<'i> for Parser {
+    type Impl = Impl;
+    type Error = SelectorParseErrorKind<'i>;
+
+    fn namespace_for_prefix(&self, prefix: &LocalName) -> Option<Namespace> {
+        NamespaceSelector::prefix(prefix).map(|namespace| Namespace::from(namespace.0))
+    }
+
+    fn parse_non_ts_pseudo_class(
+        &self,
+        location: SourceLocation,
+        name: CowRcStr<'i>,
+    ) -> Result<NonTSPseudoClass, ParseError<'i, Self::Error>> {
+        if name.eq_ignore_ascii_case("link") {
+            Ok(NonTSPseudoClass::Link)
+        } else if name.eq_ignore_ascii_case("any-link") {
+            Ok(NonTSPseudoClass::AnyLink)
+        } else {
+            Err(
+                location.new_custom_error(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(
+                    name,
+                )),
+            )
+        }
+    }
+
+    fn parse_non_ts_functional_pseudo_class<'t>(
+        &self,
+        name: CowRcStr<'i>,
+        arguments: &mut cssparser::Parser<'i, 't>,
+    ) -> Result<NonTSPseudoClass, ParseError<'i, Self::Error>> {
+        if name.eq_ignore_ascii_case("text-contains") {
+            let text = arguments.expect_string()?;
+            Ok(NonTSPseudoClass::TextContains(text.to_string()))
+        } else {
+            Err(arguments.new_custom_error(
+                SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name),
+            ))
+        }
+    }
+}
+
+/// The types the `selectors` crate parses into, with html5ever's atoms for the names
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Impl;
+
+impl parser::SelectorImpl for Impl {
+    type ExtraMatchingData = ();
+    type AttrValue = String;
+    type Identifier = LocalName;
+    type ClassName = LocalName;
+    type PartName = LocalName;
+    type LocalName = LocalName;
+    type NamespaceUrl = Namespace;
+    type NamespacePrefix = LocalName;
+    type BorrowedNamespaceUrl = Namespace;
+    type BorrowedLocalName = LocalName;
+    type NonTSPseudoClass = NonTSPseudoClass;
+    type PseudoElement = PseudoElement;
+}
+
+/// The pseudo-classes beyond the tree-structural ones which the `selectors` crate parses itself, those which a stream can decide.
+/// Every link is unvisited, so `:visited` is left out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum NonTSPseudoClass {
+    /// `:link`
+    Link,
+    /// `:any-link`
+    AnyLink,
+    /// `:text-contains("text")`, which needs a [`crate::ContentBuffer`]
+    TextContains(String),
+}
+
+impl parser::NonTSPseudoClass for NonTSPseudoClass {
+    type Impl = Impl;
+
+    fn is_active_or_hover(&self) -> bool {
+        false
+    }
+
+    fn is_user_action_state(&self) -> bool {
+        false
+    }
+
+    fn has_zero_specificity(&self) -> bool {
+        false
+    }
+}
+
+impl Visit for NonTSPseudoClass {
+    type Impl = Impl;
+
+    fn visit<V>(&self, _visitor: &mut V) -> bool
+    where
+        V: SelectorVisitor<Impl = Impl>,
+    {
+        true
+    }
+}
+
+impl ToCss for NonTSPseudoClass {
+    fn to_css<W: fmt::Write>(&self, dest: &mut W) -> fmt::Result {
+        match self {
+            NonTSPseudoClass::Link => dest.write_str(":link"),
+            NonTSPseudoClass::AnyLink => dest.write_str(":any-link"),
+            NonTSPseudoClass::TextContains(text) => {
+                dest.write_str(":text-contains(")?;
+                cssparser::serialize_string(text, dest)?;
+                dest.write_str(")")
+            }
+        }
+    }
+}
+
+/// No pseudo-elements, which don't stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PseudoElement {}
+
+impl parser::PseudoElement for PseudoElement {
+    type Impl = Impl;
+}
+
+impl ToCss for PseudoElement {
+    fn to_css<W: fmt::Write>(&self, _dest: &mut W) -> fmt::Result {
+        match *self {}
+    }
+}
+
+/// An element in its context, as the `selectors` crate walks it.
+///
+/// Without [`crate::SiblingTracking`] the earlier siblings are known only by the number of them in the element's position, so they
+/// are walked as unknown elements which match no names or attributes, for `:nth-child()` and `:first-child` to count. Those with
+/// the name of the element are counted first, from its `:nth-of-type()` position. The later siblings are always unknown, walked
+/// from the counts of the element's position, with those of its name first.
+struct Node<'a, 'e, Handle> {
+    context: &'a [HtmlPathElement<'e, Handle>],
+    /// The element, or for an unknown sibling, the nearest known sibling after it, or the element it is after
+    element: &'a HtmlPathElement<'e, Handle>,
+    /// For an unknown sibling, where it is from `element`
+    unknown: Option<Unknown>,
+    /// Set when the later siblings of an element without a count were needed, so the match can't be decided
+    uncounted: &'a Cell<bool>,
+}
+
+/// Where an unknown sibling is from the known element of its node
+#[derive(Clone, Copy, Debug)]
+enum Unknown {
+    /// How many siblings before it
+    Before(usize),
+    /// How many siblings after it
+    After(usize),
+}
+
+impl<'a, 'e, Handle> Node<'a, 'e, Handle> {
+    fn new(
+        context: &'a [HtmlPathElement<'e, Handle>],
+        element: &'a HtmlPathElement<'e, Handle>,
+        uncounted: &'a Cell<bool>,
+    ) -> Self {
+        Node {
+            context,
+            element,
+            unknown: None,
+            uncounted,
+        }
+    }
+
+    /// Whether an unknown sibling after this known element has its name, those with its name coming first
+    fn unknown_after_has_name(&self, distance: usize) -> bool {
+        self.element.position.as_ref().is_some_and(|position| {
+            position
+                .count_of_type
+                .is_some_and(|count| distance <= count - position.index_of_type)
+        })
+    }
+
+    /// Whether an unknown sibling before a known element has the name of this known element, counting the unknown siblings with the
+    /// name of `before` first, followed by those with the name of this element which aren't among the known siblings between
+    fn unknown_has_name_of(&self, before: &HtmlPathElement<'e, Handle>, distance: usize) -> bool {
+        let of_type = |element: &HtmlPathElement<'_, Handle>| {
+            element
+                .position
+                .as_ref()
+                .map_or(0, |position| position.index_of_type - 1)
+        };
+        if self.element.name == before.name {
+            return distance <= of_type(before);
+        }
+        let known = iter::successors(self.element.previous_sibling.as_deref(), |sibling| {
+            sibling.previous_sibling.as_deref()
+        })
+        .take_while(|sibling| !ptr::eq(*sibling, before))
+        .filter(|sibling| sibling.name == self.element.name)
+        .count();
+        distance > of_type(before)
+            && distance <= of_type(before) + of_type(self.element).saturating_sub(known)
+    }
+}
+
+impl<Handle> Clone for Node<'_, '_, Handle> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Handle> Copy for Node<'_, '_, Handle> {}
+
+impl<Handle> fmt::Debug for Node<'_, '_, Handle> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("depth", &self.context.len())
+            .field("name", &self.element.name)
+            .finish()
+    }
+}
+
+impl<Handle> selectors::Element for Node<'_, '_, Handle> {
+    type Impl = Impl;
+
+    fn opaque(&self) -> OpaqueElement {
+        OpaqueElement::new(self.element)
+    }
+
+    fn parent_element(&self) -> Option<Self> {
+        let (element, context) = self.context.split_last()?;
+        Some(Node::new(context, element, self.uncounted))
+    }
+
+    fn parent_node_is_shadow_root(&self) -> bool {
+        false
+    }
+
+    fn containing_shadow_host(&self) -> Option<Self> {
+        None
+    }
+
+    fn is_pseudo_element(&self) -> bool {
+        false
+    }
+
+    fn prev_sibling_element(&self) -> Option<Self> {
+        let unknown = match (self.unknown, &self.element.previous_sibling) {
+            (Some(Unknown::After(1)), _) => None,
+            (Some(Unknown::After(distance)), _) => Some(Unknown::After(distance - 1)),
+            (None, Some(sibling)) => {
+                return Some(Node {
+                    element: sibling,
+                    ..*self
+                })
+            }
+            (unknown, _) => {
+                let distance = match unknown {
+                    Some(Unknown::Before(distance)) => distance + 1,
+                    _ => 1,
+                };
+                let before = self.element.position.as_ref()?.index - 1;
+                if distance > before {
+                    return None;
+                }
+                Some(Unknown::Before(distance))
+            }
+        };
+        Some(Node { unknown, ..*self })
+    }
+
+    fn next_sibling_element(&self) -> Option<Self> {
+        let distance = match self.unknown {
+            Some(Unknown::Before(1)) => {
+                return Some(Node {
+                    unknown: None,
+                    ..*self
+                })
+            }
+            Some(Unknown::Before(distance)) => {
+                return Some(Node {
+                    unknown: Some(Unknown::Before(distance - 1)),
+                    ..*self
+                })
+            }
+            Some(Unknown::After(distance)) => distance + 1,
+            None => 1,
+        };
+        let position = self.element.position.as_ref()?;
+        let Some(count) = position.count else {
+            self.uncounted.set(true);
+            return None;
+        };
+        (distance <= count - position.index).then_some(Node {
+            unknown: Some(Unknown::After(distance)),
+            ..*self
+        })
+    }
+
+    fn is_html_element_in_html_document(&self) -> bool {
+        self.unknown.is_none() && self.element.name.ns == ns!(html)
+    }
+
+    fn has_local_name(&self, local_name: &LocalName) -> bool {
+        self.unknown.is_none() && self.element.name.local == *local_name
+    }
+
+    fn has_namespace(&self, ns: &Namespace) -> bool {
+        self.unknown.is_none() && self.element.name.ns == *ns
+    }
+
+    fn is_same_type(&self, other: &Self) -> bool {
+        match (self.unknown, other.unknown) {
+            (None, None) => self.element.name == other.element.name,
+            (None, Some(Unknown::Before(distance))) => {
+                self.unknown_has_name_of(other.element, distance)
+            }
+            (Some(Unknown::Before(distance)), None) => {
+                other.unknown_has_name_of(self.element, distance)
+            }
+            (None, Some(Unknown::After(distance))) => {
+                ptr::eq(self.element, other.element) && self.unknown_after_has_name(distance)
+            }
+            (Some(Unknown::After(distance)), None) => {
+                ptr::eq(self.element, other.element) && other.unknown_after_has_name(distance)
+            }
+            // only those with the name of the same known sibling are known to share it
+            (Some(Unknown::Before(distance)), Some(Unknown::Before(other_distance))) => {
+                ptr::eq(self.element, other.element)
+                    && self.unknown_has_name_of(self.element, distance)
+                    && other.unknown_has_name_of(other.element, other_distance)
+            }
+            (Some(Unknown::After(distance)), Some(Unknown::After(other_distance))) => {
+                ptr::eq(self.element, other.element)
+                    && self.unknown_after_has_name(distance)
+                    && self.unknown_after_has_name(other_distance)
+            }
+            _ => false,
+        }
+    }
+
+    fn attr_matches(
+        &self,
+        ns: &NamespaceConstraint<&Namespace>,
+        local_name: &LocalName,
+        operation: &AttrSelectorOperation<&String>,
+    ) -> bool {
+        self.unknown.is_none()
+            && self.element.attrs.iter().any(|attr| {
+                attr.name.local == *local_name
+                    && match ns {
+                        NamespaceConstraint::Any => true,
+                        NamespaceConstraint::Specific(ns) => attr.name.ns == **ns,
+                    }
+                    && operation.eval_str(&attr.value)
+            })
+    }
+
+    fn match_non_ts_pseudo_class<F>(
+        &self,
+        pc: &NonTSPseudoClass,
+        _context: &mut MatchingContext<Impl>,
+        _flags_setter: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&Self, ElementSelectorFlags),
+    {
+        match pc {
+            NonTSPseudoClass::Link | NonTSPseudoClass::AnyLink => self.is_link(),
+            NonTSPseudoClass::TextContains(text) => {
+                self.unknown.is_none()
+                    && self
+                        .element
+                        .content
+                        .as_ref()
+                        .is_some_and(|content| content.text.contains(&**text))
+            }
+        }
+    }
+
+    fn match_pseudo_element(
+        &self,
+        pe: &PseudoElement,
+        _context: &mut MatchingContext<Impl>,
+    ) -> bool {
+        match *pe {}
+    }
+
+    fn is_link(&self) -> bool {
+        self.is_html_element_in_html_document()
+            && matches!(&*self.element.name.local, "a" | "area" | "link")
+            && self.element.has_attr("href")
+    }
+
+    fn is_html_slot_element(&self) -> bool {
+        false
+    }
+
+    fn has_id(&self, id: &LocalName, case_sensitivity: CaseSensitivity) -> bool {
+        self.unknown.is_none()
+            && self
+                .element
+                .id()
+                .is_some_and(|actual| case_sensitivity.eq(actual.as_bytes(), id.as_bytes()))
+    }
+
+    fn has_class(&self, name: &LocalName, case_sensitivity: CaseSensitivity) -> bool {
+        self.unknown.is_none()
+            && self
+                .element
+                .classes()
+                .any(|class| case_sensitivity.eq(class.as_bytes(), name.as_bytes()))
+    }
+
+    fn exported_part(&self, _name: &LocalName) -> Option<LocalName> {
+        None
+    }
+
+    fn imported_part(&self, _name: &LocalName) -> Option<LocalName> {
+        None
+    }
+
+    fn is_part(&self, _name: &LocalName) -> bool {
+        false
+    }
+
+    fn is_empty(&self) -> bool {
+        self.unknown.is_none()
+            && self
+                .element
+                .content
+                .as_ref()
+                .is_some_and(|content| content.empty)
+    }
+
+    fn is_root(&self) -> bool {
+        self.context.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{
+        parse_document, ContentBuffer, LookaheadBuffer, ParseConfig, SelectToStrings,
+        SiblingTracking,
+    };
+
+    fn select(selector: &str) -> Vec<String> {
+        ParseConfig::default()
+            .sibling_tracking(SiblingTracking::All)
+            .parse_document(SelectToStrings::new(selector.parse::<ServoSelector>().unwrap()))
+            .one(r#"<!DOCTYPE html><html><head></head><body><p id="Intro" class="lead">A</p><p lang="en-GB">B</p><a href="/c">C</a><svg><a xlink:href="/d"><title>D</title></a></svg></body></html>"#)
+        .unwrap()
+    }
+
+    #[test]
+    fn select_with_servo_selectors() {
+        assert_eq!(select("body > :nth-child(2)"), [r#"<p lang="en-GB">B</p>"#]);
+        assert_eq!(select("p ~ :link"), [r#"<a href="/c">C</a>"#]);
+        assert_eq!(select("p:not(.lead)"), [r#"<p lang="en-GB">B</p>"#]);
+        assert_eq!(
+            select("[id=intro i], [lang|=en]"),
+            [
+                r#"<p id="Intro" class="lead">A</p>"#,
+                r#"<p lang="en-GB">B</p>"#
+            ]
+        );
+        assert_eq!(
+            select("svg|a > title, html|a, #\\49 ntro"),
+            [
+                r#"<p id="Intro" class="lead">A</p>"#,
+                r#"<a href="/c">C</a>"#,
+                "<title>D</title>"
+            ]
+        );
+        assert_eq!(
+            select(":root > body > p:first-of-type + p"),
+            [r#"<p lang="en-GB">B</p>"#]
+        );
+    }
+
+    #[test]
+    fn count_untracked_siblings() {
+        let select = |selector: &str, siblings| {
+            ParseConfig::default()
+                .sibling_tracking(siblings)
+                .parse_document(SelectToStrings::new(selector.parse::<ServoSelector>().unwrap()))
+                .one("<!DOCTYPE html><html><head></head><body><p>1</p><div>2</div><p>3</p><div>4</div><p>5</p></body></html>")
+                .unwrap()
+        };
+        for siblings in [SiblingTracking::Off, SiblingTracking::Adjacent] {
+            assert_eq!(select("body > :nth-child(3)", siblings), ["<p>3</p>"]);
+            assert_eq!(select("div:nth-of-type(2)", siblings), ["<div>4</div>"]);
+            assert_eq!(
+                select("p:nth-of-type(2n+1)", siblings),
+                ["<p>1</p>", "<p>5</p>"]
+            );
+            assert_eq!(select("body > :first-child", siblings), ["<p>1</p>"]);
+            assert_eq!(select("div ~ div", siblings), Vec::<String>::new());
+        }
+        assert_eq!(
+            select("p + div", SiblingTracking::Adjacent),
+            ["<div>2</div>", "<div>4</div>"]
+        );
+        assert_eq!(
+            select("p + div", SiblingTracking::Off),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn count_later_siblings() {
+        let select = |selector: &str, parents| {
+            let selector = selector.parse::<ServoSelector>().unwrap();
+            parse_document(
+                LookaheadBuffer::wrap(SelectToStrings::new(selector), ServoSelector::parse(parents).unwrap()),
+                Default::default(),
+            )
+            .one("<!DOCTYPE html><html><head></head><body><p>1</p><div>2</div><p>3</p><div>4</div><p>5</p><div>6</div></body></html>")
+            .unwrap()
+        };
+        assert_eq!(select("body > :last-child", "body"), ["<div>6</div>"]);
+        assert_eq!(select("p:last-of-type", "body"), ["<p>5</p>"]);
+        assert_eq!(
+            select("div:nth-last-of-type(2n+1)", "body"),
+            ["<div>2</div>", "<div>6</div>"]
+        );
+        assert_eq!(
+            select("body > :nth-last-child(5)", "body"),
+            ["<div>2</div>"]
+        );
+        assert_eq!(
+            select("body > :not(:only-child):not(p)", "body"),
+            ["<div>2</div>", "<div>4</div>", "<div>6</div>"]
+        );
+        assert_eq!(
+            select(":only-of-type", "html"),
+            [
+                "<head></head>",
+                "<body><p>1</p><div>2</div><p>3</p><div>4</div><p>5</p><div>6</div></body>"
+            ]
+        );
+        // without the count the later siblings are unknown, so those which need them select nothing
+        assert_eq!(
+            select("div:not(:last-child), p", "head"),
+            ["<p>1</p>", "<p>3</p>", "<p>5</p>"]
+        );
+        assert_eq!(
+            select("body:last-child > p:first-child", "body"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn select_by_content() {
+        let selector = ServoSelector::parse(r#"p:empty, li:text-contains("sale")"#).unwrap();
+        let selected = parse_document(
+            ContentBuffer::wrap(SelectToStrings::new(selector), ServoSelector::parse("p, li").unwrap()),
+            Default::default(),
+        )
+        .one("<!DOCTYPE html><html><head></head><body><p></p><p>Text</p><ul><li>On sale</li><li>New</li></ul></body></html>")
+        .unwrap();
+        assert_eq!(selected, ["<p></p>", "<li>On sale</li>"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |selector: &str| {
+            let error = ServoSelector::parse(selector).unwrap_err();
+            (error.position, error.message.into_owned())
+        };
+        assert_eq!(
+            error("p >"),
+            (3, "expected a selector after the combinator".into())
+        );
+        assert_eq!(error("[href="), (6, "unexpected end of selector".into()));
+        assert_eq!(
+            error("p:hover"),
+            (2, "unsupported pseudo-class or element 'hover'".into())
+        );
+        assert_eq!(error("foo|p"), (3, "unknown namespace prefix".into()));
+    }
+}
@@ -0,0 +1,172 @@
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    raw_text::text_context, selector::ContextualSelector, HtmlContext, HtmlPathElement, HtmlSink,
+    HtmlStreamError, TextBoundary,
+};
+
+/// A filter which keeps only the subtrees matching a selector along with the skeleton of their ancestors, such as the `html`, `body` and `section` wrappers with their attributes.
+///
+/// Unlike [`crate::RootFilter`] the result is still a standalone document, containing only the selected content. The doctype is kept,
+/// ancestors are only kept if they contain a match, and everything else outside of the matches is dropped.
+pub struct SkeletonFilter<Handle, S, M> {
+    inner: S,
+    matcher: M,
+    /// The handles of the ancestors which have been appended to the inner sink and may still be open
+    skeleton: Vec<Handle>,
+    /// The root of the selected subtree, with its depth, the length of its context
+    selected: Option<(Handle, usize)>,
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>, M: ContextualSelector> SkeletonFilter<Handle, S, M> {
+    pub fn wrap(inner: S, matcher: M) -> Self {
+        Self {
+            inner,
+            matcher,
+            skeleton: vec![],
+            selected: None,
+        }
+    }
+
+    /// Whether the event is in the selected subtree
+    fn is_selected(&mut self, context: HtmlContext<'_, Handle>) -> bool {
+        let Some((handle, depth)) = self.selected else {
+            return false;
+        };
+        let selected = context
+            .get(depth)
+            .is_some_and(|element| element.handle == handle);
+        if !selected {
+            self.selected = None;
+        }
+        selected
+    }
+
+    /// Appends the ancestors in the context which haven't been appended yet
+    fn append_skeleton(&mut self, context: HtmlContext<'_, Handle>) {
+        let common = self
+            .skeleton
+            .iter()
+            .zip(context)
+            .take_while(|(handle, element)| **handle == element.handle)
+            .count();
+        self.skeleton.truncate(common);
+        for depth in common..context.len() {
+            self.inner
+                .append_element(&context[..depth], &context[depth]);
+            self.skeleton.push(context[depth].handle);
+        }
+    }
+}
+
+impl<Handle, S, M> HtmlSink<Handle> for SkeletonFilter<Handle, S, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if self.is_selected(context) {
+            self.inner.append_element(context, element)
+        } else if self.matcher.context_match(context, element) {
+            self.append_skeleton(context);
+            self.inner.append_element(context, element);
+            self.selected = Some((element.handle, context.len()));
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.is_selected(context) {
+            self.inner.append_text(context, text)
+        }
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        if self.is_selected(context) {
+            self.inner
+                .append_text_with_boundary(context, text, boundary)
+        }
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        if self.is_selected(&text_context(context, element)) {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        if self.is_selected(context) {
+            self.inner.append_comment(context, text)
+        }
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        if self.is_selected(context) {
+            self.inner
+                .append_processing_instruction(context, target, data)
+        }
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.skeleton.clear();
+        self.selected = None;
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::serialize::SerializeOpts;
+
+    use super::*;
+    use crate::{css_select, test::stream_doc, HtmlSerializer};
+
+    #[test]
+    fn keep_skeleton() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(
+            r#"<!DOCTYPE html><html lang="en"><head><title>t</title></head><body class="page"><nav>menu</nav><section id="a"><h2>A</h2><p class="keep">one</p></section><section id="b"><p>no</p></section><p class="keep">two <b>!</b></p></body></html>"#,
+            SkeletonFilter::wrap(&mut serializer, css_select!(."keep")),
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html lang="en"><body class="page"><section id="a"><p class="keep">one</p></section><p class="keep">two <b>!</b></p></body></html>"#
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    selector::ContextualSelector, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    TextBoundary,
+};
+
+/// A filter which gives matched `<img>`s with a `src` but no `srcset` a `srcset` of width variants, such as from an image cdn.
+///
+/// The callback is called with the `src` and each width, and gives the url of the image at that width, or `None` to leave the width out.
+/// A `sizes` is added to images without one, `100vw` by default.
+pub struct SrcsetGenerator<S, M, F> {
+    inner: S,
+    matcher: M,
+    variant: F,
+    widths: Vec<u32>,
+    sizes: String,
+}
+
+impl<S, M: ContextualSelector, F: FnMut(&str, u32) -> Option<String>> SrcsetGenerator<S, M, F> {
+    pub fn wrap(inner: S, matcher: M, variant: F) -> Self {
+        Self {
+            inner,
+            matcher,
+            variant,
+            widths: vec![320, 640, 960, 1280, 1920],
+            sizes: "100vw".to_owned(),
+        }
+    }
+
+    /// Sets the widths of the variants in pixels, 320, 640, 960, 1280 and 1920 by default
+    pub fn widths(self, widths: &[u32]) -> Self {
+        Self {
+            widths: widths.to_vec(),
+            ..self
+        }
+    }
+
+    /// Sets the `sizes` added to images without one
+    pub fn sizes(self, sizes: &str) -> Self {
+        Self {
+            sizes: sizes.to_owned(),
+            ..self
+        }
+    }
+
+    /// The `srcset` for an image's `src`, if any width has a variant
+    fn srcset(&mut self, src: &str) -> Option<String> {
+        let Self {
+            widths, variant, ..
+        } = self;
+        let candidates = widths
+            .iter()
+            .filter_map(|width| Some(format!("{} {}w", variant(src, *width)?, width)))
+            .collect::<Vec<_>>();
+        (!candidates.is_empty()).then(|| candidates.join(", "))
+    }
+}
+
+impl<Handle, S, M, F> HtmlSink<Handle> for SrcsetGenerator<S, M, F>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+    F: FnMut(&str, u32) -> Option<String>,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let is_candidate = element.name.ns == ns!(html)
+            && element.tag_is(local_name!("img"))
+            && !element.has_attr("srcset")
+            && self.matcher.context_match(context, element);
+        let srcset = element
+            .attr_local("src")
+            .filter(|_| is_candidate)
+            .and_then(|src| self.srcset(src.trim()));
+        let Some(srcset) = srcset else {
+            return self.inner.append_element(context, element);
+        };
+        let mut img = Cow::Borrowed(element);
+        img.to_mut().set_attr("srcset", &srcset);
+        if !element.has_attr("sizes") {
+            img.to_mut().set_attr("sizes", &self.sizes);
+        }
+        self.inner.append_element(context, &img)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer};
+
+    #[test]
+    fn generate_srcset() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_document(
+            SrcsetGenerator::wrap(&mut serializer, css_select!("img"), |src, width| {
+                src.strip_prefix("/images/")
+                    .map(|path| format!("https://cdn.example.com/w_{}/{}", width, path))
+            })
+            .widths(&[400, 800]),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head></head><body><img src="/images/a.jpg"><img src="/images/b.jpg" sizes="50vw"><img src="/images/c.jpg" srcset="/images/c.jpg 1x"><img src="/icons/d.svg"></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"<!DOCTYPE html><html><head></head><body>"#,
+                r#"<img src="/images/a.jpg" srcset="https://cdn.example.com/w_400/a.jpg 400w, https://cdn.example.com/w_800/a.jpg 800w" sizes="100vw">"#,
+                r#"<img src="/images/b.jpg" sizes="50vw" srcset="https://cdn.example.com/w_400/b.jpg 400w, https://cdn.example.com/w_800/b.jpg 800w">"#,
+                r#"<img src="/images/c.jpg" srcset="/images/c.jpg 1x"><img src="/icons/d.svg"></body></html>"#
+            )
+        );
+    }
+}
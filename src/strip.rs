@@ -0,0 +1,219 @@
+use std::borrow::Cow;
+
+use html5ever::{namespace_url, ns, tendril::StrTendril, Attribute};
+
+use crate::{
+    selector::{ContextualSelector, MatchAll},
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary,
+};
+
+/// A filter which removes attributes, rather than elements, by name and by prefix, such as all `on*` event handlers,
+/// all `style` attributes, or all `data-*` attributes except an allowlist.
+///
+/// It applies to the whole document, or to the elements matching a selector given to [`AttributeStripper::scoped`].
+/// Only attributes without a namespace are stripped, and the elements in the context keep their original attributes, as with [`crate::HtmlRewriter`].
+pub struct AttributeStripper<S, M = MatchAll> {
+    inner: S,
+    scope: M,
+    names: Vec<String>,
+    prefixes: Vec<String>,
+    keep: Vec<String>,
+}
+
+impl<S> AttributeStripper<S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            scope: MatchAll,
+            names: vec![],
+            prefixes: vec![],
+            keep: vec![],
+        }
+    }
+}
+
+impl<S, M> AttributeStripper<S, M> {
+    /// Only strips attributes from the elements matching the selector
+    pub fn scoped<N: ContextualSelector>(self, scope: N) -> AttributeStripper<S, N> {
+        AttributeStripper {
+            inner: self.inner,
+            scope,
+            names: self.names,
+            prefixes: self.prefixes,
+            keep: self.keep,
+        }
+    }
+
+    /// Strips the attribute with this name, such as `style`
+    pub fn strip(mut self, name: &str) -> Self {
+        self.names.push(name.to_owned());
+        self
+    }
+
+    /// Strips the attributes starting with this prefix, such as `data-`
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.prefixes.push(prefix.to_owned());
+        self
+    }
+
+    /// Strips all the `on*` event handler attributes
+    pub fn strip_event_handlers(self) -> Self {
+        self.strip_prefix("on")
+    }
+
+    /// Keeps the attribute with this name even if it matches a prefix, such as `data-id`
+    pub fn keep(mut self, name: &str) -> Self {
+        self.keep.push(name.to_owned());
+        self
+    }
+
+    fn is_stripped(&self, attr: &Attribute) -> bool {
+        if attr.name.ns != ns!() {
+            return false;
+        }
+        let name = &*attr.name.local;
+        let is_named = |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        !is_named(&self.keep)
+            && (is_named(&self.names)
+                || self.prefixes.iter().any(|prefix| {
+                    name.len() >= prefix.len()
+                        && name.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+                }))
+    }
+}
+
+impl<Handle, S, M> HtmlSink<Handle> for AttributeStripper<S, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        if !element.attrs.iter().any(|attr| self.is_stripped(attr))
+            || !self.scope.context_match(context, element)
+        {
+            return self.inner.append_element(context, element);
+        }
+        let attrs = element
+            .attrs
+            .iter()
+            .filter(|attr| !self.is_stripped(attr))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.inner.append_element(
+            context,
+            &HtmlPathElement {
+                handle: element.handle,
+                name: element.name.clone(),
+                attrs: Cow::Owned(attrs),
+                inherited: element.inherited.clone(),
+                position: element.position,
+                previous_sibling: element.previous_sibling.clone(),
+                content: element.content.clone(),
+            },
+        )
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::serialize::SerializeOpts;
+
+    use super::*;
+    use crate::{css_select, test::stream_doc, HtmlSerializer};
+
+    #[test]
+    fn strip_attributes() {
+        let doc = r#"<!DOCTYPE html><html><head></head><body onload="init()"><p style="color: red" data-id="1" data-track="x" onclick="go()">hi</p><form style="a"></form></body></html>"#;
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(
+            doc,
+            AttributeStripper::wrap(&mut serializer)
+                .strip_event_handlers()
+                .strip("style")
+                .strip_prefix("data-")
+                .keep("data-id"),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body><p data-id="1">hi</p><form></form></body></html>"#
+        );
+
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_doc(
+            doc,
+            AttributeStripper::wrap(&mut serializer)
+                .strip("style")
+                .scoped(css_select!("form")),
+        );
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head></head><body onload="init()"><p style="color: red" data-id="1" data-track="x" onclick="go()">hi</p><form></form></body></html>"#
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use html5ever::{Attribute, QualName};
+
+use crate::{HtmlPathElement, InheritedState};
+
+/// A handle type which sinks can make new handles of, for the elements they insert into the stream
+pub trait SyntheticHandle: Eq + Copy {
+    /// The `index`th synthetic handle. These count down from the top of the type's range, the parser's handles count up from 1 so they don't meet
+    fn synthetic(index: u32) -> Self;
+}
+
+impl SyntheticHandle for u32 {
+    fn synthetic(index: u32) -> Self {
+        u32::MAX - index
+    }
+}
+
+impl SyntheticHandle for u64 {
+    fn synthetic(index: u32) -> Self {
+        u64::MAX - u64::from(index)
+    }
+}
+
+impl SyntheticHandle for usize {
+    fn synthetic(index: u32) -> Self {
+        usize::MAX - index as usize
+    }
+}
+
+/// Gives out a distinct synthetic handle for each element a sink inserts into a document
+#[derive(Debug, Default)]
+pub struct SyntheticHandles {
+    next: u32,
+}
+
+impl SyntheticHandles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate<Handle: SyntheticHandle>(&mut self) -> Handle {
+        let handle = Handle::synthetic(self.next);
+        self.next += 1;
+        handle
+    }
+
+    /// Starts again for another document
+    pub fn reset(&mut self) {
+        self.next = 0;
+    }
+}
+
+impl<Handle> HtmlPathElement<'static, Handle> {
+    /// An element to insert as a child of `parent`, or as a root, with the state it inherits computed from its attributes
+    pub fn new_child(
+        handle: Handle,
+        parent: Option<&HtmlPathElement<'_, Handle>>,
+        name: QualName,
+        attrs: Vec<Attribute>,
+    ) -> Self {
+        Self {
+            handle,
+            inherited: InheritedState::for_element(
+                parent.map(|parent| &parent.inherited),
+                &name,
+                &attrs,
+            ),
+            name,
+            attrs: attrs.into(),
+            position: None,
+            previous_sibling: None,
+            content: None,
+        }
+    }
+}
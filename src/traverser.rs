@@ -10,7 +10,9 @@ use html5ever::{
 use crate::{
     css_select,
     selector::{ContextualSelector, ElementSelector, NameSelector, Selector},
-    ElementSkipper, HtmlPathElement, HtmlSink,
+    ElementUnwrapper, ErrorPolicy, HtmlPathElement, HtmlSink, HtmlStreamError, InheritedState,
+    MemoryMetrics, PreviousSibling, SiblingChain, SiblingCounter, SiblingPosition, SiblingTracking,
+    SourceLines,
 };
 
 pub fn parse_document<Sink>(sink: Sink, opts: ParseOpts) -> Parser<ParseTraverser<Sink>>
@@ -24,7 +26,28 @@ where
 pub fn parse_fragment<Sink>(
     sink: Sink,
     opts: ParseOpts,
-) -> Parser<ParseTraverser<ElementSkipper<Sink, NameSelector>>>
+) -> Parser<ParseTraverser<ElementUnwrapper<u32, Sink, NameSelector>>>
+where
+    Sink: HtmlSink<u32>,
+{
+    parse_fragment_with(
+        sink,
+        opts,
+        ErrorPolicy::FailFast,
+        None,
+        None,
+        SiblingTracking::Off,
+    )
+}
+
+pub(crate) fn parse_fragment_with<Sink>(
+    sink: Sink,
+    opts: ParseOpts,
+    errors: ErrorPolicy,
+    max_depth: Option<usize>,
+    lines: Option<SourceLines>,
+    siblings: SiblingTracking,
+) -> Parser<ParseTraverser<ElementUnwrapper<u32, Sink, NameSelector>>>
 where
     Sink: HtmlSink<u32>,
 {
@@ -34,112 +57,178 @@ where
         local: local_name!("body"),
     };
     let context_attrs = vec![];
-    let sink = ParseTraverser::new_fragment(ElementSkipper::wrap(sink, css_select!("html"))); // TODO find a way to do this without skipping filter
+    let mut sink = ParseTraverser::new_fragment(ElementUnwrapper::wrap(sink, css_select!("html")));
+    sink.errors = errors;
+    // the fragment's elements are inside an `<html>`, which is unwrapped
+    sink.max_depth = max_depth.map(|max| max + 1);
+    sink.lines = lines;
+    sink.chain = SiblingChain::new(siblings);
     html5ever::parse_fragment(sink, opts, context_name, context_attrs)
 }
 
+/// Parses a document like [`parse_document`], recording the peak sizes of the parse's buffers into `metrics`
+pub fn parse_document_with_metrics<Sink>(
+    sink: Sink,
+    opts: ParseOpts,
+    metrics: MemoryMetrics,
+) -> Parser<ParseTraverser<Sink>>
+where
+    Sink: HtmlSink<u32>,
+{
+    let mut sink = ParseTraverser::new_document(sink);
+    sink.metrics = Some(metrics);
+    html5ever::parse_document(sink, opts)
+}
+
+/// Parses an xml document, such as a feed, sitemap or strict xhtml, producing the same sink events as html along with processing instructions
+#[cfg(feature = "xml")]
+pub fn parse_xml_document<Sink>(
+    sink: Sink,
+    opts: xml5ever::driver::XmlParseOpts,
+) -> xml5ever::driver::XmlParser<ParseTraverser<Sink>>
+where
+    Sink: HtmlSink<u32>,
+{
+    let sink = ParseTraverser::new_document(sink);
+    xml5ever::driver::parse_document(sink, opts)
+}
+
 pub struct ParseTraverser<I> {
     inner: I,
-    parse_error: Option<Cow<'static, str>>,
+    error: Option<HtmlStreamError>,
     handle: u32,
     traversal: Vec<TraversalElement>,
+    siblings: SiblingCounter,
+    pub(crate) chain: SiblingChain<u32>,
     free_nodes: HashMap<u32, Node>,
+    metrics: Option<MemoryMetrics>,
+    pub(crate) errors: ErrorPolicy,
+    /// The most elements open at once, past which the parse fails
+    pub(crate) max_depth: Option<usize>,
+    /// Where the source lines are recorded, if they are
+    pub(crate) lines: Option<SourceLines>,
+    /// The span the sink events are made in
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    elements: usize,
 }
 
-#[derive(Debug)]
 enum Node {
     Element(TraversalElement),
     Comment(html5ever::tendril::StrTendril),
+    ProcessingInstruction(
+        html5ever::tendril::StrTendril,
+        html5ever::tendril::StrTendril,
+    ),
 }
 
-#[derive(Debug)]
-struct TraversalElement {
-    handle: u32,
-    name: html5ever::QualName,
-    attrs: Vec<Attribute>,
-}
-impl TraversalElement {
-    pub(crate) fn as_html_path_element(&self) -> HtmlPathElement<u32> {
-        HtmlPathElement {
-            handle: self.handle,
-            name: self.name.clone(),
-            attrs: Cow::Borrowed(&self.attrs),
-        }
-    }
-}
-
-impl fmt::Display for TraversalElement {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "#{}: <{}{}>",
-            self.handle,
-            self.name.local,
-            self.attrs
-                .iter()
-                .map(|att| format!(" {}=\"{}\"", att.name.local, &*att.value))
-                .collect::<String>()
-        )
-    }
-}
+/// An element as it is streamed, whose inherited state, position and previous sibling are computed when it is appended and its
+/// parent is known
+type TraversalElement = HtmlPathElement<'static, u32>;
 
 impl<I> ParseTraverser<I> {
     pub(crate) fn new_document(serializer: I) -> Self {
         Self {
             inner: serializer,
-            parse_error: None,
+            error: None,
             handle: 0,
             traversal: vec![],
+            siblings: SiblingCounter::new(),
+            chain: SiblingChain::default(),
             free_nodes: HashMap::new(),
+            metrics: None,
+            errors: ErrorPolicy::FailFast,
+            max_depth: None,
+            lines: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("parse", elements = tracing::field::Empty),
+            #[cfg(feature = "tracing")]
+            elements: 0,
         }
     }
     pub(crate) fn new_fragment(serializer: I) -> Self {
         Self {
             inner: serializer,
-            parse_error: None,
+            error: None,
             handle: 1,
-            traversal: vec![TraversalElement {
-                handle: 1,
-                name: QualName {
+            traversal: vec![HtmlPathElement::new(
+                1,
+                QualName {
                     prefix: None,
                     ns: ns!(),
                     local: local_name!("body"),
                 },
-                attrs: vec![],
-            }],
+                vec![],
+            )],
+            siblings: SiblingCounter::new(),
+            chain: SiblingChain::default(),
             free_nodes: HashMap::new(),
+            metrics: None,
+            errors: ErrorPolicy::FailFast,
+            max_depth: None,
+            lines: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("parse", elements = tracing::field::Empty),
+            #[cfg(feature = "tracing")]
+            elements: 0,
         }
     }
 
-    fn element(&self, target: &u32) -> &TraversalElement {
+    fn element(&self, target: &u32) -> Option<&TraversalElement> {
         for element in self.traversal.iter().rev() {
             if &element.handle == target {
-                return element;
+                return Some(element);
             }
         }
         if let Some(Node::Element(element)) = self.free_nodes.get(target) {
-            return element;
+            return Some(element);
+        }
+        None
+    }
+
+    fn record_free_nodes(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.free_nodes(self.free_nodes.len());
         }
-        panic!("Couldn't find elem with handle {}", target);
+    }
+
+    /// Records the first error, the stream stops being forwarded after an error
+    fn fail(&mut self, error: HtmlStreamError) {
+        self.error.get_or_insert(error);
     }
 }
 
 impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
     type Handle = u32;
 
-    type Output = Result<I::Output, Cow<'static, str>>;
+    type Output = Result<I::Output, HtmlStreamError>;
 
     fn finish(self) -> Self::Output {
-        if let Some(err) = self.parse_error {
+        #[cfg(feature = "tracing")]
+        let (span, elements) = (self.span.clone(), self.elements);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        let result = if let Some(err) = self.error {
             Err(err)
         } else {
-            Ok(self.inner.finish())
+            self.inner.finish()
+        };
+        #[cfg(feature = "tracing")]
+        {
+            span.record("elements", elements);
+            tracing::debug!(elements, failed = result.is_err(), "parsed document");
         }
+        result
     }
 
     fn parse_error(&mut self, msg: Cow<'static, str>) {
-        // currently using a fast fail mode, ideally we'd tell html5ever to abort the parse
-        self.parse_error = Some(msg);
+        match &self.errors {
+            // ideally we'd tell html5ever to abort the parse
+            ErrorPolicy::FailFast => self.fail(HtmlStreamError::Parse(msg)),
+            ErrorPolicy::Collect(errors) => errors.push(msg),
+            ErrorPolicy::Ignore => {}
+        }
     }
 
     fn get_document(&mut self) -> Self::Handle {
@@ -147,7 +236,15 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
     }
 
     fn elem_name<'a>(&'a self, target: &'a Self::Handle) -> html5ever::ExpandedName<'a> {
-        self.element(target).name.expanded()
+        // elements which have been moved by an unsupported operation are unknown, by then the parse has already failed
+        static UNKNOWN: QualName = QualName {
+            prefix: None,
+            ns: ns!(),
+            local: local_name!(""),
+        };
+        self.element(target)
+            .map_or(&UNKNOWN, |element| &element.name)
+            .expanded()
     }
 
     fn create_element(
@@ -157,20 +254,21 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
         _flags: html5ever::tree_builder::ElementFlags,
     ) -> Self::Handle {
         self.handle += 1;
+        if let Some(lines) = &self.lines {
+            lines.element_created(self.handle);
+        }
         self.free_nodes.insert(
             self.handle,
-            Node::Element(TraversalElement {
-                handle: self.handle,
-                name,
-                attrs,
-            }),
+            Node::Element(HtmlPathElement::new(self.handle, name, attrs)),
         );
+        self.record_free_nodes();
         self.handle
     }
 
     fn create_comment(&mut self, text: html5ever::tendril::StrTendril) -> Self::Handle {
         self.handle += 1;
         self.free_nodes.insert(self.handle, Node::Comment(text));
+        self.record_free_nodes();
         self.handle
     }
 
@@ -179,10 +277,18 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
         target: html5ever::tendril::StrTendril,
         data: html5ever::tendril::StrTendril,
     ) -> Self::Handle {
-        todo!()
+        self.handle += 1;
+        self.free_nodes
+            .insert(self.handle, Node::ProcessingInstruction(target, data));
+        self.record_free_nodes();
+        self.handle
     }
 
     fn append(&mut self, parent: &Self::Handle, child: NodeOrText<Self::Handle>) {
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
         if *parent == self.get_document()
             || self
                 .traversal
@@ -198,49 +304,100 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
                     self.traversal.pop();
                 }
             };
-            if self.parse_error.is_none() {
+            if self.error.is_none() {
                 match child {
                     NodeOrText::AppendNode(handle) => {
-                        let node = self.free_nodes.remove(&handle).unwrap();
-                        let context = self
-                            .traversal
-                            .iter()
-                            .map(TraversalElement::as_html_path_element)
-                            .collect::<Vec<_>>(); // TODO these should be reused;
+                        let node = match self.free_nodes.remove(&handle) {
+                            Some(node) => node,
+                            None => {
+                                return self.fail(HtmlStreamError::UnsupportedTreeOperation(
+                                    "append of an attached node",
+                                ))
+                            }
+                        };
                         match node {
-                            Node::Element(element) => {
+                            Node::Element(mut element) => {
                                 assert_eq!(element.handle, handle);
-                                self.inner
-                                    .append_element(&context, &element.as_html_path_element());
+                                if let Some(max) =
+                                    self.max_depth.filter(|max| self.traversal.len() >= *max)
+                                {
+                                    return self.fail(HtmlStreamError::LimitExceeded {
+                                        limit: "depth",
+                                        max,
+                                    });
+                                }
+                                element.inherited = InheritedState::for_element(
+                                    self.traversal.last().map(|parent| &parent.inherited),
+                                    &element.name,
+                                    &element.attrs,
+                                );
+                                element.position = Some(
+                                    self.siblings.element(self.traversal.len(), &element.name),
+                                );
+                                element.previous_sibling =
+                                    self.chain.element(self.traversal.len(), &element);
+                                #[cfg(feature = "tracing")]
+                                {
+                                    self.elements += 1;
+                                }
+                                self.inner.append_element(&self.traversal, &element);
                                 self.traversal.push(element);
+                                if let Some(metrics) = &self.metrics {
+                                    metrics.traversal_depth(self.traversal.len());
+                                }
                             }
                             Node::Comment(text) => {
-                                self.inner.append_comment(&context, &text);
+                                self.inner.append_comment(&self.traversal, &text);
+                            }
+                            Node::ProcessingInstruction(target, data) => {
+                                self.inner.append_processing_instruction(
+                                    &self.traversal,
+                                    &target,
+                                    &data,
+                                );
                             }
                         }
                     }
                     NodeOrText::AppendText(text) => {
-                        self.inner.append_text(
-                            &self
-                                .traversal
-                                .iter()
-                                .map(TraversalElement::as_html_path_element)
-                                .collect::<Vec<_>>(),
-                            &text,
-                        );
+                        self.inner.append_text(&self.traversal, &text);
                     }
                 }
             }
         }
     }
 
+    /// Foster parenting moves content out of a table to before it, but the table has already been streamed,
+    /// so the content streams in the innermost table section or row, from which a reparse of the output fosters it again
     fn append_based_on_parent_node(
         &mut self,
         element: &Self::Handle,
         prev_element: &Self::Handle,
         child: html5ever::tree_builder::NodeOrText<Self::Handle>,
     ) {
-        todo!()
+        let parent = match self
+            .traversal
+            .iter()
+            .position(|node| node.handle == *element)
+        {
+            // cells end without telling the sink, fostering means they have ended
+            Some(table) => self.traversal[table..]
+                .iter()
+                .take_while(|node| {
+                    node.name.ns == ns!(html)
+                        && matches!(
+                            node.name.local,
+                            local_name!("table")
+                                | local_name!("tbody")
+                                | local_name!("thead")
+                                | local_name!("tfoot")
+                                | local_name!("tr")
+                        )
+                })
+                .last()
+                .map_or(*element, |node| node.handle),
+            None => *prev_element,
+        };
+        self.append(&parent, child)
     }
 
     fn append_doctype_to_document(
@@ -253,8 +410,9 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
             .append_doctype_to_document(&name, &public_id, &system_id)
     }
 
+    /// The contents of a template stream as its children, as they do when streaming a tree
     fn get_template_contents(&mut self, target: &Self::Handle) -> Self::Handle {
-        todo!()
+        *target
     }
 
     fn same_node(&self, x: &Self::Handle, y: &Self::Handle) -> bool {
@@ -271,18 +429,99 @@ impl<I: HtmlSink<u32>> TreeSink for ParseTraverser<I> {
         sibling: &Self::Handle,
         new_node: html5ever::tree_builder::NodeOrText<Self::Handle>,
     ) {
-        todo!()
+        self.fail(HtmlStreamError::UnsupportedTreeOperation(
+            "append_before_sibling",
+        ))
     }
 
+    /// The target has already been streamed, so the attributes are dropped, as an error under the policy
     fn add_attrs_if_missing(&mut self, target: &Self::Handle, attrs: Vec<html5ever::Attribute>) {
-        todo!()
+        if !attrs.is_empty() {
+            self.parse_error(
+                "attributes added to an element which has already been streamed".into(),
+            );
+        }
+    }
+
+    fn set_current_line(&mut self, line_number: u64) {
+        if let Some(lines) = &self.lines {
+            lines.set_current(line_number);
+        }
     }
 
     fn remove_from_parent(&mut self, target: &Self::Handle) {
-        todo!()
+        self.fail(HtmlStreamError::UnsupportedTreeOperation(
+            "remove_from_parent",
+        ))
     }
 
     fn reparent_children(&mut self, node: &Self::Handle, new_parent: &Self::Handle) {
-        todo!()
+        self.fail(HtmlStreamError::UnsupportedTreeOperation(
+            "reparent_children",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{
+        serialize::SerializeOpts,
+        tendril::{StrTendril, TendrilSink},
+    };
+
+    use super::*;
+    use crate::{parse_document, ContextExt, HtmlContext, HtmlSerializer};
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml_document() {
+        let mut buf = Vec::new();
+        let mut sink = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        parse_xml_document(&mut sink, Default::default())
+            .one(r#"<?xml-stylesheet href="feed.xsl"?><rss><channel><title>Tom &amp; Jerry</title><link/></channel></rss>"#)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<?xml-stylesheet href="feed.xsl"><rss><channel><title>Tom &amp; Jerry</title><link></link></channel></rss>"#
+        );
+    }
+
+    /// Records each text with whether it is inside a template
+    #[derive(Default)]
+    struct TemplateTexts(Vec<(String, bool)>);
+
+    impl HtmlSink<u32> for TemplateTexts {
+        type Output = Vec<(String, bool)>;
+
+        fn append_doctype_to_document(&mut self, _: &StrTendril, _: &StrTendril, _: &StrTendril) {}
+
+        fn append_element(&mut self, _: HtmlContext<u32>, _: &HtmlPathElement<u32>) {}
+
+        fn append_text(&mut self, context: HtmlContext<u32>, text: &str) {
+            self.0.push((text.to_owned(), context.in_template()))
+        }
+
+        fn append_comment(&mut self, _: HtmlContext<u32>, _: &str) {}
+
+        fn reset(&mut self) -> Self::Output {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn template_contents() {
+        let doc = "<!DOCTYPE html><html><head></head><body><template><p>x</p></template><p>y</p></body></html>";
+        let texts = parse_document(TemplateTexts::default(), Default::default())
+            .one(doc)
+            .unwrap();
+        assert_eq!(texts, [("x".to_owned(), true), ("y".to_owned(), false)]);
+
+        let html = parse_document(
+            HtmlSerializer::new(Vec::new(), SerializeOpts::default()),
+            Default::default(),
+        )
+        .one(doc)
+        .unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), doc);
     }
 }
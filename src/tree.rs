@@ -0,0 +1,481 @@
+use std::{borrow::Cow, mem};
+
+use html5ever::{
+    local_name, namespace_url, ns,
+    tendril::StrTendril,
+    tree_builder::{ElementFlags, NodeOrText, TreeSink},
+    Attribute, QualName,
+};
+
+use crate::{
+    HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, InheritedState, SiblingChain,
+    SiblingCounter, SiblingTracking,
+};
+
+/// A node of an in-memory DOM which can be replayed into an `HtmlSink` with [`stream_tree`]
+pub trait StreamTree {
+    /// Replays this node and its descendants into the streamer
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>);
+}
+
+/// Replays the tree rooted at `node` into the sink, assigning handles in document order as the parser would, then finishes the sink
+pub fn stream_tree<T, S>(node: &T, sink: S) -> Result<S::Output, HtmlStreamError>
+where
+    T: StreamTree + ?Sized,
+    S: HtmlSink<u32>,
+{
+    stream_tree_with(node, sink, SiblingTracking::Off)
+}
+
+pub(crate) fn stream_tree_with<T, S>(
+    node: &T,
+    sink: S,
+    siblings: SiblingTracking,
+) -> Result<S::Output, HtmlStreamError>
+where
+    T: StreamTree + ?Sized,
+    S: HtmlSink<u32>,
+{
+    let mut streamer = TreeStreamer::new(sink, siblings);
+    node.stream_into(&mut streamer);
+    streamer.sink.finish()
+}
+
+/// Tracks the open elements while a DOM is walked, passing the appropriate context to the sink
+pub struct TreeStreamer<S> {
+    sink: S,
+    context: Vec<HtmlPathElement<'static, u32>>,
+    siblings: SiblingCounter,
+    chain: SiblingChain<u32>,
+    handle: u32,
+}
+
+impl<S: HtmlSink<u32>> TreeStreamer<S> {
+    fn new(sink: S, siblings: SiblingTracking) -> Self {
+        Self {
+            sink,
+            context: vec![],
+            siblings: SiblingCounter::new(),
+            chain: SiblingChain::new(siblings),
+            handle: 0,
+        }
+    }
+
+    pub fn doctype(&mut self, name: &StrTendril, public_id: &StrTendril, system_id: &StrTendril) {
+        self.sink
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    /// Appends an element, then streams its children with `children` before closing it
+    pub fn element(
+        &mut self,
+        name: &QualName,
+        attrs: &[Attribute],
+        children: impl FnOnce(&mut Self),
+    ) {
+        self.handle += 1;
+        let mut element = HtmlPathElement {
+            handle: self.handle,
+            name: name.clone(),
+            attrs: Cow::Owned(attrs.to_vec()),
+            inherited: InheritedState::for_element(
+                self.context.last().map(|parent| &parent.inherited),
+                name,
+                attrs,
+            ),
+            position: Some(self.siblings.element(self.context.len(), name)),
+            previous_sibling: None,
+            content: None,
+        };
+        element.previous_sibling = self.chain.element(self.context.len(), &element);
+        self.sink.append_element(&self.context, &element);
+        self.context.push(element);
+        children(self);
+        self.context.pop();
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.sink.append_text(&self.context, text)
+    }
+
+    pub fn comment(&mut self, text: &str) {
+        self.sink.append_comment(&self.context, text)
+    }
+
+    pub fn processing_instruction(&mut self, target: &str, data: &str) {
+        self.sink
+            .append_processing_instruction(&self.context, target, data)
+    }
+}
+
+#[cfg(feature = "rcdom")]
+impl StreamTree for markup5ever_rcdom::Handle {
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>) {
+        use markup5ever_rcdom::NodeData;
+
+        let stream_children = |streamer: &mut TreeStreamer<S>, node: &Self| {
+            for child in node.children.borrow().iter() {
+                child.stream_into(streamer);
+            }
+        };
+        match &self.data {
+            NodeData::Document => stream_children(streamer, self),
+            NodeData::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => streamer.doctype(name, public_id, system_id),
+            NodeData::Text { contents } => streamer.text(&contents.borrow()),
+            NodeData::Comment { contents } => streamer.comment(contents),
+            NodeData::Element {
+                name,
+                attrs,
+                template_contents,
+                ..
+            } => streamer.element(name, &attrs.borrow(), |streamer| match &*template_contents
+                .borrow()
+            {
+                Some(contents) => stream_children(streamer, contents),
+                None => stream_children(streamer, self),
+            }),
+            NodeData::ProcessingInstruction { target, contents } => {
+                streamer.processing_instruction(target, contents)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rcdom")]
+impl StreamTree for markup5ever_rcdom::RcDom {
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>) {
+        self.document.stream_into(streamer)
+    }
+}
+
+#[cfg(feature = "scraper")]
+impl StreamTree for ego_tree::NodeRef<'_, scraper::Node> {
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>) {
+        use scraper::Node;
+
+        let stream_children = |streamer: &mut TreeStreamer<S>| {
+            for child in self.children() {
+                child.stream_into(streamer);
+            }
+        };
+        match self.value() {
+            Node::Document | Node::Fragment => stream_children(streamer),
+            Node::Doctype(doctype) => {
+                streamer.doctype(&doctype.name, &doctype.public_id, &doctype.system_id)
+            }
+            Node::Text(text) => streamer.text(text),
+            Node::Comment(comment) => streamer.comment(comment),
+            Node::Element(element) => {
+                let attrs = element
+                    .attrs
+                    .iter()
+                    .map(|(name, value)| Attribute {
+                        name: name.clone(),
+                        value: value.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                streamer.element(&element.name, &attrs, stream_children)
+            }
+            Node::ProcessingInstruction(pi) => {
+                streamer.processing_instruction(&pi.target, &pi.data)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "scraper")]
+impl StreamTree for scraper::Html {
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>) {
+        self.tree.root().stream_into(streamer)
+    }
+}
+
+#[cfg(feature = "kuchikiki")]
+impl StreamTree for kuchikiki::NodeRef {
+    fn stream_into<S: HtmlSink<u32>>(&self, streamer: &mut TreeStreamer<S>) {
+        use kuchikiki::NodeData;
+
+        let stream_children = |streamer: &mut TreeStreamer<S>, node: &Self| {
+            for child in node.children() {
+                child.stream_into(streamer);
+            }
+        };
+        match self.data() {
+            NodeData::Document(_) | NodeData::DocumentFragment => stream_children(streamer, self),
+            NodeData::Doctype(doctype) => streamer.doctype(
+                &StrTendril::from_slice(&doctype.name),
+                &StrTendril::from_slice(&doctype.public_id),
+                &StrTendril::from_slice(&doctype.system_id),
+            ),
+            NodeData::Text(text) => streamer.text(&text.borrow()),
+            NodeData::Comment(comment) => streamer.comment(&comment.borrow()),
+            NodeData::Element(element) => {
+                let attrs = element
+                    .attributes
+                    .borrow()
+                    .map
+                    .iter()
+                    .map(|(name, attr)| Attribute {
+                        name: QualName::new(
+                            attr.prefix.clone(),
+                            name.ns.clone(),
+                            name.local.clone(),
+                        ),
+                        value: StrTendril::from_slice(&attr.value),
+                    })
+                    .collect::<Vec<_>>();
+                streamer.element(&element.name, &attrs, |streamer| {
+                    stream_children(streamer, element.template_contents.as_ref().unwrap_or(self))
+                })
+            }
+            NodeData::ProcessingInstruction(pi) => {
+                let (target, data) = &*pi.borrow();
+                streamer.processing_instruction(target, data)
+            }
+        }
+    }
+}
+
+/// A sink which builds a DOM using its html5ever `TreeSink` implementation, outputting the finished DOM on each reset.
+///
+/// Behind a `RootFilter` this materializes each selected subtree for random-access processing, while the rest of the document is streamed.
+/// Context elements which weren't appended are created, so a context which was filtered upstream still produces a well-formed tree.
+pub struct DomBuilder<T: TreeSink, Handle> {
+    dom: T,
+    new_dom: fn() -> T,
+    /// The open elements, with the node their children are appended to
+    open_elements: Vec<(Handle, T::Handle)>,
+}
+
+impl<T: TreeSink, Handle: Eq + Copy> DomBuilder<T, Handle> {
+    /// Creates a builder which calls `new_dom` for an empty DOM to build each output in
+    pub fn with_factory(new_dom: fn() -> T) -> Self {
+        Self {
+            dom: new_dom(),
+            new_dom,
+            open_elements: vec![],
+        }
+    }
+
+    /// Appends an element node as a child of the deepest open element, returning the node its children should be appended to
+    fn append_element_node(&mut self, element: &HtmlPathElement<'_, Handle>) -> T::Handle {
+        let template =
+            element.name.ns == ns!(html) && element.name.local == local_name!("template");
+        let mut flags = ElementFlags::default();
+        flags.template = template;
+        let node = self
+            .dom
+            .create_element(element.name.clone(), element.attrs.to_vec(), flags);
+        let parent = self.parent();
+        self.dom
+            .append(&parent, NodeOrText::AppendNode(node.clone()));
+        if template {
+            self.dom.get_template_contents(&node)
+        } else {
+            node
+        }
+    }
+
+    fn parent(&mut self) -> T::Handle {
+        match self.open_elements.last() {
+            Some((_, parent)) => parent.clone(),
+            None => self.dom.get_document(),
+        }
+    }
+
+    /// Closes the open elements which aren't in the context and creates the ones which are missing
+    fn open_context(&mut self, context: HtmlContext<'_, Handle>) {
+        let common = context
+            .iter()
+            .zip(&self.open_elements)
+            .take_while(|(a, (b, _))| a.handle == *b)
+            .count();
+        self.open_elements.truncate(common);
+        for missing in &context[common..] {
+            let node = self.append_element_node(missing);
+            self.open_elements.push((missing.handle, node));
+        }
+    }
+}
+
+impl<T: TreeSink + Default, Handle: Eq + Copy> Default for DomBuilder<T, Handle> {
+    fn default() -> Self {
+        Self::with_factory(T::default)
+    }
+}
+
+impl<T: TreeSink, Handle: Eq + Copy> HtmlSink<Handle> for DomBuilder<T, Handle> {
+    type Output = T::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.dom
+            .append_doctype_to_document(name.clone(), public_id.clone(), system_id.clone())
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.open_context(context);
+        let node = self.append_element_node(element);
+        self.open_elements.push((element.handle, node));
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.open_context(context);
+        let parent = self.parent();
+        self.dom.append(
+            &parent,
+            NodeOrText::AppendText(StrTendril::from_slice(text)),
+        )
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.open_context(context);
+        let parent = self.parent();
+        let comment = self.dom.create_comment(StrTendril::from_slice(text));
+        self.dom.append(&parent, NodeOrText::AppendNode(comment))
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.open_context(context);
+        let parent = self.parent();
+        let pi = self
+            .dom
+            .create_pi(StrTendril::from_slice(target), StrTendril::from_slice(data));
+        self.dom.append(&parent, NodeOrText::AppendNode(pi))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.open_elements.clear();
+        mem::replace(&mut self.dom, (self.new_dom)()).finish()
+    }
+}
+
+/// Builds a `markup5ever_rcdom::RcDom` for each output
+#[cfg(feature = "rcdom")]
+pub type RcDomBuilder<Handle> = DomBuilder<markup5ever_rcdom::RcDom, Handle>;
+
+#[cfg(feature = "rcdom")]
+impl<Handle: Eq + Copy> RcDomBuilder<Handle> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Builds a `scraper::Html` document for each output, so the output of a `RootFilter` can be queried with scraper's selectors
+#[cfg(feature = "scraper")]
+pub type ScraperHtmlBuilder<Handle> = DomBuilder<scraper::Html, Handle>;
+
+#[cfg(feature = "scraper")]
+impl<Handle: Eq + Copy> ScraperHtmlBuilder<Handle> {
+    pub fn new() -> Self {
+        Self::with_factory(scraper::Html::new_document)
+    }
+}
+
+#[cfg(all(test, feature = "rcdom"))]
+mod test {
+    use super::*;
+    use crate::HtmlSerializer;
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink, ParseOpts};
+    use markup5ever_rcdom::RcDom;
+
+    #[test]
+    fn rcdom_identity() {
+        let test = "<!DOCTYPE html><html><head></head><body><!-- comment --><p class=\"a\"><b>hello</b></p><p>world!</p></body></html>";
+        let dom = html5ever::parse_document(RcDom::default(), ParseOpts::default()).one(test);
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_tree(&dom, &mut serializer).unwrap();
+        drop(serializer);
+        assert_eq!(String::from_utf8(buf).unwrap(), test);
+    }
+
+    #[test]
+    fn build_selected_rcdoms() {
+        use crate::{css_select, RootFilter};
+        use markup5ever_rcdom::SerializableHandle;
+
+        let sink = RootFilter::<_, _, _, Vec<RcDom>>::wrap(RcDomBuilder::new(), css_select!("p"));
+        let test = "<!DOCTYPE html><html><head></head><body><p><!-- comment --><b>hello</b></p><div><p>world!</p></div></body></html>";
+        let mut opts = ParseOpts::default();
+        opts.tree_builder.exact_errors = true;
+        let doms = crate::parse_document(sink, opts).one(test).unwrap();
+        let serialized = doms
+            .into_iter()
+            .map(|dom| {
+                let mut buf = Vec::new();
+                let document: SerializableHandle = dom.document.into();
+                html5ever::serialize(&mut buf, &document, SerializeOpts::default()).unwrap();
+                String::from_utf8(buf).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            serialized,
+            vec!["<p><!-- comment --><b>hello</b></p>", "<p>world!</p>"]
+        );
+    }
+}
+
+#[cfg(all(test, feature = "scraper"))]
+mod scraper_test {
+    use super::*;
+    use crate::{css_select, HtmlSerializer, RootFilter};
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink, ParseOpts};
+
+    #[test]
+    fn build_selected_scraper_documents() {
+        let test = "<!DOCTYPE html><html><head></head><body><div class=\"card\"><a href=\"/a\">A</a></div><div class=\"card\"><a href=\"/b\">B</a></div></body></html>";
+        let sink =
+            RootFilter::<_, _, _, Vec<_>>::wrap(ScraperHtmlBuilder::new(), css_select!(."card"));
+        let documents: Vec<scraper::Html> = crate::parse_document(sink, ParseOpts::default())
+            .one(test)
+            .unwrap();
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_tree(&documents[1], &mut serializer).unwrap();
+        drop(serializer);
+        assert_eq!(buf, br#"<div class="card"><a href="/b">B</a></div>"#);
+        let links = scraper::Selector::parse("a").unwrap();
+        let hrefs = documents
+            .iter()
+            .flat_map(|document| document.select(&links))
+            .filter_map(|link| link.value().attr("href"))
+            .collect::<Vec<_>>();
+        assert_eq!(hrefs, vec!["/a", "/b"]);
+    }
+}
+
+#[cfg(all(test, feature = "kuchikiki"))]
+mod kuchikiki_test {
+    use super::*;
+    use crate::HtmlSerializer;
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    #[test]
+    fn kuchikiki_identity() {
+        let test = "<!DOCTYPE html><html><head></head><body><p class=\"a\" id=\"b\"><b>hello</b></p><template><p>world!</p></template></body></html>";
+        let document = kuchikiki::parse_html().one(test);
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_tree(&document, &mut serializer).unwrap();
+        drop(serializer);
+        assert_eq!(String::from_utf8(buf).unwrap(), test);
+    }
+}
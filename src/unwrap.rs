@@ -0,0 +1,226 @@
+use std::borrow::Cow;
+
+use html5ever::tendril::StrTendril;
+
+use crate::{
+    raw_text::text_context, selector::ContextualSelector, HtmlContext, HtmlPathElement, HtmlSink,
+    HtmlStreamError, TextBoundary,
+};
+
+/// A filter which removes the elements the selector selects while keeping their children, which take their place in their parent.
+///
+/// Selectors are matched against the source, so `css_select!("p" > "span")` unwraps the spans which are children of a paragraph
+/// there, whether or not their parent was unwrapped too. The unwrapped elements are left out of the context of their descendants,
+/// so the selectors of the inner sinks match against the document without them. The open unwrapped elements are tracked by handle and
+/// depth, so events outside them pass their context through without copying it. Descendants keep the state they inherited from an
+/// unwrapped element, such as being inside a `<pre>`.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{css_select, parse_document, ElementUnwrapper, HtmlSerializer};
+///
+/// let html = parse_document(
+///     ElementUnwrapper::wrap(HtmlSerializer::new(Vec::new(), Default::default()), css_select!("font")),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><p><font color="red">Old <b>markup</b></font></p></body></html>"#)
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     "<!DOCTYPE html><html><head></head><body><p>Old <b>markup</b></p></body></html>"
+/// );
+/// ```
+pub struct ElementUnwrapper<Handle, S, M> {
+    inner: S,
+    matcher: M,
+    /// The open unwrapped elements, outermost first, with their depths, the lengths of their contexts
+    unwrapped: Vec<(Handle, usize)>,
+}
+
+impl<Handle, S, M> ElementUnwrapper<Handle, S, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    pub fn wrap(inner: S, matcher: M) -> Self {
+        Self {
+            inner,
+            matcher,
+            unwrapped: vec![],
+        }
+    }
+
+    /// The context without the open unwrapped elements, after forgetting those which have closed
+    fn context<'c>(
+        &mut self,
+        context: HtmlContext<'c, Handle>,
+    ) -> Cow<'c, [HtmlPathElement<'c, Handle>]> {
+        while let Some(&(handle, depth)) = self.unwrapped.last() {
+            if context
+                .get(depth)
+                .is_some_and(|element| element.handle == handle)
+            {
+                break;
+            }
+            self.unwrapped.pop();
+        }
+        if self.unwrapped.is_empty() {
+            return Cow::Borrowed(context);
+        }
+        let mut unwrapped = self.unwrapped.iter().map(|(_, depth)| *depth).peekable();
+        Cow::Owned(
+            context
+                .iter()
+                .enumerate()
+                .filter(|(depth, _)| unwrapped.next_if_eq(depth).is_none())
+                .map(|(_, element)| element.clone())
+                .collect(),
+        )
+    }
+}
+
+impl<Handle, S, M> HtmlSink<Handle> for ElementUnwrapper<Handle, S, M>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    M: ContextualSelector,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let unwrapped = self.context(context);
+        if self.matcher.context_match(context, element) {
+            self.unwrapped.push((element.handle, context.len()));
+        } else {
+            self.inner.append_element(&unwrapped, element)
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let context = self.context(context);
+        self.inner.append_text(&context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        let context = self.context(context);
+        self.inner
+            .append_text_with_boundary(&context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        // the whole text of an unwrapped element is left out with the element
+        let text_context = text_context(context, element);
+        if let Some((element, context)) = self
+            .context(&text_context)
+            .split_last()
+            .filter(|(last, _)| last.handle == element.handle)
+        {
+            self.inner.append_raw_text(context, element, text)
+        }
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        let context = self.context(context);
+        self.inner.append_comment(&context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        let context = self.context(context);
+        self.inner
+            .append_processing_instruction(&context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.unwrapped.clear();
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{css_select, parse_document, HtmlSerializer, SelectToStrings};
+
+    const DOC: &str = r#"<!DOCTYPE html><html><head></head><body><p><span>a <span><b>b</b></span></span> <i>c</i></p><div><span>d</span></div></body></html>"#;
+
+    #[test]
+    fn unwrap_elements() {
+        let html = parse_document(
+            ElementUnwrapper::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("span", "i"),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            "<!DOCTYPE html><html><head></head><body><p>a <b>b</b> c</p><div>d</div></body></html>"
+        );
+
+        let html = parse_document(
+            ElementUnwrapper::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                css_select!("p" > "span"),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            "<!DOCTYPE html><html><head></head><body><p>a <span><b>b</b></span> <i>c</i></p><div><span>d</span></div></body></html>"
+        );
+    }
+
+    #[test]
+    fn unwrapped_elements_leave_the_context() {
+        let selected = parse_document(
+            ElementUnwrapper::wrap(
+                SelectToStrings::new(css_select!("p" > "b", "span" "b")),
+                css_select!("span"),
+            ),
+            Default::default(),
+        )
+        .one(DOC)
+        .unwrap();
+        assert_eq!(selected, ["<b>b</b>"]);
+    }
+}
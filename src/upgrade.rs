@@ -0,0 +1,274 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    parse_srcset, BaseUrl, CssPathTracker, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    TextBoundary,
+};
+
+/// A subresource loaded over http by an https document, which a [`UrlUpgrader`] couldn't upgrade
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MixedContent {
+    /// The resolved url
+    pub url: Url,
+    /// The local name of the element, such as `img` or `script`
+    pub element: String,
+    /// The attribute the url is in, such as `src` or `srcset`
+    pub attribute: String,
+    /// The css path of the element, as described by [`CssPathTracker`]
+    pub path: String,
+}
+
+/// The attributes of an element which load subresources, rather than navigate
+fn subresource_attributes<Handle>(
+    element: &HtmlPathElement<'_, Handle>,
+) -> &'static [&'static str] {
+    if element.name.ns != ns!(html) {
+        return &[];
+    }
+    match element.name.local {
+        local_name!("img") | local_name!("source") => &["src", "srcset"],
+        local_name!("video") => &["src", "poster"],
+        local_name!("object") => &["data"],
+        local_name!("audio")
+        | local_name!("embed")
+        | local_name!("iframe")
+        | local_name!("input")
+        | local_name!("script")
+        | local_name!("track") => &["src"],
+        local_name!("link")
+            if ["stylesheet", "icon", "preload", "modulepreload", "manifest"]
+                .iter()
+                .any(|rel| element.has_token("rel", rel)) =>
+        {
+            &["href"]
+        }
+        _ => &[],
+    }
+}
+
+/// The part of a url after `//`, if it is protocol-relative or an absolute http url
+fn strip_scheme(url: &str) -> Option<(bool, &str)> {
+    if let Some(rest) = url.strip_prefix("//") {
+        return Some((false, rest));
+    }
+    let scheme = url.get(..7)?;
+    scheme
+        .eq_ignore_ascii_case("http://")
+        .then(|| (true, &url[7..]))
+}
+
+/// A filter which upgrades the subresource urls of each document to https, and outputs the mixed content it couldn't upgrade along with the inner sink's output.
+///
+/// Absolute `http://` urls are upgraded when the callback says their host supports https, protocol-relative `//host/...` urls
+/// are made absolute with the document's scheme and upgraded in the same way. Urls in an https document which still load over http,
+/// including relative urls against an http `<base>`, are reported as [`MixedContent`]. Links which navigate, such as `<a href>`, are left alone.
+pub struct UrlUpgrader<S, F> {
+    inner: S,
+    document_url: Url,
+    supports_https: F,
+    base: BaseUrl,
+    paths: CssPathTracker,
+    mixed: Vec<MixedContent>,
+}
+
+impl<S, F: FnMut(&str) -> bool> UrlUpgrader<S, F> {
+    /// A filter for the document at the url, `supports_https` is called with the host of each http url to decide whether to upgrade it
+    pub fn wrap(inner: S, document_url: Url, supports_https: F) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            supports_https,
+            paths: CssPathTracker::new(),
+            mixed: vec![],
+        }
+    }
+
+    /// Sets the url of the next document
+    pub fn document_url(&mut self, document_url: Url) {
+        self.base.reset(document_url.clone());
+        self.document_url = document_url;
+    }
+
+    /// The upgraded url, if it changes, and whether it still loads over http
+    fn upgrade(&mut self, value: &str) -> (Option<String>, Option<Url>) {
+        let value = value.trim();
+        let Some((absolute, rest)) = strip_scheme(value) else {
+            let url = self
+                .base
+                .url()
+                .join(value)
+                .ok()
+                .filter(|url| url.scheme() == "http");
+            return (None, url);
+        };
+        let secure = !absolute && self.document_url.scheme() == "https";
+        let upgraded = secure
+            || Url::parse(&format!("http://{}", rest))
+                .ok()
+                .and_then(|url| url.host_str().map(|host| (self.supports_https)(host)))
+                .unwrap_or(false);
+        let scheme = if upgraded { "https" } else { "http" };
+        let rewritten = format!("{}://{}", scheme, rest);
+        let insecure = Url::parse(&rewritten).ok().filter(|_| !upgraded);
+        let changed = !absolute || upgraded;
+        (changed.then_some(rewritten), insecure)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>, F: FnMut(&str) -> bool> HtmlSink<Handle>
+    for UrlUpgrader<S, F>
+{
+    type Output = (S::Output, Vec<MixedContent>);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.base.observe(element);
+        let mut rewritten = Cow::Borrowed(element);
+        let mut insecure = vec![];
+        for attribute in subresource_attributes(element) {
+            let Some(value) = element.attr_local(attribute) else {
+                continue;
+            };
+            if *attribute == "srcset" {
+                let mut changed = false;
+                let mut candidates = vec![];
+                for (url, descriptor) in parse_srcset(value) {
+                    let (upgraded, http) = self.upgrade(url);
+                    changed |= upgraded.is_some();
+                    insecure.extend(http.map(|url| (*attribute, url)));
+                    let url = upgraded.as_deref().unwrap_or(url);
+                    candidates.push(if descriptor.is_empty() {
+                        url.to_owned()
+                    } else {
+                        format!("{} {}", url, descriptor)
+                    });
+                }
+                if changed {
+                    rewritten
+                        .to_mut()
+                        .set_attr(attribute, &candidates.join(", "));
+                }
+            } else {
+                let (upgraded, http) = self.upgrade(value);
+                insecure.extend(http.map(|url| (*attribute, url)));
+                if let Some(upgraded) = upgraded {
+                    rewritten.to_mut().set_attr(attribute, &upgraded);
+                }
+            }
+        }
+        if self.document_url.scheme() == "https" && !insecure.is_empty() {
+            let path = self.paths.element_path(context, element);
+            self.mixed
+                .extend(insecure.into_iter().map(|(attribute, url)| MixedContent {
+                    url,
+                    element: element.name.local.to_string(),
+                    attribute: attribute.to_owned(),
+                    path: path.clone(),
+                }));
+        }
+        self.inner.append_element(context, &rewritten)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.base.reset(self.document_url.clone());
+        (self.inner.reset(), std::mem::take(&mut self.mixed))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::{serialize::SerializeOpts, tendril::TendrilSink};
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn upgrade_urls() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        let ((), mixed) = parse_document(
+            UrlUpgrader::wrap(
+                &mut serializer,
+                "https://example.com/".parse().unwrap(),
+                |host: &str| host != "legacy.example.net",
+            ),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="http://cdn.example.com/site.css"><script src="//legacy.example.net/x.js"></script></head><body><a href="http://example.org/">link</a><img src="http://legacy.example.net/a.png" srcset="http://cdn.example.com/a.png 1x, /a@2x.png 2x"></body></html>"#)
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="https://cdn.example.com/site.css"><script src="https://legacy.example.net/x.js"></script></head><body><a href="http://example.org/">link</a><img src="http://legacy.example.net/a.png" srcset="https://cdn.example.com/a.png 1x, /a@2x.png 2x"></body></html>"#
+        );
+        assert_eq!(
+            mixed,
+            [MixedContent {
+                url: "http://legacy.example.net/a.png".parse().unwrap(),
+                element: "img".to_owned(),
+                attribute: "src".to_owned(),
+                path: "html > body > img:nth-child(2)".to_owned(),
+            }]
+        );
+    }
+}
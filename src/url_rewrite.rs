@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril, Attribute};
+
+use crate::{parse_srcset, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, TextBoundary};
+
+/// The local names of the attributes of an element which hold urls, whether they navigate or load a subresource
+fn url_attributes<Handle>(element: &HtmlPathElement<'_, Handle>) -> &'static [&'static str] {
+    if element.name.ns == ns!(svg) {
+        return match element.name.local {
+            local_name!("a") | local_name!("image") | local_name!("use") => &["href"],
+            _ => &[],
+        };
+    }
+    if element.name.ns != ns!(html) {
+        return &[];
+    }
+    match element.name.local {
+        local_name!("a") | local_name!("area") | local_name!("base") | local_name!("link") => {
+            &["href"]
+        }
+        local_name!("form") => &["action"],
+        local_name!("button") => &["formaction"],
+        local_name!("input") => &["src", "formaction"],
+        local_name!("img") | local_name!("source") => &["src", "srcset"],
+        local_name!("video") => &["src", "poster"],
+        local_name!("object") => &["data"],
+        local_name!("blockquote") | local_name!("del") | local_name!("ins") | local_name!("q") => {
+            &["cite"]
+        }
+        local_name!("audio")
+        | local_name!("embed")
+        | local_name!("frame")
+        | local_name!("iframe")
+        | local_name!("script")
+        | local_name!("track") => &["src"],
+        _ => &[],
+    }
+}
+
+/// Whether an attribute of an element holds a url, svg also takes the older `xlink:href`
+pub(crate) fn is_url_attribute<Handle>(
+    element: &HtmlPathElement<'_, Handle>,
+    attr: &Attribute,
+) -> bool {
+    (attr.name.ns == ns!() || attr.name.ns == ns!(xlink) && element.name.ns == ns!(svg))
+        && url_attributes(element).contains(&&*attr.name.local)
+}
+
+/// A filter which passes each url in the attributes of the document through the callback, such as to point the links of a page at
+/// a proxy, or at the paths of a static site.
+///
+/// The urls are those in `href`, `src`, `srcset`, `action`, `formaction`, `poster`, `data` and `cite`, on the html elements which
+/// have them, and in `href` or `xlink:href` on svg's `<a>`, `<image>` and `<use>`. The callback is called with each url as it is written in the document, and gives its replacement, or `None` to leave
+/// it as it is. Each candidate of a `srcset` is passed through on its own, keeping its descriptor.
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, HtmlSerializer, UrlRewriter};
+///
+/// let html = parse_document(
+///     UrlRewriter::wrap(HtmlSerializer::new(Vec::new(), Default::default()), |url| {
+///         url.strip_prefix("https://example.com/").map(|path| format!("/proxy/{}", path))
+///     }),
+///     Default::default(),
+/// )
+/// .one(r#"<!DOCTYPE html><html><head></head><body><a href="https://example.com/a">a</a><img src="b.png"></body></html>"#)
+/// .unwrap();
+/// assert_eq!(
+///     String::from_utf8(html).unwrap(),
+///     r#"<!DOCTYPE html><html><head></head><body><a href="/proxy/a">a</a><img src="b.png"></body></html>"#
+/// );
+/// ```
+pub struct UrlRewriter<S, F> {
+    inner: S,
+    rewrite: F,
+}
+
+impl<S, F: FnMut(&str) -> Option<String>> UrlRewriter<S, F> {
+    pub fn wrap(inner: S, rewrite: F) -> Self {
+        Self { inner, rewrite }
+    }
+
+    /// The rewritten `srcset`, if the url of any candidate changes
+    fn srcset(&mut self, value: &str) -> Option<String> {
+        let mut changed = false;
+        let candidates = parse_srcset(value)
+            .into_iter()
+            .map(|(url, descriptor)| {
+                let rewritten = (self.rewrite)(url);
+                changed |= rewritten.is_some();
+                let url = rewritten.as_deref().unwrap_or(url);
+                if descriptor.is_empty() {
+                    url.to_owned()
+                } else {
+                    format!("{} {}", url, descriptor)
+                }
+            })
+            .collect::<Vec<_>>();
+        changed.then(|| candidates.join(", "))
+    }
+}
+
+impl<Handle, S, F> HtmlSink<Handle> for UrlRewriter<S, F>
+where
+    Handle: Eq + Copy,
+    S: HtmlSink<Handle>,
+    F: FnMut(&str) -> Option<String>,
+{
+    type Output = S::Output;
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        let mut rewritten = Cow::Borrowed(element);
+        for (index, attr) in element.attrs.iter().enumerate() {
+            if !is_url_attribute(element, attr) {
+                continue;
+            }
+            let url = if &*attr.name.local == "srcset" {
+                self.srcset(&attr.value)
+            } else {
+                (self.rewrite)(&attr.value)
+            };
+            if let Some(url) = url {
+                rewritten.to_mut().attrs.to_mut()[index].value = url.into();
+            }
+        }
+        self.inner.append_element(context, &rewritten)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.inner.reset()
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, HtmlSerializer};
+
+    #[test]
+    fn rewrite_urls() {
+        let mut seen = vec![];
+        let html = parse_document(
+            UrlRewriter::wrap(
+                HtmlSerializer::new(Vec::new(), Default::default()),
+                |url: &str| {
+                    seen.push(url.to_owned());
+                    url.strip_prefix('/').map(|path| format!("/static/{}", path))
+                },
+            ),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="/site.css"></head><body><form action="/search"><button formaction="https://example.com/">Go</button></form><img src="/a.png" srcset="/a.png 1x, https://cdn.example.com/a@2x.png 2x"><svg><a href="/svg">svg</a><use xlink:href="/icons.svg#i"/><rect href="/rect"/></svg><p data-src="/not-a-url" cite="/p">p</p></body></html>"#)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="/static/site.css"></head><body><form action="/static/search"><button formaction="https://example.com/">Go</button></form><img src="/static/a.png" srcset="/static/a.png 1x, https://cdn.example.com/a@2x.png 2x"><svg><a href="/static/svg">svg</a><use xlink:href="/static/icons.svg#i"></use><rect href="/rect"></rect></svg><p data-src="/not-a-url" cite="/p">p</p></body></html>"#
+        );
+        assert_eq!(
+            seen,
+            [
+                "/site.css",
+                "/search",
+                "https://example.com/",
+                "/a.png",
+                "/a.png",
+                "https://cdn.example.com/a@2x.png",
+                "/svg",
+                "/icons.svg#i"
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+use url::Url;
+
+use crate::{
+    BaseUrl, CssPathTracker, FindingKind, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    SecurityFinding, SecurityReport, TextBoundary,
+};
+
+/// Query parameters which commonly name where to go next
+const REDIRECT_PARAMETERS: &[&str] = &[
+    "continue",
+    "dest",
+    "destination",
+    "goto",
+    "next",
+    "r",
+    "redirect",
+    "redirect_uri",
+    "redirect_url",
+    "return",
+    "return_to",
+    "returnto",
+    "returnurl",
+    "url",
+];
+
+/// The attributes of an element which hold urls
+fn url_attributes<Handle>(element: &HtmlPathElement<'_, Handle>) -> &'static [&'static str] {
+    if element.name.ns != ns!(html) {
+        return &[];
+    }
+    match element.name.local {
+        local_name!("a") | local_name!("area") | local_name!("link") => &["href"],
+        local_name!("form") => &["action"],
+        local_name!("button") | local_name!("input") => &["formaction"],
+        local_name!("audio")
+        | local_name!("embed")
+        | local_name!("iframe")
+        | local_name!("img")
+        | local_name!("script")
+        | local_name!("source")
+        | local_name!("track")
+        | local_name!("video") => &["src"],
+        _ => &[],
+    }
+}
+
+/// A wrapper which scans the urls of each document for risky patterns, and outputs a [`SecurityReport`] along with the inner sink's output.
+///
+/// It flags urls with redirect parameters holding absolute urls, protocol-relative urls to hosts other than the document's or the allowed hosts,
+/// `target="_blank"` without `noopener`, and forms posting to other origins. Content is forwarded unchanged.
+pub struct UrlScanner<S> {
+    inner: S,
+    document_url: Url,
+    allowed_hosts: Vec<String>,
+    base: BaseUrl,
+    paths: CssPathTracker,
+    report: SecurityReport,
+}
+
+impl<S> UrlScanner<S> {
+    pub fn wrap(inner: S, document_url: Url) -> Self {
+        Self {
+            inner,
+            base: BaseUrl::new(document_url.clone()),
+            document_url,
+            allowed_hosts: vec![],
+            paths: CssPathTracker::new(),
+            report: SecurityReport::default(),
+        }
+    }
+
+    /// Sets the hosts besides the document's which urls are expected to point to, such as the site's cdn
+    pub fn allowed_hosts(self, allowed_hosts: &[&str]) -> Self {
+        Self {
+            allowed_hosts: allowed_hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            ..self
+        }
+    }
+
+    fn is_expected_host(&self, url: &Url) -> bool {
+        url.host_str() == self.document_url.host_str()
+            || url
+                .host_str()
+                .is_some_and(|host| self.allowed_hosts.iter().any(|allowed| allowed == host))
+    }
+
+    fn scan<Handle>(
+        &self,
+        element: &HtmlPathElement<'_, Handle>,
+        found: &mut Vec<(FindingKind, String)>,
+    ) {
+        for attribute in url_attributes(element) {
+            let Some(value) = element.attr_local(attribute) else {
+                continue;
+            };
+            let snippet = || format!("{}=\"{}\"", attribute, value);
+            let Ok(url) = self.base.url().join(value.trim()) else {
+                continue;
+            };
+            let redirects = url.query_pairs().any(|(name, value)| {
+                REDIRECT_PARAMETERS.contains(&&*name.to_ascii_lowercase())
+                    && (value.starts_with("//")
+                        || Url::parse(&value).is_ok_and(|target| target.has_host()))
+            });
+            if redirects {
+                found.push((FindingKind::OpenRedirect, snippet()));
+            }
+            if value.trim().starts_with("//") && !self.is_expected_host(&url) {
+                found.push((FindingKind::ProtocolRelativeUrl, snippet()));
+            }
+            let posts_away = matches!(*attribute, "action" | "formaction")
+                && url.origin() != self.document_url.origin()
+                && !self.is_expected_host(&url);
+            if posts_away {
+                found.push((FindingKind::ThirdPartyFormAction, snippet()));
+            }
+        }
+        let opens_blank = element
+            .attr_local("target")
+            .is_some_and(|target| target.eq_ignore_ascii_case("_blank"));
+        if opens_blank
+            && !element.has_token("rel", "noopener")
+            && !element.has_token("rel", "noreferrer")
+        {
+            let rel = element.attr_local("rel").map_or("", |rel| &**rel);
+            found.push((
+                FindingKind::BlankTargetWithoutNoopener,
+                format!("target=\"_blank\" rel=\"{}\"", rel),
+            ));
+        }
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for UrlScanner<S> {
+    type Output = (S::Output, SecurityReport);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.paths.element(context);
+        self.base.observe(element);
+        let mut found = vec![];
+        self.scan(element, &mut found);
+        if !found.is_empty() {
+            let path = self.paths.element_path(context, element);
+            self.report
+                .findings
+                .extend(found.into_iter().map(|(kind, snippet)| SecurityFinding {
+                    kind,
+                    path: path.clone(),
+                    snippet,
+                }));
+        }
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_text(context, text)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.inner
+            .append_text_with_boundary(context, text, boundary)
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.base.reset(self.document_url.clone());
+        (self.inner.reset(), std::mem::take(&mut self.report))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    #[test]
+    fn scan_urls() {
+        let ((), report) = parse_document(
+            UrlScanner::wrap((), "https://example.com/page".parse().unwrap())
+                .allowed_hosts(&["cdn.example.com"]),
+            Default::default(),
+        )
+        .one(r#"<!DOCTYPE html><html><head><script src="//cdn.example.com/app.js"></script><script src="//evil.example.net/x.js"></script></head><body><a href="/login?next=https://evil.example.net/">login</a><a href="/login?next=/home">home</a><a href="https://other.com/" target="_blank">out</a><a href="https://other.com/" target="_blank" rel="noopener">safe</a><form action="https://collect.example.org/submit"><input name="q"></form><form action="/search"></form></body></html>"#)
+        .unwrap();
+        let found = |kind, path: &str, snippet: &str| SecurityFinding {
+            kind,
+            path: path.to_owned(),
+            snippet: snippet.to_owned(),
+        };
+        assert_eq!(
+            report.findings,
+            [
+                found(
+                    FindingKind::ProtocolRelativeUrl,
+                    "html > head > script:nth-child(2)",
+                    r#"src="//evil.example.net/x.js""#
+                ),
+                found(
+                    FindingKind::OpenRedirect,
+                    "html > body > a:nth-child(1)",
+                    r#"href="/login?next=https://evil.example.net/""#
+                ),
+                found(
+                    FindingKind::BlankTargetWithoutNoopener,
+                    "html > body > a:nth-child(3)",
+                    r#"target="_blank" rel="""#
+                ),
+                found(
+                    FindingKind::ThirdPartyFormAction,
+                    "html > body > form:nth-child(5)",
+                    r#"action="https://collect.example.org/submit""#
+                ),
+            ]
+        );
+        assert_eq!(report.by_path().len(), 4);
+    }
+}
@@ -0,0 +1,329 @@
+use std::{borrow::Cow, io};
+
+use html5ever::{
+    namespace_url, ns, tendril::StrTendril, Attribute, LocalName, Namespace, Prefix, QualName,
+};
+use quick_xml::{
+    events::{BytesEnd, BytesStart, BytesText, Event},
+    name::ResolveResult,
+    NsReader,
+};
+
+use crate::{
+    is_void, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError, InheritedState, SiblingChain,
+    SiblingCounter, SiblingTracking,
+};
+
+fn xml_error(err: quick_xml::Error) -> HtmlStreamError {
+    match err {
+        quick_xml::Error::Io(err) => {
+            HtmlStreamError::Io(io::Error::new(err.kind(), err.to_string()))
+        }
+        err => HtmlStreamError::Parse(err.to_string().into()),
+    }
+}
+
+fn qualified_name(name: &QualName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local),
+        None => name.local.to_string(),
+    }
+}
+
+/// A sink which emits the stream as `quick_xml` events to a handler, typically `|event| writer.write_event(event)` on a `quick_xml::Writer`.
+///
+/// A default namespace declaration is added wherever an element's namespace differs from its parent's,
+/// and void html elements are emitted as empty elements.
+pub struct QuickXmlSink<Handle, F> {
+    handler: F,
+    open_elements: Vec<(Handle, QualName)>,
+    error: Option<HtmlStreamError>,
+}
+
+impl<Handle, F> QuickXmlSink<Handle, F>
+where
+    Handle: Eq + Copy,
+    F: FnMut(Event<'_>) -> quick_xml::Result<()>,
+{
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            open_elements: vec![],
+            error: None,
+        }
+    }
+
+    fn emit(&mut self, event: Event<'_>) {
+        if self.error.is_none() {
+            if let Err(err) = (self.handler)(event) {
+                self.error = Some(xml_error(err));
+            }
+        }
+    }
+
+    fn close_to(&mut self, context: HtmlContext<'_, Handle>) {
+        let common = context
+            .iter()
+            .zip(&self.open_elements)
+            .take_while(|(a, (b, _))| a.handle == *b)
+            .count();
+        while self.open_elements.len() > common {
+            let (_, name) = self.open_elements.pop().unwrap();
+            self.emit(Event::End(BytesEnd::new(qualified_name(&name))));
+        }
+    }
+}
+
+impl<Handle, F> HtmlSink<Handle> for QuickXmlSink<Handle, F>
+where
+    Handle: Eq + Copy,
+    F: FnMut(Event<'_>) -> quick_xml::Result<()>,
+{
+    type Output = ();
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        _public_id: &StrTendril,
+        _system_id: &StrTendril,
+    ) {
+        self.emit(Event::DocType(BytesText::from_escaped(&**name)))
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.close_to(context);
+        let mut start = BytesStart::new(qualified_name(&element.name));
+        let parent_ns = self.open_elements.last().map(|(_, name)| &name.ns);
+        if element.name.prefix.is_none()
+            && parent_ns != Some(&element.name.ns)
+            && element.name.ns != ns!()
+        {
+            start.push_attribute(("xmlns", &*element.name.ns));
+        }
+        for attr in element.attrs.iter() {
+            start.push_attribute((&*qualified_name(&attr.name), &*attr.value));
+        }
+        if is_void(&element.name) {
+            self.emit(Event::Empty(start));
+        } else {
+            self.emit(Event::Start(start));
+            self.open_elements
+                .push((element.handle, element.name.clone()));
+        }
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_to(context);
+        self.emit(Event::Text(BytesText::new(text)))
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.close_to(context);
+        self.emit(Event::Comment(BytesText::from_escaped(text)))
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.close_to(context);
+        let content = if data.is_empty() {
+            target.to_owned()
+        } else {
+            format!("{} {}", target, data)
+        };
+        self.emit(Event::PI(BytesText::from_escaped(content)))
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.close_to(&[]);
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.error.take()
+    }
+}
+
+fn resolved_namespace(
+    resolved: ResolveResult<'_>,
+    unbound: Namespace,
+) -> Result<Namespace, HtmlStreamError> {
+    match resolved {
+        ResolveResult::Bound(ns) => Ok(Namespace::from(
+            std::str::from_utf8(ns.as_ref())
+                .map_err(|err| HtmlStreamError::Parse(err.to_string().into()))?,
+        )),
+        ResolveResult::Unbound => Ok(unbound),
+        ResolveResult::Unknown(prefix) => Err(HtmlStreamError::Parse(
+            format!(
+                "Unknown namespace prefix {}",
+                String::from_utf8_lossy(&prefix)
+            )
+            .into(),
+        )),
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, HtmlStreamError> {
+    std::str::from_utf8(bytes).map_err(|err| HtmlStreamError::Parse(err.to_string().into()))
+}
+
+fn path_element(
+    reader: &NsReader<&[u8]>,
+    ns: Namespace,
+    start: &BytesStart<'_>,
+    handle: u32,
+    parent: Option<&HtmlPathElement<'_, u32>>,
+) -> Result<HtmlPathElement<'static, u32>, HtmlStreamError> {
+    let name = start.name();
+    let mut attrs = vec![];
+    for attr in start.attributes() {
+        let attr = attr.map_err(|err| xml_error(err.into()))?;
+        let (resolved, local) = reader.resolve_attribute(attr.key);
+        attrs.push(Attribute {
+            name: QualName::new(
+                attr.key
+                    .prefix()
+                    .map(|prefix| utf8(prefix.into_inner()))
+                    .transpose()?
+                    .map(Prefix::from),
+                resolved_namespace(resolved, ns!())?,
+                LocalName::from(utf8(local.into_inner())?),
+            ),
+            value: StrTendril::from_slice(&attr.unescape_value().map_err(xml_error)?),
+        });
+    }
+    let name = QualName::new(
+        name.prefix()
+            .map(|prefix| utf8(prefix.into_inner()))
+            .transpose()?
+            .map(Prefix::from),
+        ns,
+        LocalName::from(utf8(name.local_name().into_inner())?),
+    );
+    Ok(HtmlPathElement {
+        handle,
+        inherited: InheritedState::for_element(
+            parent.map(|parent| &parent.inherited),
+            &name,
+            &attrs,
+        ),
+        name,
+        attrs: Cow::Owned(attrs),
+        position: None,
+        previous_sibling: None,
+        content: None,
+    })
+}
+
+/// Reads an xml document with `quick_xml` and streams it into the sink, then finishes the sink.
+///
+/// Elements which aren't in a namespace are placed in the html namespace, so html selectors and sinks treat them as html.
+pub fn stream_quick_xml<S: HtmlSink<u32>>(
+    xml: &str,
+    sink: S,
+) -> Result<S::Output, HtmlStreamError> {
+    stream_quick_xml_with(xml, sink, SiblingTracking::Off)
+}
+
+pub(crate) fn stream_quick_xml_with<S: HtmlSink<u32>>(
+    xml: &str,
+    mut sink: S,
+    tracking: SiblingTracking,
+) -> Result<S::Output, HtmlStreamError> {
+    let mut reader = NsReader::from_str(xml);
+    let mut context: Vec<HtmlPathElement<'static, u32>> = vec![];
+    let mut siblings = SiblingCounter::new();
+    let mut chain = SiblingChain::new(tracking);
+    let mut handle = 0;
+    loop {
+        match reader.read_resolved_event().map_err(xml_error)? {
+            (resolved, Event::Start(start)) => {
+                handle += 1;
+                let ns = resolved_namespace(resolved, ns!(html))?;
+                let mut element = path_element(&reader, ns, &start, handle, context.last())?;
+                element.position = Some(siblings.element(context.len(), &element.name));
+                element.previous_sibling = chain.element(context.len(), &element);
+                sink.append_element(&context, &element);
+                context.push(element);
+            }
+            (resolved, Event::Empty(start)) => {
+                handle += 1;
+                let ns = resolved_namespace(resolved, ns!(html))?;
+                let mut element = path_element(&reader, ns, &start, handle, context.last())?;
+                element.position = Some(siblings.element(context.len(), &element.name));
+                element.previous_sibling = chain.element(context.len(), &element);
+                sink.append_element(&context, &element);
+            }
+            (_, Event::End(_)) => {
+                context.pop();
+            }
+            (_, Event::Text(text)) => {
+                sink.append_text(&context, &text.unescape().map_err(xml_error)?)
+            }
+            (_, Event::CData(text)) => sink.append_text(&context, utf8(&text)?),
+            (_, Event::Comment(text)) => sink.append_comment(&context, utf8(&text)?),
+            (_, Event::DocType(doctype)) => {
+                let doctype = utf8(&doctype)?.trim();
+                let name = doctype.split_whitespace().next().unwrap_or(doctype);
+                sink.append_doctype_to_document(
+                    &StrTendril::from_slice(name),
+                    &StrTendril::new(),
+                    &StrTendril::new(),
+                )
+            }
+            (_, Event::Eof) => break,
+            (_, Event::PI(pi)) => {
+                let pi = utf8(&pi)?;
+                let (target, data) = pi
+                    .split_once(char::is_whitespace)
+                    .map_or((pi, ""), |(target, data)| (target, data.trim_start()));
+                sink.append_processing_instruction(&context, target, data)
+            }
+            (_, Event::Decl(_)) => {}
+        }
+    }
+    sink.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::stream_doc, HtmlSerializer};
+    use html5ever::serialize::SerializeOpts;
+
+    #[test]
+    fn html_to_quick_xml() {
+        let mut writer = quick_xml::Writer::new(Vec::new());
+        stream_doc(
+            r#"<!DOCTYPE html><html><head></head><body><p class="a">fish &amp; chips<br></p><svg><circle r="1"></circle></svg></body></html>"#,
+            QuickXmlSink::new(|event| writer.write_event(event)),
+        );
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            r#"<!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml"><head></head><body><p class="a">fish &amp; chips<br/></p><svg xmlns="http://www.w3.org/2000/svg"><circle r="1"></circle></svg></body></html>"#
+        );
+    }
+
+    #[test]
+    fn quick_xml_to_html() {
+        let mut buf = Vec::new();
+        let mut serializer = HtmlSerializer::new(&mut buf, SerializeOpts::default());
+        stream_quick_xml(
+            r#"<?xml version="1.0"?><html><body><p>fish &amp; chips<br/><![CDATA[<raw>]]></p></body></html>"#,
+            &mut serializer,
+        )
+        .unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "<html><body><p>fish &amp; chips<br>&lt;raw&gt;</p></body></html>"
+        );
+    }
+}
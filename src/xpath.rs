@@ -0,0 +1,408 @@
+use html5ever::LocalName;
+
+#[cfg(feature = "regex")]
+use crate::selector::{AttrMatchSelector, Selector};
+use crate::{
+    selector::{ContextualSelector, SelectorParseError},
+    HtmlContext, HtmlPathElement,
+};
+
+/// How a step relates to the previous one, or to the document for the first step
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Axis {
+    /// `/`
+    Child,
+    /// `//`
+    Descendant,
+}
+
+#[derive(Clone, Debug)]
+enum Predicate {
+    /// `[@name]`
+    Has(LocalName),
+    /// `[@name="value"]`
+    Equals(LocalName, String),
+    /// `[@name!="value"]`
+    NotEquals(LocalName, String),
+    /// `[contains(@name, "value")]`
+    Contains(LocalName, String),
+    /// `[starts-with(@name, "value")]`
+    StartsWith(LocalName, String),
+    /// `[re:test(@name, "pattern")]`, the EXSLT regular expression test
+    #[cfg(feature = "regex")]
+    Matches(AttrMatchSelector),
+}
+
+impl Predicate {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        match self {
+            Predicate::Has(name) => element.attr_local(name).is_some(),
+            Predicate::Equals(name, value) => element
+                .attr_local(name)
+                .is_some_and(|actual| **actual == **value),
+            Predicate::NotEquals(name, value) => element
+                .attr_local(name)
+                .is_some_and(|actual| **actual != **value),
+            Predicate::Contains(name, value) => element
+                .attr_local(name)
+                .is_some_and(|actual| actual.contains(&**value)),
+            Predicate::StartsWith(name, value) => element
+                .attr_local(name)
+                .is_some_and(|actual| actual.starts_with(&**value)),
+            #[cfg(feature = "regex")]
+            Predicate::Matches(selector) => selector.is_match(element),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Step {
+    axis: Axis,
+    /// The local name, `None` for `*`
+    name: Option<LocalName>,
+    /// The position among the siblings the name test selects, from a leading `[n]` or `[position()=n]`
+    position: Option<usize>,
+    predicates: Vec<Predicate>,
+}
+
+impl Step {
+    fn is_match<Handle>(&self, element: &HtmlPathElement<'_, Handle>) -> bool {
+        self.name
+            .as_ref()
+            .map_or(true, |name| *name == element.name.local)
+            && self.position.map_or(true, |position| {
+                element.position.is_some_and(|sibling| {
+                    if self.name.is_some() {
+                        sibling.index_of_type == position
+                    } else {
+                        sibling.index == position
+                    }
+                })
+            })
+            && self
+                .predicates
+                .iter()
+                .all(|predicate| predicate.is_match(element))
+    }
+}
+
+/// A selector compiled from the subset of XPath which can be decided as each element is appended, for porting scrapers written against lxml.
+///
+/// Location paths are made of `/` and `//` steps, each testing a name or `*`, followed by predicates on the attributes:
+/// `[@name]`, `[@name="value"]`, `[@name!="value"]`, `[contains(@name, "value")]` and `[starts-with(@name, "value")]`,
+/// and with the `regex` feature, EXSLT's `[re:test(@name, "pattern")]`.
+/// A positional predicate, `[n]` or `[position()=n]`, can come first to select the nth of the siblings with the step's name, or of all
+/// the siblings after `*`, this uses the [`crate::SiblingPosition`] of the source so it doesn't match elements which filters added.
+/// Anything needing what follows the element, such as `last()` or the text, is an error.
+///
+/// Absolute paths start from the root of the contexts the selector sees, so `/div` matches a root `div`, and relative paths, such as
+/// `p`, `./p` or `.//p`, start from the root as the context node, like css's `:scope`. Under a [`crate::RootFilter`] the root is the
+/// selected element, see [`crate::selector::Scope`].
+///
+/// ```
+/// use html5ever::tendril::TendrilSink;
+/// use html5streams::{parse_document, SelectToStrings, XPath};
+///
+/// let selector = XPath::parse(r#"//div[@class="article"]/p[1]"#).unwrap();
+/// let paragraphs = parse_document(SelectToStrings::new(selector), Default::default())
+///     .one(r#"<!DOCTYPE html><html><head></head><body><div class="article"><p>One</p><p>Two</p></div></body></html>"#)
+///     .unwrap();
+/// assert_eq!(paragraphs, ["<p>One</p>"]);
+/// assert!(XPath::parse("//p[last()]").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct XPath {
+    /// Whether the first step is from the root element rather than the document
+    relative: bool,
+    steps: Vec<Step>,
+}
+
+impl XPath {
+    pub fn parse(expression: &str) -> Result<Self, SelectorParseError> {
+        let mut parser = Parser {
+            expression,
+            offset: 0,
+        };
+        let mut steps = vec![];
+        parser.skip_whitespace();
+        let relative = !parser.rest().starts_with('/');
+        // a relative path can start with a name rather than `./`
+        let mut bare = relative && !parser.eat(".");
+        while parser.offset < expression.len() {
+            steps.push(parser.step(bare)?);
+            bare = false;
+            parser.skip_whitespace();
+        }
+        if steps.is_empty() {
+            return Err(parser.error("expected a location path"));
+        }
+        Ok(Self { relative, steps })
+    }
+}
+
+impl ContextualSelector for XPath {
+    fn context_match<Handle>(
+        &self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) -> bool {
+        // for each step, whether the path up to it matches at the parent, and at any ancestor
+        let mut at_parent = vec![false; self.steps.len()];
+        let mut at_ancestor = vec![false; self.steps.len()];
+        for (depth, node) in context.iter().chain(Some(element)).enumerate() {
+            let here = self
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(index, step)| {
+                    let follows = match (index.checked_sub(1), step.axis) {
+                        (None, Axis::Child) => depth == usize::from(self.relative),
+                        (None, Axis::Descendant) => !self.relative || depth > 0,
+                        (Some(previous), Axis::Child) => at_parent[previous],
+                        (Some(previous), Axis::Descendant) => at_ancestor[previous],
+                    };
+                    follows && step.is_match(node)
+                })
+                .collect::<Vec<_>>();
+            for (ancestor, matched) in at_ancestor.iter_mut().zip(&here) {
+                *ancestor |= matched;
+            }
+            at_parent = here;
+        }
+        at_parent.last().copied().unwrap_or(false)
+    }
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.expression[self.offset..]
+    }
+
+    fn error(&self, message: &'static str) -> SelectorParseError {
+        SelectorParseError::new(self.offset, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes the token if it comes next, after any whitespace
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), SelectorParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn name(&mut self) -> Result<LocalName, SelectorParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.offset += len;
+        Ok(LocalName::from(&rest[..len]))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number = rest[..len].parse().ok()?;
+        self.offset += len;
+        Some(number)
+    }
+
+    fn literal(&mut self) -> Result<String, SelectorParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error("expected a quoted string")),
+        };
+        let Some(len) = rest[1..].find(quote) else {
+            return Err(self.error("unterminated string"));
+        };
+        self.offset += len + 2;
+        Ok(rest[1..=len].to_owned())
+    }
+
+    fn attribute(&mut self) -> Result<LocalName, SelectorParseError> {
+        self.expect("@", "expected an attribute")?;
+        self.name()
+    }
+
+    /// The arguments of `contains` or `starts-with`, after the opening parenthesis
+    fn function_args(&mut self) -> Result<(LocalName, String), SelectorParseError> {
+        let name = self.attribute()?;
+        self.expect(",", "expected ','")?;
+        let value = self.literal()?;
+        self.expect(")", "expected ')'")?;
+        Ok((name, value))
+    }
+
+    /// Parses a step, which can be `bare` of a leading `/` as the first step of a relative path
+    fn step(&mut self, bare: bool) -> Result<Step, SelectorParseError> {
+        let axis = if self.eat("//") {
+            Axis::Descendant
+        } else if self.eat("/") || bare {
+            Axis::Child
+        } else {
+            return Err(self.error("expected '/' or '//'"));
+        };
+        let name = if self.eat("*") {
+            None
+        } else {
+            Some(self.name()?)
+        };
+        let mut step = Step {
+            axis,
+            name,
+            position: None,
+            predicates: vec![],
+        };
+        while self.eat("[") {
+            let position = if self.eat("position()") {
+                self.expect("=", "expected '='")?;
+                Some(
+                    self.number()
+                        .ok_or_else(|| self.error("expected a position"))?,
+                )
+            } else {
+                self.number()
+            };
+            if let Some(position) = position {
+                if step.position.is_some() || !step.predicates.is_empty() {
+                    return Err(self
+                        .error("a position is only streamable as the first predicate of a step"));
+                }
+                step.position = Some(position);
+            } else if self.eat("contains(") {
+                let (name, value) = self.function_args()?;
+                step.predicates.push(Predicate::Contains(name, value));
+            } else if self.eat("starts-with(") {
+                let (name, value) = self.function_args()?;
+                step.predicates.push(Predicate::StartsWith(name, value));
+            } else if cfg!(feature = "regex") && self.eat("re:test(") {
+                #[cfg(feature = "regex")]
+                {
+                    let offset = self.offset;
+                    let (name, pattern) = self.function_args()?;
+                    let regex = regex::Regex::new(&pattern)
+                        .map_err(|err| SelectorParseError::new(offset, err.to_string()))?;
+                    step.predicates
+                        .push(Predicate::Matches(AttrMatchSelector::new(name, regex)));
+                }
+            } else if self.rest().starts_with('@') {
+                let name = self.attribute()?;
+                step.predicates.push(if self.eat("!=") {
+                    Predicate::NotEquals(name, self.literal()?)
+                } else if self.eat("=") {
+                    Predicate::Equals(name, self.literal()?)
+                } else {
+                    Predicate::Has(name)
+                });
+            } else if self.eat("last()") || self.eat("text()") || self.eat(".") {
+                return Err(self.error("predicates on what follows the element aren't streamable"));
+            } else {
+                return Err(self.error("expected a predicate"));
+            }
+            self.expect("]", "expected ']'")?;
+        }
+        Ok(step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::{parse_document, SelectToStrings};
+
+    #[test]
+    fn select_with_xpath() {
+        let select = |expression| {
+            parse_document(
+                SelectToStrings::new(XPath::parse(expression).unwrap()),
+                Default::default(),
+            )
+            .one(r#"<!DOCTYPE html><html><head></head><body><div class="article"><p>One</p><span><p>Nested</p></span><p lang="en-GB">Two</p></div><p>Outside</p></body></html>"#)
+            .unwrap()
+        };
+        assert_eq!(
+            select(r#"//div[@class="article"]/p"#),
+            ["<p>One</p>", r#"<p lang="en-GB">Two</p>"#]
+        );
+        assert_eq!(
+            select(r#"//div[@class="article"]//p"#),
+            ["<p>One</p>", "<p>Nested</p>", r#"<p lang="en-GB">Two</p>"#]
+        );
+        assert_eq!(select("/html/body/p"), ["<p>Outside</p>"]);
+        assert_eq!(select("//div/p[2]"), [r#"<p lang="en-GB">Two</p>"#]);
+        assert_eq!(
+            select("//div/*[ position() = 2 ]"),
+            ["<span><p>Nested</p></span>"]
+        );
+        assert_eq!(
+            select("//p[starts-with(@lang, 'en')]"),
+            [r#"<p lang="en-GB">Two</p>"#]
+        );
+        assert_eq!(select("//body/*[contains(@class, 'art')]/span").len(), 1);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn select_with_regex() {
+        let selector =
+            XPath::parse(r#"//a[re:test(@href, '^https://(www\.)?example\.com')]"#).unwrap();
+        let links = parse_document(SelectToStrings::new(selector), Default::default())
+            .one(r#"<!DOCTYPE html><html><head></head><body><a href="https://example.com/a">A</a><a href="https://example.org/">B</a><a href="https://www.example.com/c">C</a></body></html>"#)
+            .unwrap();
+        assert_eq!(
+            links,
+            [
+                r#"<a href="https://example.com/a">A</a>"#,
+                r#"<a href="https://www.example.com/c">C</a>"#
+            ]
+        );
+        assert!(XPath::parse("//a[re:test(@href, '(')]").is_err());
+    }
+
+    #[test]
+    fn reject_unstreamable() {
+        assert_eq!(
+            XPath::parse("//p[last()]").unwrap_err().message,
+            "predicates on what follows the element aren't streamable"
+        );
+        assert_eq!(XPath::parse("//p[@class][1]").unwrap_err().position, 13);
+        assert_eq!(XPath::parse("p/").unwrap_err().position, 2);
+        assert_eq!(XPath::parse(".").unwrap_err().position, 1);
+        assert_eq!(
+            XPath::parse("//p[@class=\"x]").unwrap_err().message,
+            "unterminated string"
+        );
+    }
+}
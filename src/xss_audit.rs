@@ -0,0 +1,282 @@
+use html5ever::{local_name, namespace_url, ns, tendril::StrTendril};
+
+use crate::{
+    attrs::is_executable_script, boundary::append_text_to, url_rewrite::is_url_attribute,
+    CssPathTracker, FindingKind, HtmlContext, HtmlPathElement, HtmlSink, HtmlStreamError,
+    SecurityFinding, SecurityReport, TextBoundary,
+};
+
+/// The longest snippet kept in a finding, in characters
+const SNIPPET_LENGTH: usize = 80;
+
+/// Pushes text onto a snippet, cutting it off with an ellipsis at [`SNIPPET_LENGTH`]
+fn push_snippet(snippet: &mut String, text: &str) {
+    let length = snippet.chars().count();
+    if length > SNIPPET_LENGTH {
+        return;
+    }
+    let mut chars = text.chars();
+    snippet.extend(chars.by_ref().take(SNIPPET_LENGTH - length));
+    if chars.next().is_some() {
+        snippet.push('…');
+    }
+}
+
+fn attribute_snippet(name: &str, value: &str) -> String {
+    let mut snippet = String::new();
+    push_snippet(&mut snippet, &format!("{}=\"{}\"", name, value));
+    snippet
+}
+
+/// Whether a url runs script, browsers ignore whitespace and control characters in the scheme
+fn is_script_url(value: &str) -> bool {
+    let scheme = value
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take_while(|c| *c != ':')
+        .collect::<String>();
+    value.contains(':')
+        && (scheme.eq_ignore_ascii_case("javascript") || scheme.eq_ignore_ascii_case("vbscript"))
+}
+
+/// A wrapper which reports the inline script of each document, and outputs a [`SecurityReport`] along with the inner sink's output.
+///
+/// This is an audit rather than a sanitizer, content is forwarded unchanged. It reports event handler attributes, `javascript:` urls
+/// in the attributes which hold urls, inline `<script>`s which run without a `nonce`, with the start of their text as the snippet,
+/// and svg which can run script: `<script>` and `<foreignObject>` in svg, animations of `href`, and `<use>` of external documents.
+pub struct XssAuditor<Handle, S> {
+    inner: S,
+    paths: CssPathTracker,
+    report: SecurityReport,
+    /// The inline script whose text is being added to the snippet of a finding, and the finding's index
+    script: Option<(Handle, usize)>,
+}
+
+impl<Handle: Eq + Copy> XssAuditor<Handle, ()> {
+    /// An auditor which only reports
+    pub fn new() -> Self {
+        Self::wrap(())
+    }
+}
+
+impl<Handle: Eq + Copy> Default for XssAuditor<Handle, ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> XssAuditor<Handle, S> {
+    pub fn wrap(inner: S) -> Self {
+        Self {
+            inner,
+            paths: CssPathTracker::new(),
+            report: SecurityReport::default(),
+            script: None,
+        }
+    }
+
+    /// The findings of an element's attributes and of the element itself
+    fn audit(&self, element: &HtmlPathElement<'_, Handle>) -> Vec<(FindingKind, String)> {
+        let mut found = vec![];
+        for attr in element.attrs.iter() {
+            let name = &*attr.name.local;
+            if attr.name.ns == ns!() && name.len() > 2 && name[..2].eq_ignore_ascii_case("on") {
+                found.push((
+                    FindingKind::InlineEventHandler,
+                    attribute_snippet(name, &attr.value),
+                ));
+            } else if is_url_attribute(element, attr) && is_script_url(&attr.value) {
+                found.push((FindingKind::ScriptUrl, attribute_snippet(name, &attr.value)));
+            }
+        }
+        match element.name.ns {
+            ns!(html) if element.tag_is(local_name!("script")) && !element.has_attr("src") => {
+                if !element.has_attr("nonce")
+                    && is_executable_script(element.attr_local("type").map(|t| &**t))
+                {
+                    found.push((FindingKind::ScriptWithoutNonce, String::new()));
+                }
+            }
+            ns!(svg) => {
+                let dangerous = match element.name.local {
+                    local_name!("script") | local_name!("foreignObject") => true,
+                    local_name!("animate") | local_name!("set") => element
+                        .attr_local("attributeName")
+                        .is_some_and(|name| name.ends_with("href")),
+                    local_name!("use") => element
+                        .attrs
+                        .iter()
+                        .find(|attr| &*attr.name.local == "href")
+                        .is_some_and(|href| !href.value.trim_start().starts_with('#')),
+                    _ => false,
+                };
+                if dangerous {
+                    found.push((
+                        FindingKind::DangerousSvg,
+                        format!("<{}>", element.name.local),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        found
+    }
+
+    /// Adds text to the snippet of the open script, passing it on with its boundary if it has one
+    fn text(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        text: &str,
+        boundary: Option<TextBoundary>,
+    ) {
+        if let Some((handle, index)) = self.script {
+            if context.last().is_some_and(|parent| parent.handle == handle) {
+                push_snippet(&mut self.report.findings[index].snippet, text);
+            }
+        }
+        append_text_to(&mut self.inner, context, text, boundary)
+    }
+}
+
+impl<Handle: Eq + Copy, S: HtmlSink<Handle>> HtmlSink<Handle> for XssAuditor<Handle, S> {
+    type Output = (S::Output, SecurityReport);
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: &StrTendril,
+        public_id: &StrTendril,
+        system_id: &StrTendril,
+    ) {
+        self.inner
+            .append_doctype_to_document(name, public_id, system_id)
+    }
+
+    fn append_element(
+        &mut self,
+        context: HtmlContext<'_, Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+    ) {
+        self.script = None;
+        self.paths.element(context);
+        let found = self.audit(element);
+        if !found.is_empty() {
+            let path = self.paths.element_path(context, element);
+            for (kind, snippet) in found {
+                if kind == FindingKind::ScriptWithoutNonce {
+                    self.script = Some((element.handle, self.report.findings.len()));
+                }
+                self.report.findings.push(SecurityFinding {
+                    kind,
+                    path: path.clone(),
+                    snippet,
+                });
+            }
+        }
+        self.inner.append_element(context, element)
+    }
+
+    fn append_text(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.text(context, text, None)
+    }
+
+    fn append_text_with_boundary(
+        &mut self,
+        context: HtmlContext<Handle>,
+        text: &str,
+        boundary: TextBoundary,
+    ) {
+        self.text(context, text, Some(boundary))
+    }
+
+    fn append_raw_text(
+        &mut self,
+        context: HtmlContext<Handle>,
+        element: &HtmlPathElement<'_, Handle>,
+        text: &str,
+    ) {
+        self.inner.append_raw_text(context, element, text)
+    }
+
+    fn append_comment(&mut self, context: HtmlContext<Handle>, text: &str) {
+        self.inner.append_comment(context, text)
+    }
+
+    fn append_processing_instruction(
+        &mut self,
+        context: HtmlContext<Handle>,
+        target: &str,
+        data: &str,
+    ) {
+        self.inner
+            .append_processing_instruction(context, target, data)
+    }
+
+    fn reset(&mut self) -> Self::Output {
+        self.paths.reset();
+        self.script = None;
+        (self.inner.reset(), std::mem::take(&mut self.report))
+    }
+
+    fn take_error(&mut self) -> Option<HtmlStreamError> {
+        self.inner.take_error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use html5ever::tendril::TendrilSink;
+
+    use super::*;
+    use crate::parse_document;
+
+    #[test]
+    fn audit_inline_script() {
+        let ((), report) = parse_document(XssAuditor::new(), Default::default())
+            .one(format!(
+                r##"<!DOCTYPE html><html><head><script nonce="abc">ok()</script><script>track({})</script><script type="application/ld+json">{{}}</script></head><body><img src="x" onerror="alert(1)"><a href=" Java&#10;Script:alert(2)">x</a><svg><foreignObject></foreignObject><use href="data:image/svg+xml,..."/><use href="#icon"/><animate attributeName="href" to="javascript:alert(3)"/></svg><p title="javascript:alert(4)">p</p></body></html>"##,
+                "1".repeat(100)
+            ))
+            .unwrap();
+        let found = report
+            .findings
+            .iter()
+            .map(|f| (f.kind, f.path.as_str(), f.snippet.as_str()))
+            .collect::<Vec<_>>();
+        let script = format!("track({}…", "1".repeat(74));
+        assert_eq!(
+            found,
+            [
+                (
+                    FindingKind::ScriptWithoutNonce,
+                    "html > head > script:nth-child(2)",
+                    script.as_str()
+                ),
+                (
+                    FindingKind::InlineEventHandler,
+                    "html > body > img:nth-child(1)",
+                    r#"onerror="alert(1)""#
+                ),
+                (
+                    FindingKind::ScriptUrl,
+                    "html > body > a:nth-child(2)",
+                    "href=\" Java\nScript:alert(2)\""
+                ),
+                (
+                    FindingKind::DangerousSvg,
+                    "html > body > svg:nth-child(3) > foreignObject:nth-child(1)",
+                    "<foreignObject>"
+                ),
+                (
+                    FindingKind::DangerousSvg,
+                    "html > body > svg:nth-child(3) > use:nth-child(2)",
+                    "<use>"
+                ),
+                (
+                    FindingKind::DangerousSvg,
+                    "html > body > svg:nth-child(3) > animate:nth-child(4)",
+                    "<animate>"
+                ),
+            ]
+        );
+    }
+}